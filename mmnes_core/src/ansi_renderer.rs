@@ -0,0 +1,51 @@
+use std::fmt::Write;
+use crate::nes_frame::NesFrame;
+
+const UPPER_HALF_BLOCK: char = '▀';
+const RESET_COLORS: &str = "\x1b[0m";
+
+/***
+ * Downsample the frame to ```cols``` x ```rows``` terminal cells, with 24-bit ANSI colors.
+ * Each cell is an upper half block: the foreground is the top pixel and the background the bottom one,
+ * so a cell covers two rows of samples. The colors are only emitted when they change along a line.
+ ***/
+pub fn frame_to_ansi(frame: &NesFrame, cols: u16, rows: u16) -> String {
+    let mut output = String::new();
+
+    if cols == 0 || rows == 0 || frame.width() == 0 || frame.height() == 0 {
+        return output;
+    }
+
+    let samples_rows = rows as usize * 2;
+
+    for row in 0..rows as usize {
+        let top_y = (row * 2) * frame.height() / samples_rows;
+        let bottom_y = (row * 2 + 1) * frame.height() / samples_rows;
+        let mut colors = None;
+
+        for col in 0..cols as usize {
+            let x = col * frame.width() / cols as usize;
+            let cell = (sample(frame, x, top_y), sample(frame, x, bottom_y));
+
+            if colors != Some(cell) {
+                let ((tr, tg, tb), (br, bg, bb)) = cell;
+                let _ = write!(output, "\x1b[38;2;{};{};{};48;2;{};{};{}m", tr, tg, tb, br, bg, bb);
+                colors = Some(cell);
+            }
+
+            output.push(UPPER_HALF_BLOCK);
+        }
+
+        output.push_str(RESET_COLORS);
+        output.push('\n');
+    }
+
+    output
+}
+
+fn sample(frame: &NesFrame, x: usize, y: usize) -> (u8, u8, u8) {
+    let index = (y * frame.width() + x) * 4;
+    let pixels = frame.pixels();
+
+    (pixels[index], pixels[index + 1], pixels[index + 2])
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use crate::apu_snapshot::ApuSnapshot;
 use crate::cpu::CpuError;
 use crate::irq_source::IrqError;
 use crate::memory::MemoryError;
@@ -72,4 +73,7 @@ pub trait APU {
     /// ```start_cycle```: current cycle of execution,  
     /// ```credits```: the number of cycles available to execute instructions
     fn run(&mut self, start_cycle: u32, credits: u32) -> Result<(u32, Option<NesSamples>), ApuError>;
+
+    /// State of the channels (periods, volumes, length counters, ...), without side effect.
+    fn snapshot(&self) -> ApuSnapshot;
 }
\ No newline at end of file
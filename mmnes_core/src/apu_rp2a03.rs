@@ -5,21 +5,26 @@ use std::rc::Rc;
 use log::{info, trace};
 use crate::apu::{ApuError, APU};
 use crate::apu::ApuType::RP2A03;
+use crate::apu_snapshot::{ApuChannelSnapshot, ApuSnapshot};
 use crate::bus::Bus;
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::cpu::CPU;
 use crate::cpu_6502::{APU_DMC_IRQ, APU_FRAME_COUNTER_IRQ};
+use crate::expansion_audio::ExpansionAudio;
 use crate::irq_source::IrqSource;
 use crate::memory::{Memory, MemoryError};
 use crate::nes_samples::NesSamples;
+use crate::region::Region;
 use crate::sound_playback::SoundPlayback;
+use crate::wav_sink::MultiChannelWavSink;
 
 const APU_NAME: &str = "APU RP2A03";
 const APU_EXTERNAL_ADDRESS_SPACE: (u16, u16) = (0x4000, 0x4017);
+/// $4014 is the OAM DMA and $4016 the controller port
+const APU_REGISTERS_ADDRESS_RANGES: [(u16, u16); 3] = [(0x4000, 0x4013), (0x4015, 0x4015), (0x4017, 0x4017)];
 const APU_EXTERNAL_MEMORY_SIZE: usize = 32;
-const APU_RATE: f64 = 894_886.5;
-const AUDIO_RATE: f64 = 44_100.0;
-const APU_CYCLES_PER_SAMPLE: f64 = APU_RATE / AUDIO_RATE; // ~20.29
+/// Native sample rate of the APU output, resampled to the host rate by the sound playback.
+pub const AUDIO_RATE: f64 = 44_100.0;
 
 const DUTY_CYCLES: [[u8; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1],
@@ -334,6 +339,11 @@ impl Pulse {
     fn duty_bit(&self) -> u8 {
         DUTY_CYCLES[self.duty_cycle][self.duty_cycle_index]
     }
+
+    fn snapshot(&self) -> ApuChannelSnapshot {
+        ApuChannelSnapshot::new(self.enabled, self.is_muted(), self.timer_period, self.envelope.get_volume(),
+                                self.envelope.const_volume, self.length_counter.counter, self.duty_cycle as u8)
+    }
 }
 
 #[derive(Debug)]
@@ -345,6 +355,10 @@ const NOISE_PERIOD_DURATIONS: [u16; 16] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068
 ];
 
+const PAL_NOISE_PERIOD_DURATIONS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778
+];
+
 #[derive(Debug)]
 struct Noise {
     enabled: bool,
@@ -393,8 +407,9 @@ impl Noise {
         }
     }
 
-    fn period(value: u8) -> u16 {
-        NOISE_PERIOD_DURATIONS[value as usize]
+    fn snapshot(&self) -> ApuChannelSnapshot {
+        ApuChannelSnapshot::new(self.enabled, self.is_muted(), self.timer_period, self.envelope.get_volume(),
+                                self.envelope.const_volume, self.length_counter.counter, 0)
     }
 }
 
@@ -466,12 +481,23 @@ impl Triangle {
     fn num_of_sequences(&self) -> usize {
         TRIANGLE_SEQUENCES.len()
     }
+
+    fn snapshot(&self) -> ApuChannelSnapshot {
+        let volume = if self.is_muted() { 0 } else { 15 };
+
+        ApuChannelSnapshot::new(self.enabled, self.is_muted(), self.timer_period, volume,
+                                true, self.length_counter.counter, 0)
+    }
 }
 
 const DMC_PERIODS: [u16; 16] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54
 ];
 
+const PAL_DMC_PERIODS: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50
+];
+
 #[derive(Debug, PartialEq)]
 enum Reload {
     None,
@@ -554,10 +580,6 @@ impl<U: CPU + ?Sized, V: Bus + ?Sized> Dmc<U, V> {
         }
     }
 
-    fn period(value: u8) -> u16 {
-        DMC_PERIODS[value as usize]
-    }
-
     fn dma_read_and_update_sample_buffer_and_counter(&mut self) -> Result<u8, MemoryError> {
         match self.current_address {
             Some(addr) => {
@@ -627,7 +649,7 @@ impl<U: CPU + ?Sized, V: Bus + ?Sized> Dmc<U, V> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum FrameCounterMode {
     FourStep,
     FiveStep
@@ -655,12 +677,22 @@ const FRAME_COUNTER_5_STEPS_SEQUENCES: [(bool, bool, bool); 5] = [
     (true,  true, false), // step 4 (18640) : quarter + half
 ];
 
+/***
+ * PAL frame sequencer runs with longer step periods, sequences are identical
+ * https://www.nesdev.org/wiki/APU_Frame_Counter
+ */
+const PAL_FRAME_COUNTER_4_STEPS_EVENTS: [u32; 4] = [4156, 8313, 12469, 16626];
+
+const PAL_FRAME_COUNTER_5_STEPS_EVENTS: [u32; 5] = [4156, 8313, 12469, 16626, 20782];
+
 #[derive(Debug)]
 struct FrameCounter<U: CPU + ?Sized> {
     mode: FrameCounterMode,
     inhibit_irq: Cell<bool>,
     apu_cycle: u32,
     next_step: usize,
+    pending_reset: Option<(FrameCounterMode, u32)>,
+    region: Region,
     cpu: Rc<RefCell<U>>
 }
 
@@ -675,31 +707,104 @@ impl<U: CPU + ?Sized> IrqSource<U> for FrameCounter<U> {
 }
 
 impl<U: CPU + ?Sized> FrameCounter<U> {
-    fn new(cpu: Rc<RefCell<U>>) -> Self {
+    fn new(cpu: Rc<RefCell<U>>, region: Region) -> Self {
         FrameCounter {
             mode: FrameCounterMode::FourStep,
             inhibit_irq: Cell::new(false),
             apu_cycle: 0,
             next_step: 0,
+            pending_reset: None,
+            region,
             cpu
         }
     }
 
+    /// The mode written to $4017 survives a reset, the sequencer restarts as if it was written again.
     fn reset(&mut self) {
-        self.mode = FrameCounterMode::FourStep;
         self.inhibit_irq = Cell::new(false);
         self.apu_cycle = 0;
         self.next_step = 0;
+
+        if let Some((mode, _)) = self.pending_reset.take() {
+            self.mode = mode;
+        }
     }
 
     fn frame_tables(&self) -> (&'static [u32], &'static [(bool, bool, bool)]) {
-        match self.mode {
-            FrameCounterMode::FourStep => (&FRAME_COUNTER_4_STEPS_EVENTS, &FRAME_COUNTER_4_STEPS_SEQUENCES),
-            FrameCounterMode::FiveStep => (&FRAME_COUNTER_5_STEPS_EVENTS, &FRAME_COUNTER_5_STEPS_SEQUENCES),
+        match (&self.mode, self.region.has_pal_apu()) {
+            (FrameCounterMode::FourStep, false) => (&FRAME_COUNTER_4_STEPS_EVENTS, &FRAME_COUNTER_4_STEPS_SEQUENCES),
+            (FrameCounterMode::FiveStep, false) => (&FRAME_COUNTER_5_STEPS_EVENTS, &FRAME_COUNTER_5_STEPS_SEQUENCES),
+            (FrameCounterMode::FourStep, true) => (&PAL_FRAME_COUNTER_4_STEPS_EVENTS, &FRAME_COUNTER_4_STEPS_SEQUENCES),
+            (FrameCounterMode::FiveStep, true) => (&PAL_FRAME_COUNTER_5_STEPS_EVENTS, &FRAME_COUNTER_5_STEPS_SEQUENCES),
         }
     }
 }
 
+/***
+ * Clock rates of the APU, and the period tables that depend on them:
+ *   - the pulse, triangle and noise timers and the frame sequencer are clocked every cpu_cycles_per_apu_cycle CPU cycles,
+ *   - the noise and DMC periods are in CPU cycles, PAL has its own tables to keep the pitches close to NTSC.
+ *
+ * The ratio and the CPU clock rate can be changed for experimentation, the tables stay the ones of the region.
+ *
+ * https://www.nesdev.org/wiki/APU_Noise
+ * https://www.nesdev.org/wiki/APU_DMC
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApuClockRates {
+    cpu_clock_rate: f64,
+    cpu_cycles_per_apu_cycle: u64,
+    noise_periods: &'static [u16; 16],
+    dmc_periods: &'static [u16; 16]
+}
+
+impl ApuClockRates {
+    pub fn new(region: Region) -> Self {
+        let (noise_periods, dmc_periods) = if region.has_pal_apu() {
+            (&PAL_NOISE_PERIOD_DURATIONS, &PAL_DMC_PERIODS)
+        } else {
+            (&NOISE_PERIOD_DURATIONS, &DMC_PERIODS)
+        };
+
+        ApuClockRates {
+            cpu_clock_rate: region.cpu_clock_rate(),
+            cpu_cycles_per_apu_cycle: 2,
+            noise_periods,
+            dmc_periods
+        }
+    }
+
+    pub fn with_cpu_clock_rate(mut self, cpu_clock_rate: f64) -> Self {
+        self.cpu_clock_rate = cpu_clock_rate;
+        self
+    }
+
+    pub fn with_cpu_cycles_per_apu_cycle(mut self, cpu_cycles_per_apu_cycle: u64) -> Self {
+        self.cpu_cycles_per_apu_cycle = cpu_cycles_per_apu_cycle.max(1);
+        self
+    }
+
+    pub fn apu_clock_rate(&self) -> f64 {
+        self.cpu_clock_rate / self.cpu_cycles_per_apu_cycle as f64
+    }
+
+    pub fn cpu_cycles_per_apu_cycle(&self) -> u64 {
+        self.cpu_cycles_per_apu_cycle
+    }
+
+    pub fn convert_cpu_cycles_to_apu_cycles(&self, cpu_cycles: u64) -> u64 {
+        cpu_cycles / self.cpu_cycles_per_apu_cycle
+    }
+
+    fn noise_period(&self, value: u8) -> u16 {
+        self.noise_periods[value as usize]
+    }
+
+    fn dmc_period(&self, value: u8) -> u16 {
+        self.dmc_periods[value as usize]
+    }
+}
+
 #[derive(Debug)]
 pub struct ApuRp2A03<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> {
     pulse1: Pulse,
@@ -709,7 +814,12 @@ pub struct ApuRp2A03<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> {
     dmc: Dmc<U, V>,
     frame_counter: FrameCounter<U>,
     apu_cycles_acc: f64,
-    sound_player: T
+    apu_cycles_per_sample: f64,
+    cpu_cycles: u64,
+    clock_rates: ApuClockRates,
+    sound_player: T,
+    expansion_audio: Option<Rc<RefCell<dyn ExpansionAudio>>>,
+    channel_sink: Option<Rc<RefCell<MultiChannelWavSink>>>,
 }
 
 impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> BusDevice for ApuRp2A03<T, U, V> {
@@ -724,6 +834,10 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> BusDevice for ApuRp2A03
     fn get_virtual_address_range(&self) -> (u16, u16) {
         APU_EXTERNAL_ADDRESS_SPACE
     }
+
+    fn get_address_ranges(&self) -> Vec<(u16, u16)> {
+        APU_REGISTERS_ADDRESS_RANGES.to_vec()
+    }
 }
 
 impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus+ ?Sized> Memory for ApuRp2A03<T, U, V> {
@@ -797,19 +911,38 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus+ ?Sized> Memory for ApuRp2A03<T,
 }
 
 impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
-    pub fn new(sound_player: T, cpu: Rc<RefCell<U>>, bus: Rc<RefCell<V>>) -> Self {
+    pub fn new(sound_player: T, cpu: Rc<RefCell<U>>, bus: Rc<RefCell<V>>, region: Region) -> Self {
+        ApuRp2A03::new_with_clock_rates(sound_player, cpu, bus, region, ApuClockRates::new(region))
+    }
+
+    pub fn new_with_clock_rates(sound_player: T, cpu: Rc<RefCell<U>>, bus: Rc<RefCell<V>>, region: Region, clock_rates: ApuClockRates) -> Self {
         ApuRp2A03 {
             pulse1: Pulse::new(),
             pulse2: Pulse::new(),
             noise: Noise::new(),
             triangle: Triangle::new(),
             dmc: Dmc::new(cpu.clone(), bus.clone()),
-            frame_counter: FrameCounter::new(cpu.clone()),
+            frame_counter: FrameCounter::new(cpu.clone(), region),
             sound_player,
-            apu_cycles_acc: 0.0
+            expansion_audio: None,
+            channel_sink: None,
+            apu_cycles_acc: 0.0,
+            apu_cycles_per_sample: clock_rates.apu_clock_rate() / AUDIO_RATE, // ~20.29 on NTSC, ~18.85 on PAL
+            cpu_cycles: 0,
+            clock_rates,
         }
     }
 
+    /// Channels of the cartridge, mixed with the APU channels.
+    pub fn attach_expansion_audio(&mut self, expansion_audio: Rc<RefCell<dyn ExpansionAudio>>) {
+        self.expansion_audio = Some(expansion_audio);
+    }
+
+    /// Records the output of each channel, before the mixer, at each sample tick.
+    pub fn attach_channel_sink(&mut self, channel_sink: Rc<RefCell<MultiChannelWavSink>>) {
+        self.channel_sink = Some(channel_sink);
+    }
+
     fn read_pulse(&self, _: u16) -> Result<u8, MemoryError> {
         Ok(0)
     }
@@ -948,7 +1081,7 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
             dmc.reload = Reload::None
         }
 
-        dmc.timer_period = Dmc::<U, V>::period(value & 0x0F);
+        dmc.timer_period = self.clock_rates.dmc_period(value & 0x0F);
 
         Ok(())
     }
@@ -1051,10 +1184,16 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
 
     /***
      * 0x4017 - frame counter
+     *
+     * The IRQ inhibit flag applies at once, but the sequencer restarts in the new mode 3 CPU cycles after
+     * the write if it happens on an APU cycle, 4 otherwise. Until then, the previous sequence keeps running:
+     * its IRQ step can still fire, unless the write has just inhibited it.
+     *
+     * https://www.nesdev.org/wiki/APU_Frame_Counter
      */
     fn write_frame_counter(&mut self, value: u8) -> Result<(), MemoryError> {
         let inhibit_irq = (value & 0x40) != 0;
-        self.frame_counter.mode = match (value & 0x80) != 0 {
+        let mode = match (value & 0x80) != 0 {
             true => FrameCounterMode::FiveStep,
             false => FrameCounterMode::FourStep,
         };
@@ -1066,6 +1205,26 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
                 MemoryError::IllegalState(e.to_string()))?
         }
 
+        // the write is the last CPU cycle run, the next one is on an APU cycle when it is even
+        let delay = if self.cpu_cycles.is_multiple_of(2) { 4 } else { 3 };
+        self.frame_counter.pending_reset = Some((mode, delay));
+
+        Ok(())
+    }
+
+    fn clock_frame_counter_reset(&mut self) {
+        let (mode, delay) = match self.frame_counter.pending_reset {
+            Some(pending_reset) => pending_reset,
+            None => return,
+        };
+
+        if delay > 1 {
+            self.frame_counter.pending_reset = Some((mode, delay - 1));
+            return;
+        }
+
+        self.frame_counter.pending_reset = None;
+        self.frame_counter.mode = mode;
         self.frame_counter.next_step = 0;
         self.frame_counter.apu_cycle = 0;
 
@@ -1074,8 +1233,6 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
             self.tick_length_counters();
             self.tick_sweep_units();
         }
-
-        Ok(())
     }
 
     fn write_noise_control(&mut self, value: u8) -> Result<(), MemoryError> {
@@ -1094,7 +1251,7 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
         let noise = &mut self.noise;
 
         let idx = value & 0x0F;
-        noise.timer_period = Noise::period(idx);
+        noise.timer_period = self.clock_rates.noise_period(idx);
         noise.shift_mode = if (value & 0x80) == 0 {
             ShiftMode::Zero
         } else {
@@ -1301,10 +1458,23 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> ApuRp2A03<T, U, V> {
     }
 
     fn clock_mixer(&mut self) {
+        if let Some(channel_sink) = &self.channel_sink {
+            channel_sink.borrow_mut().push_samples([
+                self.pulse1.get_sample(),
+                self.pulse2.get_sample(),
+                self.triangle.get_sample(),
+                self.noise.get_sample(),
+                self.dmc.get_sample(),
+            ]);
+        }
+
         let pulse_out = self.pulse_out();
         let tnd_out = self.tnd_out();
+        let expansion_out = self.expansion_audio
+            .as_ref()
+            .map_or(0.0, |expansion_audio| expansion_audio.borrow().sample());
 
-        self.sound_player.push_sample(pulse_out + tnd_out);
+        self.sound_player.push_sample(pulse_out + tnd_out + expansion_out);
     }
 }
 
@@ -1317,6 +1487,11 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> APU for ApuRp2A03<T, U,
         self.noise.reset();
         self.dmc.reset();
         self.frame_counter.reset();
+
+        // $4015 is cleared: channels are silenced and the pending interrupts are acknowledged
+        self.dmc.clear_interrupt()?;
+        self.frame_counter.clear_interrupt()?;
+
         Ok(())
     }
 
@@ -1337,17 +1512,23 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> APU for ApuRp2A03<T, U,
      ***/
     fn run(&mut self, start_cycle: u32, credits: u32) -> Result<(u32, Option<NesSamples>), ApuError> {
 
-        for counter in 0..credits {
+        for _ in 0..credits {
+
+            self.clock_frame_counter_reset();
 
             /***
              * DMC channel is clocked at the CPU clock rate
              */
             self.clock_dmc_timer()?;
 
+            if let Some(expansion_audio) = &self.expansion_audio {
+                expansion_audio.borrow_mut().clock();
+            }
+
             /***
              * other channels are the frame counter are clocked at the APU clock rate
              */
-            if counter % 2 == 0 {
+            if self.cpu_cycles.is_multiple_of(self.clock_rates.cpu_cycles_per_apu_cycle()) {
                 self.clock_pulse_timers();
                 self.clock_triangle_timer();
                 self.clock_noise_timer();
@@ -1355,11 +1536,13 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> APU for ApuRp2A03<T, U,
 
                 self.apu_cycles_acc += 1.0;
 
-                while self.apu_cycles_acc >= APU_CYCLES_PER_SAMPLE {
+                while self.apu_cycles_acc >= self.apu_cycles_per_sample {
                     self.clock_mixer();
-                    self.apu_cycles_acc -= APU_CYCLES_PER_SAMPLE;
+                    self.apu_cycles_acc -= self.apu_cycles_per_sample;
                 }
             }
+
+            self.cpu_cycles += 1;
         }
 
         let buffer = self.sound_player.samples();
@@ -1371,4 +1554,9 @@ impl<T: SoundPlayback, U: CPU + ?Sized, V: Bus + ?Sized> APU for ApuRp2A03<T, U,
 
         Ok((start_cycle + credits, samples))
     }
+
+    fn snapshot(&self) -> ApuSnapshot {
+        ApuSnapshot::new(self.pulse1.snapshot(), self.pulse2.snapshot(), self.triangle.snapshot(), self.noise.snapshot(),
+                         self.dmc.bytes_remaining > 0, self.dmc.output_level)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+/// State of a pulse, triangle or noise channel.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ApuChannelSnapshot {
+    enabled: bool,
+    muted: bool,
+    timer_period: u16,
+    volume: u8,
+    constant_volume: bool,
+    length_counter: u8,
+    duty_cycle: u8,
+}
+
+impl ApuChannelSnapshot {
+    pub fn new(enabled: bool, muted: bool, timer_period: u16, volume: u8, constant_volume: bool, length_counter: u8, duty_cycle: u8) -> Self {
+        ApuChannelSnapshot {
+            enabled,
+            muted,
+            timer_period,
+            volume,
+            constant_volume,
+            length_counter,
+            duty_cycle,
+        }
+    }
+
+    /// Enabled by $4015.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Silenced by the length counter, the linear counter (triangle) or the period (pulse sweep).
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    /// Constant volume or envelope decay level (0-15). The triangle has no volume control: 15 when not muted.
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn constant_volume(&self) -> bool {
+        self.constant_volume
+    }
+
+    pub fn length_counter(&self) -> u8 {
+        self.length_counter
+    }
+
+    /// Duty cycle of the pulse channels (0: 12.5%, 1: 25%, 2: 50%, 3: 75%), 0 for the other channels.
+    pub fn duty_cycle(&self) -> u8 {
+        self.duty_cycle
+    }
+}
+
+/***
+ * Read-only state of the APU channels, for the visualizers.
+ * https://www.nesdev.org/wiki/APU
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ApuSnapshot {
+    pulse1: ApuChannelSnapshot,
+    pulse2: ApuChannelSnapshot,
+    triangle: ApuChannelSnapshot,
+    noise: ApuChannelSnapshot,
+    dmc_enabled: bool,
+    dmc_output_level: u8,
+}
+
+impl ApuSnapshot {
+    pub fn new(pulse1: ApuChannelSnapshot, pulse2: ApuChannelSnapshot, triangle: ApuChannelSnapshot, noise: ApuChannelSnapshot, dmc_enabled: bool, dmc_output_level: u8) -> Self {
+        ApuSnapshot {
+            pulse1,
+            pulse2,
+            triangle,
+            noise,
+            dmc_enabled,
+            dmc_output_level,
+        }
+    }
+
+    pub fn pulse1(&self) -> &ApuChannelSnapshot {
+        &self.pulse1
+    }
+
+    pub fn pulse2(&self) -> &ApuChannelSnapshot {
+        &self.pulse2
+    }
+
+    pub fn triangle(&self) -> &ApuChannelSnapshot {
+        &self.triangle
+    }
+
+    pub fn noise(&self) -> &ApuChannelSnapshot {
+        &self.noise
+    }
+
+    /// DMC sample bytes remaining.
+    pub fn dmc_enabled(&self) -> bool {
+        self.dmc_enabled
+    }
+
+    /// DMC output level (0-127).
+    pub fn dmc_output_level(&self) -> u8 {
+        self.dmc_output_level
+    }
+}
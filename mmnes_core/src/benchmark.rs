@@ -0,0 +1,63 @@
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use crate::nes_console::{NesConsole, NesConsoleError};
+
+/// Emulated frames and instructions over the wall-clock duration of a benchmark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkReport {
+    frames: u64,
+    instructions: u64,
+    elapsed: Duration,
+}
+
+impl BenchmarkReport {
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl Display for BenchmarkReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frames, {} instructions in {:.3} s: {:.1} fps, {:.0} instructions/s",
+               self.frames, self.instructions, self.elapsed.as_secs_f64(),
+               self.frames_per_second(), self.instructions_per_second())
+    }
+}
+
+/***
+ * Run the console as fast as possible, without pacing, for at least ```duration```.
+ * The samples of every frame are dropped: the sound playback of the console is passive
+ * and drained by each frame, so the audio never throttles the emulation.
+ ***/
+pub fn run_benchmark(console: &mut NesConsole, duration: Duration) -> Result<BenchmarkReport, NesConsoleError> {
+    let instructions_start = console.instructions_executed();
+    let start = Instant::now();
+    let mut frames = 0u64;
+
+    while start.elapsed() < duration {
+        let _ = console.step_frame()?;
+        frames += 1;
+    }
+
+    Ok(BenchmarkReport {
+        frames,
+        instructions: console.instructions_executed() - instructions_start,
+        elapsed: start.elapsed(),
+    })
+}
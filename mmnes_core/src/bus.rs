@@ -4,10 +4,8 @@ use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 #[cfg(test)]
 use mockall::mock;
-use crate::bus_device::BusDevice;
-use crate::memory::Memory;
-#[cfg(test)]
-use crate::memory::{MemoryError};
+use crate::bus_device::{BusDevice, BusDeviceType};
+use crate::memory::{Memory, MemoryError};
 
 #[derive(Debug, Default, Clone)]
 pub enum BusType {
@@ -17,6 +15,23 @@ pub enum BusType {
 
 pub trait Bus: Memory {
     fn add_device(&mut self, memory: Rc<RefCell<dyn BusDevice>>) -> Result<(), BusError>;
+
+    /// The address ranges decoded to each device, in address order: a device added later
+    /// overwrites part of the range of a previous one, which is then split around it.
+    /// The addresses left to the open bus are not listed.
+    fn describe_mapping(&self) -> Vec<((u16, u16), String)>;
+
+    /// Unmap every device, the whole address space is left to the open bus.
+    fn remove_devices(&mut self);
+
+    /// The state of each device mapped, in address order (see BusDevice::serialize_state).
+    fn serialize_devices(&self) -> Vec<(BusDeviceType, Vec<u8>)>;
+
+    /// Restores the states given by ```serialize_devices```, the same devices must be mapped.
+    fn deserialize_devices(&mut self, states: &[(BusDeviceType, Vec<u8>)]) -> Result<(), MemoryError>;
+
+    /// A write done by a DMA unit without going through the bus (the OAM DMA writes to $2004), for the write logs.
+    fn log_dma_write(&self, _addr: u16, _value: u8) {}
 }
 
 #[cfg(test)]
@@ -26,6 +41,10 @@ mock! {
 
     impl Bus for BusStub {
         fn add_device(&mut self, memory: Rc<RefCell<dyn BusDevice>>) -> Result<(), BusError>;
+        fn describe_mapping(&self) -> Vec<((u16, u16), String)>;
+        fn remove_devices(&mut self);
+        fn serialize_devices(&self) -> Vec<(BusDeviceType, Vec<u8>)>;
+        fn deserialize_devices(&mut self, states: &[(BusDeviceType, Vec<u8>)]) -> Result<(), MemoryError>;
     }
 
     #[derive(Debug)]
@@ -7,9 +7,7 @@ use crate::apu::ApuType;
 use crate::cartridge::CartridgeType;
 use crate::controller::ControllerType;
 use crate::dma::DmaType;
-use crate::memory::{Memory, MemoryType};
-#[cfg(test)]
-use crate::memory::{MemoryError};
+use crate::memory::{Memory, MemoryError, MemoryType};
 use crate::ppu::PpuType;
 
 #[derive(Debug, Clone)]
@@ -44,6 +42,7 @@ impl PartialEq for BusDeviceType {
             (BusDeviceType::PPU(a), BusDeviceType::PPU(b)) => a == b,
             (BusDeviceType::APU(a), BusDeviceType::APU(b)) => a == b,
             (BusDeviceType::CARTRIDGE(a), BusDeviceType::CARTRIDGE(b)) => a == b,
+            (BusDeviceType::DMA(a), BusDeviceType::DMA(b)) => a == b,
             (BusDeviceType::CONTROLLER(a), BusDeviceType::CONTROLLER(b)) => a == b,
             (BusDeviceType::OPENBUS, BusDeviceType::OPENBUS) => true,
             _ => false,
@@ -55,6 +54,25 @@ pub trait BusDevice: Memory {
     fn get_name(&self) -> String;
     fn get_device_type(&self) -> BusDeviceType;
     fn get_virtual_address_range(&self) -> (u16, u16);
+
+    /// The ranges actually decoded by the bus, for the devices with holes in their address range.
+    /// Defaults to the whole virtual address range.
+    fn get_address_ranges(&self) -> Vec<(u16, u16)> {
+        vec![self.get_virtual_address_range()]
+    }
+
+    /***
+     * The state of the device, for the save states: the content of its RAM, its bank registers, ...
+     * The devices without state (ROM, open bus, ...) keep the default, an empty state.
+     ***/
+    fn serialize_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores a state given by ```serialize_state```, the default ignores it.
+    fn deserialize_state(&mut self, _state: &[u8]) -> Result<(), MemoryError> {
+        Ok(())
+    }
 }
 
 impl Ord for dyn BusDevice {
@@ -90,6 +108,9 @@ mock! {
         fn get_name(&self) -> String;
         fn get_device_type(&self) -> BusDeviceType;
         fn get_virtual_address_range(&self) -> (u16, u16);
+        fn get_address_ranges(&self) -> Vec<(u16, u16)>;
+        fn serialize_state(&self) -> Vec<u8>;
+        fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError>;
     }
 
     #[derive(Debug)]
@@ -1,17 +1,18 @@
 use std::cell::RefCell;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::{BufReader, Error, Read, Seek, SeekFrom};
+use std::io::{Error, Read, Seek, SeekFrom};
 use std::rc::Rc;
 use log::debug;
 use crate::bus_device::BusDevice;
+use crate::expansion_audio::ExpansionAudio;
 use crate::memory::{Memory, MemoryError};
 use crate::memory_bank::MemoryBank;
 use crate::memory_ciram::PpuNameTableMirroring;
 
 pub const PPU_ADDRESS_SPACE: (u16, u16) = (0x0000, 0x1FFF);
 pub const CPU_ADDRESS_SPACE: (u16, u16) = (0x8000, 0xFFFF);
+pub const DEFAULT_CHR_RAM_SIZE: usize = 8 * 1024;
 
 #[derive(Debug, PartialEq)]
 pub enum CartridgeError {
@@ -74,19 +75,73 @@ impl PartialEq for CartridgeType {
     }
 }
 
+pub const PRG_BANK_WINDOWS: [u16; 4] = [0x8000, 0xA000, 0xC000, 0xE000];
+pub const CHR_BANK_WINDOWS: [u16; 2] = [0x0000, 0x1000];
+
+/***
+ * The banks mapped by the cartridge, for the debugger: the 8 KB PRG ROM bank in each window of PRG_BANK_WINDOWS
+ * and the 4 KB CHR bank in each pattern table (CHR_BANK_WINDOWS). The banks are numbered in units of
+ * their window, a 16 KB bank n is seen as the 8 KB banks 2n and 2n + 1.
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankState {
+    pub prg_banks: [usize; 4],
+    pub chr_banks: [usize; 2],
+}
+
+impl BankState {
+    /// The 16 KB PRG ROM banks at $8000 and $C000, the 4 KB CHR banks at $0000 and $1000.
+    pub fn from_16k_prg_banks(prg_bank_lo: usize, prg_bank_hi: usize, chr_bank_lo: usize, chr_bank_hi: usize) -> Self {
+        BankState {
+            prg_banks: [prg_bank_lo * 2, prg_bank_lo * 2 + 1, prg_bank_hi * 2, prg_bank_hi * 2 + 1],
+            chr_banks: [chr_bank_lo, chr_bank_hi],
+        }
+    }
+}
+
+impl Default for BankState {
+    /// 32 KB of PRG ROM and 8 KB of CHR memory, without bank switching.
+    fn default() -> Self {
+        BankState {
+            prg_banks: [0, 1, 2, 3],
+            chr_banks: [0, 1],
+        }
+    }
+}
+
+impl Display for BankState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let prg = PRG_BANK_WINDOWS.iter().zip(self.prg_banks)
+            .map(|(addr, bank)| format!("${:04X}: {}", addr, bank))
+            .collect::<Vec<String>>();
+        let chr = CHR_BANK_WINDOWS.iter().zip(self.chr_banks)
+            .map(|(addr, bank)| format!("${:04X}: {}", addr, bank))
+            .collect::<Vec<String>>();
+
+        write!(f, "PRG {} | CHR {}", prg.join(", "), chr.join(", "))
+    }
+}
+
 pub trait Cartridge: BusDevice {
     fn get_chr_rom(&self) -> Rc<RefCell<dyn BusDevice>>;
     fn get_prg_ram(&self) -> Option<Rc<RefCell<dyn BusDevice>>> {
         None
     }
     fn get_mirroring(&self) -> Rc<RefCell<PpuNameTableMirroring>>;
+    fn get_expansion_audio(&self) -> Option<Rc<RefCell<dyn ExpansionAudio>>> {
+        None
+    }
+    /// The banks currently mapped, see BankState.
+    fn bank_state(&self) -> BankState {
+        BankState::default()
+    }
 }
 
 /***
  * helper functions
  ***/
 
-pub fn write_rom_data(rom: &mut dyn Memory, size: usize, data: &mut BufReader<File>) -> Result<(), CartridgeError> {
+pub fn write_rom_data(rom: &mut dyn Memory, size: usize, data: &mut impl Read) -> Result<(), CartridgeError> {
     let mut buf = vec![0u8; size];
     data.read_exact(&mut buf)?;
 
@@ -99,12 +154,17 @@ pub fn write_rom_data(rom: &mut dyn Memory, size: usize, data: &mut BufReader<Fi
 
 pub fn get_chr_memory_size_and_type(chr_rom_size: usize, chr_ram_size: usize) -> (usize, bool) {
     /***
-     * loader guarantees that one of chr_rom and chr_ram is non-zero.
+     * without CHR ROM, the pattern tables are in CHR RAM, written by the game through $2007.
+     * an iNES header without CHR ROM implies 8 KB of CHR RAM, a NES 2.0 header may declare neither:
+     * the boards without CHR ROM have at least 8 KB of CHR RAM.
+     * https://www.nesdev.org/wiki/CHR_ROM_vs._CHR_RAM
      */
     if chr_rom_size > 0 {
         (chr_rom_size, true)
-    } else {
+    } else if chr_ram_size > 0 {
         (chr_ram_size, false)
+    } else {
+        (DEFAULT_CHR_RAM_SIZE, false)
     }
 }
 
@@ -126,7 +186,7 @@ pub fn create_split_ram_memory(total_size: usize, bank_size: usize, address_rang
     Ok(memory_banks)
 }
 
-pub fn create_split_rom_memory(data: &mut BufReader<File>, offset: u64, total_size: usize, bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
+pub fn create_split_rom_memory<R: Read + Seek>(data: &mut R, offset: u64, total_size: usize, bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
     let (mut memory_banks, num_memory_banks) = memory_banks_vec(total_size, bank_size)?;
 
     data.seek(SeekFrom::Start(offset))?;
@@ -144,7 +204,7 @@ pub fn create_split_rom_memory(data: &mut BufReader<File>, offset: u64, total_si
     Ok(memory_banks)
 }
 
-pub fn create_chr_rom_memory(data: &mut BufReader<File>, chr_rom_offset: u64, chr_rom_total_size: usize, chr_rom_bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
+pub fn create_chr_rom_memory<R: Read + Seek>(data: &mut R, chr_rom_offset: u64, chr_rom_total_size: usize, chr_rom_bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
     create_split_rom_memory(data, chr_rom_offset, chr_rom_total_size, chr_rom_bank_size, address_range)
 }
 
@@ -152,10 +212,10 @@ pub fn create_chr_ram_memory(chr_ram_total_size: usize, chr_ram_bank_size: usize
     create_split_ram_memory(chr_ram_total_size, chr_ram_bank_size, address_range)
 }
 
-pub fn create_chr_memory(data: Option<&mut BufReader<File>>, offset: u64, total_size: usize, bank_size: usize, is_chr_rom: bool, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
+pub fn create_chr_memory<R: Read + Seek>(data: Option<&mut R>, offset: u64, total_size: usize, bank_size: usize, is_chr_rom: bool, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
     let chr = if is_chr_rom {
-        if let Some(mut data) = data {
-            create_chr_rom_memory(&mut data, offset, total_size, bank_size, address_range)?
+        if let Some(data) = data {
+            create_chr_rom_memory(data, offset, total_size, bank_size, address_range)?
         } else {
             Err(CartridgeError::IllegalState(format!("data can not be empty for CHR rom (offset: 0x{:04X})", offset)))?
         }
@@ -166,7 +226,7 @@ pub fn create_chr_memory(data: Option<&mut BufReader<File>>, offset: u64, total_
     Ok(chr)
 }
 
-pub fn create_prg_rom_memory(data: &mut BufReader<File>, prg_rom_offset: u64, prg_rom_total_size: usize, prg_rom_bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
+pub fn create_prg_rom_memory<R: Read + Seek>(data: &mut R, prg_rom_offset: u64, prg_rom_total_size: usize, prg_rom_bank_size: usize, address_range: (u16, u16)) -> Result<Vec<MemoryBank>, CartridgeError> {
     create_split_rom_memory(data, prg_rom_offset, prg_rom_total_size, prg_rom_bank_size, address_range)
 }
 
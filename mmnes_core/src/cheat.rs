@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+const GAME_GENIE_LETTERS: &str = "APZLGITYEOXUKSVN";
+pub(crate) const PRG_ROM_START_ADDRESS: u16 = 0x8000;
+
+/***
+ * Patch of a byte read by the CPU from the PRG space, with an optional compare byte:
+ * the patch only applies when the original byte matches it (8 characters Game Genie codes).
+ ***/
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cheat {
+    address: u16,
+    value: u8,
+    compare: Option<u8>,
+}
+
+impl Cheat {
+    pub fn new(address: u16, value: u8, compare: Option<u8>) -> Self {
+        Cheat {
+            address,
+            value,
+            compare,
+        }
+    }
+
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    pub fn compare(&self) -> Option<u8> {
+        self.compare
+    }
+
+    /// Game Genie codes (6 or 8 letters), or raw codes ```AAAA:VV``` and ```AAAA:VV:CC``` in hexadecimal.
+    pub fn from_code(code: &str) -> Result<Cheat, CheatError> {
+        if code.contains(':') {
+            Cheat::from_raw_code(code)
+        } else {
+            Cheat::from_game_genie_code(code)
+        }
+    }
+
+    fn parse_hex_byte(code: &str, field: &str) -> Result<u8, CheatError> {
+        u8::from_str_radix(field, 16).map_err(|_| CheatError::InvalidCode(code.to_string()))
+    }
+
+    fn from_raw_code(code: &str) -> Result<Cheat, CheatError> {
+        let fields = code.trim().split(':').collect::<Vec<&str>>();
+
+        let (address, value, compare) = match fields.as_slice() {
+            [address, value] => (address, value, None),
+            [address, value, compare] => (address, value, Some(Cheat::parse_hex_byte(code, compare)?)),
+            _ => return Err(CheatError::InvalidCode(code.to_string())),
+        };
+
+        let address = u16::from_str_radix(address, 16).map_err(|_| CheatError::InvalidCode(code.to_string()))?;
+        let value = Cheat::parse_hex_byte(code, value)?;
+
+        Ok(Cheat::new(address, value, compare))
+    }
+
+    /***
+     * each letter encodes 4 bits, scrambled into a 15 bits address (in $8000 - $FFFF), a value,
+     * and for 8 letters codes a compare byte.
+     * https://www.nesdev.org/wiki/Game_Genie
+     ***/
+    fn from_game_genie_code(code: &str) -> Result<Cheat, CheatError> {
+        let n = code.trim()
+            .to_ascii_uppercase()
+            .chars()
+            .map(|letter| GAME_GENIE_LETTERS.find(letter).map(|index| index as u16))
+            .collect::<Option<Vec<u16>>>()
+            .ok_or_else(|| CheatError::InvalidCode(code.to_string()))?;
+
+        if n.len() != 6 && n.len() != 8 {
+            return Err(CheatError::InvalidCode(code.to_string()));
+        }
+
+        let address = PRG_ROM_START_ADDRESS
+            | ((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8) | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4) | ((n[1] & 8) << 4)
+            | (n[4] & 7) | (n[3] & 8);
+
+        let value_low_bit3 = if n.len() == 6 { n[5] & 8 } else { n[7] & 8 };
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | value_low_bit3;
+
+        let compare = if n.len() == 8 {
+            Some((((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8)) as u8)
+        } else {
+            None
+        };
+
+        Ok(Cheat::new(address, value as u8, compare))
+    }
+
+    fn apply(&self, addr: u16, value: u8) -> Option<u8> {
+        match self.compare {
+            _ if addr != self.address => None,
+            Some(compare) if compare != value => None,
+            _ => Some(self.value),
+        }
+    }
+}
+
+/***
+ * Active cheats, shared between the console (to add and remove them) and the bus (to patch the reads)
+ ***/
+#[derive(Debug, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Cheats::default()
+    }
+
+    pub fn add(&mut self, cheat: Cheat) {
+        if !self.cheats.contains(&cheat) {
+            self.cheats.push(cheat);
+        }
+    }
+
+    /// Returns whether the cheat was active.
+    pub fn remove(&mut self, cheat: &Cheat) -> bool {
+        let count = self.cheats.len();
+        self.cheats.retain(|active| active != cheat);
+
+        self.cheats.len() != count
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Value read by the CPU at ```addr``` once patched by the first matching cheat.
+    pub fn patch(&self, addr: u16, value: u8) -> u8 {
+        self.cheats.iter()
+            .find_map(|cheat| cheat.apply(addr, value))
+            .unwrap_or(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CheatError {
+    InvalidCode(String),
+}
+
+impl Error for CheatError {}
+
+impl Display for CheatError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            CheatError::InvalidCode(code) => write!(f, "invalid cheat code: {}", code),
+        }
+    }
+}
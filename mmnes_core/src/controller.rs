@@ -7,21 +7,26 @@ use crate::key_event::KeyEvents;
 pub enum ControllerType {
     #[default]
     StandardController,
+    FamicomWithMic,
+    FourScore,
 }
 
 impl Display for ControllerType {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             ControllerType::StandardController => write!(f, "controller type: Standard Controller"),
+            ControllerType::FamicomWithMic => write!(f, "controller type: Famicom Controller with Microphone"),
+            ControllerType::FourScore => write!(f, "controller type: Four Score"),
         }
     }
 }
 
 impl PartialEq for ControllerType {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (ControllerType::StandardController, ControllerType::StandardController) => true
-        }
+        matches!((self, other),
+            (ControllerType::StandardController, ControllerType::StandardController) |
+            (ControllerType::FamicomWithMic, ControllerType::FamicomWithMic) |
+            (ControllerType::FourScore, ControllerType::FourScore))
     }
 }
 
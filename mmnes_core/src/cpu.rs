@@ -1,9 +1,12 @@
+use std::cell::RefCell;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
 use crate::memory::MemoryError;
 #[cfg(test)]
 use mockall::mock;
 use crate::cpu_debugger::{Breakpoints, CpuSnapshot};
+use crate::ppu::PpuClock;
 
 #[derive(Default, Debug, Clone)]
 pub enum CpuType {
@@ -13,6 +16,10 @@ pub enum CpuType {
 
 pub trait CPU: Interruptible + Debug {
     fn reset(&mut self) -> Result<(), CpuError>;
+
+    /// Reset button: the registers are preserved, the stack pointer is decremented by 3 (the reset sequence
+    /// performs 3 suppressed pushes), interrupts are disabled and the PC is reloaded from the reset vector.
+    fn soft_reset(&mut self) -> Result<(), CpuError>;
     fn initialize(&mut self) -> Result<(), CpuError>;
     fn panic(&self, error: &CpuError);
     fn dump_registers(&self);
@@ -23,6 +30,18 @@ pub trait CPU: Interruptible + Debug {
     /// Execute 1 single instruction and return the number of cycles used.
     fn step_instruction(&mut self) -> Result<u32, CpuError>;
 
+    /// Execute exactly ```count``` instructions and return the number of cycles used.
+    /// An interrupt is taken at the end of the instruction it follows, it is not counted as an instruction.
+    fn run_instructions(&mut self, count: u64) -> Result<u32, CpuError> {
+        let mut cycles = 0;
+
+        for _ in 0..count {
+            cycles += self.step_instruction()?;
+        }
+
+        Ok(cycles)
+    }
+
     /// Run the CPU for at least the specified number of cycles, returning the new cycle count after execution.  
     /// ```start_cycle```: current cycle of execution,  
     /// ```credits```: the number of cycles available to execute instructions
@@ -34,17 +53,37 @@ pub trait CPU: Interruptible + Debug {
     /// ```credits```: the number of cycles available to execute instructions
     /// ```breakpoints```: the breakpoints halting the execution
     fn run_until_breakpoint(&mut self, start_cycle: u32, credits: u32, breakpoints: Box<dyn Breakpoints>) -> Result<(u32, bool), CpuError>;
+
+    /// Run the CPU until the current subroutine returns (step out), or for at most ```max_instructions``` instructions,
+    /// returning a pair containing the number of cycles used and a boolean indicating whether the subroutine returned.
+    fn run_until_return(&mut self, max_instructions: u64) -> Result<(u32, bool), CpuError>;
     
     fn set_pc_immediate(&mut self, address: u16) -> Result<(), CpuError>;
     fn set_pc_indirect(&mut self, address: u16) -> Result<(), CpuError>;
     fn snapshot(&self) -> Result<Box<dyn CpuSnapshot>, CpuError>;
+
+    /// Share the PPU dot clock with the CPU, which advances it by the cycles of every executed instruction.
+    fn attach_ppu_clock(&mut self, clock: Rc<RefCell<PpuClock>>);
+
+    /// Number of instructions executed since the CPU was created.
+    fn instructions_executed(&self) -> u64;
+
+    /// The registers and the pending interrupts, for the save states. Defaults to an empty state.
+    fn serialize_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores a state given by ```serialize_state```, the default ignores it.
+    fn deserialize_state(&mut self, _state: &[u8]) -> Result<(), CpuError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum CpuError {
     MemoryError(MemoryError),
-    Unimplemented(String),
-    InvalidOperand(String),
+    Unimplemented { opcode: u8 },
+    InvalidOperand { opcode: u8, mode: &'static str },
     StackOverflow(u16),
     StackUnderflow(u16),
     ConfigurationError(String),
@@ -71,14 +110,34 @@ impl Display for CpuError {
             CpuError::MemoryError(error) => write!(f, "-> memory error: {}", error),
             CpuError::StackOverflow(addr) => { write!(f, "stack overflow 0x{:04X}", addr) },
             CpuError::StackUnderflow(addr) => { write!(f, "stack underflow 0x{:04X}", addr) },
-            CpuError::InvalidOperand(s) => { write!(f, "missing or invalid operand: {}", s) },
+            CpuError::InvalidOperand { opcode, mode } => { write!(f, "missing or invalid operand: opcode 0x{:02X}, {} addressing", opcode, mode) },
             CpuError::ConfigurationError(s) => { write!(f, "configuration error: {}", s) },
-            CpuError::Unimplemented(s) => { write!(f, "unimplemented: {}", s) },
+            CpuError::Unimplemented { opcode } => { write!(f, "unimplemented: opcode 0x{:02X}", opcode) },
             CpuError::Halted(addr) => { write!(f, "cpu halted 0x{:04X}", addr) }
         }
     }
 }
 
+/***
+ * An interrupt asserted from the outside of the console (the debugger), to test the interrupt handlers.
+ * The IRQ is the one of a source (see cpu_6502::APU_FRAME_COUNTER_IRQ, ...): it is only taken when the
+ * interrupt disable flag is clear, and stays asserted until the handler acknowledges its source.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptKind {
+    Nmi,
+    Irq(u8),
+}
+
+impl Display for InterruptKind {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            InterruptKind::Nmi => write!(f, "NMI"),
+            InterruptKind::Irq(source) => write!(f, "IRQ (source 0x{:02X})", source),
+        }
+    }
+}
+
 pub trait Interruptible {
     fn signal_irq(&mut self, irq_source: u8) -> Result<(), CpuError>;
     fn clear_irq(&mut self, irq_source: u8) -> Result<(), CpuError>;
@@ -87,6 +146,8 @@ pub trait Interruptible {
     fn signal_nmi(&mut self) -> Result<(), CpuError>;
     fn clear_nmi(&mut self) -> Result<(), CpuError>;
     fn is_asserted_nmi(&self) -> Result<bool, CpuError>;
+    /// Samples the NMI and IRQ lines, the interrupt polled is the one taken at the end of the current instruction.
+    fn poll(&mut self) -> Result<(), CpuError>;
 }
 
 #[cfg(test)]
@@ -96,6 +157,7 @@ mock! {
 
     impl CPU for CpuStub {
         fn reset(&mut self) -> Result<(), CpuError>;
+        fn soft_reset(&mut self) -> Result<(), CpuError>;
         fn initialize(&mut self) -> Result<(), CpuError>;
         fn panic(&self, error: &CpuError);
         fn dump_registers(&self);
@@ -108,6 +170,9 @@ mock! {
         fn snapshot(&self) -> Result<Box<dyn CpuSnapshot>, CpuError>;
         fn step_instruction(&mut self) -> Result<u32, CpuError>;
         fn run_until_breakpoint(&mut self, start_cycle: u32, credits: u32, breakpoints: Box<dyn Breakpoints>) -> Result<(u32, bool), CpuError>;
+        fn run_until_return(&mut self, max_instructions: u64) -> Result<(u32, bool), CpuError>;
+        fn attach_ppu_clock(&mut self, clock: Rc<RefCell<PpuClock>>);
+        fn instructions_executed(&self) -> u64;
     }
 
     impl Interruptible for CpuStub {
@@ -118,5 +183,6 @@ mock! {
         fn signal_nmi(&mut self) -> Result<(), CpuError>;
         fn clear_nmi(&mut self) -> Result<(), CpuError>;
         fn is_asserted_nmi(&self) -> Result<bool, CpuError>;
+        fn poll(&mut self) -> Result<(), CpuError>;
     }
 }
\ No newline at end of file
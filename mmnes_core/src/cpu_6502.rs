@@ -1,13 +1,19 @@
 use std::fmt;
 use std::cell::RefCell;
 use std::fmt::{Debug, Display, Formatter};
+#[cfg(feature = "tracing")]
+use std::io::Write;
 use std::rc::Rc;
 use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use crate::bus::Bus;
 use crate::cpu::{CPU, CpuError, Interruptible};
 use crate::cpu_debugger::{Breakpoints, CpuSnapshot};
-use crate::memory::{MemoryError};
+#[cfg(feature = "tracing")]
+use crate::cpu_tracer::Tracer;
+use crate::memory::{Memory, MemoryError};
+use crate::ppu::PpuClock;
+use crate::save_state::{SaveStateError, StateReader};
 
 //const CLOCK_HZ: usize = 1_789_773;
 const STACK_BASE_ADDRESS: u16 = 0x0100;
@@ -18,6 +24,7 @@ const NMI_VECTOR: u16 = 0xFFFA;
 const BRK_VECTOR: u16 = 0xFFFE;
 const RESET_VECTOR: u16 = 0xFFFC;
 const NUM_OP_CODES: usize = 256;
+const RESET_SEQUENCE_CYCLES: u64 = 7;
 
 static INSTRUCTION_TABLE: Lazy<Vec<Instruction>> = Lazy::new(|| {
     Cpu6502::build_instruction_table()
@@ -26,6 +33,7 @@ static INSTRUCTION_TABLE: Lazy<Vec<Instruction>> = Lazy::new(|| {
 macro_rules! add_instruction {
             ($table:ident, $opcode:expr, $op:ident, $addr_mode:ident, $bytes:expr, $cycles:expr, $exec:ident, $category:ident) => {
                 $table[$opcode] = Instruction {
+                    code: $opcode as u8,
                     opcode: OpCode::$op,
                     addressing_mode: AddressingMode::$addr_mode,
                     bytes: $bytes,
@@ -90,6 +98,26 @@ enum AddressingMode {
     IndirectIndexedY,       // val = PEEK(PEEK(arg) + PEEK((arg + 1) % 256) * 256 + Y)
 }
 
+impl AddressingMode {
+    fn name(&self) -> &'static str {
+        match self {
+            AddressingMode::Implicit => "implicit",
+            AddressingMode::Accumulator => "accumulator",
+            AddressingMode::Immediate => "immediate",
+            AddressingMode::ZeroPage => "zero page",
+            AddressingMode::ZeroPageIndexedX => "zero page,X",
+            AddressingMode::ZeroPageIndexedY => "zero page,Y",
+            AddressingMode::Absolute => "absolute",
+            AddressingMode::AbsoluteIndexedX => "absolute,X",
+            AddressingMode::AbsoluteIndexedY => "absolute,Y",
+            AddressingMode::Relative => "relative",
+            AddressingMode::Indirect => "indirect",
+            AddressingMode::IndirectIndexedX => "(indirect,X)",
+            AddressingMode::IndirectIndexedY => "(indirect),Y",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum InstructionCategory {
     Standard,
@@ -137,6 +165,27 @@ impl Registers {
         }
     }
 
+    /***
+     * B and the unused bit only exist on the stack: PHP and BRK push them set, an interrupt pushes B clear.
+     * Pulled by PLP or RTI, they are ignored and the register keeps B clear and the unused bit set.
+     * https://www.nesdev.org/wiki/Status_flags#The_B_flag
+     ***/
+    fn set_status_from_stack(&mut self, status: u8) {
+        let stack_only = StatusFlag::BreakCommand.bits() | StatusFlag::Unused.bits();
+        self.p = (status & !stack_only) | StatusFlag::Unused.bits();
+    }
+
+    /// The status pushed by an interrupt (```brk```: by BRK), with the stack only bits.
+    fn status_for_stack(&self, brk: bool) -> u8 {
+        let status = self.p | StatusFlag::Unused.bits();
+
+        if brk {
+            status | StatusFlag::BreakCommand.bits()
+        } else {
+            status & !StatusFlag::BreakCommand.bits()
+        }
+    }
+
     fn get_status(&self, flag: StatusFlag) -> bool {
         //debug!("CPU: status flag: {:?}, {:04X}", flag, flag.bits());
         (self.p & flag.bits()) != 0
@@ -164,6 +213,21 @@ pub const APU_FRAME_COUNTER_IRQ: u8 = 0x01;
 pub const APU_DMC_IRQ: u8 = 0x02;
 pub const PPU_NMI: u8 = 0x80;
 
+/***
+ * Value stored by the unstable SH* opcodes (SHA, SHX, SHY and TAS):
+ *   - StableHigh: always ANDed with the high byte of the address + 1,
+ *   - Hardware: when the RDY line is pulled low (DMA) in the cycle following the opcode fetch,
+ *     the AND with the high byte drops off and the plain value is stored at the effective address.
+ *
+ * https://hitmen.c02.at/files/docs/c64/NoMoreSecrets-NMOS6510UnintendedOpcodes-20162412.pdf
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnstableOpcodeMode {
+    #[default]
+    StableHigh,
+    Hardware
+}
+
 #[derive(Debug, Default)]
 struct InterruptMask(u8);
 
@@ -189,6 +253,13 @@ impl InterruptMask {
     }
 }
 
+/// The interrupt sampled by the last poll, taken at the end of the instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PolledInterrupt {
+    Nmi,
+    Irq,
+}
+
 #[derive(Clone, Debug)]
 struct Cpu6502Snapshot {
     registers: Registers,
@@ -197,6 +268,8 @@ struct Cpu6502Snapshot {
     is_illegal: bool,
     operand: String,
     cycles: u32,
+    base_cycles: u32,
+    page_cross_penalty: bool,
 }
 
 impl CpuSnapshot for Cpu6502Snapshot {
@@ -226,16 +299,25 @@ impl CpuSnapshot for Cpu6502Snapshot {
     fn cycles(&self) -> u32 {
         self.cycles
     }
+
+    fn base_cycles(&self) -> u32 {
+        self.base_cycles
+    }
+
+    fn page_cross_penalty(&self) -> bool {
+        self.page_cross_penalty
+    }
 }
 
 impl Cpu6502Snapshot {
 
     fn new(registers: Registers, bus: Rc<RefCell<dyn Bus>>, cycles: u32) -> Result<Cpu6502Snapshot, CpuError> {
-        let byte = bus.borrow().read_byte(registers.pc)?;
+        // a snapshot only looks at the CPU: the opcode is peeked and the dummy reads are not performed
+        let byte = bus.borrow().trace_read_byte(registers.pc)?;
         let instr0 = Cpu6502::decode_instruction(byte)?;
         let instruction = Cpu6502Snapshot::build_instruction(instr0, &registers, bus.clone())?;
         let mnemonic = instr0.opcode.to_string();
-        let oper0 = Cpu6502::fetch_operand(instr0, &registers, bus.clone())?;
+        let oper0 = Cpu6502::fetch_operand(instr0, &registers, bus.clone(), false)?;
         let operand = Cpu6502Snapshot::build_operand(&instr0, &oper0, &registers, bus.clone())?;
         
         let snapshot = Cpu6502Snapshot {
@@ -245,6 +327,8 @@ impl Cpu6502Snapshot {
             is_illegal: instr0.category == InstructionCategory::Illegal,
             operand,
             cycles,
+            base_cycles: instr0.cycles,
+            page_cross_penalty: instr0.can_cross_page(),
         };
         
         Ok(snapshot)
@@ -311,9 +395,7 @@ impl Cpu6502Snapshot {
                 format!("#${:02X}", byte),
 
             _ => {
-                return Err(CpuError::InvalidOperand(
-                    format!("could not format instruction and operand: {:?}, {:?}", instruction.addressing_mode, operand)
-                ))
+                return Err(instruction.invalid_operand())
             }
         };
 
@@ -327,7 +409,16 @@ pub struct Cpu6502 {
     bus: Rc<RefCell<dyn Bus>>,
     instructions_executed: u64,
     interrupt: InterruptMask,
+    polled_interrupt: Option<PolledInterrupt>,
     cycles: u32,
+    total_cycles: u64,
+    #[cfg(feature = "tracing")]
+    tracer: Option<Tracer>,
+    ppu_clock: Option<Rc<RefCell<PpuClock>>>,
+    decimal_mode: bool,
+    unstable_opcode_mode: UnstableOpcodeMode,
+    halt_on_unimplemented: bool,
+    rdy_low: bool,
 }
 
 impl Interruptible for Cpu6502 {
@@ -364,6 +455,19 @@ impl Interruptible for Cpu6502 {
     fn is_asserted_nmi(&self) -> Result<bool, CpuError> {
         Ok(self.interrupt.has_nmi())
     }
+
+    /// The NMI wins over the IRQ, the IRQ is only polled when the interrupt disable flag is clear.
+    fn poll(&mut self) -> Result<(), CpuError> {
+        self.polled_interrupt = if self.is_asserted_nmi()? {
+            Some(PolledInterrupt::Nmi)
+        } else if self.is_asserted_irq()? && !self.registers.get_status(StatusFlag::InterruptDisable) {
+            Some(PolledInterrupt::Irq)
+        } else {
+            None
+        };
+
+        Ok(())
+    }
 }
 
 impl CPU for Cpu6502 {
@@ -378,6 +482,23 @@ impl CPU for Cpu6502 {
         self.registers.set_status(StatusFlag::Unused, true);
         self.registers.sp = 0xFD;
         self.set_pc_indirect(RESET_VECTOR)?;
+        self.total_cycles = RESET_SEQUENCE_CYCLES;
+        self.sync_ppu_clock();
+
+        Ok(())
+    }
+
+    /***
+     * https://www.nesdev.org/wiki/CPU_power_up_state#After_reset
+     ***/
+    fn soft_reset(&mut self) -> Result<(), CpuError> {
+        info!("soft resetting CPU");
+
+        self.registers.sp = self.registers.sp.wrapping_sub(3);
+        self.registers.set_status(StatusFlag::InterruptDisable, true);
+        self.set_pc_indirect(RESET_VECTOR)?;
+        self.total_cycles = RESET_SEQUENCE_CYCLES;
+        self.sync_ppu_clock();
 
         Ok(())
     }
@@ -422,25 +543,14 @@ impl CPU for Cpu6502 {
     }
 
     fn step_instruction(&mut self) -> Result<u32, CpuError> {
-        let byte = self.bus.borrow().read_byte(self.registers.pc)?;
-        let instruction = Cpu6502::decode_instruction(byte)?;
-        let operand = Cpu6502::fetch_operand(instruction, &self.registers, self.bus.clone())?;
-
-        let additional_cycles = self.execute_instruction(&instruction, &operand)?;
-        let cycles = instruction.cycles + additional_cycles;
-
-        if self.registers.is_pc_dirty == false {
-            self.registers.pc = self.registers.safe_pc_add(instruction.bytes as i16)?;
-        } else {
-            self.registers.is_pc_dirty = false;
+        #[cfg(feature = "tracing")]
+        if self.tracer.is_some() {
+            self.trace()?;
         }
 
-        self.instructions_executed += 1;
-        self.cycles += cycles;
-
-        self.interrupt()?;  // some additional cycles are probably needed here (7?)
-
-        Ok(cycles)
+        let byte = self.bus.borrow().read_byte(self.registers.pc)?;
+        let instruction = Cpu6502::decode_instruction(byte)?;
+        self.step_decoded_instruction(instruction)
     }
 
     fn run(&mut self, start_cycle: u32, credits: u32) -> Result<u32, CpuError> {
@@ -477,6 +587,33 @@ impl CPU for Cpu6502 {
         Ok((cycles, false))
     }
 
+    /***
+     * The stack pointer is captured when the command is issued: the return address of the current subroutine
+     * is just above it. The subroutine returns when an RTS pops from there, i.e. when an RTS executes with
+     * the stack pointer at (or above) the captured one.
+     * An interrupt (or a nested JSR) taken in the meantime pushes below the captured stack pointer,
+     * so the RTS of the nested subroutines do not match.
+     * The decision is taken before the RTS executes: an interrupt taken right after it does not hide the return.
+     ***/
+    fn run_until_return(&mut self, max_instructions: u64) -> Result<(u32, bool), CpuError> {
+        let frame_sp = self.registers.sp;
+        let mut cycles = 0;
+
+        for _ in 0..max_instructions {
+            let byte = self.bus.borrow().read_byte(self.registers.pc)?;
+            let opcode = Cpu6502::decode_instruction(byte)?.opcode;
+            let is_frame_return = matches!(opcode, OpCode::RTS) && (self.registers.sp.wrapping_sub(frame_sp) as i8) >= 0;
+
+            cycles += self.step_instruction()?;
+
+            if is_frame_return {
+                return Ok((cycles, true));
+            }
+        }
+
+        Ok((cycles, false))
+    }
+
     fn set_pc_immediate(&mut self, address: u16) -> Result<(), CpuError> {
         self.registers.pc = address;
         //debug!("CPU: pc set to effective address 0x{:04X}", self.registers.pc);
@@ -506,9 +643,92 @@ impl CPU for Cpu6502 {
         let snapshot = Cpu6502Snapshot::new(registers, self.bus.clone(), self.cycles)?;
         Ok(Box::new(snapshot))
     }
+
+    fn attach_ppu_clock(&mut self, clock: Rc<RefCell<PpuClock>>) {
+        self.ppu_clock = Some(clock);
+        self.sync_ppu_clock();
+    }
+
+    fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// A, X, Y, P, SP, PC, the pending interrupts and the total of cycles.
+    fn serialize_state(&self) -> Vec<u8> {
+        let mut state = vec![self.registers.a, self.registers.x, self.registers.y, self.registers.p, self.registers.sp];
+
+        state.extend_from_slice(&self.registers.pc.to_le_bytes());
+        state.push(self.interrupt.0);
+        state.extend_from_slice(&self.total_cycles.to_le_bytes());
+
+        state
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), CpuError> {
+        self.restore_state(&mut StateReader::new(state)).map_err(MemoryError::from)?;
+        Ok(())
+    }
 }
 
 impl Cpu6502 {
+    fn restore_state(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.registers.a = reader.byte()?;
+        self.registers.x = reader.byte()?;
+        self.registers.y = reader.byte()?;
+        self.registers.p = reader.byte()?;
+        self.registers.sp = reader.byte()?;
+        self.registers.pc = reader.word()?;
+        self.registers.is_pc_dirty = false;
+        self.interrupt = InterruptMask(reader.byte()?);
+        self.total_cycles = reader.long()?;
+
+        Ok(())
+    }
+
+    /***
+     * Static disassembly of the instruction at ```addr```, without the registers: the operand is shown as
+     * written in the program (e.g. $0200,X), a branch with its target. The bytes are read without side effects.
+     * Returns the line, in the tracer format without the registers, and the address of the next instruction:
+     *
+     * C000  4C F5 C5  JMP $C5F5
+     ***/
+    pub fn disassemble(memory: &dyn Memory, addr: u16) -> Result<(String, u16), CpuError> {
+        let instruction = Cpu6502::decode_instruction(memory.trace_read_byte(addr)?)?;
+
+        let bytes = (0..instruction.bytes as u16)
+            .map(|i| memory.trace_read_byte(addr.wrapping_add(i)))
+            .collect::<Result<Vec<u8>, MemoryError>>()?;
+
+        let byte = bytes.get(1).copied().unwrap_or_default();
+        let word = (bytes.get(2).copied().unwrap_or_default() as u16) << 8 | byte as u16;
+
+        let operand = match instruction.addressing_mode {
+            AddressingMode::Implicit => "".to_string(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", byte),
+            AddressingMode::ZeroPage => format!("${:02X}", byte),
+            AddressingMode::ZeroPageIndexedX => format!("${:02X},X", byte),
+            AddressingMode::ZeroPageIndexedY => format!("${:02X},Y", byte),
+            AddressingMode::Absolute => format!("${:04X}", word),
+            AddressingMode::AbsoluteIndexedX => format!("${:04X},X", word),
+            AddressingMode::AbsoluteIndexedY => format!("${:04X},Y", word),
+            AddressingMode::Relative => format!("${:04X}", addr.wrapping_add(2).wrapping_add(byte as i8 as u16)),
+            AddressingMode::Indirect => format!("(${:04X})", word),
+            AddressingMode::IndirectIndexedX => format!("(${:02X},X)", byte),
+            AddressingMode::IndirectIndexedY => format!("(${:02X}),Y", byte),
+        };
+
+        let hex = bytes.iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let illegal_marker = if instruction.category == InstructionCategory::Illegal { "*" } else { " " };
+        let line = format!("{:04X}  {:<9}{}{} {}", addr, hex, illegal_marker, instruction.opcode, operand);
+
+        Ok((line.trim_end().to_string(), addr.wrapping_add(instruction.bytes as u16)))
+    }
+
     pub fn new(bus: Rc<RefCell<dyn Bus>>) -> Self {
         Cpu6502 {
             registers: Registers {
@@ -523,32 +743,124 @@ impl Cpu6502 {
             bus,
             instructions_executed: 0,
             interrupt: InterruptMask::default(),
+            polled_interrupt: None,
             cycles: 0,
+            total_cycles: 0,
+            #[cfg(feature = "tracing")]
+            tracer: None,
+            ppu_clock: None,
+            decimal_mode: false,
+            unstable_opcode_mode: UnstableOpcodeMode::default(),
+            halt_on_unimplemented: false,
+            rdy_low: false,
+        }
+    }
+
+    pub fn set_unstable_opcode_mode(&mut self, mode: UnstableOpcodeMode) {
+        info!("CPU: unstable opcode mode: {:?}", mode);
+        self.unstable_opcode_mode = mode;
+    }
+
+    /// An opcode without implementation, or an unofficial one, stops the CPU with ```CpuError::Unimplemented```
+    /// instead of being skipped or executed.
+    pub fn set_halt_on_unimplemented(&mut self, enabled: bool) {
+        info!("CPU: halt on unimplemented opcodes {}", if enabled { "enabled" } else { "disabled" });
+        self.halt_on_unimplemented = enabled;
+    }
+
+    /// A DMA halts the CPU during the next instruction: the RDY line is released once it is executed.
+    pub fn pull_rdy_low(&mut self) {
+        self.rdy_low = true;
+    }
+
+    fn is_sh_high_byte_dropped(&self) -> bool {
+        self.unstable_opcode_mode == UnstableOpcodeMode::Hardware && self.rdy_low
+    }
+
+    /// The 2A03 has the D flag but no BCD circuitry, a generic 6502 honours it.
+    /// Only the decimal adjustment of ARR is implemented: ADC and SBC stay binary.
+    pub fn set_decimal_mode(&mut self, enabled: bool) {
+        info!("CPU: decimal mode {}", if enabled { "enabled" } else { "disabled" });
+        self.decimal_mode = enabled;
+    }
+
+    fn is_decimal(&self) -> bool {
+        self.decimal_mode && self.registers.get_status(StatusFlag::DecimalMode)
+    }
+
+    /// Write a Nintendulator-style line for every executed instruction, suitable to be diffed against nestest.log
+    #[cfg(feature = "tracing")]
+    pub fn enable_tracing(&mut self, writer: Box<dyn Write>) {
+        self.enable_tracing_filtered(writer, None);
+    }
+
+    /// Same as ```enable_tracing```, only the instructions whose PC is in ```pc_range``` (inclusive) are written.
+    #[cfg(feature = "tracing")]
+    pub fn enable_tracing_filtered(&mut self, writer: Box<dyn Write>, pc_range: Option<(u16, u16)>) {
+        match pc_range {
+            Some((lo, hi)) => info!("CPU: tracing enabled for 0x{:04X} - 0x{:04X}", lo, hi),
+            None => info!("CPU: tracing enabled"),
+        }
+
+        self.tracer = Some(Tracer::with_pc_range(writer, pc_range));
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn disable_tracing(&mut self) {
+        if let Some(mut tracer) = self.tracer.take()
+            && let Err(e) = tracer.flush() {
+            warn!("CPU: failed to flush trace: {}", e);
+        }
+    }
+
+    /// Realign the PPU dot clock with the CPU cycles elapsed since power-up or reset.
+    fn sync_ppu_clock(&self) {
+        if let Some(clock) = &self.ppu_clock {
+            let mut clock = clock.borrow_mut();
+            clock.reset();
+            clock.advance(self.total_cycles);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace(&mut self) -> Result<(), CpuError> {
+        if !self.tracer.as_ref().is_some_and(|tracer| tracer.is_traced(self.registers.pc)) {
+            return Ok(());
+        }
+
+        let snapshot = Cpu6502Snapshot::new(self.registers.clone(), self.bus.clone(), self.cycles)?;
+        let ppu_position = match &self.ppu_clock {
+            Some(clock) => (clock.borrow().scanline(), clock.borrow().dot()),
+            None => Tracer::ppu_position_from_cycles(self.total_cycles),
+        };
+
+        if let Some(tracer) = self.tracer.as_mut()
+            && let Err(e) = tracer.trace(&snapshot, ppu_position, self.total_cycles) {
+            warn!("CPU: failed to write trace, disabling tracing: {}", e);
+            self.tracer = None;
         }
+
+        Ok(())
     }
 
+    /// Takes the interrupt polled during the instruction, an IRQ polled is taken even if its source was acknowledged since.
     fn interrupt(&mut self) -> Result<(), CpuError> {
-        if self.is_asserted_nmi()? {
-            self.nmi()?;
-            self.clear_nmi()?;
-        } else if self.is_asserted_irq()? && !self.registers.get_status(StatusFlag::InterruptDisable) {
-            self.irq()?;
+        match self.polled_interrupt.take() {
+            Some(PolledInterrupt::Nmi) => {
+                self.nmi()?;
+                self.clear_nmi()?;
+            },
+            Some(PolledInterrupt::Irq) => self.irq()?,
+            None => {},
         }
 
         Ok(())
     }
 
     fn build_instruction_table() -> Vec<Instruction> {
-        let illegal_instruction = Instruction {
-            opcode: OpCode::XXX,
-            addressing_mode: AddressingMode::Implicit,
-            bytes: 1,
-            cycles: 1,
-            execute: Instruction::illegal,
-            category: InstructionCategory::Standard
-        };
-
-        let mut table = [illegal_instruction; NUM_OP_CODES].to_vec();
+        let mut table = (0..NUM_OP_CODES)
+            .map(|code| Instruction::unimplemented(code as u8))
+            .collect::<Vec<Instruction>>();
         include!("instructions_macro_all.rs");
 
         //debug!("CPU: dumping instruction table:");
@@ -620,6 +932,13 @@ impl Cpu6502 {
         self.registers.set_status(StatusFlag::Negative, value & 0x80 != 0);
     }
 
+    /// Flags of CMP, CPX and CPY: C when ```register``` >= ```value```, Z and N from ```register``` - ```value```
+    /// (N is bit 7 of the wrapped subtraction, not the sign of the comparison).
+    fn compare(&mut self, register: u8, value: u8) {
+        self.update_flags_zero_negative(register.wrapping_sub(value));
+        self.registers.set_status(StatusFlag::Carry, register >= value);
+    }
+
     fn shift_left_and_update_carry_flags(&mut self, value: u8) -> u8 {
         let original_value = value;
         let result = value << 1;
@@ -640,11 +959,17 @@ impl Cpu6502 {
         result
     }
 
-    fn overwrite(&mut self, operand: &Operand, value: u8) -> Result<(), CpuError> {
+    /***
+     * read-modify-write instructions write the unmodified value back before the modified one (dummy write),
+     * the registers reacting to writes (mappers, PPU) see both.
+     ***/
+    fn overwrite(&mut self, operand: &Operand, original_value: u8, value: u8) -> Result<(), CpuError> {
         match operand {
             Operand::Address(addr) |
             Operand::AddressAndEffectiveAddress(_, addr, _) => {
-                self.bus.borrow_mut().write_byte(*addr, value)?;
+                let mut bus = self.bus.borrow_mut();
+                bus.write_byte(*addr, original_value)?;
+                bus.write_byte(*addr, value)?;
                 Ok(())
             },
 
@@ -654,11 +979,19 @@ impl Cpu6502 {
             },
 
             _ => {
-                Err(CpuError::InvalidOperand(format!("{}", operand)))
+                Err(self.invalid_operand())
             }
         }
     }
 
+    /// Operand helpers run while PC still points to the opcode of the instruction being executed.
+    fn invalid_operand(&self) -> CpuError {
+        match self.bus.borrow().trace_read_byte(self.registers.pc) {
+            Ok(byte) => INSTRUCTION_TABLE[byte as usize].invalid_operand(),
+            Err(error) => CpuError::MemoryError(error),
+        }
+    }
+
     fn get_operand_byte_value(&self, operand: &Operand) -> Result<u8, CpuError> {
         let value = match operand {
             Operand::Accumulator => {
@@ -676,7 +1009,7 @@ impl Cpu6502 {
                 Ok(value)
             }
             Operand::None => {
-                Err(CpuError::InvalidOperand(format!("{}", operand)))
+                Err(self.invalid_operand())
             }
         };
 
@@ -693,7 +1026,7 @@ impl Cpu6502 {
                 let value = *effective;
                 Ok(value)
             },
-            _ => Err(CpuError::InvalidOperand(format!("{}", operand)))
+            _ => Err(self.invalid_operand())
         };
 
         value
@@ -706,6 +1039,21 @@ impl Cpu6502 {
         page1 != page2
     }
 
+    /***
+     * indexed accesses first read at the base page before the high byte is fixed up:
+     * loads only pay for it when the page is crossed, stores and read/modify/write instructions always perform it.
+     * the dummy read hits the bus, with the side effects of the targeted register (PPU status, data, mappers, ...).
+     * https://www.nesdev.org/wiki/CPU_addressing_modes
+     ***/
+    fn dummy_read_on_indexed_access(instruction: &Instruction, base_addr: u16, effective_addr: u16, page_crossed: bool, bus: &Rc<RefCell<dyn Bus>>) -> Result<(), CpuError> {
+        if page_crossed || instruction.writes_to_memory() {
+            let unfixed_addr = (base_addr & 0xFF00) | (effective_addr & 0x00FF);
+            bus.borrow().read_byte(unfixed_addr)?;
+        }
+
+        Ok(())
+    }
+
     fn get_cycles_by_page_crossing_for_conditional_jump(&self, source: u16, destination: u16) -> u32 {
         if Cpu6502::is_page_crossed(source, destination) { 2 } else { 1 }
     }
@@ -727,7 +1075,8 @@ impl Cpu6502 {
         Ok(instruction)
     }
 
-    fn fetch_operand(instruction: &Instruction, registers: &Registers, bus: Rc<RefCell<dyn Bus>>) -> Result<Operand, CpuError> {
+    /// ```executed```: the instruction is being executed, the dummy reads of the indexed accesses hit the bus.
+    fn fetch_operand(instruction: &Instruction, registers: &Registers, bus: Rc<RefCell<dyn Bus>>, executed: bool) -> Result<Operand, CpuError> {
 
         //debug!("CPU: fetching operand for instruction: {:?}, {:?}", instruction.opcode, instruction.addressing_mode);
 
@@ -758,6 +1107,9 @@ impl Cpu6502 {
                 let addr = bus.borrow().read_word(pc)?;
                 let effective_addr = addr.wrapping_add(registers.x as u16);
                 let page_crossed = Cpu6502::is_page_crossed(addr, effective_addr);
+                if executed {
+                    Cpu6502::dummy_read_on_indexed_access(instruction, addr, effective_addr, page_crossed, &bus)?;
+                }
 
                 Operand::AddressAndEffectiveAddress(addr, effective_addr, page_crossed)
             }
@@ -767,6 +1119,9 @@ impl Cpu6502 {
                 let addr = bus.borrow().read_word(pc)?;
                 let effective_addr = addr.wrapping_add(registers.y as u16);
                 let page_crossed = Cpu6502::is_page_crossed(addr, effective_addr);
+                if executed {
+                    Cpu6502::dummy_read_on_indexed_access(instruction, addr, effective_addr, page_crossed, &bus)?;
+                }
 
                 Operand::AddressAndEffectiveAddress(addr, effective_addr, page_crossed)
             }
@@ -815,9 +1170,12 @@ impl Cpu6502 {
             AddressingMode::IndirectIndexedY => {
                 let pc = registers.safe_pc_add(1)?;
                 let addr = bus.borrow().read_byte(pc)?;
-                let indirect_addr = Cpu6502::read_word_with_page_wrap(addr as u16, bus)?;
+                let indirect_addr = Cpu6502::read_word_with_page_wrap(addr as u16, bus.clone())?;
                 let effective_addr = indirect_addr.wrapping_add(registers.y as u16);
                 let page_crossed = Cpu6502::is_page_crossed(indirect_addr, effective_addr);
+                if executed {
+                    Cpu6502::dummy_read_on_indexed_access(instruction, indirect_addr, effective_addr, page_crossed, &bus)?;
+                }
 
                 Operand::AddressAndEffectiveAddress(addr as u16, effective_addr, page_crossed)
             },
@@ -843,6 +1201,55 @@ impl Cpu6502 {
         Ok(cycles)
     }
 
+    fn step_decoded_instruction(&mut self, instruction: &Instruction) -> Result<u32, CpuError> {
+        // stopped before its operand is fetched, PC stays on the opcode
+        if self.halt_on_unimplemented && instruction.is_unofficial() {
+            return instruction.unimplemented_opcode(self);
+        }
+
+        let operand = Cpu6502::fetch_operand(instruction, &self.registers, self.bus.clone(), true)?;
+
+        /***
+         * the lines are polled during the penultimate cycle of the instruction, i.e. before its last bus access
+         * (the write of a store, the read of a load...): an interrupt signalled by that access is taken after the
+         * next instruction, and CLI / SEI / PLP change the interrupt disable flag after the poll.
+         * RTI restores the flags before the poll, it polls once executed.
+         * https://www.nesdev.org/wiki/CPU_interrupts#Detailed_interrupt_behavior
+         ***/
+        let polls_after_execution = matches!(instruction.opcode, OpCode::RTI);
+
+        if !polls_after_execution {
+            self.poll()?;
+        }
+
+        let additional_cycles = self.execute_instruction(&instruction, &operand)?;
+
+        if polls_after_execution {
+            self.poll()?;
+        }
+
+        let cycles = instruction.cycles + additional_cycles;
+
+        if self.registers.is_pc_dirty == false {
+            self.registers.pc = self.registers.safe_pc_add(instruction.bytes as i16)?;
+        } else {
+            self.registers.is_pc_dirty = false;
+        }
+
+        self.rdy_low = false;
+        self.instructions_executed += 1;
+        self.cycles += cycles;
+        self.total_cycles += cycles as u64;
+
+        if let Some(clock) = &self.ppu_clock {
+            clock.borrow_mut().advance(cycles as u64);
+        }
+
+        self.interrupt()?;  // some additional cycles are probably needed here (7?)
+
+        Ok(cycles)
+    }
+
     fn execute_instruction(&mut self, instruction: &Instruction, operand: &Operand) -> Result<u32, CpuError> {
 
         //debug!("CPU: executing instruction: opcode: {:?}, addressing mode: {:?}, operand: {}",
@@ -852,11 +1259,9 @@ impl Cpu6502 {
     }
 
     fn interrupt_preamble(&mut self) -> Result<(), CpuError> {
-        self.registers.p &= !StatusFlag::BreakCommand.bits();
-
         self.push_stack((self.registers.pc >> 8) as u8)?;
         self.push_stack((self.registers.pc & 0xFF) as u8)?;
-        self.push_stack(self.registers.p)?;
+        self.push_stack(self.registers.status_for_stack(false))?;
 
         self.registers.set_status(StatusFlag::InterruptDisable, true);
         Ok(())
@@ -901,6 +1306,7 @@ impl Cpu6502 {
 
 #[derive(Clone, Copy, Debug)]
 struct Instruction {
+    code: u8,
     opcode: OpCode,
     addressing_mode: AddressingMode,
     bytes: usize,
@@ -911,6 +1317,67 @@ struct Instruction {
 
 impl Instruction {
 
+    /// The entry of an opcode missing from the instruction table.
+    fn unimplemented(code: u8) -> Instruction {
+        Instruction {
+            code,
+            opcode: OpCode::XXX,
+            addressing_mode: AddressingMode::Implicit,
+            bytes: 1,
+            cycles: 1,
+            execute: Instruction::illegal,
+            category: InstructionCategory::Standard
+        }
+    }
+
+    /// Skipped with a warning, unless the CPU halts on the unimplemented opcodes.
+    fn unimplemented_opcode(&self, cpu: &mut Cpu6502) -> Result<u32, CpuError> {
+        if cpu.halt_on_unimplemented {
+            return Err(CpuError::Unimplemented { opcode: self.code });
+        }
+
+        warn!("CPU: unimplemented opcode 0x{:02X} at {:04X}", self.code, cpu.registers.pc);
+        Ok(0)
+    }
+
+    /// The unofficial opcodes, the JAM ones excepted: they freeze the CPU on their own.
+    fn is_unofficial(&self) -> bool {
+        self.category == InstructionCategory::Illegal && !matches!(self.opcode, OpCode::JAM)
+    }
+
+    /// Stores and read/modify/write instructions, TAS being encoded as TAX with absolute,Y addressing.
+    fn writes_to_memory(&self) -> bool {
+        match self.opcode {
+            OpCode::STA | OpCode::STX | OpCode::STY | OpCode::SAX |
+            OpCode::SHA | OpCode::SHX | OpCode::SHY |
+            OpCode::ASL | OpCode::LSR | OpCode::ROL | OpCode::ROR | OpCode::INC | OpCode::DEC |
+            OpCode::SLO | OpCode::SRE | OpCode::RLA | OpCode::RRA | OpCode::DCP | OpCode::ISB => true,
+            OpCode::TAX => matches!(self.addressing_mode, AddressingMode::AbsoluteIndexedY),
+            _ => false
+        }
+    }
+
+    /***
+     * Loads through an indexed address take one more cycle when the index crosses a page.
+     * Branches are reported too: one more cycle when taken, and another one when the
+     * destination is on another page.
+     * https://www.nesdev.org/wiki/6502_cycle_times
+     ***/
+    fn can_cross_page(&self) -> bool {
+        match self.opcode {
+            OpCode::BCC | OpCode::BCS | OpCode::BEQ | OpCode::BMI |
+            OpCode::BNE | OpCode::BPL | OpCode::BVC | OpCode::BVS => true,
+            OpCode::ADC | OpCode::AND | OpCode::CMP | OpCode::EOR | OpCode::LDA | OpCode::LDX | OpCode::LDY |
+            OpCode::NOP | OpCode::ORA | OpCode::SBC | OpCode::LAS | OpCode::LAX =>
+                matches!(self.addressing_mode, AddressingMode::AbsoluteIndexedX | AddressingMode::AbsoluteIndexedY | AddressingMode::IndirectIndexedY),
+            _ => false
+        }
+    }
+
+    fn invalid_operand(&self) -> CpuError {
+        CpuError::InvalidOperand { opcode: self.code, mode: self.addressing_mode.name() }
+    }
+
     fn adc_add_memory_to_accumulator_with_carry(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let value = cpu.get_operand_byte_value(operand)?;
         let carry = cpu.registers.get_status(StatusFlag::Carry) as u8;
@@ -949,7 +1416,7 @@ impl Instruction {
         let result = cpu.shift_left_and_update_carry_flags(value);
 
         cpu.update_flags_zero_negative(result);
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         Ok(0)
     }
@@ -1060,13 +1527,11 @@ impl Instruction {
     }
 
     fn brk_force_break(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
-        cpu.registers.p |= StatusFlag::BreakCommand.bits();
-
         let next_pc = cpu.registers.safe_pc_add(2)?;
 
         cpu.push_stack((next_pc >> 8) as u8)?;
         cpu.push_stack((next_pc & 0xFF) as u8)?;
-        cpu.push_stack(cpu.registers.p)?;
+        cpu.push_stack(cpu.registers.status_for_stack(true))?;
 
         cpu.registers.set_status(StatusFlag::InterruptDisable, true);
 
@@ -1134,11 +1599,7 @@ impl Instruction {
         let value = cpu.get_operand_byte_value(operand)?;
         let cycles = cpu.get_cycles_by_page_crossing_for_load(operand);
 
-        let result = cpu.registers.a.wrapping_sub(value);
-
-        cpu.registers.set_status(StatusFlag::Zero, result == 0);
-        cpu.registers.set_status(StatusFlag::Negative, result & 0x80 != 0);
-        cpu.registers.set_status(StatusFlag::Carry, value <= cpu.registers.a);
+        cpu.compare(cpu.registers.a, value);
 
         Ok(cycles)
     }
@@ -1146,11 +1607,7 @@ impl Instruction {
     fn cpx_compare_memory_and_index_x(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let value = cpu.get_operand_byte_value(operand)?;
 
-        let result = cpu.registers.x.wrapping_sub(value);
-
-        cpu.registers.set_status(StatusFlag::Zero, result == 0);
-        cpu.registers.set_status(StatusFlag::Negative, result & 0x80 != 0);
-        cpu.registers.set_status(StatusFlag::Carry, value <= cpu.registers.x);
+        cpu.compare(cpu.registers.x, value);
 
         Ok(0)
     }
@@ -1158,11 +1615,7 @@ impl Instruction {
     fn cpy_compare_memory_and_index_y(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let value = cpu.get_operand_byte_value(operand)?;
 
-        let result = cpu.registers.y.wrapping_sub(value);
-
-        cpu.registers.set_status(StatusFlag::Zero, result == 0);
-        cpu.registers.set_status(StatusFlag::Negative, result & 0x80 != 0);
-        cpu.registers.set_status(StatusFlag::Carry, value <= cpu.registers.y);
+        cpu.compare(cpu.registers.y, value);
 
         Ok(0)
     }
@@ -1172,7 +1625,7 @@ impl Instruction {
         let result = value.wrapping_sub(1);
 
         cpu.update_flags_zero_negative(result);
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         Ok(0)
     }
@@ -1212,7 +1665,7 @@ impl Instruction {
         let result = value.wrapping_add(1);
 
         cpu.update_flags_zero_negative(result);
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         Ok(0)
     }
@@ -1298,7 +1751,7 @@ impl Instruction {
         let result = cpu.shift_right_and_update_carry_flags(value);
 
         cpu.update_flags_zero_negative(result);
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         Ok(0)
     }
@@ -1340,8 +1793,7 @@ impl Instruction {
      *     PLP  <-  0 0 - - 0 0 1 1  =  $03
      ***/
     fn php_push_processor_status_on_stack(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
-        let value = cpu.registers.p | StatusFlag::BreakCommand.bits() | StatusFlag::Unused.bits();
-        cpu.push_stack(value)?;
+        cpu.push_stack(cpu.registers.status_for_stack(true))?;
 
         Ok(0)
     }
@@ -1370,8 +1822,7 @@ impl Instruction {
      ***/
     fn plp_pull_processor_status_from_stack(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
         let status = cpu.pop_stack()?;
-
-        cpu.registers.p = (cpu.registers.p & 0x30) | status & !0x30;
+        cpu.registers.set_status_from_stack(status);
 
         Ok(0)
     }
@@ -1381,7 +1832,7 @@ impl Instruction {
         let carry_in = if cpu.registers.get_status(StatusFlag::Carry) { 0x01 } else { 0 };
         let result = (value << 1) | carry_in;
 
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         cpu.registers.set_status(StatusFlag::Carry, value & 0x80 != 0);
         cpu.registers.set_status(StatusFlag::Zero, result == 0);
@@ -1395,7 +1846,7 @@ impl Instruction {
         let carry_in = if cpu.registers.get_status(StatusFlag::Carry) { 0x80 } else { 0 };
         let result = (value >> 1) | carry_in;
 
-        cpu.overwrite(operand, result)?;
+        cpu.overwrite(operand, value, result)?;
 
         cpu.registers.set_status(StatusFlag::Carry, value & 0x01 != 0);
         cpu.registers.set_status(StatusFlag::Zero, result == 0);
@@ -1406,8 +1857,7 @@ impl Instruction {
 
     fn rti_return_from_interrupt(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
         let status = cpu.pop_stack()?;
-
-        cpu.registers.p = (status & 0xCF) | 0x20;
+        cpu.registers.set_status_from_stack(status);
 
         let pcl = cpu.pop_stack()?;
         let pch = cpu.pop_stack()?;
@@ -1578,6 +2028,10 @@ impl Instruction {
     }
 
     fn arr_and_oper_plus_ror(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
+        if cpu.is_decimal() {
+            return self.arr_and_oper_plus_ror_decimal(cpu, operand);
+        }
+
         self.and_and_memory_with_accumulator(cpu, operand)?;
 
         cpu.registers.set_status(StatusFlag::Overflow, (cpu.registers.a ^ (cpu.registers.a >> 1)) & 0x40 == 0x40);
@@ -1593,18 +2047,56 @@ impl Instruction {
         Ok(0)
     }
 
-    fn dcp_dec_plus_cmp(&self, _: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
-        Err(CpuError::Unimplemented(format!("{:?}", self.opcode)))
+    /***
+     * ARR in decimal mode: N, Z and V are set from the rotated value, before the BCD fix-up of each nibble
+     * (+6 on the low one, +$60 on the high one, which also sets the carry). The fix-up is decided on the
+     * AND result: a nibble is adjusted when its value plus its lowest bit is above 5.
+     * https://www.nesdev.org/6502_cpu.txt
+     ***/
+    fn arr_and_oper_plus_ror_decimal(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
+        let value = cpu.get_operand_byte_value(operand)?;
+        let t = cpu.registers.a & value;
+        let carry = cpu.registers.get_status(StatusFlag::Carry) as u8;
+        let mut a = (t >> 1) | (carry << 7);
+
+        cpu.registers.set_status(StatusFlag::Negative, carry == 1);
+        cpu.registers.set_status(StatusFlag::Zero, a == 0);
+        cpu.registers.set_status(StatusFlag::Overflow, (t ^ a) & 0x40 == 0x40);
+
+        if (t & 0x0F) + (t & 0x01) > 0x05 {
+            a = (a & 0xF0) | (a.wrapping_add(0x06) & 0x0F);
+        }
+
+        let high_adjust = (t & 0xF0) as u16 + (t & 0x10) as u16 > 0x50;
+
+        if high_adjust {
+            a = (a & 0x0F) | (a.wrapping_add(0x60) & 0xF0);
+        }
+
+        cpu.registers.set_status(StatusFlag::Carry, high_adjust);
+        cpu.registers.a = a;
+
+        Ok(0)
+    }
+
+    fn dcp_dec_plus_cmp(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
+        self.unimplemented_opcode(cpu)
     }
 
+    /***
+     * the illegal read-modify-write instructions (DCP, ISB, SLO, SRE, RLA, RRA) are composed of two legal handlers.
+     * they always take the cycles of the instruction table: the page crossing cycle returned by the embedded
+     * read instruction (CMP, SBC, ORA, EOR, AND, ADC) is dropped, the indexed RMW already pays for the fix-up.
+     * https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+     ***/
     fn dcp_dec_oper_plus_cmp_oper(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         self.dec_decrement_memory_by_one(cpu, operand)?;
         self.cmp_compare_memory_with_accumulator(cpu, operand)?;
         Ok(0)
     }
 
-    fn isb_inc_plus_sbc(&self, _: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
-        Err(CpuError::Unimplemented(format!("{:?}", self.opcode)))
+    fn isb_inc_plus_sbc(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
+        self.unimplemented_opcode(cpu)
     }
 
     fn isc_inc_oper_plus_sbc_oper(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
@@ -1618,10 +2110,12 @@ impl Instruction {
     }
 
     fn las_lda_tsx_oper(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
+        let cycles = cpu.get_cycles_by_page_crossing_for_load(operand);
+
         self.lda_load_accumulator_with_memory(cpu, operand)?;
         self.tsx_transfer_stack_pointer_to_index_x(cpu, operand)?;
 
-        Ok(0)
+        Ok(cycles)
     }
 
     fn lax_lda_oper_plus_ldx_oper(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
@@ -1664,13 +2158,10 @@ impl Instruction {
 
     fn sbx_cmp_and_dex_at_once_sets_flags_like_cmp(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let value = cpu.get_operand_byte_value(operand)?;
-        let result0 = cpu.registers.a & cpu.registers.x;
-        let result1 = result0.wrapping_sub(value);
-
-        cpu.update_flags_zero_negative(result1);
-        cpu.registers.set_status(StatusFlag::Carry, result1 <= result0);
+        let result = cpu.registers.a & cpu.registers.x;
 
-        cpu.registers.x = result1;
+        cpu.compare(result, value);
+        cpu.registers.x = result.wrapping_sub(value);
 
         Ok(0)
     }
@@ -1678,7 +2169,7 @@ impl Instruction {
     /***
      * holy shit
      * https://github.com/100thCoin/TriCNES/blob/main/Emulator.cs#L6379
-     * missing:
+     * with the Hardware unstable opcode mode:
      *  - Sometimes the actual value is stored in memory and the AND with <addrhi+1> part drops
      *   off (ex. SHY becomes true STY). This happens when the RDY line is used to stop the CPU
      *   (pulled low), i.e. either a 'bad line' or sprite DMA starts, in the second half of the cycle
@@ -1699,7 +2190,10 @@ impl Instruction {
         };
         let hi = (*addr >> 8) as u8;
 
-        if page_crossed == true {
+        if cpu.is_sh_high_byte_dropped() {
+            let value = cpu.registers.a & cpu.registers.x;
+            cpu.bus.borrow_mut().write_byte(*addr, value)?;
+        } else if page_crossed == true {
             let hi_unstable = hi & cpu.registers.x;
             let target = (*addr & 0x00FF) | ((hi_unstable as u16) << 8);
             let value = cpu.registers.a & (cpu.registers.x | 0xF5) & hi;
@@ -1714,7 +2208,7 @@ impl Instruction {
 
     fn shx_stores_x_and_at_addr(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let addr = cpu.get_operand_word_value(operand)?;
-        let strange_h1 = ((addr >> 8) as u8).wrapping_add(1);
+        let strange_h1 = if cpu.is_sh_high_byte_dropped() { 0xFF } else { ((addr >> 8) as u8).wrapping_add(1) };
         let result = cpu.registers.x & strange_h1;
 
         cpu.bus.borrow_mut().write_byte(addr, result)?;
@@ -1723,7 +2217,7 @@ impl Instruction {
 
     fn shy_stores_y_and_at_addr(&self, cpu: &mut Cpu6502, operand: &Operand) -> Result<u32, CpuError> {
         let addr = cpu.get_operand_word_value(operand)?;
-        let strange_h1 = ((addr >> 8) as u8).wrapping_add(1);
+        let strange_h1 = if cpu.is_sh_high_byte_dropped() { 0xFF } else { ((addr >> 8) as u8).wrapping_add(1) };
         let result = cpu.registers.y & strange_h1;
 
         cpu.bus.borrow_mut().write_byte(addr, result)?;
@@ -1756,7 +2250,6 @@ impl Instruction {
     }
 
     fn illegal(&self, cpu: &mut Cpu6502, _: &Operand) -> Result<u32, CpuError> {
-        warn!("CPU: illegal instruction at {:04X}", cpu.registers.pc);
-        Ok(0)
+        self.unimplemented_opcode(cpu)
     }
 }
\ No newline at end of file
@@ -20,6 +20,7 @@ pub enum DebugCommand {
     DeleteBreakpoint(u16),
     DeleteAllBreakpoints,
     ListBreakpoints,
+    DumpTimings,
     Detach
 }
 
@@ -35,6 +36,10 @@ pub trait CpuSnapshot: Debug + Send {
     fn is_illegal(&self) -> bool;
     fn operand(&self) -> String;
     fn cycles(&self) -> u32;
+    /// Cycles of the instruction at ```pc```, without the page crossing or branch taken penalties.
+    fn base_cycles(&self) -> u32;
+    /// Whether the instruction at ```pc``` may take more than ```base_cycles```.
+    fn page_cross_penalty(&self) -> bool;
 }
 
 pub trait Breakpoints: Debug {
@@ -0,0 +1,81 @@
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use crate::cpu_debugger::CpuSnapshot;
+
+const PPU_DOTS_PER_SCANLINE: u64 = 341;
+const PPU_SCANLINES_PER_FRAME: u64 = 262;
+const PPU_DOTS_PER_CPU_CYCLE: u64 = 3;
+
+/***
+ * Nintendulator-style instruction tracer, one line per executed instruction:
+ *
+ * C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+ *
+ * An optional PC range restricts the trace to the instructions located in [lo, hi] (a subroutine).
+ *
+ * https://www.qmtpro.com/~nes/misc/nestest.log
+ ***/
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    pc_range: Option<(u16, u16)>,
+}
+
+impl Debug for Tracer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Tracer")
+    }
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Tracer::with_pc_range(writer, None)
+    }
+
+    pub fn with_pc_range(writer: Box<dyn Write>, pc_range: Option<(u16, u16)>) -> Self {
+        Tracer {
+            writer,
+            pc_range
+        }
+    }
+
+    pub fn is_traced(&self, pc: u16) -> bool {
+        match self.pc_range {
+            Some((lo, hi)) => (lo..=hi).contains(&pc),
+            None => true,
+        }
+    }
+
+    /// PPU position derived from the CPU cycle count, assuming both chips started together.
+    pub fn ppu_position_from_cycles(cycles: u64) -> (u16, u16) {
+        let dots = cycles * PPU_DOTS_PER_CPU_CYCLE;
+        let scanline = (dots / PPU_DOTS_PER_SCANLINE) % PPU_SCANLINES_PER_FRAME;
+        let dot = dots % PPU_DOTS_PER_SCANLINE;
+
+        (scanline as u16, dot as u16)
+    }
+
+    pub fn format_line(snapshot: &dyn CpuSnapshot, ppu_position: (u16, u16), cycles: u64) -> String {
+        let bytes = snapshot.instruction()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let illegal_marker = if snapshot.is_illegal() { "*" } else { " " };
+        let disassembly = format!("{} {}", snapshot.mnemonic(), snapshot.operand());
+
+        format!("{:04X}  {:<9}{}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+                snapshot.pc(), bytes, illegal_marker, disassembly,
+                snapshot.a(), snapshot.x(), snapshot.y(), snapshot.p(), snapshot.sp(),
+                ppu_position.0, ppu_position.1, cycles)
+    }
+
+    pub fn trace(&mut self, snapshot: &dyn CpuSnapshot, ppu_position: (u16, u16), cycles: u64) -> std::io::Result<()> {
+        let line = Tracer::format_line(snapshot, ppu_position, cycles);
+        writeln!(self.writer, "{}", line)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
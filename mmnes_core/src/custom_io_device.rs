@@ -0,0 +1,140 @@
+use std::fmt::{Debug, Formatter};
+use log::info;
+use crate::bus_device::{BusDevice, BusDeviceType};
+use crate::memory::{Memory, MemoryError, MemoryType};
+
+/// Called with the CPU address and the stored byte, returns the byte read by the CPU.
+pub type CustomIoRead = Box<dyn Fn(u16, u8) -> u8>;
+/// Called with the CPU address and the byte written by the CPU, after it is stored.
+pub type CustomIoWrite = Box<dyn FnMut(u16, u8)>;
+
+/***
+ * Memory-mapped registers for the homebrew and the test harnesses, e.g. the status byte and the text
+ * output of the blargg test ROMs at $6000, or a "print" port. The bytes are stored in a Vec covering the
+ * range and handed to optional closures on the reads and writes. Without read closure, the last byte
+ * written is read back.
+ *
+ * The bus maps the address modulo the size of the device: the range must be aligned on its size rounded
+ * up to a power of two (e.g. $5000 - $5000, $6000 - $6FFF).
+ ***/
+pub struct CustomIoDevice {
+    name: String,
+    range: (u16, u16),
+    memory: Vec<u8>,
+    on_read: Option<CustomIoRead>,
+    on_write: Option<CustomIoWrite>,
+}
+
+impl CustomIoDevice {
+    pub fn new(name: &str, range: (u16, u16)) -> Self {
+        let length = (range.1 - range.0) as usize + 1;
+
+        CustomIoDevice {
+            name: name.to_string(),
+            range,
+            memory: vec![0; length.next_power_of_two()],
+            on_read: None,
+            on_write: None,
+        }
+    }
+
+    pub fn with_read(mut self, on_read: CustomIoRead) -> Self {
+        self.on_read = Some(on_read);
+        self
+    }
+
+    pub fn with_write(mut self, on_write: CustomIoWrite) -> Self {
+        self.on_write = Some(on_write);
+        self
+    }
+
+    fn cpu_address(&self, addr: u16) -> u16 {
+        self.range.0.wrapping_add(addr)
+    }
+
+    fn offset(&self, addr: u16) -> Result<usize, MemoryError> {
+        let offset = addr as usize;
+
+        if offset < self.memory.len() {
+            Ok(offset)
+        } else {
+            Err(MemoryError::OutOfRange(addr))
+        }
+    }
+}
+
+impl Debug for CustomIoDevice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomIoDevice")
+            .field("name", &self.name)
+            .field("range", &self.range)
+            .field("on_read", &self.on_read.is_some())
+            .field("on_write", &self.on_write.is_some())
+            .finish()
+    }
+}
+
+impl Memory for CustomIoDevice {
+    fn initialize(&mut self) -> Result<usize, MemoryError> {
+        info!("initializing {}: 0x{:04X} - 0x{:04X}", self.name, self.range.0, self.range.1);
+        self.memory.fill(0);
+
+        Ok(self.memory.len())
+    }
+
+    fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        let value = self.memory[self.offset(addr)?];
+
+        match &self.on_read {
+            Some(on_read) => Ok(on_read(self.cpu_address(addr), value)),
+            None => Ok(value),
+        }
+    }
+
+    fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        Ok(self.memory[self.offset(addr)?])
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        let offset = self.offset(addr)?;
+        let cpu_address = self.cpu_address(addr);
+
+        self.memory[offset] = value;
+
+        if let Some(on_write) = &mut self.on_write {
+            on_write(cpu_address, value);
+        }
+
+        Ok(())
+    }
+
+    fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
+        let lo = self.read_byte(addr)?;
+        let hi = self.read_byte(addr.wrapping_add(1))?;
+
+        Ok((hi as u16) << 8 | lo as u16)
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) -> Result<(), MemoryError> {
+        self.write_byte(addr, value as u8)?;
+        self.write_byte(addr.wrapping_add(1), (value >> 8) as u8)
+    }
+
+    fn size(&self) -> usize {
+        self.memory.len()
+    }
+}
+
+impl BusDevice for CustomIoDevice {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_device_type(&self) -> BusDeviceType {
+        BusDeviceType::WRAM(MemoryType::StandardMemory)
+    }
+
+    fn get_virtual_address_range(&self) -> (u16, u16) {
+        self.range
+    }
+}
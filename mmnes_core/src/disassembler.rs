@@ -0,0 +1,26 @@
+use crate::cpu::CpuError;
+use crate::cpu_6502::Cpu6502;
+use crate::memory::Memory;
+
+/***
+ * Linear sweep disassembly of [start, end]: each instruction is decoded right after the previous one,
+ * so the data mixed with the code is disassembled as if it were code, and an instruction
+ * crossing ```end``` is listed whole. The sweep stops at the end of the address space.
+ ***/
+pub fn disassemble_range(memory: &dyn Memory, start: u16, end: u16) -> Result<Vec<String>, CpuError> {
+    let mut lines = Vec::new();
+    let mut addr = start;
+
+    while addr <= end {
+        let (line, next) = Cpu6502::disassemble(memory, addr)?;
+        lines.push(line);
+
+        if next <= addr {
+            break;
+        }
+
+        addr = next;
+    }
+
+    Ok(lines)
+}
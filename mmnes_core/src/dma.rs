@@ -3,19 +3,19 @@ use std::fmt;
 use std::fmt::{Display, Formatter};
 use crate::memory::MemoryError;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DmaType {
     PpuDma(PpuDmaType),
     ApuDma(ApuDmaType)
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum PpuDmaType {
     #[default]
     NESPPUDMA
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum ApuDmaType {
     #[default]
     NESAPUDMA
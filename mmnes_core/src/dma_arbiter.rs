@@ -0,0 +1,105 @@
+use log::debug;
+use crate::bus::Bus;
+use crate::dma_device::DmaDevice;
+use crate::memory::MemoryError;
+
+const OAM_DMA_LENGTH: u16 = 256;
+/// The OAM DMA writes the PPU OAM data register.
+const OAM_DATA_ADDR: u16 = 0x2004;
+/// the halt cycle and the dummy cycle of the DMC DMA, the sample is fetched on the next get cycle
+const DMC_DMA_SETUP_CYCLES: u32 = 2;
+
+/***
+ * Sequences the OAM DMA ($4014) and the DMC sample DMA sharing the CPU bus, cycle by cycle.
+ * The DMA unit alternates get (read) and put (write) cycles, after 1 cycle to halt the CPU:
+ *   - OAM DMA: 256 get / put pairs, plus 1 alignment cycle when the halt ends on a get cycle (513 or 514 cycles),
+ *   - DMC DMA: halt, dummy, then the sample is read on the next get cycle (3 or 4 cycles),
+ *   - both: the DMC has the priority, its fetch replaces an OAM get and its halt and dummy cycles are absorbed
+ *     by the OAM DMA. The OAM DMA then idles for 1 put cycle to realign: 2 cycles are added.
+ *
+ * https://www.nesdev.org/wiki/DMA
+ ***/
+#[derive(Debug, Default)]
+pub struct DmaArbiter {
+    oam_page: Option<u8>,
+    dmc_request: Option<(u16, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DmaTransfer {
+    cycles: u32,
+    dmc_sample: Option<u8>,
+}
+
+impl DmaTransfer {
+    /// CPU cycles stolen by the DMA.
+    pub fn cycles(&self) -> u32 {
+        self.cycles
+    }
+
+    pub fn dmc_sample(&self) -> Option<u8> {
+        self.dmc_sample
+    }
+}
+
+impl DmaArbiter {
+
+    pub fn new() -> Self {
+        DmaArbiter::default()
+    }
+
+    /// Copy of the page ```page``` (```page``` << 8 to ```page``` << 8 | 0xFF) to the OAM.
+    pub fn request_oam(&mut self, page: u8) {
+        self.oam_page = Some(page);
+    }
+
+    /// DMC sample fetch at ```addr```, requested ```at_cycle``` cycles after the start of the DMA.
+    pub fn request_dmc(&mut self, addr: u16, at_cycle: u32) {
+        self.dmc_request = Some((addr, at_cycle));
+    }
+
+    /// Run the pending transfers to completion. ```halt_on_get```: the halt cycle is a get cycle,
+    /// i.e. the cycle following it is a put cycle.
+    pub fn run(&mut self, bus: &dyn Bus, oam: &mut dyn DmaDevice, halt_on_get: bool) -> Result<DmaTransfer, MemoryError> {
+        let mut oam_addr = self.oam_page.take().map(|page| (page as u16) << 8);
+        let mut oam_index: u16 = 0;
+        let mut oam_latch: Option<u8> = None;
+        let mut dmc_sample = None;
+
+        // the halt cycle
+        let mut cycles: u32 = 1;
+
+        while oam_addr.is_some() || self.dmc_request.is_some() {
+            let is_get = cycles.is_multiple_of(2) == halt_on_get;
+            let dmc_ready = self.dmc_request.is_some_and(|(_, at_cycle)| cycles >= at_cycle + DMC_DMA_SETUP_CYCLES);
+
+            if is_get && dmc_ready {
+                if let Some((addr, _)) = self.dmc_request.take() {
+                    dmc_sample = Some(bus.read_byte(addr)?);
+                }
+            } else if is_get {
+                if let (Some(addr), None) = (oam_addr, oam_latch) {
+                    oam_latch = Some(bus.read_byte(addr + oam_index)?);
+                }
+            } else if let Some(value) = oam_latch.take() {
+                // put cycle, idle when nothing was read on the previous get cycle (alignment)
+                oam.dma_write(oam_index as u8, value)?;
+                bus.log_dma_write(OAM_DATA_ADDR, value);
+                oam_index += 1;
+
+                if oam_index == OAM_DMA_LENGTH {
+                    oam_addr = None;
+                }
+            }
+
+            cycles += 1;
+        }
+
+        debug!("DMA: {} cycles, DMC sample: {:?}", cycles, dmc_sample);
+
+        Ok(DmaTransfer {
+            cycles,
+            dmc_sample
+        })
+    }
+}
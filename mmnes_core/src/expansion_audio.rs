@@ -0,0 +1,16 @@
+use std::fmt::Debug;
+
+/***
+ * Audio channels provided by a cartridge (Konami VRC6, MMC5, Namco 163, ...), mixed into the APU output.
+ * The cartridge exposes them through ```Cartridge::get_expansion_audio()```, the registers of the channels
+ * are mapped by the cartridge itself.
+ *
+ * https://www.nesdev.org/wiki/Expansion_audio
+ ***/
+pub trait ExpansionAudio: Debug {
+    /// Clocked at the CPU clock rate.
+    fn clock(&mut self);
+
+    /// Current output, already scaled to the APU mixer output, added to the pulse and triangle/noise/DMC outputs.
+    fn sample(&self) -> f32;
+}
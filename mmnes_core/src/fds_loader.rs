@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
+use log::info;
+use crate::cartridge::Cartridge;
+use crate::loader::{Loader, LoaderError};
+
+const FWNES_HEADER_SIZE: usize = 16;
+const FWNES_PREAMBLE: [u8; 4] = [0x46, 0x44, 0x53, 0x1A];
+const DISK_SIDE_SIZE: usize = 65500;
+
+const DISK_INFO_BLOCK_CODE: u8 = 0x01;
+const DISK_INFO_BLOCK_SIZE: usize = 56;
+const DISK_VERIFICATION: &[u8; 14] = b"*NINTENDO-HVC*";
+const FILE_AMOUNT_BLOCK_CODE: u8 = 0x02;
+const FILE_AMOUNT_BLOCK_SIZE: usize = 2;
+const FILE_HEADER_BLOCK_CODE: u8 = 0x03;
+const FILE_HEADER_BLOCK_SIZE: usize = 16;
+const FILE_DATA_BLOCK_CODE: u8 = 0x04;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FdsFileKind {
+    Program,
+    Character,
+    NameTable,
+}
+
+impl Display for FdsFileKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FdsFileKind::Program => write!(f, "PRG"),
+            FdsFileKind::Character => write!(f, "CHR"),
+            FdsFileKind::NameTable => write!(f, "NT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FdsFile {
+    number: u8,
+    id: u8,
+    name: String,
+    load_address: u16,
+    kind: FdsFileKind,
+    data: Vec<u8>,
+}
+
+impl FdsFile {
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    /// Files with an id lower or equal to the boot id of the disk info block are loaded at boot.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn load_address(&self) -> u16 {
+        self.load_address
+    }
+
+    pub fn kind(&self) -> FdsFileKind {
+        self.kind
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FdsDiskSide {
+    game_name: String,
+    side_number: u8,
+    boot_file_id: u8,
+    file_amount: u8,
+    files: Vec<FdsFile>,
+}
+
+impl FdsDiskSide {
+    pub fn game_name(&self) -> &str {
+        &self.game_name
+    }
+
+    pub fn side_number(&self) -> u8 {
+        self.side_number
+    }
+
+    pub fn boot_file_id(&self) -> u8 {
+        self.boot_file_id
+    }
+
+    /// Number of files declared by the file amount block, some games hide additional files after them.
+    pub fn file_amount(&self) -> u8 {
+        self.file_amount
+    }
+
+    pub fn files(&self) -> &[FdsFile] {
+        &self.files
+    }
+}
+
+/***
+ * Famicom Disk System image, with or without the 16 bytes fwNES header.
+ * Each disk side is 65500 bytes of blocks, without the CRCs and gaps of the real disk:
+ *   - block 1: disk info (56 bytes)
+ *   - block 2: file amount (2 bytes)
+ *   - block 3: file header (16 bytes), followed by
+ *   - block 4: file data (1 + file size bytes)
+ *
+ * Only the parsing of the disk sides is implemented: the FDS BIOS mapped at $E000, the RAM adapter
+ * and its disk registers ($4020 - $409F), and the disk side swapping are not emulated yet.
+ *
+ * https://www.nesdev.org/wiki/FDS_file_format
+ * https://www.nesdev.org/wiki/FDS_disk_format
+ ***/
+#[derive(Debug)]
+pub struct FdsLoader {
+    sides: Vec<FdsDiskSide>,
+}
+
+impl Loader for FdsLoader {
+
+    fn from_file(path: PathBuf) -> Result<FdsLoader, LoaderError> {
+        let mut file = File::open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        FdsLoader::from_bytes(&bytes)
+    }
+
+    fn build_cartridge(self) -> Result<Rc<RefCell<dyn Cartridge>>, LoaderError> {
+        info!("building disk system for {} disk side(s)...", self.sides.len());
+
+        Err(LoaderError::UnsupportedFormat("Famicom Disk System (BIOS and disk drive are not emulated)".to_string()))
+    }
+}
+
+impl FdsLoader {
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<FdsLoader, LoaderError> {
+        let disk = if bytes.len() >= FWNES_HEADER_SIZE && bytes[0..4] == FWNES_PREAMBLE {
+            let sides_count = bytes[4] as usize;
+            let disk = &bytes[FWNES_HEADER_SIZE..];
+
+            if disk.len() < sides_count * DISK_SIDE_SIZE {
+                return Err(LoaderError::InvalidRomFormat);
+            }
+
+            &disk[..sides_count * DISK_SIDE_SIZE]
+        } else {
+            bytes
+        };
+
+        if disk.is_empty() || disk.len() % DISK_SIDE_SIZE != 0 {
+            return Err(LoaderError::InvalidRomFormat);
+        }
+
+        let sides = disk.chunks(DISK_SIDE_SIZE)
+            .map(FdsLoader::parse_disk_side)
+            .collect::<Result<Vec<FdsDiskSide>, LoaderError>>()?;
+
+        info!("FDS image: {} disk side(s)", sides.len());
+
+        Ok(FdsLoader {
+            sides
+        })
+    }
+
+    pub fn sides(&self) -> &[FdsDiskSide] {
+        &self.sides
+    }
+
+    fn read_block(side: &[u8], offset: usize, code: u8, size: usize) -> Result<&[u8], LoaderError> {
+        match side.get(offset..offset + size) {
+            Some(block) if block[0] == code => Ok(block),
+            _ => Err(LoaderError::InvalidRomFormat),
+        }
+    }
+
+    fn parse_disk_side(side: &[u8]) -> Result<FdsDiskSide, LoaderError> {
+        let disk_info = FdsLoader::read_block(side, 0, DISK_INFO_BLOCK_CODE, DISK_INFO_BLOCK_SIZE)?;
+
+        if &disk_info[1..15] != DISK_VERIFICATION {
+            return Err(LoaderError::InvalidRomFormat);
+        }
+
+        let game_name = String::from_utf8_lossy(&disk_info[16..19]).to_string();
+        let side_number = disk_info[21];
+        let boot_file_id = disk_info[25];
+
+        let file_amount = FdsLoader::read_block(side, DISK_INFO_BLOCK_SIZE, FILE_AMOUNT_BLOCK_CODE, FILE_AMOUNT_BLOCK_SIZE)?[1];
+
+        let mut files = Vec::new();
+        let mut offset = DISK_INFO_BLOCK_SIZE + FILE_AMOUNT_BLOCK_SIZE;
+
+        // hidden files may follow the declared ones, the end of the side is filled with zeros
+        while side.get(offset) == Some(&FILE_HEADER_BLOCK_CODE) {
+            let header = FdsLoader::read_block(side, offset, FILE_HEADER_BLOCK_CODE, FILE_HEADER_BLOCK_SIZE)?;
+            let size = u16::from_le_bytes([header[13], header[14]]) as usize;
+
+            let kind = match header[15] {
+                0 => FdsFileKind::Program,
+                1 => FdsFileKind::Character,
+                2 => FdsFileKind::NameTable,
+                _ => return Err(LoaderError::InvalidRomFormat),
+            };
+
+            offset += FILE_HEADER_BLOCK_SIZE;
+            let data = FdsLoader::read_block(side, offset, FILE_DATA_BLOCK_CODE, size + 1)?;
+            offset += size + 1;
+
+            files.push(FdsFile {
+                number: header[1],
+                id: header[2],
+                name: String::from_utf8_lossy(&header[3..11]).to_string(),
+                load_address: u16::from_le_bytes([header[11], header[12]]),
+                kind,
+                data: data[1..].to_vec(),
+            });
+        }
+
+        Ok(FdsDiskSide {
+            game_name,
+            side_number,
+            boot_file_id,
+            file_amount,
+            files,
+        })
+    }
+}
@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+use log::debug;
+use crate::bus_device::{BusDevice, BusDeviceType};
+use crate::bus_device::BusDeviceType::CONTROLLER;
+use crate::controller::{Controller, ControllerError, ControllerType};
+use crate::input::Input;
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_PLAYER_KEYS};
+use crate::memory::{Memory, MemoryError};
+
+const DEVICE_NAME: &str = "Four Score";
+const FOUR_SCORE_ADDRESS_SPACE: (u16, u16) = (0x4016, 0x4017);
+const FRAME_COUNTER_ADDR: u16 = 0x4017;
+const PORT_2: usize = 1;
+const FOUR_SCORE_MEMORY_SIZE: usize = 2;
+const FOUR_SCORE_PLAYERS: usize = 4;
+const CONTROLLER_NUM_BUTTONS: usize = 8;
+/// Shifted out after the two controllers of each port, games check them to detect the adapter.
+const SIGNATURES: [u8; 2] = [0x10, 0x20];
+/// The bits shifted in once a port is empty, reads return 1 past the 24 bits.
+const SHIFT_REGISTER_FILL: u32 = 0x80_0000;
+
+/***
+ * Four Score multitap, 4 controllers on the 2 ports. Each port shifts out 24 bits (LSB first) after the strobe:
+ *   - $4016: the 8 buttons of player 1, then of player 3, then the signature $10,
+ *   - $4017: the 8 buttons of player 2, then of player 4, then the signature $20.
+ * The writes to $4017 are the APU frame counter: they are forwarded to the device mapped there before
+ * the Four Score (```frame_counter```).
+ * The keys of the players 2 - 4 are offset by NES_CONTROLLER_PLAYER_KEYS (see key_event).
+ * https://www.nesdev.org/wiki/Four_Score
+ ***/
+#[derive(Debug)]
+pub struct FourScore<T: Input> {
+    inputs: [T; FOUR_SCORE_PLAYERS],
+    frame_counter: Option<Rc<RefCell<dyn BusDevice>>>,
+    strobe: bool,
+    control_states: [[u8; CONTROLLER_NUM_BUTTONS]; FOUR_SCORE_PLAYERS],
+    shift_registers: RefCell<[u32; 2]>,
+}
+
+impl<T: Input> Controller for FourScore<T> {
+    fn set_input(&mut self, input: KeyEvents) -> Result<(), ControllerError> {
+        let mut player_inputs: [KeyEvents; FOUR_SCORE_PLAYERS] = Default::default();
+
+        for event in input {
+            let player = event.key / NES_CONTROLLER_PLAYER_KEYS;
+
+            if player >= FOUR_SCORE_PLAYERS {
+                return Err(ControllerError::IncorrectInput(format!("key 0x{:02X} is not of a Four Score player", event.key)));
+            }
+
+            player_inputs[player].push_back(KeyEvent { key: event.key % NES_CONTROLLER_PLAYER_KEYS, pressed: event.pressed });
+        }
+
+        for (input, player_input) in self.inputs.iter_mut().zip(player_inputs) {
+            if !player_input.is_empty() {
+                input.set_input_state(player_input);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Input> Memory for FourScore<T> {
+    fn initialize(&mut self) -> Result<usize, MemoryError> {
+        debug!("initializing Four Score at 0x{:04X} - 0x{:04X}", FOUR_SCORE_ADDRESS_SPACE.0, FOUR_SCORE_ADDRESS_SPACE.1);
+        Ok(FOUR_SCORE_MEMORY_SIZE)
+    }
+
+    fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        let value = self.trace_read_byte(addr)?;
+
+        if !self.strobe {
+            let port = FourScore::<T>::port(addr);
+            let mut shift_registers = self.shift_registers.borrow_mut();
+
+            shift_registers[port] = (shift_registers[port] >> 1) | SHIFT_REGISTER_FILL;
+        }
+
+        Ok(value)
+    }
+
+    /// While the strobe is high, the shift registers are reloaded and reads return the A button of players 1 and 2.
+    fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        let port = FourScore::<T>::port(addr);
+        Ok((self.shift_registers.borrow()[port] & 0x01) as u8)
+    }
+
+    /// The buttons are latched on each write while the strobe is high, and on the falling edge of the strobe.
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        if FourScore::<T>::port(addr) == PORT_2 {
+            return match &self.frame_counter {
+                Some(frame_counter) => {
+                    // the address translated as the bus does for the device
+                    let effective_addr = FRAME_COUNTER_ADDR & (frame_counter.borrow().size() - 1) as u16;
+                    frame_counter.borrow_mut().write_byte(effective_addr, value)
+                },
+                None => Ok(()),
+            };
+        }
+
+        let was_strobe = self.strobe;
+        self.strobe = value & 0x01 != 0;
+
+        if was_strobe || self.strobe {
+            self.latch();
+        }
+
+        Ok(())
+    }
+
+    fn read_word(&self, _: u16) -> Result<u16, MemoryError> {
+        Ok(0)
+    }
+
+    fn write_word(&mut self, _: u16, _: u16) -> Result<(), MemoryError> {
+        Ok(())
+    }
+
+    fn dump(&self) {
+        let shift_registers = self.shift_registers.borrow();
+        debug!("Four Score: strobe {}, $4016 shift register 0x{:06X}, $4017 shift register 0x{:06X}",
+            self.strobe, shift_registers[0] & 0xFF_FFFF, shift_registers[1] & 0xFF_FFFF);
+    }
+
+    fn size(&self) -> usize {
+        FOUR_SCORE_MEMORY_SIZE
+    }
+}
+
+impl<T: Input> BusDevice for FourScore<T> {
+    fn get_name(&self) -> String {
+        DEVICE_NAME.to_string()
+    }
+
+    fn get_device_type(&self) -> BusDeviceType {
+        CONTROLLER(ControllerType::FourScore)
+    }
+
+    fn get_virtual_address_range(&self) -> (u16, u16) {
+        FOUR_SCORE_ADDRESS_SPACE
+    }
+}
+
+impl<T: Input> FourScore<T> {
+    pub fn new(inputs: [T; FOUR_SCORE_PLAYERS], frame_counter: Option<Rc<RefCell<dyn BusDevice>>>) -> FourScore<T> {
+        FourScore {
+            inputs,
+            frame_counter,
+            strobe: false,
+            control_states: [[0; CONTROLLER_NUM_BUTTONS]; FOUR_SCORE_PLAYERS],
+            // reads return 1 until the first strobe
+            shift_registers: RefCell::new([u32::MAX; 2]),
+        }
+    }
+
+    /// 0 for $4016, 1 for $4017.
+    fn port(addr: u16) -> usize {
+        (addr & 0x01) as usize
+    }
+
+    fn latch(&mut self) {
+        for (input, control_states) in self.inputs.iter_mut().zip(self.control_states.iter_mut()) {
+            input.get_input_state(control_states);
+        }
+
+        let mut shift_registers = self.shift_registers.borrow_mut();
+
+        for (port, signature) in SIGNATURES.iter().enumerate() {
+            let first = FourScore::<T>::buttons(&self.control_states[port]);
+            let second = FourScore::<T>::buttons(&self.control_states[port + 2]);
+
+            shift_registers[port] = first as u32 | (second as u32) << 8 | (*signature as u32) << 16;
+        }
+    }
+
+    /// The buttons in the order they are shifted out, A on bit 0.
+    fn buttons(control_states: &[u8; CONTROLLER_NUM_BUTTONS]) -> u8 {
+        control_states.iter()
+            .enumerate()
+            .fold(0, |buttons, (index, state)| buttons | (state & 0x01) << index)
+    }
+}
@@ -1,22 +1,23 @@
 use std::cell::RefCell;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Cursor, Read};
 use std::path::PathBuf;
 use std::rc::Rc;
 use log::info;
 use crate::cartridge::Cartridge;
-use crate::loader::{Loader, LoaderError};
+use crate::loader::{Loader, LoaderError, RomData};
 use crate::mapper::NesMapper;
 use crate::memory_ciram::PpuNameTableMirroring;
 use crate::mmc1_cartridge::Mmc1Cartridge;
 use crate::nrom_cartridge::NromCartridge;
+use crate::region::Region;
 use crate::unrom_cartridge::UnromCartridge;
 
 const HEADER_SIZE: usize = 16;
 
 pub trait FromINes: Debug {
-    fn from_ines(file: File, header: INesRomHeader) -> Result<impl Cartridge, LoaderError>
+    fn from_ines(data: Box<dyn RomData>, header: INesRomHeader) -> Result<impl Cartridge, LoaderError>
     where
         Self: Sized;
 }
@@ -24,30 +25,25 @@ pub trait FromINes: Debug {
 #[derive(Debug)]
 pub struct INesLoader {
     header: INesRomHeader,
-    file: File
+    data: Box<dyn RomData>
 }
 
 impl Loader for INesLoader {
 
     fn from_file(path: PathBuf) -> Result<INesLoader, LoaderError> {
-        let mut file = File::open(path)?;
-        let header = INesLoader::load_header(&mut file)?;
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
 
-        let loader = INesLoader {
-            header,
-            file
-        };
-
-        Ok(loader)
+        INesLoader::from_data(Box::new(file), file_size)
     }
 
     fn build_cartridge(self) -> Result<Rc<RefCell<dyn Cartridge>>, LoaderError> {
         info!("building cartridge...");
 
         let cartridge: Rc<RefCell<dyn Cartridge>> = match self.header.mapper {
-            NesMapper::NROM => Rc::new(RefCell::new(NromCartridge::from_ines(self.file, self.header)?)),
-            NesMapper::UxROM => Rc::new(RefCell::new(UnromCartridge::from_ines(self.file, self.header)?)),
-            NesMapper::MMC1 => Rc::new(RefCell::new(Mmc1Cartridge::from_ines(self.file, self.header)?)),
+            NesMapper::NROM => Rc::new(RefCell::new(NromCartridge::from_ines(self.data, self.header)?)),
+            NesMapper::UxROM => Rc::new(RefCell::new(UnromCartridge::from_ines(self.data, self.header)?)),
+            NesMapper::MMC1 => Rc::new(RefCell::new(Mmc1Cartridge::from_ines(self.data, self.header)?)),
             _ => Err(LoaderError::UnsupportedMapper(self.header.mapper.name().to_string()))?
         };
 
@@ -57,12 +53,39 @@ impl Loader for INesLoader {
 
 impl INesLoader {
 
-    fn load_header(file: &mut File) -> Result<INesRomHeader, LoaderError> {
+    /// The image of a ROM already in memory, downloaded or embedded in the program.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<INesLoader, LoaderError> {
+        let size = bytes.len() as u64;
+        INesLoader::from_data(Box::new(Cursor::new(bytes)), size)
+    }
+
+    fn from_data(mut data: Box<dyn RomData>, size: u64) -> Result<INesLoader, LoaderError> {
+        INesLoader::verify_file_size(HEADER_SIZE as u64, size)?;
+        let header = INesLoader::load_header(&mut data)?;
+        INesLoader::verify_file_size(header.rom_size(), size)?;
+
+        let loader = INesLoader {
+            header,
+            data
+        };
+
+        Ok(loader)
+    }
+
+    fn load_header(data: &mut impl Read) -> Result<INesRomHeader, LoaderError> {
         let mut buffer = vec![0u8; HEADER_SIZE];
-        file.read_exact(&mut buffer)?;
+        data.read_exact(&mut buffer)?;
 
         INesRomHeader::from_bytes(&buffer)
     }
+
+    fn verify_file_size(expected: u64, actual: u64) -> Result<(), LoaderError> {
+        if actual < expected {
+            Err(LoaderError::TruncatedRom { expected, actual })
+        } else {
+            Ok(())
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -104,25 +127,6 @@ impl Display for ConsoleType {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Region {
-    NTSC,
-    PAL,
-    Multiple,
-    Dendy,
-}
-
-impl Display for Region {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Region::NTSC => write!(f, "NTSC"),
-            Region::PAL => write!(f, "PAL"),
-            Region::Multiple => write!(f, "Multiple"),
-            Region::Dendy => write!(f, "Dendy"),
-        }
-    }
-}
-
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ExpansionDevice {
     Unspecified,
@@ -269,6 +273,11 @@ impl INesRomHeader {
         }
     }
 
+    /// Header, trainer, PRG ROM and CHR ROM: the size of the file (the misc ROM is optional).
+    pub fn rom_size(&self) -> u64 {
+        self.prg_offset() + self.prg_rom_size as u64 + self.chr_rom_size as u64
+    }
+
     fn verify_raw_header_size(bytes: &[u8]) -> Result<(), LoaderError> {
         if bytes.len() < HEADER_SIZE {
             Err(LoaderError::InvalidRomFormat)
@@ -6,6 +6,24 @@ pub trait Input: Debug {
     fn set_input_state(&mut self, _key_events: KeyEvents) {
         unreachable!()
     }
+    fn microphone(&self) -> bool {
+        false
+    }
+}
+
+/***
+ * Simultaneous opposing cardinal directions (SOCD): Left + Right or Up + Down can't be pressed on the d-pad
+ * of a controller, they can on a keyboard and some games glitch when they read both.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SocdMode {
+    /// both directions are reported
+    #[default]
+    Off,
+    /// neither direction is reported while both are held
+    Neutral,
+    /// only the direction pressed last is reported while both are held
+    LastWins,
 }
 
 #[derive(Debug, PartialEq)]
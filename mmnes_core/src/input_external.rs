@@ -1,29 +1,91 @@
-use crate::input::Input;
-use crate::key_event::{KeyEvents};
+use crate::input::{Input, SocdMode};
+use crate::key_event::{KeyEvents, NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_MICROPHONE, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_UP};
+
+const DPAD_AXES: [(usize, usize); 2] = [(NES_CONTROLLER_KEY_UP, NES_CONTROLLER_KEY_DOWN), (NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT)];
 
 #[derive(Debug)]
 pub struct InputExternal {
     key_events: KeyEvents,
+    microphone: bool,
+    socd_mode: SocdMode,
+    /// the directions held on the keyboard, indexed by key, before the SOCD resolution
+    held_directions: [bool; 8],
+    /// the direction pressed last on each axis
+    last_directions: [usize; 2],
 }
 
 impl Input for InputExternal {
     fn get_input_state(&mut self, control_states: &mut [u8; 8]) {
+        // the keys of the other players (Four Score) are not for this controller
         while let Some(event) = self.key_events.next() {
-            control_states[event.key] = event.pressed as u8;
+            if let Some(control_state) = control_states.get_mut(event.key) {
+                *control_state = event.pressed as u8;
+            }
+
+            self.hold_direction(event.key, event.pressed);
+        }
+
+        if self.socd_mode != SocdMode::Off {
+            self.resolve_opposing_directions(control_states);
         }
     }
 
+    /// The microphone is not latched by the strobe, its state changes immediately.
     fn set_input_state(&mut self, key_events: KeyEvents) {
         for event in key_events {
-            self.key_events.push_back(event);
+            if event.key == NES_CONTROLLER_KEY_MICROPHONE {
+                self.microphone = event.pressed;
+            } else {
+                self.key_events.push_back(event);
+            }
         }
     }
+
+    fn microphone(&self) -> bool {
+        self.microphone
+    }
 }
 
 impl InputExternal {
     pub fn new() -> Self {
         InputExternal {
             key_events: KeyEvents::new(),
+            microphone: false,
+            socd_mode: SocdMode::default(),
+            held_directions: [false; 8],
+            last_directions: [NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_RIGHT],
+        }
+    }
+
+    pub fn with_socd_mode(mut self, socd_mode: SocdMode) -> Self {
+        self.socd_mode = socd_mode;
+        self
+    }
+
+    fn hold_direction(&mut self, key: usize, pressed: bool) {
+        for (axis, (first, second)) in DPAD_AXES.iter().enumerate() {
+            if key == *first || key == *second {
+                self.held_directions[key] = pressed;
+
+                if pressed {
+                    self.last_directions[axis] = key;
+                }
+            }
+        }
+    }
+
+    fn resolve_opposing_directions(&self, control_states: &mut [u8; 8]) {
+        for (axis, (first, second)) in DPAD_AXES.iter().enumerate() {
+            let (first_held, second_held) = (self.held_directions[*first], self.held_directions[*second]);
+
+            let (first_state, second_state) = match (first_held && second_held, self.socd_mode) {
+                (true, SocdMode::Neutral) => (false, false),
+                (true, SocdMode::LastWins) => (self.last_directions[axis] == *first, self.last_directions[axis] == *second),
+                _ => (first_held, second_held),
+            };
+
+            control_states[*first] = first_state as u8;
+            control_states[*second] = second_state as u8;
         }
     }
 }
@@ -0,0 +1,12 @@
+use crate::input::Input;
+use crate::key_event::KeyEvents;
+
+/// Input of a headless console: no button is ever pressed, the key events set are ignored.
+#[derive(Debug, Default)]
+pub struct NullInputSource;
+
+impl Input for NullInputSource {
+    fn get_input_state(&mut self, _control_states: &mut [u8; 8]) {}
+
+    fn set_input_state(&mut self, _key_events: KeyEvents) {}
+}
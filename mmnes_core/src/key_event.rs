@@ -8,6 +8,10 @@ pub const NES_CONTROLLER_KEY_UP: usize = 0x04;
 pub const NES_CONTROLLER_KEY_DOWN: usize = 0x05;
 pub const NES_CONTROLLER_KEY_LEFT: usize = 0x06;
 pub const NES_CONTROLLER_KEY_RIGHT: usize = 0x07;
+/// Famicom second controller microphone, it is not shifted out with the buttons.
+pub const NES_CONTROLLER_KEY_MICROPHONE: usize = 0x08;
+/// Keys of the players 2 - 4 of the Four Score: the keys of player n (1 - 4) are offset by (n - 1) * NES_CONTROLLER_PLAYER_KEYS.
+pub const NES_CONTROLLER_PLAYER_KEYS: usize = 0x10;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct KeyEvent {
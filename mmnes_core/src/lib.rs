@@ -4,6 +4,8 @@ pub mod memory;
 pub mod memory_bank;
 pub mod loader;
 pub mod ines_loader;
+pub mod fds_loader;
+pub mod raw_loader;
 pub mod nes_console;
 pub mod nes_bus;
 pub mod ppu;
@@ -18,6 +20,7 @@ pub mod palette;
 pub mod palette_2c02;
 pub mod dma_device;
 pub mod dma;
+pub mod dma_arbiter;
 pub mod ppu_dma;
 pub mod nes_frame;
 pub mod apu_rp2a03;
@@ -25,19 +28,45 @@ pub mod renderer;
 pub mod memory_palette;
 pub mod controller;
 pub mod standard_controller;
+pub mod four_score;
 pub mod input;
 pub mod sound_playback;
 pub mod mapper;
 pub mod irq_source;
 pub mod unrom_cartridge;
 pub mod memory_mirror;
+pub mod region;
+pub mod ppu_memory_dump;
+pub mod apu_snapshot;
+pub mod ppu_snapshot;
+pub mod perf_counters;
+pub mod expansion_audio;
+pub mod benchmark;
+pub mod cheat;
+pub mod custom_io_device;
+pub mod ntsc_filter;
+pub mod ansi_renderer;
+pub mod session;
+pub mod test_rom;
+pub mod sample_history;
+pub mod timing_spans;
+pub mod ram_search;
+pub mod disassembler;
+pub mod save_state;
+pub mod nes;
+pub mod wav_sink;
 
 #[cfg(test)]
 pub mod tests;
 pub mod key_event;
 mod input_external;
+mod input_null;
 mod sound_playback_passive;
+mod sound_playback_null;
+mod sound_playback_resampler;
 pub mod nes_samples;
 mod mmc1_cartridge;
 pub mod cpu_debugger;
+#[cfg(feature = "tracing")]
+pub mod cpu_tracer;
 mod memory_ciram;
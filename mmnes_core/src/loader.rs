@@ -1,20 +1,29 @@
 use std::cell::RefCell;
 use std::fmt::{Debug, Display, Formatter};
-use std::io::Error;
+use std::io::{Error, Read, Seek};
 use std::path::PathBuf;
 use std::rc::Rc;
 use crate::cartridge::{Cartridge, CartridgeError};
-use crate::ines_loader::INesLoader;
 use crate::memory::MemoryError;
 
 #[derive(Default, Debug, Clone)]
 pub enum LoaderType {
     #[default]
-    INESV2
+    INESV2,
+    FDS,
+    /// headerless PRG binary, copied at ```load_addr```; the reset vector defaults to ```load_addr```
+    RawBinary { load_addr: u16, reset_vector: Option<u16> }
 }
 
+/// The content of a ROM image, a file or the bytes of one already in memory.
+pub trait RomData: Read + Seek + Debug {}
+
+impl<T: Read + Seek + Debug> RomData for T {}
+
 pub trait Loader: Debug  {
-    fn from_file(path: PathBuf) -> Result<INesLoader, LoaderError>;
+    fn from_file(path: PathBuf) -> Result<Self, LoaderError>
+    where
+        Self: Sized;
     fn build_cartridge(self) -> Result<Rc<RefCell<dyn Cartridge>>, LoaderError>;
 }
 
@@ -22,9 +31,12 @@ pub trait Loader: Debug  {
 pub enum LoaderError {
     IoError(Error),
     InvalidRomFormat,
+    /// the file is shorter than the size its header declares (or than the header itself)
+    TruncatedRom { expected: u64, actual: u64 },
     MemoryError(MemoryError),
     CartridgeError(CartridgeError),
-    UnsupportedMapper(String)
+    UnsupportedMapper(String),
+    UnsupportedFormat(String)
 }
 
 impl From<Error> for LoaderError {
@@ -50,9 +62,11 @@ impl Display for LoaderError {
         match self {
             LoaderError::IoError(e) => { write!(f, "i/o error {}", e) },
             LoaderError::InvalidRomFormat => { write!(f, "invalid ROM format") },
+            LoaderError::TruncatedRom { expected, actual } => { write!(f, "truncated ROM: {} bytes expected, {} bytes found", expected, actual) },
             LoaderError::MemoryError(e) => { write!(f, "-> memory error: {}", e) }
             LoaderError::CartridgeError(e) => { write!(f, "-> cartridge error: {}", e) }
             LoaderError::UnsupportedMapper(s) => { write!(f, "unsupported mapper: {}", s) }
+            LoaderError::UnsupportedFormat(s) => { write!(f, "unsupported format: {}", s) }
         }
     }
 }
\ No newline at end of file
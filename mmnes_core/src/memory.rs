@@ -35,6 +35,20 @@ pub trait Memory: Debug {
         self.read_byte(addr)
     }
 
+    /// Little-endian word read without side effects, built from 2 ```trace_read_byte```.
+    fn peek_word(&self, addr: u16) -> Result<u16, MemoryError> {
+        let lo = self.trace_read_byte(addr)?;
+        let hi = self.trace_read_byte(addr.wrapping_add(1))?;
+
+        Ok((hi as u16) << 8 | lo as u16)
+    }
+
+    /// Write without side effects (cheats, debugging), only implemented by plain RAM:
+    /// the devices with registers refuse it, the ROMs report ```MemoryError::ReadOnly```.
+    fn poke_byte(&mut self, addr: u16, _value: u8) -> Result<(), MemoryError> {
+        Err(MemoryError::IllegalState(format!("no side effect free write at 0x{:04X}", addr)))
+    }
+
     /***
      * XXX
      * should be implemented by default (double call to read/write byte)
@@ -62,7 +76,8 @@ pub enum MemoryError {
     OutOfRange(u16),
     BusError(u16),
     IllegalState(String),
-    InvalidAddressSpace(String)
+    InvalidAddressSpace(String),
+    ReadOnly(u16)
 }
 
 impl Error for MemoryError {}
@@ -74,6 +89,7 @@ impl Display for MemoryError {
             MemoryError::BusError(addr) => { write!(f, "bus error: 0x{:04X}", addr) },
             MemoryError::IllegalState(s) => { write!(f, "illegal state: {}", s) }
             MemoryError::InvalidAddressSpace(s) => { write!(f, "invalid address space: {}", s) }
+            MemoryError::ReadOnly(addr) => { write!(f, "write to read-only memory: 0x{:04X}", addr) }
         }
     }
 }
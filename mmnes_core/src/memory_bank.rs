@@ -6,6 +6,30 @@ use crate::memory::MemoryType::StandardMemory;
 pub const MEMORY_BASE_ADDRESS: usize = 0x0000;
 const DEVICE_NAME: &str = "Memory Bank";
 
+/***
+ * Power-on content of the RAM: the real RAM comes up semi-random, so that tests and accuracy
+ * comparisons need to pick the state of a reference emulator.
+ * https://www.nesdev.org/wiki/CPU_power_up_state
+ ***/
+#[derive(Debug, Default, Clone, Copy)]
+pub enum RamInit {
+    #[default]
+    Zeros,
+    Ones,
+    /// value of each byte, from its offset in the memory
+    Pattern(fn(usize) -> u8),
+}
+
+impl RamInit {
+    pub fn value(&self, offset: usize) -> u8 {
+        match self {
+            RamInit::Zeros => 0x00,
+            RamInit::Ones => 0xFF,
+            RamInit::Pattern(pattern) => pattern(offset),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MemoryBank {
     memory: Vec<u8>,
@@ -45,6 +69,10 @@ impl Memory for MemoryBank {
         }
     }
 
+    fn poke_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.write_byte(addr, value)
+    }
+
     fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
         let lo = self.read_byte(addr)?;
         let next = self.wrapping_add(addr, 1);
@@ -94,6 +122,20 @@ impl BusDevice for MemoryBank {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         self.address_space
     }
+
+    fn serialize_state(&self) -> Vec<u8> {
+        self.memory.clone()
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        if state.len() != self.memory.len() {
+            return Err(MemoryError::IllegalState(
+                format!("memory bank state of {} bytes, expected {} bytes", state.len(), self.memory.len())));
+        }
+
+        self.memory.copy_from_slice(state);
+        Ok(())
+    }
 }
 
 impl MemoryBank {
@@ -105,6 +147,16 @@ impl MemoryBank {
         }
     }
 
+    pub fn bytes(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn fill_with(&mut self, ram_init: RamInit) {
+        for (offset, byte) in self.memory.iter_mut().enumerate() {
+            *byte = ram_init.value(offset);
+        }
+    }
+
     fn wrapping_add(&self, addr: u16, n: u16) -> u16 {
         let size = self.size() as u32;
 
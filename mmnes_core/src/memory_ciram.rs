@@ -107,4 +107,12 @@ impl BusDevice for CiramMemory {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         PPU_CIRAM_VIRTUAL_ADDRESS_RANGE
     }
+
+    fn serialize_state(&self) -> Vec<u8> {
+        self.memory.borrow().serialize_state()
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        self.memory.borrow_mut().deserialize_state(state)
+    }
 }
\ No newline at end of file
@@ -2,23 +2,31 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::memory::{Memory, MemoryError};
-use crate::memory_bank::MemoryBank;
 
+/***
+ * A device seen through another address space:
+ *   - new: the address space is at most as large as the device, the addresses are forwarded as is,
+ *   - with_mask: the address space repeats the device, the addresses are ANDed with addr_mask before
+ *     being forwarded (WRAM: $0000 - $1FFF with $07FF, PPU registers: $2000 - $3FFF with $0007).
+ *     The bus masks the addresses with the size of the mirror: the address space size must be a power of 2.
+ ***/
 #[derive(Debug)]
 pub struct MemoryMirror {
-    memory: Rc<RefCell<MemoryBank>>,
-    address_space: (u16, u16)
+    memory: Rc<RefCell<dyn BusDevice>>,
+    address_space: (u16, u16),
+    addr_mask: u16,
+    size: usize
 }
 
 impl MemoryMirror {
 
-    fn is_address_space_valid(memory: Rc<RefCell<MemoryBank>>, address_space: (u16, u16)) -> bool {
+    fn is_address_space_valid(memory: Rc<RefCell<dyn BusDevice>>, address_space: (u16, u16)) -> bool {
         let real_virtual_size0 = memory.borrow().get_virtual_address_range().1 - memory.borrow().get_virtual_address_range().0;
         let mirror_virtual_size1 = address_space.1 - address_space.0;
         mirror_virtual_size1 <= real_virtual_size0
     }
     
-    pub fn new(memory: Rc<RefCell<MemoryBank>>, address_space: (u16, u16)) -> Result<Self, MemoryError> {
+    pub fn new(memory: Rc<RefCell<dyn BusDevice>>, address_space: (u16, u16)) -> Result<Self, MemoryError> {
         let result = if MemoryMirror::is_address_space_valid(memory.clone(), address_space) == false {
             Err(MemoryError::InvalidAddressSpace(
                 format!("mirror virtual memory mirror can not be larger as the primary memory: {} versus {}.",
@@ -26,9 +34,12 @@ impl MemoryMirror {
                         address_space.1 - address_space.0 + 1))
             )
         } else {
+            let size = memory.borrow().size();
             let mirror = MemoryMirror {
                 memory,
-                address_space
+                address_space,
+                addr_mask: 0xFFFF,
+                size
             };
 
             Ok(mirror)
@@ -36,6 +47,27 @@ impl MemoryMirror {
         
         result
     }
+
+    pub fn with_mask(memory: Rc<RefCell<dyn BusDevice>>, address_space: (u16, u16), addr_mask: u16) -> Result<Self, MemoryError> {
+        let size = (address_space.1 - address_space.0) as usize + 1;
+
+        if addr_mask as usize >= memory.borrow().size() {
+            return Err(MemoryError::InvalidAddressSpace(
+                format!("mirror mask 0x{:04X} is out of the primary memory: {} bytes", addr_mask, memory.borrow().size())));
+        }
+
+        if !size.is_power_of_two() {
+            return Err(MemoryError::InvalidAddressSpace(
+                format!("mirror address space size must be a power of 2: {} bytes", size)));
+        }
+
+        Ok(MemoryMirror {
+            memory,
+            address_space,
+            addr_mask,
+            size
+        })
+    }
 }
 
 impl Memory for MemoryMirror {
@@ -44,23 +76,27 @@ impl Memory for MemoryMirror {
     }
 
     fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
-        self.memory.borrow().read_byte(addr)
+        self.memory.borrow().read_byte(addr & self.addr_mask)
     }
 
     fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
-        self.read_byte(addr)
+        self.memory.borrow().trace_read_byte(addr & self.addr_mask)
     }
 
     fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
-        self.memory.borrow_mut().write_byte(addr, value)
+        self.memory.borrow_mut().write_byte(addr & self.addr_mask, value)
+    }
+
+    fn poke_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.memory.borrow_mut().poke_byte(addr & self.addr_mask, value)
     }
 
     fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
-        self.memory.borrow().read_word(addr)
+        self.memory.borrow().read_word(addr & self.addr_mask)
     }
 
     fn write_word(&mut self, addr: u16, value: u16) -> Result<(), MemoryError> {
-        self.memory.borrow_mut().write_word(addr, value)
+        self.memory.borrow_mut().write_word(addr & self.addr_mask, value)
     }
 
     fn dump(&self) {
@@ -68,7 +104,7 @@ impl Memory for MemoryMirror {
     }
 
     fn size(&self) -> usize {
-        self.memory.borrow().size()
+        self.size
     }
 }
 
@@ -85,4 +121,12 @@ impl BusDevice for MemoryMirror {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         self.address_space
     }
+
+    fn serialize_state(&self) -> Vec<u8> {
+        self.memory.borrow().serialize_state()
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        self.memory.borrow_mut().deserialize_state(state)
+    }
 }
\ No newline at end of file
@@ -29,14 +29,22 @@ impl Memory for MemoryPalette {
         self.memory.write_byte(mirrored_addr, value)
     }
 
-    fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
+    fn poke_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
         let mirrored_addr = self.get_mirrored_address(addr);
-        self.memory.read_word(mirrored_addr)
+        self.memory.poke_byte(mirrored_addr, value)
+    }
+
+    /// Built from 2 byte accesses, each byte is mirrored on its own.
+    fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
+        let lo = self.read_byte(addr)?;
+        let hi = self.read_byte(addr.wrapping_add(1))?;
+
+        Ok((hi as u16) << 8 | lo as u16)
     }
 
     fn write_word(&mut self, addr: u16, value: u16) -> Result<(), MemoryError> {
-        let mirrored_addr = self.get_mirrored_address(addr);
-        self.memory.write_word(mirrored_addr, value)
+        self.write_byte(addr, (value & 0xFF) as u8)?;
+        self.write_byte(addr.wrapping_add(1), (value >> 8) as u8)
     }
 
     fn dump(&self) {
@@ -60,6 +68,14 @@ impl BusDevice for MemoryPalette {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         self.memory.get_virtual_address_range()
     }
+
+    fn serialize_state(&self) -> Vec<u8> {
+        self.memory.serialize_state()
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        self.memory.deserialize_state(state)
+    }
 }
 
 impl MemoryPalette {
@@ -69,7 +85,15 @@ impl MemoryPalette {
         }
     }
 
+    /***
+     * the backdrop entries of the sprite palettes ($3F10, $3F14, $3F18, $3F1C) are the ones of
+     * the background palettes ($3F00, $3F04, $3F08, $3F0C), on reads and writes.
+     * The address is relative to the 32 bytes of the palette, mirrored up to $3FFF by the bus.
+     * https://www.nesdev.org/wiki/PPU_palettes#Memory_Map
+     ***/
     fn get_mirrored_address(&self, addr: u16) -> u16 {
+        let addr = addr & 0x1F;
+
         match addr {
             0x10 | 0x14 | 0x18 | 0x1C => addr - 0x10,
             _ => addr,
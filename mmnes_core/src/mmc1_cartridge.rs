@@ -1,15 +1,14 @@
 use std::cell::RefCell;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read, Seek};
 use std::rc::Rc;
 use log::{debug, info};
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::cartridge;
-use crate::cartridge::{Cartridge, CartridgeError, PPU_ADDRESS_SPACE};
+use crate::cartridge::{BankState, Cartridge, CartridgeError, PPU_ADDRESS_SPACE};
 use crate::cartridge::CartridgeType::MMC1;
 use crate::ines_loader::{FromINes, INesRomHeader};
-use crate::loader::LoaderError;
+use crate::loader::{LoaderError, RomData};
 use crate::memory::{Memory, MemoryError, MemoryType};
 use crate::memory_bank::MemoryBank;
 use crate::memory_ciram::PpuNameTableMirroring;
@@ -147,6 +146,28 @@ impl BusDevice for SwitchableMemory {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         self.virtual_addr_space
     }
+
+    /// The content of the banks, one after the other.
+    fn serialize_state(&self) -> Vec<u8> {
+        self.memory_banks.iter().flat_map(|bank| bank.serialize_state()).collect()
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        let mut offset = 0;
+
+        for bank in self.memory_banks.iter_mut() {
+            let end = offset + bank.size();
+
+            if end > state.len() {
+                return Err(MemoryError::IllegalState(format!("{} state of {} bytes is too short", self.name, state.len())));
+            }
+
+            bank.deserialize_state(&state[offset..end])?;
+            offset = end;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -399,7 +420,7 @@ impl Mmc1Cartridge {
         Ok(memory)
     }
 
-    pub fn new(mut data: BufReader<File>,
+    pub fn new(mut data: impl Read + Seek,
                prg_rom_offset: u64, prg_rom_size: usize, prg_ram_size: usize,
                chr_rom_offset: u64, chr_rom_size: usize, chr_ram_size: usize,
                mirroring: PpuNameTableMirroring) -> Result<Mmc1Cartridge, CartridgeError> {
@@ -440,12 +461,12 @@ impl Mmc1Cartridge {
     }
 
 
-    fn build(file: File,
+    fn build(data: Box<dyn RomData>,
              prg_rom_offset: u64, prg_rom_size: usize, prg_ram_size: usize,
              chr_rom_offset: Option<u64>, chr_rom_size: usize, chr_ram_size: usize, mirroring: PpuNameTableMirroring) -> Result<Mmc1Cartridge, LoaderError> {
         debug!("creating MMC1 cartridge");
 
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(data);
         let chr_rom_offset = if let Some(chr_rom_offset_unwrapped) = chr_rom_offset { chr_rom_offset_unwrapped } else { 0 };
 
         let cartridge = Mmc1Cartridge::new(reader, prg_rom_offset, prg_rom_size, prg_ram_size, chr_rom_offset, chr_rom_size, chr_ram_size, mirroring)?;
@@ -455,14 +476,14 @@ impl Mmc1Cartridge {
 
 impl FromINes for Mmc1Cartridge {
     #[allow(refining_impl_trait)]
-    fn from_ines(file: File, header: INesRomHeader) -> Result<Mmc1Cartridge, LoaderError>
+    fn from_ines(data: Box<dyn RomData>, header: INesRomHeader) -> Result<Mmc1Cartridge, LoaderError>
     where
         Self: Sized
     {
 
         let prg_ram_size = if header.prg_ram_size == 0 { (PRG_RAM_ADDRESS_SPACE.1 - PRG_RAM_ADDRESS_SPACE.0 + 1) as usize } else { header.prg_ram_size };
 
-        let cartridge = Mmc1Cartridge::build(file,
+        let cartridge = Mmc1Cartridge::build(data,
                                               header.prg_offset(), header.prg_rom_size, prg_ram_size,
                                               header.chr_offset(), header.chr_rom_size, header.chr_ram_size,
                                               header.nametables_layout)?;
@@ -483,6 +504,26 @@ impl BusDevice for Mmc1Cartridge {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         (PRG_ROM_ADDRESS_SPACE.0, PRG_ROM_ADDRESS_SPACE.1)
     }
+
+    /// The shift register and the internal registers, the banks are remapped from them on restore.
+    fn serialize_state(&self) -> Vec<u8> {
+        vec![self.shift_register, self.control_register, self.control_chr_bank0, self.control_chr_bank1, self.control_prg_bank]
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        match state {
+            [shift_register, control_register, control_chr_bank0, control_chr_bank1, control_prg_bank] => {
+                self.shift_register = *shift_register;
+                self.control_register = *control_register;
+                self.control_chr_bank0 = *control_chr_bank0;
+                self.control_chr_bank1 = *control_chr_bank1;
+                self.control_prg_bank = *control_prg_bank;
+
+                self.apply_control()
+            },
+            _ => Err(MemoryError::IllegalState(format!("MMC1 state of {} bytes, expected 5 bytes", state.len()))),
+        }
+    }
 }
 
 impl Memory for Mmc1Cartridge {
@@ -491,6 +532,10 @@ impl Memory for Mmc1Cartridge {
         self.prg_rom.read_byte(addr)
     }
 
+    fn poke_byte(&mut self, addr: u16, _: u8) -> Result<(), MemoryError> {
+        Err(MemoryError::ReadOnly(addr))
+    }
+
     /***
      * On the fifth write, the MMC1 copies bit 0 and the shift register contents into an internal register selected by bits 14 and 13 of the address, and then it clears the shift register.
      * Only on the fifth write does the address matter, and even then, only bits 14 and 13 of the address matter because the mapper doesn't see the lower address bits
@@ -538,4 +583,10 @@ impl Cartridge for Mmc1Cartridge {
     fn get_mirroring(&self) -> Rc<RefCell<PpuNameTableMirroring>> {
         self.mirroring.clone()
     }
+
+    /// The PRG ROM and the CHR memory are both split in two halves, each mapped to its bank.
+    fn bank_state(&self) -> BankState {
+        let chr_rom = self.chr_rom.borrow();
+        BankState::from_16k_prg_banks(self.prg_rom.current_bank_lo, self.prg_rom.current_bank_hi, chr_rom.current_bank_lo, chr_rom.current_bank_hi)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::apu::ApuType::RP2A03;
+use crate::bus::BusType;
+use crate::bus_device::BusDeviceType::{APU, CARTRIDGE, CONTROLLER, PPU, WRAM};
+use crate::cartridge::CartridgeType::NROM;
+use crate::controller::ControllerType;
+use crate::cpu::CpuType;
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_B, NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_SELECT, NES_CONTROLLER_KEY_START, NES_CONTROLLER_KEY_UP, NES_CONTROLLER_PLAYER_KEYS};
+use crate::loader::LoaderType::INESV2;
+use crate::memory::MemoryType::StandardMemory;
+use crate::nes_console::{NesConsole, NesConsoleBuilder, NesConsoleError};
+use crate::nes_frame::NesFrame;
+use crate::nes_samples::NesSamples;
+use crate::ppu::PpuType::NES2C02;
+use crate::region::Region;
+
+pub const NES_PLAYERS: u8 = 4;
+
+/// The buttons of a controller, true when pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ButtonState {
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool,
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl ButtonState {
+    fn keys(&self) -> [(usize, bool); 8] {
+        [
+            (NES_CONTROLLER_KEY_A, self.a),
+            (NES_CONTROLLER_KEY_B, self.b),
+            (NES_CONTROLLER_KEY_SELECT, self.select),
+            (NES_CONTROLLER_KEY_START, self.start),
+            (NES_CONTROLLER_KEY_UP, self.up),
+            (NES_CONTROLLER_KEY_DOWN, self.down),
+            (NES_CONTROLLER_KEY_LEFT, self.left),
+            (NES_CONTROLLER_KEY_RIGHT, self.right),
+        ]
+    }
+}
+
+/***
+ * The console in its standard configuration, for the programs embedding the emulator:
+ * a NTSC NES with the cartridge of an iNES image, the frames are stepped by the caller.
+ *
+ *   let mut nes = Nes::new(&rom_bytes)?;
+ *   nes.set_buttons(1, ButtonState { start: true, ..Default::default() })?;
+ *   let frame = nes.step_frame()?;
+ *
+ * The controllers are plugged through a Four Score: the players 1 and 2 are read as the two standard
+ * controllers by any game, the players 3 and 4 by the games supporting the Four Score.
+ * The builder (NesConsoleBuilder) stays the way to any other configuration.
+ ***/
+pub struct Nes {
+    console: NesConsole,
+    frame: Option<NesFrame>,
+    samples: NesSamples,
+}
+
+impl Nes {
+    pub fn new(rom_bytes: &[u8]) -> Result<Nes, NesError> {
+        let mut console = NesConsoleBuilder::new()
+            .with_cpu(CpuType::NES6502)
+            .with_bus_type(BusType::NESBus)
+            .with_bus_device_type(WRAM(StandardMemory))
+            .with_bus_device_type(CARTRIDGE(NROM))
+            .with_bus_device_type(APU(RP2A03))
+            .with_bus_device_type(PPU(NES2C02))
+            .with_bus_device_type(CONTROLLER(ControllerType::FourScore))
+            .with_loader_type(INESV2)
+            .with_rom_bytes(rom_bytes.to_vec())
+            .with_region(Region::NTSC)
+            .build()?;
+
+        console.power_on()?;
+
+        Ok(Nes {
+            console,
+            frame: None,
+            samples: NesSamples::default(),
+        })
+    }
+
+    /// Runs the console up to its next frame, the frame and its samples are kept until the next step.
+    pub fn step_frame(&mut self) -> Result<&NesFrame, NesError> {
+        let (frame, samples) = self.console.step_frame()?;
+        self.samples = samples;
+
+        Ok(self.frame.insert(frame))
+    }
+
+    /// The buttons of ```player``` (1 - 4), held until they are set again.
+    pub fn set_buttons(&mut self, player: u8, buttons: ButtonState) -> Result<(), NesError> {
+        if player == 0 || player > NES_PLAYERS {
+            return Err(NesError::InvalidPlayer(player));
+        }
+
+        let offset = (player - 1) as usize * NES_CONTROLLER_PLAYER_KEYS;
+        let events = buttons.keys()
+            .into_iter()
+            .map(|(key, pressed)| KeyEvent { key: offset + key, pressed })
+            .collect::<KeyEvents>();
+
+        self.console.set_input(events)?;
+        Ok(())
+    }
+
+    /// The last frame stepped, none before the first step.
+    pub fn frame(&self) -> Option<&NesFrame> {
+        self.frame.as_ref()
+    }
+
+    /// The samples of the last frame stepped.
+    pub fn audio_samples(&self) -> &[f32] {
+        self.samples.samples()
+    }
+
+    /// The console behind the facade, for what it does not cover (save states, cheats, the debugger...).
+    pub fn console(&mut self) -> &mut NesConsole {
+        &mut self.console
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NesError {
+    InvalidPlayer(u8),
+    ConsoleError(NesConsoleError),
+}
+
+impl From<NesConsoleError> for NesError {
+    fn from(error: NesConsoleError) -> Self {
+        NesError::ConsoleError(error)
+    }
+}
+
+impl Error for NesError {}
+
+impl Display for NesError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            NesError::InvalidPlayer(player) => write!(f, "no player {}, the players are 1 - {}", player, NES_PLAYERS),
+            NesError::ConsoleError(e) => write!(f, "-> console error: {}", e),
+        }
+    }
+}
@@ -1,17 +1,50 @@
 use std::cell::RefCell;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 use log::{debug, trace};
 use crate::bus::{Bus, BusError};
 use crate::bus_device::{BusDevice, BusDeviceType};
+use crate::cheat::{Cheats, PRG_ROM_START_ADDRESS};
 use crate::memory::{Memory, MemoryError};
 
 pub const BUS_ADDRESSABLE_SIZE: usize = 64 * 1024;
 
+/// Who wrote the byte logged: the CPU, or a DMA unit on its behalf.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteSource {
+    Cpu,
+    Dma,
+}
+
+impl Display for WriteSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteSource::Cpu => write!(f, "CPU"),
+            WriteSource::Dma => write!(f, "DMA"),
+        }
+    }
+}
+
+/// Called with the CPU address, the byte written and who wrote it.
+pub type BusWriteLog = Box<dyn FnMut(u16, u8, WriteSource)>;
+
+struct WriteLogger {
+    range: (u16, u16),
+    log: BusWriteLog,
+}
+
+impl Debug for WriteLogger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WriteLogger(0x{:04X} - 0x{:04X})", self.range.0, self.range.1)
+    }
+}
+
 #[derive(Debug)]
 pub struct NESBus {
     devices: Vec<Rc<RefCell<dyn BusDevice>>>,
     num_devices: usize,
+    cheats: Option<Rc<RefCell<Cheats>>>,
+    write_loggers: RefCell<Vec<WriteLogger>>,
 }
 
 impl Memory for NESBus {
@@ -23,23 +56,33 @@ impl Memory for NESBus {
         let (memory, effective_addr) = self.lookup_address(addr)?;
         let value = memory.borrow().read_byte(effective_addr)?;
 
-        Ok(value)
+        Ok(self.patch_with_cheats(addr, value))
     }
 
     fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
         let (memory, effective_addr) = self.lookup_address(addr)?;
         let value = memory.borrow().trace_read_byte(effective_addr)?;
 
-        Ok(value)
+        Ok(self.patch_with_cheats(addr, value))
     }
 
     fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        // logged before the device is written: a write to $4014 logs the DMA writes it triggers after it
+        self.log_write(addr, value, WriteSource::Cpu);
+
         let (memory, effective_addr) = self.lookup_address(addr)?;
         memory.borrow_mut().write_byte(effective_addr, value)?;
 
         Ok(())
     }
 
+    fn poke_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        let (memory, effective_addr) = self.lookup_address(addr)?;
+        memory.borrow_mut().poke_byte(effective_addr, value)?;
+
+        Ok(())
+    }
+
     fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
         let (memory, effective_addr) = self.lookup_address(addr)?;
         let value = memory.borrow().read_word(effective_addr)?;
@@ -68,18 +111,20 @@ impl Bus for NESBus {
 
     fn add_device(&mut self, device: Rc<RefCell<dyn BusDevice>>) -> Result<(), BusError> {
         let size = device.borrow().size();
-        let address_space = device.borrow().get_virtual_address_range();
+        let address_ranges = device.borrow().get_address_ranges();
 
-        debug!("BUS: adding device {} - size: {} bytes, address range: 0x{:04X} - 0x{:04X}",
-        device.borrow().get_name(), size, address_space.0, address_space.1);
+        for address_space in address_ranges {
+            debug!("BUS: adding device {} - size: {} bytes, address range: 0x{:04X} - 0x{:04X}",
+            device.borrow().get_name(), size, address_space.0, address_space.1);
 
-        for addr in address_space.0..=address_space.1 {
-            if self.devices[addr as usize].borrow().get_device_type() != BusDeviceType::OPENBUS {
-                debug!("BUS: address 0x{:04X} already mapped by device {}, overwriting by {} ...",
-                         addr, self.devices[addr as usize].borrow().get_name(), device.borrow().get_name());
-            }
+            for addr in address_space.0..=address_space.1 {
+                if self.devices[addr as usize].borrow().get_device_type() != BusDeviceType::OPENBUS {
+                    debug!("BUS: address 0x{:04X} already mapped by device {}, overwriting by {} ...",
+                             addr, self.devices[addr as usize].borrow().get_name(), device.borrow().get_name());
+                }
 
-            self.devices[addr as usize] = device.clone();
+                self.devices[addr as usize] = device.clone();
+            }
         }
 
         let count = self.count_addresses_in_bus();
@@ -89,6 +134,65 @@ impl Bus for NESBus {
 
         Ok(())
     }
+
+    fn describe_mapping(&self) -> Vec<((u16, u16), String)> {
+        let mut mapping: Vec<((u16, u16), String)> = Vec::new();
+        let mut start = 0usize;
+
+        for addr in 1..=self.devices.len() {
+            let is_end_of_range = addr == self.devices.len() || !Rc::ptr_eq(&self.devices[addr], &self.devices[start]);
+
+            if is_end_of_range {
+                let device = self.devices[start].borrow();
+
+                if device.get_device_type() != BusDeviceType::OPENBUS {
+                    mapping.push(((start as u16, (addr - 1) as u16), device.get_name()));
+                }
+
+                start = addr;
+            }
+        }
+
+        mapping
+    }
+
+    fn remove_devices(&mut self) {
+        let open_bus: Rc<RefCell<dyn BusDevice>> = Rc::new(RefCell::new(OpenBus::new()));
+
+        self.devices.fill(open_bus);
+        self.num_devices = 0;
+    }
+
+    fn serialize_devices(&self) -> Vec<(BusDeviceType, Vec<u8>)> {
+        self.mapped_devices()
+            .iter()
+            .map(|device| (device.borrow().get_device_type(), device.borrow().serialize_state()))
+            .collect()
+    }
+
+    fn deserialize_devices(&mut self, states: &[(BusDeviceType, Vec<u8>)]) -> Result<(), MemoryError> {
+        let devices = self.mapped_devices();
+
+        if devices.len() != states.len() {
+            return Err(MemoryError::IllegalState(
+                format!("{} device states for {} devices mapped", states.len(), devices.len())));
+        }
+
+        for (device, (device_type, state)) in devices.iter().zip(states) {
+            if device.borrow().get_device_type() != *device_type {
+                return Err(MemoryError::IllegalState(
+                    format!("state of a {} for the {}", device_type, device.borrow().get_device_type())));
+            }
+
+            device.borrow_mut().deserialize_state(state)?;
+        }
+
+        Ok(())
+    }
+
+    fn log_dma_write(&self, addr: u16, value: u8) {
+        self.log_write(addr, value, WriteSource::Dma);
+    }
 }
 
 impl NESBus {
@@ -99,6 +203,37 @@ impl NESBus {
         NESBus {
             devices: vec![open_bus.clone(); 65536],
             num_devices: 0,
+            cheats: None,
+            write_loggers: RefCell::new(Vec::new()),
+        }
+    }
+
+    /***
+     * Calls ```log``` on each write in ```range``` (inclusive), lighter than a watchpoint to follow the writes
+     * to the PPU or mapper registers. The writes of the CPU and of the DMA units (the OAM DMA to $2004) are logged,
+     * the pokes of the debugger are not.
+     ***/
+    pub fn log_writes_in_range(&mut self, range: (u16, u16), log: BusWriteLog) {
+        self.write_loggers.borrow_mut().push(WriteLogger { range, log });
+    }
+
+    fn log_write(&self, addr: u16, value: u8, source: WriteSource) {
+        for logger in self.write_loggers.borrow_mut().iter_mut() {
+            if addr >= logger.range.0 && addr <= logger.range.1 {
+                (logger.log)(addr, value, source);
+            }
+        }
+    }
+
+    /// The reads of the PRG space are patched by the active cheats, the underlying memory is left untouched.
+    pub fn attach_cheats(&mut self, cheats: Rc<RefCell<Cheats>>) {
+        self.cheats = Some(cheats);
+    }
+
+    fn patch_with_cheats(&self, addr: u16, value: u8) -> u8 {
+        match &self.cheats {
+            Some(cheats) if addr >= PRG_ROM_START_ADDRESS && !cheats.borrow().is_empty() => cheats.borrow().patch(addr, value),
+            _ => value,
         }
     }
 
@@ -119,6 +254,21 @@ impl NESBus {
         Ok((device, effective_addr))
     }
 
+    /// Each device mapped once, in address order, the open bus left out.
+    fn mapped_devices(&self) -> Vec<Rc<RefCell<dyn BusDevice>>> {
+        let mut devices: Vec<Rc<RefCell<dyn BusDevice>>> = Vec::new();
+
+        for device in &self.devices {
+            let is_mapped = device.borrow().get_device_type() != BusDeviceType::OPENBUS;
+
+            if is_mapped && !devices.iter().any(|d| Rc::ptr_eq(d, device)) {
+                devices.push(device.clone());
+            }
+        }
+
+        devices
+    }
+
     fn count_addresses_in_bus(&self) -> usize {
         self.devices
             .iter()
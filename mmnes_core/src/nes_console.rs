@@ -1,55 +1,71 @@
 use std::cell::RefCell;
 use std::fmt::{Display, Formatter};
+use std::fs;
 use std::path::PathBuf;
 use std::rc::Rc;
-use log::debug;
+use log::{debug, info, warn};
+use serde_json::{json, Value};
 use crate::apu::{ApuError, ApuType, APU};
-use crate::apu_rp2a03::ApuRp2A03;
+use crate::apu_rp2a03::{ApuRp2A03, AUDIO_RATE};
+use crate::apu_snapshot::{ApuChannelSnapshot, ApuSnapshot};
 use crate::bus::{Bus, BusError, BusType};
 use crate::bus_device::{BusDevice, BusDeviceType};
-use crate::cartridge::Cartridge;
+use crate::cartridge::{BankState, Cartridge};
+use crate::cheat::{Cheat, CheatError, Cheats};
 use crate::controller::{Controller, ControllerType};
-use crate::cpu::{CPU, CpuError, CpuType};
-use crate::cpu_6502::Cpu6502;
+use crate::cpu::{CPU, CpuError, CpuType, InterruptKind};
+use crate::cpu_6502::{Cpu6502, UnstableOpcodeMode};
 use crate::cpu_debugger::CpuSnapshot;
 use crate::dma::PpuDmaType;
 use crate::dma_device::DmaDevice;
+use crate::fds_loader::FdsLoader;
+use crate::raw_loader::RawLoader;
+use crate::custom_io_device::CustomIoDevice;
 use crate::ines_loader::INesLoader;
-use crate::input::InputError;
+use crate::input::{Input, InputError, SocdMode};
 use crate::input_external::InputExternal;
+use crate::input_null::NullInputSource;
 use crate::key_event::KeyEvents;
 use crate::loader::{Loader, LoaderError, LoaderType};
 use crate::memory::{Memory, MemoryError, MemoryType};
-use crate::memory_bank::MemoryBank;
+use crate::memory_bank::{MemoryBank, RamInit};
 use crate::memory_ciram::PpuNameTableMirroring;
+use crate::memory_mirror::MemoryMirror;
 use crate::nes_bus::NESBus;
 use crate::nes_frame::NesFrame;
 use crate::nes_samples::NesSamples;
+use crate::palette_2c02::PaletteError;
+use crate::perf_counters::PerfCounters;
 use crate::ppu::{PPU, PpuError, PpuType};
-use crate::ppu_2c02::Ppu2c02;
+use crate::ppu_2c02::{Ppu2c02, DEFAULT_OAM_DECAY_FRAMES};
+use crate::ppu_memory_dump::PpuMemoryDump;
+use crate::ppu_snapshot::PpuSnapshot;
 use crate::ppu_dma::PpuDma;
-use crate::sound_playback::SoundPlaybackError;
-use crate::sound_playback_passive::SoundPlaybackPassive;
+use crate::region::Region;
+use crate::session::{Session, SessionError};
+use crate::sound_playback::{SoundPlayback, SoundPlaybackError};
+use crate::sound_playback_null::NullSoundPlayback;
+use crate::sound_playback_passive::{SoundPlaybackPassive, DEFAULT_BUFFER_SIZE};
+use crate::sound_playback_resampler::SoundPlaybackResampler;
 use crate::standard_controller::StandardController;
+use crate::four_score::FourScore;
+use crate::wav_sink::MultiChannelWavSink;
+use crate::timing_spans::{timed, TimingSpans};
+use crate::ram_search::{RamSearch, SearchCriteria};
+use crate::disassembler::disassemble_range;
+use crate::save_state::{SaveState, SaveStateError};
 
 const WRAM_MEMORY_SIZE: usize = 2 * 1024;
 const WRAM_START_ADDR: u16 = 0x0000;
 const WRAM_END_ADDR: u16 = 0x1FFF;
+const WRAM_MIRROR_MASK: u16 = 0x07FF;
+const PPU_REGISTERS_ADDRESS_SPACE: (u16, u16) = (0x2000, 0x3FFF);
+const PPU_REGISTERS_MIRROR_MASK: u16 = 0x0007;
 const DEFAULT_START_ADDRESS: u16 = 0xFFFC;
+const NMI_VECTOR_ADDR: u16 = 0xFFFA;
+const IRQ_VECTOR_ADDR: u16 = 0xFFFE;
 const CYCLE_START_SEQUENCE: u32 = 7;
 
-/// The cycle credits given to the CPU, the PPU, and the APU
-/// the default value of 114 cycles is the number of cycles
-/// needed for the PPU to render a single scanline.
-const CYCLE_CREDITS: u32 = 114;
-
-/// The maximum number of cycles the CPU can go ahead
-/// before being caught up by the APU.
-const APU_CYCLES_THRESHOLD: u32 = 114;
-
-/// The maximum number of cycles the CPU can go ahead
-/// before being caught up by the PPU.
-const PPU_CYCLES_THRESHOLD: u32 = 114;
 
 ///
 /// CPU cycle counter for CPU, APU and PPU
@@ -76,7 +92,29 @@ impl CyclesCounter {
     }
 }
 
+/***
+ * A JAM (KIL) opcode freezes the CPU until a reset, while the PPU and the APU keep running:
+ * the console is then halted, at the address of the opcode.
+ * With ```with_halt_on_unimplemented```, an opcode the CPU does not implement, or an unofficial one, stops it the same way,
+ * the state keeps the opcode for the debugger.
+ * https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleState {
+    Running,
+    Halted(u16),
+    Unimplemented { pc: u16, opcode: u8 },
+}
+
+impl ConsoleState {
+    /// The CPU waits for a reset, the PPU and the APU keep running.
+    pub fn is_cpu_stopped(&self) -> bool {
+        !matches!(self, ConsoleState::Running)
+    }
+}
+
 pub struct NesConsole {
+    bus: Rc<RefCell<dyn Bus>>,
     cpu: Rc<RefCell<dyn CPU>>,
     ppu: Rc<RefCell<dyn PPU>>,
     apu: Rc<RefCell<dyn APU>>,
@@ -85,11 +123,21 @@ pub struct NesConsole {
     cpu_counter: CyclesCounter,
     apu_counter: CyclesCounter,
     ppu_counter: CyclesCounter,
+    region: Region,
+    cheats: Rc<RefCell<Cheats>>,
+    state: ConsoleState,
+    wram: Option<Rc<RefCell<MemoryBank>>>,
+    cartridge: Option<Rc<RefCell<dyn Cartridge>>>,
+    ram_init: RamInit,
+    samples_produced: u64,
+    timing_spans: TimingSpans,
+    ram_search: Option<RamSearch>,
 }
 
 impl NesConsole {
-    fn new(cpu: Rc<RefCell<dyn CPU>>,ppu: Rc<RefCell<dyn PPU>>, apu: Rc<RefCell<dyn APU>>, controller: Rc<RefCell<dyn Controller>>, entry_point: Option<u16>) -> NesConsole {
+    fn new(bus: Rc<RefCell<dyn Bus>>, cpu: Rc<RefCell<dyn CPU>>,ppu: Rc<RefCell<dyn PPU>>, apu: Rc<RefCell<dyn APU>>, controller: Rc<RefCell<dyn Controller>>, entry_point: Option<u16>) -> NesConsole {
         NesConsole {
+            bus,
             cpu,
             ppu,
             apu,
@@ -98,14 +146,306 @@ impl NesConsole {
             cpu_counter: CyclesCounter::new(CYCLE_START_SEQUENCE),
             apu_counter: CyclesCounter::new(0),
             ppu_counter: CyclesCounter::new(0),
+            region: Region::default(),
+            cheats: Rc::new(RefCell::new(Cheats::new())),
+            state: ConsoleState::Running,
+            wram: None,
+            cartridge: None,
+            ram_init: RamInit::default(),
+            samples_produced: 0,
+            timing_spans: TimingSpans::default(),
+            ram_search: None,
+        }
+    }
+
+    pub fn state(&self) -> ConsoleState {
+        self.state
+    }
+
+    /// Byte at the address on the CPU bus, without the side effects of a read (e.g. on the PPU registers).
+    pub fn peek(&self, addr: u16) -> Result<u8, NesConsoleError> {
+        Ok(self.bus.borrow().trace_read_byte(addr)?)
+    }
+
+    /***
+     * The work RAM addresses ($0000-$07FF) still candidates of the cheat search and matching ```criteria```
+     * since the previous frame, see RamSearch. Empty without work RAM.
+     ***/
+    pub fn ram_search(&mut self, criteria: SearchCriteria) -> Vec<u16> {
+        match (&mut self.ram_search, &self.wram) {
+            (Some(ram_search), Some(wram)) => ram_search.search(wram.borrow().bytes(), criteria).to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Makes the whole work RAM a candidate of the cheat search again.
+    pub fn reset_ram_search(&mut self) {
+        if let Some(ram_search) = &mut self.ram_search {
+            ram_search.reset();
+        }
+    }
+
+    /// The state of each device on the CPU bus (work RAM, mapper registers, ...), for a save state.
+    pub fn save_device_states(&self) -> Vec<(BusDeviceType, Vec<u8>)> {
+        self.bus.borrow().serialize_devices()
+    }
+
+    /// Restores the states given by ```save_device_states``` for the same cartridge.
+    pub fn load_device_states(&mut self, states: &[(BusDeviceType, Vec<u8>)]) -> Result<(), NesConsoleError> {
+        Ok(self.bus.borrow_mut().deserialize_devices(states)?)
+    }
+
+    /// A save state of the CPU and of the devices on its bus, see SaveState for what is saved.
+    pub fn save_state(&self) -> Vec<u8> {
+        let devices = self.save_device_states()
+            .into_iter()
+            .map(|(device_type, state)| (device_type.to_string(), state))
+            .collect();
+
+        SaveState::new(self.cpu.borrow().serialize_state(), devices).to_bytes()
+    }
+
+    /***
+     * Restores a state given by ```save_state```, for a console built with the same devices and cartridge.
+     * Every section is checked against the console (device, size) before anything is restored: a state
+     * rejected by a device anyway puts the console back as it was, it is never half restored.
+     ***/
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), NesConsoleError> {
+        let save_state = SaveState::from_bytes(bytes)?;
+        let current_states = self.save_device_states();
+        let current_cpu_state = self.cpu.borrow().serialize_state();
+
+        if save_state.devices().len() != current_states.len() {
+            return Err(SaveStateError::DeviceMismatch(
+                format!("{} devices saved, {} devices in the console", save_state.devices().len(), current_states.len())).into());
+        }
+
+        if save_state.cpu().len() != current_cpu_state.len() {
+            return Err(SaveStateError::DeviceMismatch(
+                format!("CPU state of {} bytes, {} bytes expected", save_state.cpu().len(), current_cpu_state.len())).into());
+        }
+
+        let mut states = current_states.clone();
+
+        for ((device_type, state), (saved_type, saved_state)) in states.iter_mut().zip(save_state.devices()) {
+            if device_type.to_string() != *saved_type {
+                return Err(SaveStateError::DeviceMismatch(format!("{} saved for the {}", saved_type, device_type)).into());
+            }
+
+            if saved_state.len() != state.len() {
+                return Err(SaveStateError::DeviceMismatch(
+                    format!("{} state of {} bytes, {} bytes expected", saved_type, saved_state.len(), state.len())).into());
+            }
+
+            *state = saved_state.clone();
+        }
+
+        let restored = self.load_device_states(&states)
+            .and_then(|_| Ok(self.cpu.borrow_mut().deserialize_state(save_state.cpu())?));
+
+        if restored.is_err() {
+            self.load_device_states(&current_states)?;
+            self.cpu.borrow_mut().deserialize_state(&current_cpu_state)?;
+        }
+
+        restored
+    }
+
+    fn snapshot_ram(&mut self) {
+        if let (Some(ram_search), Some(wram)) = (&mut self.ram_search, &self.wram) {
+            ram_search.snapshot(wram.borrow().bytes());
+        }
+    }
+
+    /***
+     * Disassembly of the PRG space mapped in the CPU address space, from the reset vector to the vectors
+     * (a linear sweep, see disassemble_range), after a header with the three vectors.
+     ***/
+    pub fn disassemble_prg(&self) -> Result<Vec<String>, NesConsoleError> {
+        let bus = self.bus.borrow();
+        let vector = |addr: u16| -> Result<u16, NesConsoleError> {
+            Ok((bus.trace_read_byte(addr + 1)? as u16) << 8 | bus.trace_read_byte(addr)? as u16)
+        };
+
+        let (nmi, reset, irq) = (vector(NMI_VECTOR_ADDR)?, vector(DEFAULT_START_ADDRESS)?, vector(IRQ_VECTOR_ADDR)?);
+        let mut lines = vec![format!("; NMI: ${:04X}, RESET: ${:04X}, IRQ: ${:04X}", nmi, reset, irq)];
+
+        lines.extend(disassemble_range(&*bus, reset, NMI_VECTOR_ADDR - 1)?);
+        Ok(lines)
+    }
+
+    /// The CPU memory map, as decoded by the bus.
+    pub fn describe_memory_map(&self) -> Vec<((u16, u16), String)> {
+        self.bus.borrow().describe_mapping()
+    }
+
+    /// A halted (or stopped) CPU burns its cycles, so that the PPU and the APU keep being caught up.
+    fn halt_on_jam<T>(&mut self, result: Result<T, CpuError>, halted: T) -> Result<T, NesConsoleError> {
+        match result {
+            Err(CpuError::Halted(pc)) => {
+                warn!("CPU halted at 0x{:04X}, waiting for a reset", pc);
+                self.state = ConsoleState::Halted(pc);
+                Ok(halted)
+            },
+            Err(CpuError::Unimplemented { opcode }) => {
+                let pc = self.cpu.borrow().snapshot()?.pc();
+                warn!("CPU stopped on the unimplemented opcode 0x{:02X} at 0x{:04X}, waiting for a reset", opcode, pc);
+                self.state = ConsoleState::Unimplemented { pc, opcode };
+                Ok(halted)
+            },
+            other => Ok(other?),
         }
     }
 
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// The cycle credits given to the CPU, the PPU, and the APU:
+    /// the number of cycles needed for the PPU to render a single scanline
+    /// (114 on NTSC, 107 on PAL).
+    fn cycle_credits(&self) -> u32 {
+        self.region.cpu_cycles_per_scanline() as u32
+    }
+
+    /// The maximum number of cycles the CPU can go ahead
+    /// before being caught up by the PPU and the APU.
+    fn cycles_threshold(&self) -> u32 {
+        self.cycle_credits()
+    }
+
+    #[cfg(test)]
+    pub fn get_ppu(&self) -> Rc<RefCell<dyn PPU>> {
+        self.ppu.clone()
+    }
+
     pub fn set_input(&self, events: KeyEvents) -> Result<(), NesConsoleError>{
         self.controller.borrow_mut().set_input(events).map_err(|e|
             NesConsoleError::ControllerError(format!("{}", e.to_string())))
     }
 
+    /// The banks mapped by the cartridge, none for a console built without a cartridge.
+    pub fn bank_state(&self) -> Option<BankState> {
+        self.cartridge.as_ref().map(|cartridge| cartridge.borrow().bank_state())
+    }
+
+    /// Hide the background or the sprites (```Some(false)```) whatever the mask register, for debugging.
+    pub fn set_layer_override(&self, background: Option<bool>, sprites: Option<bool>) {
+        self.ppu.borrow_mut().set_layer_override(background, sprites);
+    }
+
+    /// Palette RAM and both pattern tables, colorized with the palette ```palette_index``` (0-7), for the viewers.
+    pub fn dump_pattern_tables(&self, palette_index: u8) -> Result<PpuMemoryDump, NesConsoleError> {
+        let dump = self.ppu.borrow().dump_pattern_tables(palette_index)?;
+        Ok(dump)
+    }
+
+    /// Logical nametable ```index``` (0-3) rendered without the scroll, for the viewers.
+    pub fn render_nametable(&self, index: u8) -> Result<NesFrame, NesConsoleError> {
+        let frame = self.ppu.borrow().render_nametable(index)?;
+        Ok(frame)
+    }
+
+    pub fn instructions_executed(&self) -> u64 {
+        self.cpu.borrow().instructions_executed()
+    }
+
+    /// Counters since the console was built, to profile the emulator.
+    pub fn perf_counters(&self) -> PerfCounters {
+        PerfCounters {
+            instructions: self.instructions_executed(),
+            samples: self.samples_produced,
+            ..self.ppu.borrow().perf_counters()
+        }
+    }
+
+    /// Time spent in the CPU, the PPU layers and the APU since the console was built (timing_spans feature).
+    pub fn timing_spans(&self) -> TimingSpans {
+        TimingSpans {
+            cpu: self.timing_spans.cpu,
+            apu: self.timing_spans.apu,
+            ..self.ppu.borrow().timing_spans()
+        }
+    }
+
+    /// State of the APU channels, for the visualizers.
+    pub fn apu_snapshot(&self) -> ApuSnapshot {
+        self.apu.borrow().snapshot()
+    }
+
+    pub fn ppu_snapshot(&self) -> PpuSnapshot {
+        self.ppu.borrow().snapshot()
+    }
+
+    /***
+     * compact state of the CPU registers, of the PPU registers and of the APU channels, to be included in a prompt:
+     * the memory is left out.
+     ***/
+    pub fn state_summary(&self) -> Result<Value, NesConsoleError> {
+        let cpu = self.cpu_snapshot()?;
+        let ppu = self.ppu_snapshot();
+        let apu = self.apu_snapshot();
+
+        let channel = |channel: &ApuChannelSnapshot| json!({
+            "enabled": channel.enabled(),
+            "muted": channel.muted(),
+            "timer_period": channel.timer_period(),
+            "volume": channel.volume(),
+            "length_counter": channel.length_counter(),
+        });
+
+        let summary = json!({
+            "cpu": {
+                "pc": cpu.pc(),
+                "a": cpu.a(),
+                "x": cpu.x(),
+                "y": cpu.y(),
+                "sp": cpu.sp(),
+                "p": cpu.p(),
+                "instruction": format!("{} {}", cpu.mnemonic(), cpu.operand()).trim_end(),
+            },
+            "ppu": {
+                "control": ppu.control(),
+                "mask": ppu.mask(),
+                "status": ppu.status(),
+                "v": ppu.v(),
+                "t": ppu.t(),
+                "fine_x": ppu.fine_x(),
+                "scanline": ppu.scanline(),
+                "dot": ppu.dot(),
+            },
+            "apu": {
+                "pulse1": channel(apu.pulse1()),
+                "pulse2": channel(apu.pulse2()),
+                "triangle": channel(apu.triangle()),
+                "noise": channel(apu.noise()),
+                "dmc": {
+                    "enabled": apu.dmc_enabled(),
+                    "output_level": apu.dmc_output_level(),
+                },
+            },
+        });
+
+        Ok(summary)
+    }
+
+    /// Activate a Game Genie (6 or 8 letters) or raw (```AAAA:VV[:CC]```) cheat code.
+    pub fn add_cheat(&mut self, code: &str) -> Result<(), NesConsoleError> {
+        let cheat = Cheat::from_code(code)?;
+        info!("adding cheat {}: 0x{:04X} = 0x{:02X}, compare: {:?}", code, cheat.address(), cheat.value(), cheat.compare());
+
+        self.cheats.borrow_mut().add(cheat);
+        Ok(())
+    }
+
+    /// Returns whether the cheat code was active.
+    pub fn remove_cheat(&mut self, code: &str) -> Result<bool, NesConsoleError> {
+        let cheat = Cheat::from_code(code)?;
+        info!("removing cheat {}", code);
+
+        Ok(self.cheats.borrow_mut().remove(&cheat))
+    }
+
     pub fn get_sample(&self) -> Result<Vec<f32>, NesConsoleError> {
         let vec = Vec::new();
 
@@ -127,7 +467,13 @@ impl NesConsole {
         }
 
         if self.cpu_counter.ahead(&self.apu_counter, apu_threshold) {
-            let (apu_cycles, apu_samples) = self.apu.borrow_mut().run(self.apu_counter.current, apu_threshold)?;
+            let (result, elapsed) = timed(|| self.apu.borrow_mut().run(self.apu_counter.current, apu_threshold));
+            self.timing_spans.apu += elapsed;
+            let (apu_cycles, apu_samples) = result?;
+            if let Some(samples) = &apu_samples {
+                self.samples_produced += samples.samples().len() as u64;
+            }
+
             out_samples = apu_samples;
 
             self.apu_counter.current = apu_cycles;
@@ -144,20 +490,66 @@ impl NesConsole {
     ///     - returns an optional frame, an optional sound samples buffer, and a CPU snapshot  
     /// 
     pub fn step_instruction(&mut self) -> Result<(Option<NesFrame>, Option<NesSamples>, Box<dyn CpuSnapshot>), NesConsoleError> {
-
-        self.cpu_counter.current += self.cpu.borrow_mut().step_instruction()?;
+        let (_, out_frame, out_samples) = self.step()?;
         let snapshot = self.cpu.borrow().snapshot()?;
 
-        let (out_frame ,out_samples) = self.catch_up_ppu_and_apu(PPU_CYCLES_THRESHOLD, APU_CYCLES_THRESHOLD)?;
+        Ok((out_frame, out_samples, snapshot))
+    }
+
+    /// Execute exactly ```count``` CPU instructions, the PPU and APU are caught up after each of them,
+    /// and return the number of CPU cycles used. Unlike ```step_frame```, the budget is not in cycles:
+    /// an interrupt is taken at the end of the instruction it follows and is not counted as an instruction.
+    pub fn run_instructions(&mut self, count: u64) -> Result<u32, NesConsoleError> {
+        let mut cycles = 0;
+
+        for _ in 0..count {
+            let (instruction_cycles, _, _) = self.step()?;
+            cycles += instruction_cycles;
+        }
+
+        Ok(cycles)
+    }
+
+    /// Asserts ```kind``` as a device would, the interrupt is taken at the end of the next instruction.
+    pub fn signal_interrupt(&mut self, kind: InterruptKind) -> Result<(), NesConsoleError> {
+        info!("signaling {}", kind);
+
+        match kind {
+            InterruptKind::Nmi => self.cpu.borrow_mut().signal_nmi()?,
+            InterruptKind::Irq(source) => self.cpu.borrow_mut().signal_irq(source)?,
+        }
+
+        Ok(())
+    }
+
+    /// State of the CPU registers, after the last executed instruction.
+    pub fn cpu_snapshot(&self) -> Result<Box<dyn CpuSnapshot>, NesConsoleError> {
+        Ok(self.cpu.borrow().snapshot()?)
+    }
+
+    fn step(&mut self) -> Result<(u32, Option<NesFrame>, Option<NesSamples>), NesConsoleError> {
+        let cycles = if self.state.is_cpu_stopped() {
+            1
+        } else {
+            let (result, elapsed) = timed(|| self.cpu.borrow_mut().step_instruction());
+            self.timing_spans.cpu += elapsed;
+            self.halt_on_jam(result, 1)?
+        };
+
+        self.cpu_counter.current += cycles;
+
+        let threshold = self.cycles_threshold();
+        let (out_frame ,out_samples) = self.catch_up_ppu_and_apu(threshold, threshold)?;
         self.cpu_counter.previous = self.cpu_counter.current;
 
-        Ok((out_frame, out_samples, snapshot))
+        Ok((cycles, out_frame, out_samples))
     }
     
     pub fn step_frame_debug(&mut self) -> Result<(NesFrame, NesSamples, Vec<Box<dyn CpuSnapshot>>), NesConsoleError> {
         let out_frame: Option<NesFrame>;
         let mut out_samples: NesSamples = NesSamples::default();
         let mut snapshots: Vec<Box<dyn CpuSnapshot>> = Vec::new();
+        self.snapshot_ram();
 
         loop {
             let (frame, samples, snapshot) = self.step_instruction()?;
@@ -177,15 +569,25 @@ impl NesConsole {
     }
 
     pub fn step_frame(&mut self) -> Result<(NesFrame, NesSamples), NesConsoleError> {
-        let credits = CYCLE_CREDITS;
+        let credits = self.cycle_credits();
+        let threshold = self.cycles_threshold();
         let out_frame: Option<NesFrame>;
         let mut out_samples: NesSamples = NesSamples::default();
+        self.snapshot_ram();
 
         loop {
-            self.cpu_counter.current = self.cpu.borrow_mut().run(self.cpu_counter.current, credits - self.cpu_counter.debt)?;
+            let cpu_credits = credits - self.cpu_counter.debt;
+
+            self.cpu_counter.current = if self.state.is_cpu_stopped() {
+                self.cpu_counter.current + cpu_credits
+            } else {
+                let (result, elapsed) = timed(|| self.cpu.borrow_mut().run(self.cpu_counter.current, cpu_credits));
+                self.timing_spans.cpu += elapsed;
+                self.halt_on_jam(result, self.cpu_counter.current + cpu_credits)?
+            };
             self.cpu_counter.debt = (self.cpu_counter.current - self.cpu_counter.previous) - (credits - self.cpu_counter.debt);
 
-            let (frame, samples) = self.catch_up_ppu_and_apu(PPU_CYCLES_THRESHOLD, APU_CYCLES_THRESHOLD)?;
+            let (frame, samples) = self.catch_up_ppu_and_apu(threshold, threshold)?;
             self.cpu_counter.previous = self.cpu_counter.current;
 
             if let Some(s) = samples {
@@ -211,6 +613,7 @@ impl NesConsole {
         Ok(())
     }
 
+    /// First boot, after the build: the devices are already in their power on state.
     pub fn power_on(&mut self) -> Result<(), NesConsoleError> {
         self.reset_entry_point()?;
         Ok(())
@@ -222,18 +625,53 @@ impl NesConsole {
         self.ppu_counter = CyclesCounter::new(0);
     }
 
+    /// Reset button: the CPU reloads the reset vector, the PPU and the APU registers are cleared,
+    /// while the RAM and the cartridge state (including the battery-backed RAM) are preserved.
     pub fn reset(&mut self) -> Result<(), NesConsoleError> {
-        self.cpu.borrow_mut().reset()?;
+        self.cpu.borrow_mut().soft_reset()?;
         self.ppu.borrow_mut().reset()?;
         self.apu.borrow_mut().reset()?;
 
         self.reset_counters();
         self.reset_entry_point()?;
+        self.state = ConsoleState::Running;
+
+        Ok(())
+    }
+
+    /***
+     * Cold boot, the console switched off and on again: unlike the reset button (warm boot), the RAM and the OAM
+     * lose their content and are filled again with the configured RamInit, and the CPU registers are cleared
+     * instead of only moving the stack pointer.
+     * The cartridge is left as is: the battery-backed RAM is preserved, as on the hardware.
+     ***/
+    pub fn power_cycle(&mut self) -> Result<(), NesConsoleError> {
+        if let Some(wram) = &self.wram {
+            wram.borrow_mut().fill_with(self.ram_init);
+        }
+
+        self.cpu.borrow_mut().reset()?;
+        self.ppu.borrow_mut().cold_reset(self.ram_init)?;
+        self.apu.borrow_mut().reset()?;
+
+        self.reset_counters();
+        self.reset_entry_point()?;
+        self.state = ConsoleState::Running;
 
         Ok(())
     }
 }
 
+/***
+ * The devices hold the CPU to signal their interrupts, while the CPU holds the bus: the devices are unmapped
+ * to break the cycles, so that the whole console is freed when a ROM is loaded in place of another one.
+ ***/
+impl Drop for NesConsole {
+    fn drop(&mut self) {
+        self.bus.borrow_mut().remove_devices();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum NesConsoleError {
     BuilderError(String),
@@ -245,7 +683,10 @@ pub enum NesConsoleError {
     InternalError(String),
     ControllerError(String),
     ChannelCommunication(String),
-    Terminated(String)
+    Terminated(String),
+    CheatError(String),
+    PaletteError(String),
+    SaveStateError(String)
 }
 
 impl From<std::io::Error> for NesConsoleError {
@@ -266,6 +707,24 @@ impl From<CpuError> for NesConsoleError {
     }
 }
 
+impl From<CheatError> for NesConsoleError {
+    fn from(error: CheatError) -> Self {
+        NesConsoleError::CheatError(error.to_string())
+    }
+}
+
+impl From<PaletteError> for NesConsoleError {
+    fn from(error: PaletteError) -> Self {
+        NesConsoleError::PaletteError(error.to_string())
+    }
+}
+
+impl From<SaveStateError> for NesConsoleError {
+    fn from(error: SaveStateError) -> Self {
+        NesConsoleError::SaveStateError(error.to_string())
+    }
+}
+
 impl From<BusError> for NesConsoleError {
     fn from(error: BusError) -> Self {
         NesConsoleError::BuilderError(error.to_string())
@@ -278,6 +737,12 @@ impl From<LoaderError> for NesConsoleError {
     }
 }
 
+impl From<SessionError> for NesConsoleError {
+    fn from(error: SessionError) -> Self {
+        NesConsoleError::BuilderError(error.to_string())
+    }
+}
+
 impl From<PpuError> for NesConsoleError {
     fn from(error: PpuError) -> Self {
         NesConsoleError::PpuError(error)
@@ -320,6 +785,9 @@ impl Display for NesConsoleError {
             NesConsoleError::ControllerError(s) => { write!(f, "controller error: {}", s) }
             NesConsoleError::ChannelCommunication(s) => { write!(f, "channel communication error: {}", s) }
             NesConsoleError::Terminated(s) => {write!(f, "emulator terminated: {}", s) }
+            NesConsoleError::CheatError(s) => { write!(f, "cheat error: {}", s) }
+            NesConsoleError::PaletteError(s) => { write!(f, "palette error: {}", s) }
+            NesConsoleError::SaveStateError(s) => { write!(f, "save state error: {}", s) }
         }
     }
 }
@@ -334,11 +802,29 @@ pub struct NesConsoleBuilder {
     apu: Option<Rc<RefCell<dyn APU>>>,
     apu_type: Option<ApuType>,
     controller: Option<Rc<RefCell<dyn Controller>>>,
+    frame_counter: Option<Rc<RefCell<dyn BusDevice>>>,
     device_types: Vec<BusDeviceType>,
     loader_type: Option<LoaderType>,
     rom_file: Option<PathBuf>,
+    rom_bytes: Option<Vec<u8>>,
     entry_point: Option<u16>,
     cartridge: Option<Rc<RefCell<dyn Cartridge>>>,
+    wram: Option<Rc<RefCell<MemoryBank>>>,
+    region: Region,
+    sound_buffer_size: usize,
+    sample_rate: u32,
+    cheats: Rc<RefCell<Cheats>>,
+    cheat_codes: Vec<String>,
+    sram_file: Option<PathBuf>,
+    ram_init: RamInit,
+    custom_io_devices: Vec<CustomIoDevice>,
+    oam_decay: bool,
+    sprite_limit_disabled: bool,
+    unstable_opcode_mode: UnstableOpcodeMode,
+    halt_on_unimplemented: bool,
+    channel_sink: Option<Rc<RefCell<MultiChannelWavSink>>>,
+    socd_mode: SocdMode,
+    headless: bool,
 }
 
 impl NesConsoleBuilder {
@@ -353,11 +839,29 @@ impl NesConsoleBuilder {
             apu: None,
             apu_type: None,
             controller: None,
+            frame_counter: None,
             device_types: Vec::new(),
             loader_type: None,
             rom_file: None,
+            rom_bytes: None,
             entry_point: None,
             cartridge: None,
+            wram: None,
+            region: Region::NTSC,
+            sound_buffer_size: DEFAULT_BUFFER_SIZE,
+            sample_rate: AUDIO_RATE as u32,
+            cheats: Rc::new(RefCell::new(Cheats::new())),
+            cheat_codes: Vec::new(),
+            sram_file: None,
+            ram_init: RamInit::default(),
+            custom_io_devices: Vec::new(),
+            oam_decay: false,
+            sprite_limit_disabled: false,
+            unstable_opcode_mode: UnstableOpcodeMode::default(),
+            halt_on_unimplemented: false,
+            channel_sink: None,
+            socd_mode: SocdMode::default(),
+            headless: false,
         }
     }
 
@@ -389,17 +893,172 @@ impl NesConsoleBuilder {
         self
     }
 
+    /// The iNES image of the ROM, in place of a ROM file.
+    pub fn with_rom_bytes(mut self, rom_bytes: Vec<u8>) -> Self {
+        debug!("setting rom image: {} bytes", rom_bytes.len());
+
+        self.rom_bytes = Some(rom_bytes);
+        self
+    }
+
     pub fn with_entry_point(mut self, entry_point: Option<u16>) -> Self {
         self.entry_point = entry_point;
         self
     }
 
+    pub fn with_region(mut self, region: Region) -> Self {
+        debug!("setting region: {}", region);
+
+        self.region = region;
+        self
+    }
+
+    /// Maximum number of samples buffered by the APU between two frames.
+    pub fn with_sound_buffer_size(mut self, sound_buffer_size: usize) -> Self {
+        debug!("setting sound buffer size: {} samples", sound_buffer_size);
+
+        self.sound_buffer_size = sound_buffer_size;
+        self
+    }
+
+    /// Sample rate of the host audio device, the APU output is resampled from its native 44100 Hz.
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        debug!("setting sample rate: {} Hz", sample_rate);
+
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn with_ram_init(mut self, ram_init: RamInit) -> Self {
+        debug!("setting ram init: {:?}", ram_init);
+
+        self.ram_init = ram_init;
+        self
+    }
+
+    /// OAM decay emulation of the PPU, off by default: a few test ROMs check it.
+    pub fn with_oam_decay(mut self, oam_decay: bool) -> Self {
+        debug!("setting oam decay: {}", oam_decay);
+
+        self.oam_decay = oam_decay;
+        self
+    }
+
+    /// No flicker mode of the PPU, off by default: more than 8 sprites are rendered on a scanline.
+    pub fn with_sprite_limit_disabled(mut self, sprite_limit_disabled: bool) -> Self {
+        debug!("setting sprite limit disabled: {}", sprite_limit_disabled);
+
+        self.sprite_limit_disabled = sprite_limit_disabled;
+        self
+    }
+
+    /// Value stored by the unstable SH* opcodes, the AND with the high byte can drop off during a DMA.
+    pub fn with_unstable_opcode_mode(mut self, unstable_opcode_mode: UnstableOpcodeMode) -> Self {
+        debug!("setting unstable opcode mode: {:?}", unstable_opcode_mode);
+
+        self.unstable_opcode_mode = unstable_opcode_mode;
+        self
+    }
+
+    /// Off by default, the unimplemented opcodes are skipped with a warning and the unofficial ones executed:
+    /// on, both stop the console (see ConsoleState).
+    pub fn with_halt_on_unimplemented(mut self, halt_on_unimplemented: bool) -> Self {
+        debug!("setting halt on unimplemented: {}", halt_on_unimplemented);
+
+        self.halt_on_unimplemented = halt_on_unimplemented;
+        self
+    }
+
+    /// Left + Right and Up + Down held together on the keyboard, reported as is by default.
+    pub fn with_socd_mode(mut self, socd_mode: SocdMode) -> Self {
+        debug!("setting socd mode: {:?}", socd_mode);
+
+        self.socd_mode = socd_mode;
+        self
+    }
+
+    /***
+     * Off by default. On, the console runs pure computation for servers and CI: the APU samples are
+     * dropped (NullSoundPlayback) and the controllers read no button (NullInputSource), the frames
+     * are still rendered.
+     ***/
+    pub fn with_headless(mut self, headless: bool) -> Self {
+        debug!("setting headless: {}", headless);
+
+        self.headless = headless;
+        self
+    }
+
+    /// The output of each APU channel is recorded in ```channel_sink```, kept by the caller to write the WAV files.
+    pub fn with_channel_wav_sink(mut self, channel_sink: Rc<RefCell<MultiChannelWavSink>>) -> Self {
+        debug!("recording the APU channels");
+
+        self.channel_sink = Some(channel_sink);
+        self
+    }
+
+    /// Mapped after the other devices, over the addresses they may already decode.
+    pub fn with_custom_io_device(mut self, device: CustomIoDevice) -> Self {
+        debug!("adding custom io device: {:?}", device);
+
+        self.custom_io_devices.push(device);
+        self
+    }
+
+    /// Activated once the console is built, an invalid code fails the build.
+    pub fn with_cheat_code(mut self, code: &str) -> Self {
+        debug!("adding cheat code: {}", code);
+
+        self.cheat_codes.push(code.to_string());
+        self
+    }
+
+    /// Battery backed RAM image loaded into the PRG RAM of the cartridge, if the file exists.
+    pub fn with_sram_file(mut self, sram_file: PathBuf) -> Self {
+        debug!("setting sram file: {:?}", sram_file);
+
+        self.sram_file = Some(sram_file);
+        self
+    }
+
+    /// Rom, sram, cheats and region of the session: the ```with_*``` calls made after it take precedence.
+    pub fn with_session(self, session: &Session) -> Self {
+        let mut builder = self.with_rom_file(session.rom_file().to_path_buf());
+
+        if let Some(sram_file) = session.sram_file() {
+            builder = builder.with_sram_file(sram_file.to_path_buf());
+        }
+
+        if let Some(region) = session.region() {
+            builder = builder.with_region(region);
+        }
+
+        session.cheats().iter().fold(builder, |builder, code| builder.with_cheat_code(code))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn rom_file(&self) -> Option<&PathBuf> {
+        self.rom_file.as_ref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn region(&self) -> Region {
+        self.region
+    }
+
+    #[cfg(test)]
+    pub(crate) fn cheat_codes(&self) -> &[String] {
+        &self.cheat_codes
+    }
+
     fn build_cpu(&mut self, bus: Rc<RefCell<dyn Bus>>) -> Result<Rc<RefCell<dyn CPU>>, NesConsoleError> {
         debug!("creating cpu: {:?}", self.cpu_type.clone().unwrap());
 
         let result: Result<Rc<RefCell<dyn CPU>>, NesConsoleError> = match &self.cpu_type {
             Some(CpuType::NES6502) => {
                 let mut cpu = Cpu6502::new(bus);
+                cpu.set_unstable_opcode_mode(self.unstable_opcode_mode);
+                cpu.set_halt_on_unimplemented(self.halt_on_unimplemented);
                 cpu.initialize()?;
                 Ok(Rc::new(RefCell::new(cpu)))
             },
@@ -417,7 +1076,8 @@ impl NesConsoleBuilder {
 
         let result: Result<Rc<RefCell<dyn Bus>>, NesConsoleError> = match self.bus_type {
             Some(BusType::NESBus) => {
-                let bus = NESBus::new();
+                let mut bus = NESBus::new();
+                bus.attach_cheats(self.cheats.clone());
                 Ok(Rc::new(RefCell::new(bus)))
             },
 
@@ -429,18 +1089,25 @@ impl NesConsoleBuilder {
         result
     }
 
-    fn build_wram_device(&self, memory_type: &MemoryType) -> Result<Rc<RefCell<dyn BusDevice>>, NesConsoleError> {
+    fn build_wram_device(&mut self, memory_type: &MemoryType) -> Result<Rc<RefCell<dyn BusDevice>>, NesConsoleError> {
         debug!("creating wram: {:?}", memory_type);
 
         let mut wram = match memory_type {
             MemoryType::StandardMemory => {
-                MemoryBank::new(WRAM_MEMORY_SIZE, (WRAM_START_ADDR, WRAM_END_ADDR))
+                MemoryBank::new(WRAM_MEMORY_SIZE, (WRAM_START_ADDR, WRAM_START_ADDR + WRAM_MIRROR_MASK))
             }
             _ => Err(NesConsoleError::BuilderError("invalid wram type specified".to_string()))?
         };
 
         wram.initialize()?;
-        Ok(Rc::new(RefCell::new(wram)))
+        wram.fill_with(self.ram_init);
+
+        // the 2KB are mirrored 4 times, the console keeps the RAM to fill it again on a power cycle
+        let wram = Rc::new(RefCell::new(wram));
+        self.wram = Some(wram.clone());
+
+        let mirror = MemoryMirror::with_mask(wram, (WRAM_START_ADDR, WRAM_END_ADDR), WRAM_MIRROR_MASK)?;
+        Ok(Rc::new(RefCell::new(mirror)))
     }
 
     fn build_ppu_dma(&self, ppu_dma_type: &PpuDmaType, bus: Rc<RefCell<dyn Bus>>, ppu: Rc<RefCell<dyn DmaDevice>>) -> Result<Rc<RefCell<dyn BusDevice>>, NesConsoleError>{
@@ -462,11 +1129,20 @@ impl NesConsoleBuilder {
 
         let result = match ppu_type {
             PpuType::NES2C02 => {
-                Ppu2c02::new(chr_rom, mirroring, cpu)?
+                let mut ppu = Ppu2c02::new(chr_rom, mirroring, cpu.clone(), self.region)?;
+
+                if self.oam_decay {
+                    ppu.set_oam_decay(Some(DEFAULT_OAM_DECAY_FRAMES));
+                }
+
+                ppu.set_sprite_limit_disabled(self.sprite_limit_disabled);
+
+                ppu
             },
         };
 
         let ppu = Rc::new(RefCell::new(result));
+        cpu.borrow_mut().attach_ppu_clock(ppu.borrow().clock());
         let dma = self.build_ppu_dma(&PpuDmaType::NESPPUDMA, bus.clone(), ppu.clone())?;
 
         ppu.borrow_mut().initialize()?;
@@ -475,20 +1151,35 @@ impl NesConsoleBuilder {
         self.ppu = Some(ppu.clone());
         self.ppu_type = Some(ppu_type.clone());
 
-        Ok((ppu.clone(), dma))
+        // the 8 registers are mirrored every 8 bytes
+        let registers = MemoryMirror::with_mask(ppu, PPU_REGISTERS_ADDRESS_SPACE, PPU_REGISTERS_MIRROR_MASK)?;
+        Ok((Rc::new(RefCell::new(registers)), dma))
+    }
+
+    fn build_input(&self) -> InputExternal {
+        InputExternal::new().with_socd_mode(self.socd_mode)
+    }
+
+    fn build_controller<T: Input + 'static>(&self, controller_type: &ControllerType, build_input: impl Fn() -> T) -> Rc<RefCell<dyn Controller>> {
+        match controller_type {
+            ControllerType::StandardController => Rc::new(RefCell::new(StandardController::new(build_input()))),
+            ControllerType::FamicomWithMic => Rc::new(RefCell::new(StandardController::with_microphone(build_input()))),
+            ControllerType::FourScore => {
+                let inputs = [build_input(), build_input(), build_input(), build_input()];
+                Rc::new(RefCell::new(FourScore::new(inputs, self.frame_counter.clone())))
+            },
+        }
     }
 
     fn build_controller_device(&self, controller_type: &ControllerType) -> Result<Rc<RefCell<dyn Controller>>, NesConsoleError> {
         debug!("creating controller {:?}", controller_type);
 
-        let result = match controller_type {
-            ControllerType::StandardController => {
-                let input = InputExternal::new();
-                StandardController::new(input)
-            },
+        let controller = if self.headless {
+            self.build_controller(controller_type, || NullInputSource)
+        } else {
+            self.build_controller(controller_type, || self.build_input())
         };
 
-        let controller = Rc::new(RefCell::new(result));
         controller.borrow_mut().initialize()?;
 
         Ok(controller)
@@ -497,30 +1188,69 @@ impl NesConsoleBuilder {
     fn build_apu_device(&mut self, apu_type: &ApuType, bus: Rc<RefCell<dyn Bus>>, cpu: Rc<RefCell<dyn CPU>>) -> Result<Rc<RefCell<dyn BusDevice>>, NesConsoleError> {
         debug!("creating apu {:?}", apu_type);
 
-        let result = match apu_type {
+        let apu = match apu_type {
+            ApuType::RP2A03 if self.headless => self.attach_rp2a03(NullSoundPlayback, bus, cpu)?,
             ApuType::RP2A03 => {
-                let sound_player = SoundPlaybackPassive::new();
-                ApuRp2A03::new(sound_player, cpu, bus)
+                let sound_player = SoundPlaybackResampler::new(
+                    SoundPlaybackPassive::with_buffer_size(self.sound_buffer_size), AUDIO_RATE as u32, self.sample_rate);
+                self.attach_rp2a03(sound_player, bus, cpu)?
             },
         };
 
-        let apu = Rc::new(RefCell::new(result));
+        self.apu_type = Some(apu_type.clone());
+
+        Ok(apu)
+    }
+
+    fn attach_rp2a03<T: SoundPlayback + 'static>(&mut self, sound_player: T, bus: Rc<RefCell<dyn Bus>>, cpu: Rc<RefCell<dyn CPU>>) -> Result<Rc<RefCell<dyn BusDevice>>, NesConsoleError> {
+        let mut apu = ApuRp2A03::new(sound_player, cpu, bus, self.region);
+
+        if let Some(expansion_audio) = self.cartridge.as_ref().and_then(|cartridge| cartridge.borrow().get_expansion_audio()) {
+            debug!("mixing the cartridge expansion audio");
+            apu.attach_expansion_audio(expansion_audio);
+        }
+
+        if let Some(channel_sink) = &self.channel_sink {
+            apu.attach_channel_sink(channel_sink.clone());
+        }
+
+        let apu = Rc::new(RefCell::new(apu));
         apu.borrow_mut().initialize()?;
 
         self.apu = Some(apu.clone());
-        self.apu_type = Some(apu_type.clone());
 
         Ok(apu)
     }
 
+    /***
+     * The image is truncated to the size of the PRG RAM, a missing file leaves the PRG RAM as it is:
+     * the game has never been saved yet.
+     ***/
+    fn load_sram_file(&self, prg_ram: &Rc<RefCell<dyn BusDevice>>) -> Result<(), NesConsoleError> {
+        let sram_file = match &self.sram_file {
+            Some(sram_file) if sram_file.exists() => sram_file,
+            _ => return Ok(()),
+        };
+
+        let bytes = fs::read(sram_file)?;
+        let mut prg_ram = prg_ram.borrow_mut();
+        let size = prg_ram.size();
+
+        for (addr, value) in bytes.iter().take(size).enumerate() {
+            prg_ram.write_byte(addr as u16, *value)?;
+        }
+
+        info!("sram loaded from {}", sram_file.display());
+        Ok(())
+    }
+
     fn build_cartridge_device(&self) -> Result<Rc<RefCell<dyn Cartridge>>, NesConsoleError> {
         debug!("creating cartridge");
 
         if let Some(ref rom_file) = self.rom_file {
-            let loader = self.build_loader(rom_file.clone())?;
-            let cartridge = loader.build_cartridge()?;
-
-            Ok(cartridge)
+            self.load_cartridge(rom_file.clone())
+        } else if let Some(ref rom_bytes) = self.rom_bytes {
+            self.load_cartridge_from_bytes(rom_bytes.clone())
         } else {
             Err(NesConsoleError::BuilderError("rom file not specified".to_string()))
         }
@@ -536,6 +1266,7 @@ impl NesConsoleBuilder {
                 let prg_ram = cartridge.borrow().get_prg_ram();
                 if let Some(prg_ram) = prg_ram {
                     debug!("adding prg_ram: {} kb", prg_ram.borrow().size());
+                    self.load_sram_file(&prg_ram)?;
                     bus.borrow_mut().add_device(prg_ram)?;
                 }
                 bus.borrow_mut().add_device(cartridge.clone())?;
@@ -573,7 +1304,9 @@ impl NesConsoleBuilder {
 
             BusDeviceType::APU(apu_type) => {
                 let apu= self.build_apu_device(apu_type,bus.clone(), cpu)?;
-                bus.borrow_mut().add_device(apu)?;
+                bus.borrow_mut().add_device(apu.clone())?;
+                // the Four Score reads $4017, the writes go on to the APU frame counter
+                self.frame_counter = Some(apu);
             }
 
             _ => {}
@@ -582,7 +1315,7 @@ impl NesConsoleBuilder {
         Ok(())
     }
 
-    fn build_loader(&self, path: PathBuf) -> Result<impl Loader, NesConsoleError> {
+    fn load_cartridge(&self, path: PathBuf) -> Result<Rc<RefCell<dyn Cartridge>>, NesConsoleError> {
         debug!("creating loader: {:?}", self.loader_type.clone().unwrap());
 
         match self.loader_type {
@@ -590,9 +1323,70 @@ impl NesConsoleBuilder {
                 Err(NesConsoleError::BuilderError("loader not set".to_string()))
             },
             Some(LoaderType::INESV2) => {
-                Ok(INesLoader::from_file(path)?)
+                Ok(INesLoader::from_file(path)?.build_cartridge()?)
+            },
+            Some(LoaderType::FDS) => {
+                Ok(FdsLoader::from_file(path)?.build_cartridge()?)
+            },
+            Some(LoaderType::RawBinary { .. }) => {
+                Ok(RawLoader::from_file(path)?.build_cartridge()?)
+            }
+        }
+    }
+
+    fn load_cartridge_from_bytes(&self, bytes: Vec<u8>) -> Result<Rc<RefCell<dyn Cartridge>>, NesConsoleError> {
+        match self.loader_type {
+            Some(LoaderType::INESV2) => Ok(INesLoader::from_bytes(bytes)?.build_cartridge()?),
+            Some(ref loader_type) => Err(NesConsoleError::BuilderError(format!("{:?} loader only loads ROM files", loader_type))),
+            None => Err(NesConsoleError::BuilderError("loader not set".to_string())),
+        }
+    }
+
+    /// the raw binary goes through the bus once every device is mapped, as it may span the work RAM
+    fn load_raw_binary(&self, bus: Rc<RefCell<dyn Bus>>) -> Result<(), NesConsoleError> {
+        if let (Some(LoaderType::RawBinary { load_addr, reset_vector }), Some(rom_file)) = (&self.loader_type, &self.rom_file) {
+            RawLoader::from_file(rom_file.clone())?.write_to_bus(&mut *bus.borrow_mut(), *load_addr, *reset_vector)?;
+        }
+
+        Ok(())
+    }
+
+    /***
+     * The last device added wins on the overlapping addresses: the OAM DMA (0x4014) and the controller (0x4016)
+     * are added after the APU, in case it maps the whole 0x4000 - 0x4017 range.
+     * The PPU also needs the CHR ROM and the mirroring of the cartridge.
+     * The devices are sorted in that order (stable for the same rank), whatever the order they were given in.
+     ***/
+    fn ordered_device_types(&self) -> Result<Vec<BusDeviceType>, NesConsoleError> {
+        let mut device_types = self.device_types.clone();
+
+        for (index, device_type) in device_types.iter().enumerate() {
+            let rank = NesConsoleBuilder::mapping_rank(device_type);
+
+            if device_types[..index].iter().any(|other| NesConsoleBuilder::mapping_rank(other) == rank) {
+                return Err(NesConsoleError::BuilderError(format!("more than one device for {}", device_type)));
             }
         }
+
+        device_types.sort_by_key(NesConsoleBuilder::mapping_rank);
+
+        if device_types != self.device_types {
+            debug!("devices reordered for the bus mapping: {:?}", device_types);
+        }
+
+        Ok(device_types)
+    }
+
+    fn mapping_rank(device_type: &BusDeviceType) -> u8 {
+        match device_type {
+            BusDeviceType::WRAM(_) => 0,
+            BusDeviceType::CARTRIDGE(_) => 1,
+            BusDeviceType::APU(_) => 2,
+            BusDeviceType::PPU(_) => 3,
+            BusDeviceType::DMA(_) => 4,
+            BusDeviceType::CONTROLLER(_) => 5,
+            BusDeviceType::OPENBUS => 6,
+        }
     }
 
     fn build_nes(mut self) -> Result<NesConsole, NesConsoleError> {
@@ -602,12 +1396,19 @@ impl NesConsoleBuilder {
         self.bus = Some(bus.clone());
         self.cpu = Some(cpu.clone());
 
-        let device_types = self.device_types.clone();
+        let device_types = self.ordered_device_types()?;
 
         for device_type in device_types {
             self.build_device_and_connect_to_bus(&device_type, bus.clone(), cpu.clone())?;
         }
 
+        for mut device in self.custom_io_devices.drain(..) {
+            device.initialize()?;
+            bus.borrow_mut().add_device(Rc::new(RefCell::new(device)))?;
+        }
+
+        self.load_raw_binary(bus.clone())?;
+
         let cpu = self.cpu.take()
             .ok_or(NesConsoleError::BuilderError("cpu missing".to_string()))?;
 
@@ -620,13 +1421,25 @@ impl NesConsoleBuilder {
         let controller = self.controller.take()
             .ok_or(NesConsoleError::BuilderError("controller missing".to_string()))?;
 
-        let console = NesConsole::new(cpu, ppu, apu, controller, self.entry_point.take());
+        let mut console = NesConsole::new(bus, cpu, ppu, apu, controller, self.entry_point.take());
+        console.region = self.region;
+        console.cheats = self.cheats.clone();
+        console.wram = self.wram.take();
+        console.cartridge = self.cartridge.take();
+        console.ram_search = console.wram.as_ref().map(|wram| RamSearch::new(wram.borrow().bytes()));
+        console.ram_init = self.ram_init;
+
+        for code in &self.cheat_codes {
+            console.add_cheat(code)?;
+        }
 
         Ok(console)
     }
 
     pub fn build(self) -> Result<NesConsole, NesConsoleError> {
-        if let (Some(_), Some(_), Some(_), Some(_)) = (&self.bus_type, &self.cpu_type, &self.loader_type, &self.rom_file) {
+        let has_rom = self.rom_file.is_some() || self.rom_bytes.is_some();
+
+        if let (Some(_), Some(_), Some(_), true) = (&self.bus_type, &self.cpu_type, &self.loader_type, has_rom) {
             self.build_nes()
         } else {
             Err(NesConsoleError::BuilderError("missing required components".to_string()))
@@ -58,6 +58,17 @@ impl NesFrame {
         (self.pixels[index], self.pixels[index + 1], self.pixels[index + 2])
     }
     
+    /// Same frame (counter and state) with other pixels, for the post-processing filters.
+    pub(crate) fn with_pixels(&self, width: usize, pixels: Vec<u8>) -> NesFrame {
+        NesFrame {
+            pixels,
+            width,
+            height: self.height,
+            counter: self.counter,
+            state: self.state.clone()
+        }
+    }
+
     pub fn state(&self) -> FrameState {
         self.state.clone()
     }
@@ -1,15 +1,14 @@
 use std::cell::RefCell;
 use std::fmt::Debug;
-use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read, Seek};
 use std::rc::Rc;
 use log::{debug, info};
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::cartridge;
-use crate::cartridge::{Cartridge, CartridgeError, CPU_ADDRESS_SPACE, PPU_ADDRESS_SPACE};
+use crate::cartridge::{BankState, Cartridge, CartridgeError, CPU_ADDRESS_SPACE, PPU_ADDRESS_SPACE};
 use crate::cartridge::CartridgeType::NROM;
 use crate::ines_loader::{FromINes, INesRomHeader};
-use crate::loader::LoaderError;
+use crate::loader::{LoaderError, RomData};
 use crate::memory::{Memory, MemoryError};
 use crate::memory_bank::MemoryBank;
 use crate::memory_ciram::PpuNameTableMirroring;
@@ -19,6 +18,35 @@ const NROM_PRG_MEMORY_BANK_SIZE_32K: usize = 32 * 1024;
 const NROM_CHR_MEMORY_BANK_SIZE: usize = 8 * 1024;
 const MAPPER_NAME: &str = "NROM";
 
+/***
+ * NROM-128 has 16 KB of PRG ROM, mirrored at $8000 and $C000 (the reset vector is read from the mirror),
+ * NROM-256 has 32 KB of PRG ROM mapped from $8000 to $FFFF.
+ * https://www.nesdev.org/wiki/NROM
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NromVariant {
+    Nrom128,
+    Nrom256,
+}
+
+impl NromVariant {
+    pub fn from_prg_rom_size(prg_rom_size: usize) -> Result<NromVariant, CartridgeError> {
+        match prg_rom_size {
+            0 => Err(CartridgeError::Unsupported("NROM cartridge without PRG ROM".to_string())),
+            size if size <= NROM_PRG_MEMORY_BANK_SIZE_16K => Ok(NromVariant::Nrom128),
+            size if size <= NROM_PRG_MEMORY_BANK_SIZE_32K => Ok(NromVariant::Nrom256),
+            size => Err(CartridgeError::Unsupported(format!("NROM cartridge with {} bytes of PRG ROM (max: {})", size, NROM_PRG_MEMORY_BANK_SIZE_32K))),
+        }
+    }
+
+    pub fn prg_memory_bank_size(&self) -> usize {
+        match self {
+            NromVariant::Nrom128 => NROM_PRG_MEMORY_BANK_SIZE_16K,
+            NromVariant::Nrom256 => NROM_PRG_MEMORY_BANK_SIZE_32K,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NromCartridge {
     prg_rom: Rc<RefCell<MemoryBank>>,
@@ -26,11 +54,12 @@ pub struct NromCartridge {
     device_type: BusDeviceType,
     mirroring: Rc<RefCell<PpuNameTableMirroring>>,
     prg_rom_size: usize,
+    variant: NromVariant,
 }
 
 impl NromCartridge {
 
-    pub fn new(mut data: BufReader<File>,
+    pub fn new(mut data: impl Read + Seek,
                prg_rom_offset: u64, prg_rom_size: usize,
                chr_rom_offset: u64, chr_rom_size: usize,
                chr_ram_size: usize, mirroring: PpuNameTableMirroring) -> Result<NromCartridge, CartridgeError> {
@@ -40,17 +69,18 @@ impl NromCartridge {
                 format!("NROM cartridge does not support both CHR-ROM (detected: {} bytes) and CHR-RAM (detected: {} bytes)", chr_rom_size, chr_ram_size)))?
         }
 
-        let prg_memory_bank_size = if prg_rom_size <= NROM_PRG_MEMORY_BANK_SIZE_16K { NROM_PRG_MEMORY_BANK_SIZE_16K } else { NROM_PRG_MEMORY_BANK_SIZE_32K };
+        let variant = NromVariant::from_prg_rom_size(prg_rom_size)?;
+        let prg_memory_bank_size = variant.prg_memory_bank_size();
         let prg_memory_banks = cartridge::create_prg_rom_memory(&mut data, prg_rom_offset, prg_rom_size, prg_memory_bank_size, CPU_ADDRESS_SPACE)?;
 
         let prg_rom = cartridge::get_first_bank_or_fail(prg_memory_banks, prg_rom_size, prg_memory_bank_size, true)?;
-        debug!("NROM: prg rom size: {}, prg rom bank size: {}, number of bank: {}", prg_rom_size, prg_memory_bank_size, 1);
+        debug!("NROM: {:?}, prg rom size: {}, prg rom bank size: {}, number of bank: {}", variant, prg_rom_size, prg_memory_bank_size, 1);
 
         let (chr_memory_size, is_chr_rom) = cartridge::get_chr_memory_size_and_type(chr_rom_size, chr_ram_size);
         let rom_data = if is_chr_rom { Some(&mut data) } else { None };
 
         let chr_memory_banks = cartridge::create_chr_memory(rom_data, chr_rom_offset, chr_memory_size, NROM_CHR_MEMORY_BANK_SIZE, is_chr_rom, PPU_ADDRESS_SPACE)?;
-        let chr_mem = cartridge::get_first_bank_or_fail(chr_memory_banks, chr_rom_size, NROM_CHR_MEMORY_BANK_SIZE, is_chr_rom)?;
+        let chr_mem = cartridge::get_first_bank_or_fail(chr_memory_banks, chr_memory_size, NROM_CHR_MEMORY_BANK_SIZE, is_chr_rom)?;
 
         debug!("NROM: chr memory size: {}, number of bank: {}, ram: {}", chr_memory_size, 1, !is_chr_rom);
 
@@ -60,18 +90,28 @@ impl NromCartridge {
             device_type: BusDeviceType::CARTRIDGE(NROM),
             mirroring: Rc::new(RefCell::new(mirroring)),
             prg_rom_size,
+            variant,
         };
 
         Ok(cartridge)
     }
 
-    fn build(file: File,
+    pub fn variant(&self) -> NromVariant {
+        self.variant
+    }
+
+    /// The PRG ROM of NROM-128 is seen twice in the CPU address space.
+    fn prg_addr(&self, addr: u16) -> u16 {
+        addr % self.variant.prg_memory_bank_size() as u16
+    }
+
+    fn build(data: Box<dyn RomData>,
              prg_rom_offset: u64, prg_rom_size: usize,
              chr_rom_offset: Option<u64>, chr_rom_size: usize,
              chr_ram_size: usize, mirroring: PpuNameTableMirroring) -> Result<NromCartridge, LoaderError> {
         info!("creating NROM cartridge");
 
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(data);
         let chr_rom_offset = if let Some(chr_rom_offset_unwrapped) = chr_rom_offset { chr_rom_offset_unwrapped } else { 0 };
 
         let cartridge = NromCartridge::new(reader, prg_rom_offset, prg_rom_size, chr_rom_offset, chr_rom_size, chr_ram_size, mirroring)?;
@@ -81,11 +121,11 @@ impl NromCartridge {
 
 impl FromINes for NromCartridge {
     #[allow(refining_impl_trait)]
-    fn from_ines(file: File, header: INesRomHeader) -> Result<NromCartridge, LoaderError>
+    fn from_ines(data: Box<dyn RomData>, header: INesRomHeader) -> Result<NromCartridge, LoaderError>
     where
         Self: Sized
     {
-        let cartridge = NromCartridge::build(file,
+        let cartridge = NromCartridge::build(data,
                                              header.prg_offset(), header.prg_rom_size,
                                              header.chr_offset(), header.chr_rom_size,
                                              header.chr_ram_size,
@@ -106,23 +146,27 @@ impl Memory for NromCartridge {
     }
 
     fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
-        self.prg_rom.borrow().read_byte(addr)
+        self.prg_rom.borrow().read_byte(self.prg_addr(addr))
     }
 
     fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
         self.read_byte(addr)
     }
 
+    fn poke_byte(&mut self, addr: u16, _: u8) -> Result<(), MemoryError> {
+        Err(MemoryError::ReadOnly(addr))
+    }
+
     fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
-        self.prg_rom.borrow_mut().write_byte(addr, value)
+        self.prg_rom.borrow_mut().write_byte(self.prg_addr(addr), value)
     }
 
     fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
-        self.prg_rom.borrow().read_word(addr)
+        self.prg_rom.borrow().read_word(self.prg_addr(addr))
     }
 
     fn write_word(&mut self, addr: u16, value: u16) -> Result<(), MemoryError> {
-        self.prg_rom.borrow_mut().write_word(addr, value)
+        self.prg_rom.borrow_mut().write_word(self.prg_addr(addr), value)
     }
 
     fn dump(&self) {
@@ -156,4 +200,11 @@ impl Cartridge for NromCartridge {
     fn get_mirroring(&self) -> Rc<RefCell<PpuNameTableMirroring>> {
         self.mirroring.clone()
     }
+
+    fn bank_state(&self) -> BankState {
+        match self.variant {
+            NromVariant::Nrom128 => BankState::from_16k_prg_banks(0, 0, 0, 1),
+            NromVariant::Nrom256 => BankState::default(),
+        }
+    }
 }
\ No newline at end of file
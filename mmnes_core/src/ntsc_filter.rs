@@ -0,0 +1,104 @@
+use std::f32::consts::PI;
+use crate::nes_frame::NesFrame;
+
+pub const NTSC_FILTER_SCALE: usize = 2;
+const LUMA_RADIUS: usize = 1;
+const CHROMA_RADIUS: usize = 3;
+const CROSSTALK: f32 = 0.5;
+const SUBCARRIER_PHASES: usize = 12;
+const PIXEL_PHASE_STEP: usize = 8;
+const LINE_PHASE_STEP: usize = 4;
+const FRAME_PHASE_STEP: usize = 4;
+
+type Yiq = (f32, f32, f32);
+
+/***
+ * Basic approximation of the NES composite video output, applied over the RGBA frame of the PPU.
+ * Each pixel is split in NTSC_FILTER_SCALE samples, and in YIQ:
+ *   - the chroma (I and Q) has a lower bandwidth than the luma and bleeds over the neighbour pixels,
+ *   - the chroma transitions leak into the luma, modulated by the color subcarrier whose phase
+ *     advances by 8/12 of a cycle per pixel, 4/12 per scanline and 4/12 per frame (dot crawl).
+ *
+ * Flat areas are left untouched, the artifacts only show where the color changes.
+ *
+ * https://www.nesdev.org/wiki/NTSC_video
+ ***/
+pub struct NtscFilter;
+
+impl NtscFilter {
+
+    pub fn apply(frame: &NesFrame) -> NesFrame {
+        let width = frame.width() * NTSC_FILTER_SCALE;
+        let mut pixels = vec![0xFF; width * frame.height() * 4];
+
+        for y in 0..frame.height() {
+            let src = &frame.pixels()[y * frame.width() * 4..(y + 1) * frame.width() * 4];
+            let dst = &mut pixels[y * width * 4..(y + 1) * width * 4];
+
+            NtscFilter::filter_line(src, dst, y, frame.count() as usize);
+        }
+
+        frame.with_pixels(width, pixels)
+    }
+
+    fn filter_line(src: &[u8], dst: &mut [u8], line: usize, frame_count: usize) {
+        let samples = src.chunks_exact(4)
+            .flat_map(|rgba| [rgba; NTSC_FILTER_SCALE])
+            .collect::<Vec<&[u8]>>();
+
+        let yiq = samples.iter()
+            .map(|rgba| NtscFilter::rgb_to_yiq(rgba[0], rgba[1], rgba[2]))
+            .collect::<Vec<Yiq>>();
+
+        let line_phase = line * LINE_PHASE_STEP + frame_count * FRAME_PHASE_STEP;
+
+        for (x, rgba) in samples.iter().enumerate() {
+            let (y0, i0, q0) = yiq[x];
+            let (luma, _, _) = NtscFilter::average(&yiq, x, LUMA_RADIUS);
+            let (_, i, q) = NtscFilter::average(&yiq, x, CHROMA_RADIUS);
+
+            let phase = (x * PIXEL_PHASE_STEP / NTSC_FILTER_SCALE + line_phase) % SUBCARRIER_PHASES;
+            let angle = 2.0 * PI * phase as f32 / SUBCARRIER_PHASES as f32;
+            let crawl = CROSSTALK * ((i0 - i) * angle.cos() + (q0 - q) * angle.sin());
+
+            // only the difference to the source color goes through the YIQ conversion,
+            // to keep flat colors exact despite the rounding
+            let (dr, dg, db) = NtscFilter::yiq_to_rgb(luma - y0 + crawl, i - i0, q - q0);
+
+            let index = x * 4;
+            dst[index] = NtscFilter::to_component(rgba[0], dr);
+            dst[index + 1] = NtscFilter::to_component(rgba[1], dg);
+            dst[index + 2] = NtscFilter::to_component(rgba[2], db);
+            dst[index + 3] = rgba[3];
+        }
+    }
+
+    fn average(yiq: &[Yiq], x: usize, radius: usize) -> Yiq {
+        let start = x.saturating_sub(radius);
+        let end = (x + radius).min(yiq.len() - 1);
+        let count = (end - start + 1) as f32;
+
+        let (y, i, q) = yiq[start..=end].iter()
+            .fold((0.0, 0.0, 0.0), |acc, sample| (acc.0 + sample.0, acc.1 + sample.1, acc.2 + sample.2));
+
+        (y / count, i / count, q / count)
+    }
+
+    fn to_component(value: u8, delta: f32) -> u8 {
+        (value as f32 + delta).round().clamp(0.0, 255.0) as u8
+    }
+
+    fn rgb_to_yiq(r: u8, g: u8, b: u8) -> Yiq {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+
+        (0.299 * r + 0.587 * g + 0.114 * b,
+         0.596 * r - 0.274 * g - 0.322 * b,
+         0.211 * r - 0.523 * g + 0.312 * b)
+    }
+
+    fn yiq_to_rgb(y: f32, i: f32, q: f32) -> (f32, f32, f32) {
+        (y + 0.956 * i + 0.621 * q,
+         y - 0.272 * i - 0.647 * q,
+         y - 1.106 * i + 1.703 * q)
+    }
+}
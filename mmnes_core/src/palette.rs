@@ -5,4 +5,5 @@ pub trait Palette {
     fn rgba_transparent(color: u8) -> (u8, u8, u8, u8);
     fn is_transparent(alpha: u8) -> bool;
     fn transparent_alpha() -> u8;
+    fn opaque_alpha() -> u8;
 }
\ No newline at end of file
@@ -1,7 +1,14 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use crate::palette::Palette;
 
 const TRANSPARENT: u8 = 0;
 const OPAQUE: u8 = 255;
+const PALETTE_COLORS: usize = 64;
+const EMPHASIS_VARIANTS: usize = 8;
+const PAL_FILE_SIZE: usize = PALETTE_COLORS * 3;
+const EXTENDED_PAL_FILE_SIZE: usize = PAL_FILE_SIZE * EMPHASIS_VARIANTS;
 
 pub static SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
     (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96), (0xA1, 0x00, 0x5E),
@@ -18,13 +25,72 @@ pub static SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
     (0xFF, 0xEF, 0xA6), (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
     (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
 ];
+
+type EmphasisPalettes = [[(u8, u8, u8); PALETTE_COLORS]; EMPHASIS_VARIANTS];
+
+thread_local! {
+    /***
+     * the palette is loaded per thread: the thread running the console must load it.
+     * the built-in palette does not emulate the color emphasis, its 8 variants are identical.
+     ***/
+    static ACTIVE_PALETTES: RefCell<EmphasisPalettes> = const { RefCell::new([SYSTEM_PALETTE; EMPHASIS_VARIANTS]) };
+}
+
 pub struct Palette2C02 {
 }
 
+impl Palette2C02 {
+
+    /***
+     * .pal file: 64 RGB triples (192 bytes), or 8 x 64 RGB triples (1536 bytes) for each combination
+     * of the emphasis bits of PPUMASK, in the order: none, red, green, red + green, blue, red + blue, ...
+     * https://www.nesdev.org/wiki/.pal
+     ***/
+    pub fn load_from_pal(bytes: &[u8]) -> Result<(), PaletteError> {
+        let palettes = match bytes.len() {
+            PAL_FILE_SIZE => [Palette2C02::parse_rgb_triples(bytes); EMPHASIS_VARIANTS],
+            EXTENDED_PAL_FILE_SIZE => {
+                let mut palettes = [SYSTEM_PALETTE; EMPHASIS_VARIANTS];
+
+                for (palette, chunk) in palettes.iter_mut().zip(bytes.chunks_exact(PAL_FILE_SIZE)) {
+                    *palette = Palette2C02::parse_rgb_triples(chunk);
+                }
+
+                palettes
+            },
+            size => return Err(PaletteError::InvalidSize(size)),
+        };
+
+        ACTIVE_PALETTES.with(|active| *active.borrow_mut() = palettes);
+        Ok(())
+    }
+
+    pub fn reset_to_system_palette() {
+        ACTIVE_PALETTES.with(|active| *active.borrow_mut() = [SYSTEM_PALETTE; EMPHASIS_VARIANTS]);
+    }
+
+    /// ```emphasis``` holds the 3 emphasis bits of PPUMASK (bits 5 - 7) shifted to bits 0 - 2.
+    pub fn rgb_emphasized(color: u8, emphasis: u8) -> (u8, u8, u8) {
+        let index = color as usize % PALETTE_COLORS;
+        let variant = emphasis as usize % EMPHASIS_VARIANTS;
+
+        ACTIVE_PALETTES.with(|active| active.borrow()[variant][index])
+    }
+
+    fn parse_rgb_triples(bytes: &[u8]) -> [(u8, u8, u8); PALETTE_COLORS] {
+        let mut palette = [(0, 0, 0); PALETTE_COLORS];
+
+        for (color, rgb) in palette.iter_mut().zip(bytes.chunks_exact(3)) {
+            *color = (rgb[0], rgb[1], rgb[2]);
+        }
+
+        palette
+    }
+}
+
 impl Palette for Palette2C02 {
     fn rgb(color: u8) -> (u8, u8, u8) {
-        let index = color as usize % 64;
-        SYSTEM_PALETTE[index]
+        Palette2C02::rgb_emphasized(color, 0)
     }
 
     fn rgba_opaque(color: u8) -> (u8, u8, u8, u8) {
@@ -44,4 +110,23 @@ impl Palette for Palette2C02 {
     fn transparent_alpha() -> u8 {
         TRANSPARENT
     }
-}
\ No newline at end of file
+
+    fn opaque_alpha() -> u8 {
+        OPAQUE
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PaletteError {
+    InvalidSize(usize),
+}
+
+impl Error for PaletteError {}
+
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PaletteError::InvalidSize(size) => write!(f, "invalid palette file size: {} bytes (expected {} or {})", size, PAL_FILE_SIZE, EXTENDED_PAL_FILE_SIZE),
+        }
+    }
+}
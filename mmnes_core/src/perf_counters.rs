@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+
+/***
+ * Coarse counters of the work done by the emulator since it was built, to profile it:
+ * the difference of two readings gives the work done in between (see ```since```).
+ * The tile cache counters stay at 0 without the ppu_tile_cache feature.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PerfCounters {
+    pub(crate) instructions: u64,
+    pub(crate) scanlines: u64,
+    pub(crate) samples: u64,
+    pub(crate) tile_cache_hits: u64,
+    pub(crate) tile_cache_misses: u64,
+}
+
+impl PerfCounters {
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn scanlines(&self) -> u64 {
+        self.scanlines
+    }
+
+    /// Samples produced by the APU.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn tile_cache_hits(&self) -> u64 {
+        self.tile_cache_hits
+    }
+
+    pub fn tile_cache_misses(&self) -> u64 {
+        self.tile_cache_misses
+    }
+
+    /// None when no tile went through the cache.
+    pub fn tile_cache_hit_ratio(&self) -> Option<f64> {
+        let lookups = self.tile_cache_hits + self.tile_cache_misses;
+
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.tile_cache_hits as f64 / lookups as f64)
+        }
+    }
+
+    /// The work done since the ```earlier``` reading.
+    pub fn since(&self, earlier: &PerfCounters) -> PerfCounters {
+        PerfCounters {
+            instructions: self.instructions.saturating_sub(earlier.instructions),
+            scanlines: self.scanlines.saturating_sub(earlier.scanlines),
+            samples: self.samples.saturating_sub(earlier.samples),
+            tile_cache_hits: self.tile_cache_hits.saturating_sub(earlier.tile_cache_hits),
+            tile_cache_misses: self.tile_cache_misses.saturating_sub(earlier.tile_cache_misses),
+        }
+    }
+}
+
+impl Display for PerfCounters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instructions, {} scanlines, {} samples", self.instructions, self.scanlines, self.samples)?;
+
+        match self.tile_cache_hit_ratio() {
+            Some(ratio) => write!(f, ", tile cache: {:.1}% hits", ratio * 100.0),
+            None => Ok(()),
+        }
+    }
+}
@@ -7,6 +7,16 @@ use crate::cpu::CpuError;
 use crate::dma_device::DmaDevice;
 use crate::nes_frame::NesFrame;
 use crate::memory::MemoryError;
+use crate::memory_bank::RamInit;
+use crate::perf_counters::PerfCounters;
+use crate::timing_spans::TimingSpans;
+use crate::ppu_memory_dump::PpuMemoryDump;
+use crate::ppu_snapshot::PpuSnapshot;
+use crate::region::Region;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const PPU_DOTS_PER_SCANLINE: u16 = 341;
 
 #[derive(Default, Debug, Clone)]
 pub enum PpuType {
@@ -32,15 +42,141 @@ impl PartialEq for PpuType {
 
 pub trait PPU: BusDevice + DmaDevice {
     fn reset(&mut self) -> Result<(), PpuError>;
+
+    /// Reset at power on: the OAM loses its content and is filled with ```oam_init```.
+    fn cold_reset(&mut self, oam_init: RamInit) -> Result<(), PpuError>;
     fn panic(&self, error: &PpuError);
 
-    /// Run the PPU for 114 cycles (completing 1 scanline), returning the new cycle count after execution and a full frame if available (after having rendered 240 scanlines).
+    /// Run the PPU for 1 scanline (114 cycles on NTSC, 107 on PAL), returning the new cycle count after execution and a full frame if available (after having rendered 240 scanlines).
     /// The current implementation ignore the credits input, and will always render a full scanline, updating
-    /// the current cycle count by 114.
+    /// the current cycle count by the scanline duration of the region.
     /// ```start_cycle```: current cycle of execution,
     /// ```credits```: the number of cycles available to execute instructions (ignored)
     fn run(&mut self, start_cycle: u32, credits: u32) -> Result<(u32, Option<NesFrame>), PpuError>;
+    /// The frame being drawn, the completed frames are the ones returned by ```run```.
     fn frame(&self) -> NesFrame;
+
+    /// Total number of scanlines per frame for the configured region (262 on NTSC, 312 on PAL).
+    fn scanlines_per_frame(&self) -> u16;
+
+    /// Scanline the PPU is on at the current CPU cycle (0-239 visible, 241 vblank start, last one is pre-render).
+    fn current_scanline(&self) -> u16;
+
+    /// Dot (0-340) within the current scanline at the current CPU cycle.
+    fn current_dot(&self) -> u16;
+
+    /// The dot clock shared with the CPU, which advances it after every instruction.
+    fn clock(&self) -> Rc<RefCell<PpuClock>>;
+
+    /// Palette RAM and both pattern tables rendered to RGBA tiles with the palette ```palette_index```
+    /// (0-3: background palettes, 4-7: sprite palettes).
+    fn dump_pattern_tables(&self, palette_index: u8) -> Result<PpuMemoryDump, PpuError>;
+
+    /// Logical nametable ```index``` (0-3) rendered to a 256x240 frame, with the current pattern table and palettes
+    /// and without the scroll.
+    fn render_nametable(&self, index: u8) -> Result<NesFrame, PpuError>;
+
+    /// State of the registers and of the rendering position.
+    fn snapshot(&self) -> PpuSnapshot;
+
+    /// Hide the background or the sprites (```Some(false)```) whatever the mask register, for debugging.
+    fn set_layer_override(&mut self, background: Option<bool>, sprites: Option<bool>);
+
+    /// Scanlines rendered and tile cache hits and misses since the PPU was created, the other counters are left to 0.
+    fn perf_counters(&self) -> PerfCounters;
+
+    /// Time spent rendering the background and the sprites, the other spans are left to 0.
+    fn timing_spans(&self) -> TimingSpans;
+}
+
+/***
+ * PPU dot clock, driven by the CPU cycles: 3 dots per CPU cycle on NTSC and Dendy, 3.2 on PAL.
+ * 341 dots per scanline, the scanline wraps at the end of the frame.
+ * https://www.nesdev.org/wiki/PPU_rendering
+ * https://www.nesdev.org/wiki/Cycle_reference_chart
+ ***/
+#[derive(Debug, Clone)]
+pub struct PpuClock {
+    scanline: u16,
+    dot: u16,
+    scanlines_per_frame: u16,
+    dots_per_cycle_numerator: u32,
+    dots_per_cycle_denominator: u32,
+    remainder: u32,
+    cpu_cycles: u64,
+}
+
+impl PpuClock {
+    pub fn new(region: Region) -> Self {
+        let (dots_per_cycle_numerator, dots_per_cycle_denominator) = match region {
+            Region::PAL => (16, 5),
+            _ => (3, 1),
+        };
+
+        PpuClock {
+            scanline: 0,
+            dot: 0,
+            scanlines_per_frame: region.scanlines_per_frame(),
+            dots_per_cycle_numerator,
+            dots_per_cycle_denominator,
+            remainder: 0,
+            cpu_cycles: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.scanline = 0;
+        self.dot = 0;
+        self.remainder = 0;
+        self.cpu_cycles = 0;
+    }
+
+    pub fn set_position(&mut self, scanline: u16, dot: u16) {
+        self.scanline = scanline % self.scanlines_per_frame;
+        self.dot = dot % PPU_DOTS_PER_SCANLINE;
+        self.remainder = 0;
+    }
+
+    /// Advance the clock by the dots elapsed during ```cpu_cycles``` CPU cycles.
+    pub fn advance(&mut self, cpu_cycles: u64) {
+        self.cpu_cycles += cpu_cycles;
+        self.advance_dots(cpu_cycles);
+    }
+
+    /***
+     * Realign the position on the PPU, which renders the scanlines after the CPU has run them:
+     * ```cpu_cycles_ahead``` CPU cycles into ```scanline```, the scanline the PPU renders next.
+     * The CPU cycles elapsed since power-up or reset are left untouched.
+     ***/
+    pub fn sync(&mut self, scanline: u16, cpu_cycles_ahead: u64) {
+        self.set_position(scanline, 0);
+        self.advance_dots(cpu_cycles_ahead);
+    }
+
+    fn advance_dots(&mut self, cpu_cycles: u64) {
+        let ticks = cpu_cycles * self.dots_per_cycle_numerator as u64 + self.remainder as u64;
+        let dots = ticks / self.dots_per_cycle_denominator as u64;
+        self.remainder = (ticks % self.dots_per_cycle_denominator as u64) as u32;
+
+        let dots = self.dot as u64 + dots;
+        let scanlines = self.scanline as u64 + dots / PPU_DOTS_PER_SCANLINE as u64;
+
+        self.dot = (dots % PPU_DOTS_PER_SCANLINE as u64) as u16;
+        self.scanline = (scanlines % self.scanlines_per_frame as u64) as u16;
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// CPU cycles elapsed since power-up or reset.
+    pub fn cpu_cycles(&self) -> u64 {
+        self.cpu_cycles
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,26 +1,34 @@
 use std::cell::RefCell;
+#[cfg(feature = "ppu_tile_cache")]
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
-use log::info;
+use log::{debug, info};
 use crate::bus::Bus;
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::cpu::CPU;
 use crate::dma_device::DmaDevice;
-use crate::nes_frame::{FrameState, NesFrame};
+use crate::nes_frame::NesFrame;
 use crate::memory::{Memory, MemoryError};
+use crate::memory_bank::RamInit;
+use crate::perf_counters::PerfCounters;
 use crate::memory_ciram::{CiramMemory, PpuNameTableMirroring};
 use crate::memory_palette::MemoryPalette;
 use crate::nes_bus::NESBus;
 use crate::palette::Palette;
 use crate::palette_2c02::Palette2C02;
-use crate::ppu::{PPU, PpuError, PpuType};
+use crate::ppu::{PPU, PpuClock, PpuError, PpuType};
+use crate::ppu_memory_dump::{PatternTile, PpuMemoryDump, PATTERN_TABLES_COUNT, PATTERN_TILES_PER_TABLE};
+use crate::ppu_snapshot::PpuSnapshot;
 use crate::ppu_2c02::ControlFlag::{BackgroundPatternTableAddr, GenerateNmi, SpritePatternTableAddr, SpriteSize, VramIncrement};
 use crate::ppu_2c02::MaskFlag::{ShowBackground, ShowSprites};
 use crate::ppu_2c02::PpuFlag::{Control, Mask, Status};
 use crate::ppu_2c02::SpriteAttribute::{FlipHorizontal, FlipVertical};
 use crate::ppu_2c02::StatusFlag::{Sprite0Hit, SpriteOverflow, VBlank};
+use crate::region::Region;
+use crate::save_state::{push_chunk, StateReader};
 use crate::renderer::Renderer;
-use crate::util::vec_to_array;
+use crate::timing_spans::{timed, TimingSpans};
 
 const PPU_NAME: &str = "PPU 2C02";
 
@@ -37,6 +45,8 @@ pub const NT_BASES: [(u16,u16); 8] = [
 
 
 const NAME_TABLE_SIZE: usize = 1024;
+const NAME_TABLE_COLUMNS: u8 = 32;
+const NAME_TABLE_ROWS: u8 = 30;
 const ATTRIBUTE_TABLE_SIZE: usize = 64;
 const PATTERN_TABLE_LEFT_ADDR: u16 = 0x0000;
 const PATTERN_TABLE_RIGHT_ADDR: u16 = 0x1000;
@@ -44,6 +54,20 @@ const PATTERN_TABLE_RIGHT_ADDR: u16 = 0x1000;
 const PALETTE_ADDRESS_SPACE: (u16, u16) = (0x3F00, 0x3FFF);
 const SPRITE_PALETTE_ADDR: u16 = 0x3F10;
 const PALETTE_SIZE: usize = 32;
+const PALETTES_COUNT: u8 = 8;
+
+const WARM_UP_CPU_CYCLES: u64 = 29658;
+
+const VBLANK_SET_SCANLINE: u16 = 241;
+const VBLANK_SET_DOT: u16 = 1;
+const SECONDARY_OAM_CLEAR_DOTS: (u16, u16) = (1, 64);
+const Y_INCREMENT_DOT: u16 = 256;
+const OAM_ATTRIBUTES_UNUSED_BITS: u8 = 0x1C;
+const STATUS_OPEN_BUS_BITS: u8 = 0x1F;
+const OAM_SIZE: usize = 256;
+const OAM_DECAYED_VALUE: u8 = 0xFF;
+const SPRITES_PER_SCANLINE: usize = 8;
+pub const DEFAULT_OAM_DECAY_FRAMES: u32 = 2;
 
 const V_INCR_GOING_ACROSS: u8 = 1;
 const V_INCR_GOING_DOWN: u8 = 32;
@@ -59,7 +83,6 @@ const SPRITE_WIDTH: u8 = 8;
 const PATTERN_DATA_SIZE: usize = 16;
 const MERGED_PATTERN_DATA_SIZE: usize = 64;
 
-const CLOCK_CYCLES_PER_SCANLINE: u16 = 114;
 
 
 #[derive(Debug)]
@@ -116,7 +139,7 @@ enum SpriteAttribute {
 
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum SpritePriority {
+pub(crate) enum SpritePriority {
     Front,
     Back,
     None
@@ -228,8 +251,8 @@ enum PixelMode {
     Sprite
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Pixel {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Pixel {
     r: u8,
     g: u8,
     b: u8,
@@ -250,7 +273,7 @@ impl Default for Pixel {
 }
 
 impl Pixel {
-    fn new(r: u8, g: u8, b: u8, a: u8, priority: SpritePriority) -> Self {
+    pub(crate) const fn new(r: u8, g: u8, b: u8, a: u8, priority: SpritePriority) -> Self {
         Pixel {
             r,
             g,
@@ -262,7 +285,7 @@ impl Pixel {
 }
 
 #[derive(Debug)]
-struct PixelLines {
+pub(crate) struct PixelLines {
     rgba_pixels: [Pixel; PIXEL_X_MAX as usize + 1]
 }
 
@@ -280,11 +303,11 @@ impl PixelLines {
         self.rgba_pixels = [Pixel::default(); PIXEL_X_MAX as usize+ 1]
     }
 
-    fn get_pixel_rgba(&self, x: u8) -> &Pixel {
+    pub(crate) fn get_pixel_rgba(&self, x: u8) -> &Pixel {
         &self.rgba_pixels[x as usize]
     }
 
-    fn set_pixel_rgba(&mut self, x: u8, pixel: Pixel) {
+    pub(crate) fn set_pixel_rgba(&mut self, x: u8, pixel: Pixel) {
         self.rgba_pixels[x as usize] = pixel;
     }
 
@@ -292,7 +315,13 @@ impl PixelLines {
         Palette2C02::is_transparent(self.rgba_pixels[x as usize].a)
     }
 
-    fn merge(&self, other: &PixelLines) -> PixelLines {
+    /***
+     * ```self``` is the background line, ```other``` the sprites line where the opaque pixel of the sprite
+     * with the lowest OAM index has already won: a front priority sprite is drawn over the background,
+     * a back priority sprite only shows through the transparent pixels of the background.
+     * https://www.nesdev.org/wiki/PPU_sprite_priority
+     ***/
+    pub(crate) fn merge(&self, other: &PixelLines) -> PixelLines {
         let mut merged_pixels = PixelLines::default();
 
         for (x, pixel) in self.rgba_pixels.iter().enumerate() {
@@ -326,6 +355,43 @@ impl Display for PpuState {
     }
 }
 
+/***
+ * State of the PPU at the start of a visible scanline, before it is rendered: the scroll position
+ * (v, with the fine X scroll) and the control and mask registers used to render it.
+ ***/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScanlineEvent {
+    scanline: u16,
+    v: u16,
+    fine_x: u8,
+    control: u8,
+    mask: u8,
+}
+
+impl ScanlineEvent {
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn v(&self) -> u16 {
+        self.v
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.fine_x
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    pub fn mask(&self) -> u8 {
+        self.mask
+    }
+}
+
+pub type ScanlineHook = Box<dyn FnMut(ScanlineEvent)>;
+
 pub struct Ppu2c02 {
     register: RefCell<Register>,
     bus: Box<dyn Bus>,
@@ -333,14 +399,27 @@ pub struct Ppu2c02 {
     v: RefCell<u16>,
     t: u16,
     x: u8,
+    fine_x: u8,
+    pending_v: Option<u16>,
     latch: RefCell<Latch>,
+    io_latch: RefCell<u8>,
     renderer: RefCell<Renderer>,
     cpu: Rc<RefCell<dyn CPU>>,
     state: PpuState,
+    vblank_suppressed: RefCell<bool>,
+    region: Region,
+    clock: Rc<RefCell<PpuClock>>,
+    cpu_cycles_rendered: u64,
+    scanlines_rendered: u64,
+    timing_spans: TimingSpans,
     #[cfg(feature = "ppu_tile_cache")]
     tile_cache: TileCache,
     background_pixels_line: PixelLines,
     sprites_pixels_line: PixelLines,
+    background_override: Option<bool>,
+    sprites_override: Option<bool>,
+    scanline_hook: Option<ScanlineHook>,
+    warming_up: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -384,14 +463,18 @@ impl Display for Tile {
 #[cfg(feature = "ppu_tile_cache")]
 
 struct TileCache {
-    tiles: HashMap<u16, Rc<Tile>>
+    tiles: HashMap<u16, Rc<Tile>>,
+    hits: u64,
+    misses: u64,
 }
 
 #[cfg(feature = "ppu_tile_cache")]
 impl Default for TileCache {
     fn default() -> Self {
         TileCache {
-            tiles: HashMap::new()
+            tiles: HashMap::new(),
+            hits: 0,
+            misses: 0,
         }
     }
 }
@@ -403,6 +486,18 @@ impl TileCache {
         self.tiles.clear();
     }
 
+    /// Lookup of the renderer, counted as a hit or a miss.
+    fn lookup(&mut self, addr: u16) -> Option<Rc<Tile>> {
+        let tile = self.get_cached_tile(addr);
+
+        match tile {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+
+        tile
+    }
+
     fn get_cached_tile(&self, addr: u16) -> Option<Rc<Tile>> {
         if let Some(tile) = self.tiles.get(&addr) {
             Some(tile.clone())
@@ -419,21 +514,71 @@ impl TileCache {
 
 struct OAM {
     primary: [Sprite; 64],
-    secondary: [Sprite; 8],
-    sprite_count: usize
+    secondary: [Sprite; 64],
+    sprite_count: usize,
+    sprite_limit_disabled: bool,
+    decay_frames: Option<u32>,
+    frames_since_refresh: [u32; OAM_SIZE]
 }
 
 impl Default for OAM {
     fn default() -> Self {
         OAM {
             primary: [Sprite::default(); 64],
-            secondary: [Sprite::default(); 8],
-            sprite_count: 0
+            secondary: [Sprite::default(); 64],
+            sprite_count: 0,
+            sprite_limit_disabled: false,
+            decay_frames: None,
+            frames_since_refresh: [0; OAM_SIZE]
         }
     }
 }
 
 impl OAM {
+    fn write_byte(&mut self, addr: u8, value: u8) {
+        self.set_byte(addr, value);
+        self.frames_since_refresh[addr as usize] = 0;
+    }
+
+    fn set_byte(&mut self, addr: u8, value: u8) {
+        let sprite = &mut self.primary[(addr / 4) as usize];
+
+        match addr % 4 {
+            0 => sprite.y = value,
+            1 => sprite.tile_index = value,
+            2 => sprite.attributes = value & !OAM_ATTRIBUTES_UNUSED_BITS,
+            3 => sprite.x = value,
+            _ => unreachable!(),
+        }
+    }
+
+    /***
+     * OAM is a dynamic memory, refreshed by the sprite evaluation while rendering:
+     * when rendering is disabled, the bytes not written for decay_frames frames lose their value.
+     * The decayed bytes are set to $FF, which moves the sprites off screen.
+     * https://www.nesdev.org/wiki/PPU_OAM#Dynamic_RAM_decay
+     ***/
+    fn end_frame(&mut self, is_rendering: bool) {
+        let decay_frames = match self.decay_frames {
+            Some(decay_frames) => decay_frames,
+            None => return
+        };
+
+        if is_rendering {
+            self.frames_since_refresh = [0; OAM_SIZE];
+            return;
+        }
+
+        for addr in 0..=u8::MAX {
+            let frames = &mut self.frames_since_refresh[addr as usize];
+            *frames = (*frames + 1).min(decay_frames);
+
+            if *frames == decay_frames {
+                self.set_byte(addr, OAM_DECAYED_VALUE);
+            }
+        }
+    }
+
     fn clear_secondary(&mut self) {
         self.secondary
             .iter_mut()
@@ -455,9 +600,25 @@ impl PPU for Ppu2c02 {
 
         self.latch.borrow_mut().reset();
         *self.v.borrow_mut() = 0;
+        self.pending_v = None;
+        self.warming_up = true;
 
         self.set_flag(Status(VBlank), true);
 
+        // the frame goes on from the current scanline, the CPU cycles are counted again from the reset
+        self.cpu_cycles_rendered = 0;
+        self.sync_clock();
+
+        Ok(())
+    }
+
+    fn cold_reset(&mut self, oam_init: RamInit) -> Result<(), PpuError> {
+        self.reset()?;
+
+        for addr in 0..=u8::MAX {
+            self.oam.write_byte(addr, oam_init.value(addr as usize));
+        }
+
         Ok(())
     }
 
@@ -482,11 +643,8 @@ impl PPU for Ppu2c02 {
             }
         }
 
-        let frame = if self.renderer.borrow().frame().state() == FrameState::Completed {
-            Some(self.frame())
-        } else {
-            None
-        };
+        self.sync_clock();
+        let frame = self.renderer.borrow_mut().take_completed();
 
         Ok((cycles, frame))
     }
@@ -494,6 +652,145 @@ impl PPU for Ppu2c02 {
     fn frame(&self) -> NesFrame {
         self.renderer.borrow().frame().clone()
     }
+
+    fn scanlines_per_frame(&self) -> u16 {
+        self.region.scanlines_per_frame()
+    }
+
+    fn current_scanline(&self) -> u16 {
+        self.clock.borrow().scanline()
+    }
+
+    fn current_dot(&self) -> u16 {
+        self.clock.borrow().dot()
+    }
+
+    fn clock(&self) -> Rc<RefCell<PpuClock>> {
+        self.clock.clone()
+    }
+
+    fn snapshot(&self) -> PpuSnapshot {
+        let register = self.register.borrow();
+
+        PpuSnapshot {
+            control: register.control,
+            mask: register.mask,
+            status: register.status,
+            oam_addr: register.oam_addr,
+            v: *self.v.borrow(),
+            t: self.t,
+            fine_x: self.fine_x,
+            scanline: self.current_scanline(),
+            dot: self.current_dot(),
+        }
+    }
+
+    /***
+     * debugging of the layers: ```Some(false)``` hides the background or the sprites whatever the mask register,
+     * ```None``` leaves them to the mask register. Only the drawing is affected: the layers are still rendered
+     * as the game asks (v updates, sprite evaluation, sprite 0 hit), so a layer hidden by the mask register
+     * cannot be forced visible and ```Some(true)``` is the same as ```None```.
+     ***/
+    fn set_layer_override(&mut self, background: Option<bool>, sprites: Option<bool>) {
+        self.background_override = background;
+        self.sprites_override = sprites;
+    }
+
+    fn perf_counters(&self) -> PerfCounters {
+        #[cfg(feature = "ppu_tile_cache")]
+        let (tile_cache_hits, tile_cache_misses) = (self.tile_cache.hits, self.tile_cache.misses);
+        #[cfg(not(feature = "ppu_tile_cache"))]
+        let (tile_cache_hits, tile_cache_misses) = (0, 0);
+
+        PerfCounters {
+            scanlines: self.scanlines_rendered,
+            tile_cache_hits,
+            tile_cache_misses,
+            ..PerfCounters::default()
+        }
+    }
+
+    fn timing_spans(&self) -> TimingSpans {
+        self.timing_spans
+    }
+
+    /***
+     * the color 0 of the tiles is rendered with the universal background color, opaque,
+     * so that the viewer shows the tiles as they would appear on screen.
+     ***/
+    fn dump_pattern_tables(&self, palette_index: u8) -> Result<PpuMemoryDump, PpuError> {
+        let palette_index = palette_index % PALETTES_COUNT;
+        let mut palette = [0u8; PALETTE_SIZE];
+
+        for (i, color) in palette.iter_mut().enumerate() {
+            *color = self.bus.read_byte(PALETTE_ADDRESS_SPACE.0 + i as u16)?;
+        }
+
+        let colors = if palette_index < PALETTES_COUNT / 2 {
+            self.get_background_palette_colors(palette_index)?
+        } else {
+            self.get_sprite_palette_colors(palette_index - PALETTES_COUNT / 2)?
+        };
+
+        let mut tiles = Vec::with_capacity(PATTERN_TILES_PER_TABLE * PATTERN_TABLES_COUNT);
+
+        for pattern_table_addr in [PATTERN_TABLE_LEFT_ADDR, PATTERN_TABLE_RIGHT_ADDR] {
+            for tile_index in 0..PATTERN_TILES_PER_TABLE {
+                let pattern_data = self.fetch_pattern_data(tile_index as u8, pattern_table_addr, false)?;
+                let mut pixels = Vec::with_capacity(pattern_data.len() * 4);
+
+                for color in pattern_data {
+                    let (r, g, b, a) = match color {
+                        0 => Palette2C02::rgba_opaque(colors.0),
+                        1 => Palette2C02::rgba_opaque(colors.1),
+                        2 => Palette2C02::rgba_opaque(colors.2),
+                        3 => Palette2C02::rgba_opaque(colors.3),
+                        _ => unreachable!("unknown color: {}", color)
+                    };
+
+                    pixels.extend_from_slice(&[r, g, b, a]);
+                }
+
+                tiles.push(PatternTile::new(pixels));
+            }
+        }
+
+        Ok(PpuMemoryDump::new(palette, palette_index, tiles))
+    }
+
+    /***
+     * the 32x30 tiles of the logical nametable ```index``` (0-3, through the mirroring of the cartridge), drawn with
+     * the background pattern table and palettes currently selected, ignoring the scroll.
+     * The tiles are fetched without the tile cache: v, t and the rendering state are left as they are.
+     ***/
+    fn render_nametable(&self, index: u8) -> Result<NesFrame, PpuError> {
+        let name_table_addr = self.get_name_table_addr(index % 4);
+        let attribute_table_addr = self.get_attribute_table_addr(name_table_addr);
+        let pattern_table_addr = self.get_background_pattern_table_addr();
+        let mut frame = NesFrame::new(NAME_TABLE_COLUMNS as usize * 8, NAME_TABLE_ROWS as usize * 8);
+
+        for coarse_y in 0..NAME_TABLE_ROWS {
+            for coarse_x in 0..NAME_TABLE_COLUMNS {
+                let tile = self.fetch_tile(coarse_x, coarse_y, name_table_addr, pattern_table_addr, attribute_table_addr)?;
+
+                for (offset, color) in tile.pattern_table.iter().enumerate() {
+                    let palette_color = match color {
+                        0 => tile.colors.0,
+                        1 => tile.colors.1,
+                        2 => tile.colors.2,
+                        3 => tile.colors.3,
+                        _ => unreachable!("unknown color: {}", color)
+                    };
+
+                    let x = coarse_x * 8 + (offset % 8) as u8;
+                    let y = coarse_y * 8 + (offset / 8) as u8;
+                    frame.set_pixel(x, y, Palette2C02::rgb_emphasized(palette_color, 0));
+                }
+            }
+        }
+
+        Ok(frame)
+    }
 }
 
 impl Memory for Ppu2c02 {
@@ -504,21 +801,27 @@ impl Memory for Ppu2c02 {
         Ok(PPU_EXTERNAL_MEMORY_SIZE)
     }
 
+    /***
+     * $2000, $2001, $2003, $2005 and $2006 are write-only, reading them returns the PPU open bus:
+     * the io latch, holding the last value written to any register or read from a readable one.
+     * the low 5 bits of $2002 are unused and come from the io latch too. the latch decay is not emulated.
+     * https://www.nesdev.org/wiki/Open_bus_behavior#PPU_open_bus
+     ***/
     fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
         ////trace!("PPU: registers access: reading byte at 0x{:04X} (0x{:04X})", addr, addr + 0x2000);
 
         let value = match addr {
-            0x00 => self.read_control_register(),
-            0x01 => self.read_mask_register(),
+            0x00 | 0x01 | 0x03 | 0x05 | 0x06 => return Ok(*self.io_latch.borrow()),
             0x02 => self.read_status_register(),
-            0x03 => self.read_oam_address_register(),
-            0x04 => self.read_oam_data_register(self.register.borrow().oam_addr),
-            0x05 => self.read_scroll_register(),
-            0x06 => self.read_addr_register(),
+            0x04 => {
+                let oam_addr = self.register.borrow().oam_addr;
+                self.read_oam_data_register(oam_addr)
+            },
             0x07 => self.read_data_register()?,
             _ => unreachable!(),
         };
 
+        *self.io_latch.borrow_mut() = value;
         Ok(value)
     }
 
@@ -529,7 +832,10 @@ impl Memory for Ppu2c02 {
             0x01 => self.register.borrow().mask,
             0x02 => self.register.borrow().status,
             0x03 => self.register.borrow().oam_addr,
-            0x04 => self.read_oam_data_register(self.register.borrow().oam_addr),
+            0x04 => {
+                let oam_addr = self.register.borrow().oam_addr;
+                self.read_oam_data_register(oam_addr)
+            },
             0x05 => self.register.borrow().scroll,
             0x06 => {
                 if self.latch.borrow().state == LatchState::HIGH {
@@ -548,6 +854,13 @@ impl Memory for Ppu2c02 {
     fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
         //trace!("PPU: registers access: writing byte (0x{:02X}) at 0x{:04X}", value, addr);
 
+        *self.io_latch.borrow_mut() = value;
+
+        if matches!(addr, 0x00 | 0x01 | 0x05 | 0x06) && self.is_warming_up() {
+            debug!("PPU: ignoring write (0x{:02X}) to 0x{:04X} during the warm-up", value, addr + 0x2000);
+            return Ok(());
+        }
+
         match addr {
             0x00 => self.write_control_register(value),
             0x01 => self.write_mask_register(value),
@@ -601,6 +914,42 @@ impl BusDevice for Ppu2c02 {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         PPU_EXTERNAL_ADDRESS_SPACE
     }
+
+    /***
+     * The registers, v, t, x and the write latch, the primary OAM, then the state of each device
+     * of the PPU bus (palettes, CHR memory, nametables). The position of the dot clock is not saved:
+     * the states are taken and restored between frames.
+     ***/
+    fn serialize_state(&self) -> Vec<u8> {
+        let register = self.register.borrow();
+        let mut state = vec![register.control, register.mask, register.status, register.oam_addr, register.scroll, register.data];
+
+        state.extend_from_slice(&self.v.borrow().to_le_bytes());
+        state.extend_from_slice(&self.t.to_le_bytes());
+        state.extend_from_slice(&[self.x, self.fine_x, (self.latch.borrow().state == LatchState::LOW) as u8, *self.io_latch.borrow()]);
+
+        for sprite in &self.oam.primary {
+            state.extend_from_slice(&[sprite.y, sprite.tile_index, sprite.attributes, sprite.x]);
+        }
+
+        let devices = self.bus.serialize_devices();
+        state.extend_from_slice(&(devices.len() as u16).to_le_bytes());
+
+        for (_, device_state) in &devices {
+            push_chunk(&mut state, device_state);
+        }
+
+        state
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        self.restore_state(&mut StateReader::new(state))?;
+
+        #[cfg(feature = "ppu_tile_cache")]
+        self.tile_cache.clear();
+
+        Ok(())
+    }
 }
 
 impl DmaDevice for Ppu2c02 {
@@ -613,22 +962,49 @@ impl DmaDevice for Ppu2c02 {
 
 impl Ppu2c02 {
 
+    /***
+     * The shared dot clock is advanced by the CPU after every instruction, its position is realigned
+     * on the PPU after a run or a reset: in the scanline rendered next, the one the CPU is running,
+     * by the CPU cycles not rendered yet. The clock cannot drift from the scanlines rendered.
+     ***/
+    fn sync_clock(&self) {
+        let scanline = match self.state {
+            PpuState::Rendering(scanline) | PpuState::VBlank(scanline) => scanline,
+        };
+
+        let mut clock = self.clock.borrow_mut();
+        let cpu_cycles_ahead = clock.cpu_cycles().saturating_sub(self.cpu_cycles_rendered);
+        clock.sync(scanline, cpu_cycles_ahead);
+    }
+
     fn v_wrapping_add(&self, n: u16) -> u16 {
         self.v.borrow().wrapping_add(n) % (PPU_INTERNAL_ADDRESS_SPACE.1 + 1)
     }
 
-    fn read_control_register(&self) -> u8 {
-        self.register.borrow().control
+    /***
+     * after power-up or reset, writes to $2000, $2001, $2005 and $2006 are ignored
+     * for about 29658 CPU cycles, the CPU cycles are counted by the shared dot clock.
+     * https://www.nesdev.org/wiki/PPU_power_up_state
+     ***/
+    fn is_warming_up(&mut self) -> bool {
+        if self.warming_up && self.clock.borrow().cpu_cycles() >= WARM_UP_CPU_CYCLES {
+            self.warming_up = false;
+        }
+
+        self.warming_up
     }
 
     fn write_control_register(&mut self, value: u8) {
         //trace!("PPU: writing to control register: 0x{:02X}", value);
 
+        // the NMI is generated on the rising edge of the enable bit, the previous value is read before the write
+        let generate_nmi = self.get_flag(Control(GenerateNmi));
+
         self.register.borrow_mut().control = value;
         self.t = (self.t & 0xF3FF) | (((value & 0x03) as u16) << 10);
 
         if let PpuState::VBlank(_) = self.state {
-            if value & 0x80 != 0 && self.get_flag(Status(VBlank)) && self.get_flag(Control(GenerateNmi)) == false {
+            if value & 0x80 != 0 && self.get_flag(Status(VBlank)) && generate_nmi == false {
                 //trace!("PPU: forcing NMI as status changed: 0x{:02X}", value);
                 let cpu = self.cpu.as_ptr();
                 let _ = unsafe { &mut *cpu }.signal_nmi();
@@ -636,17 +1012,36 @@ impl Ppu2c02 {
         }
     }
 
-    fn read_mask_register(&self) -> u8 {
-        self.register.borrow().mask
-    }
-
     fn write_mask_register(&mut self, value: u8) {
         //trace!("PPU: writing to mask register: 0x{:02X}", value);
         self.register.borrow_mut().mask = value;
     }
 
+    /***
+     * reading $2002 around the dot where vblank is set races with the flag:
+     * - one dot before, the flag reads clear and is never set for this frame, the NMI is not generated,
+     * - on the same dot or one later, the flag reads set, is cleared, and the NMI is suppressed.
+     * the position is the one of the PPU (see sync_clock), sampled at the start of the reading instruction.
+     * https://www.nesdev.org/wiki/PPU_frame_timing#VBL_Flag_Timing
+     ***/
     fn read_status_register(&self) -> u8 {
-        let result = self.register.borrow().status;
+        let mut result = (self.register.borrow().status & !STATUS_OPEN_BUS_BITS) | (*self.io_latch.borrow() & STATUS_OPEN_BUS_BITS);
+
+        if self.current_scanline() == VBLANK_SET_SCANLINE {
+            match self.current_dot() {
+                dot if dot == VBLANK_SET_DOT - 1 => {
+                    *self.vblank_suppressed.borrow_mut() = true;
+                },
+
+                dot if dot == VBLANK_SET_DOT || dot == VBLANK_SET_DOT + 1 => {
+                    result |= VBlank as u8;
+                    *self.vblank_suppressed.borrow_mut() = true;
+                },
+
+                _ => {}
+            }
+        }
+
         self.set_flag(Status(VBlank), false);
         self.latch.borrow_mut().reset();
 
@@ -658,23 +1053,32 @@ impl Ppu2c02 {
         self.register.borrow_mut().status = value;
     }
 
-    fn read_oam_address_register(&self) -> u8 {
-        self.register.borrow().oam_addr
-    }
-
     fn write_oam_address_register(&mut self, value: u8) {
         //trace!("PPU: writing to oam address register: 0x{:02X}", value);
         self.register.borrow_mut().oam_addr = value;
     }
 
+    /***
+     * OAM data read
+     * - bits 2-4 of the attribute byte do not exist and read back as 0,
+     * - on the visible scanlines with rendering enabled, the secondary OAM is cleared during dots 1 - 64
+     *   and the reads return $FF, the position is the one of the PPU (see sync_clock).
+     * https://www.nesdev.org/wiki/PPU_registers#OAMDATA
+     ***/
     fn read_oam_data_register(&self, addr: u8) -> u8 {
+        let is_rendering = self.get_flag(Mask(ShowBackground)) || self.get_flag(Mask(ShowSprites));
+
+        if is_rendering && self.current_scanline() <= 239 && (SECONDARY_OAM_CLEAR_DOTS.0..=SECONDARY_OAM_CLEAR_DOTS.1).contains(&self.current_dot()) {
+            return 0xFF;
+        }
+
         let sprite_index = (addr / 4) as usize;
         let offset = addr % 4;
 
         match offset {
             0 => self.oam.primary[sprite_index].y,
             1 => self.oam.primary[sprite_index].tile_index,
-            2 => self.oam.primary[sprite_index].attributes,
+            2 => self.oam.primary[sprite_index].attributes & !OAM_ATTRIBUTES_UNUSED_BITS,
             3 => self.oam.primary[sprite_index].x,
             _ => unreachable!(),
         }
@@ -691,31 +1095,21 @@ impl Ppu2c02 {
             //trace!("PPU: ignoring write to OAM address 0x{:02X} as PPU is in state {}", addr, self.state);
             self.register.borrow_mut().oam_addr = addr.wrapping_add(4);
         } else {
-            let sprite_index = (addr / 4) as usize;
-            let offset = addr % 4;
-
-            match offset {
-                0 => self.oam.primary[sprite_index].y = value,
-                1 => self.oam.primary[sprite_index].tile_index = value,
-                2 => self.oam.primary[sprite_index].attributes = value & !0x1C,
-                3 => self.oam.primary[sprite_index].x = value,
-                _ => unreachable!(),
-            }
-
+            self.oam.write_byte(addr, value);
             self.register.borrow_mut().oam_addr = addr.wrapping_add(1);
         }
     }
 
-    fn read_scroll_register(&self) -> u8 {
-        self.register.borrow().scroll
-    }
-
     fn write_scroll_register(&mut self, value: u8) {
         //trace!("PPU: writing to scroll register: 0x{:02X}", value);
 
         if self.latch.borrow().state == LatchState::HIGH {
             self.t = (self.t & !0x001F) | ((value as u16) >> 3);
             self.x = value & 0x07;
+
+            if !self.is_rendering_scanline() {
+                self.fine_x = self.x;
+            }
         } else {
             let a = ((value & 0x07) as u16) << 12;
             let b = ((value >> 3) as u16) << 5;
@@ -726,15 +1120,6 @@ impl Ppu2c02 {
         self.register.borrow_mut().scroll = value;
     }
 
-    fn read_addr_register(&self) -> u8 {
-        let v = *self.v.borrow();
-
-        match self.latch.borrow().state {
-            LatchState::LOW => v as u8,
-            LatchState::HIGH => (v >> 8) as u8,
-        }
-    }
-
     fn write_addr_register(&mut self, value: u8) {
         //trace!("PPU: writing to PPU addr register: 0x{:02X}", value);
 
@@ -742,12 +1127,35 @@ impl Ppu2c02 {
             self.t = (self.t & 0x00FF) | ((value as u16 & 0x3F) << 8);
         } else {
             self.t = (self.t & 0x7F00) | (value as u16);
-            *self.v.borrow_mut() = self.t;
+
+            if self.is_rendering_scanline() {
+                self.pending_v = Some(self.v_for_next_scanline(self.t));
+            } else {
+                *self.v.borrow_mut() = self.t;
+            }
         }
 
         self.latch.borrow_mut().latch();
     }
 
+    /***
+     * v after a $2007 access. While a visible scanline is rendered, v is the rendering address: the access
+     * increments it the way the rendering does, a coarse X increment together with a Y increment,
+     * instead of adding 1 or 32.
+     * https://www.nesdev.org/wiki/PPU_scrolling#$2007_reads_and_writes
+     ***/
+    fn v_after_data_access(&self) -> u16 {
+        if !self.is_rendering_scanline() {
+            return self.v_wrapping_add(self.get_v_increment_value() as u16);
+        }
+
+        let v = *self.v.borrow();
+        let (name_table, coarse_x) = self.coarse_x_increment(v & 0x0C00, (v & 0x001F) as u8);
+        let (name_table, fine_y, coarse_y) = self.fine_and_coarse_y_increment(name_table, ((v & 0x7000) >> 12) as u8, ((v & 0x03E0) >> 5) as u8);
+
+        ((fine_y as u16) << 12) | (name_table & 0x0C00) | ((coarse_y as u16) << 5) | coarse_x as u16
+    }
+
     /***
      * https://www.nesdev.org/wiki/PPU_registers#PPUDATA
      * https://forums.nesdev.org/viewtopic.php?t=9353
@@ -755,8 +1163,7 @@ impl Ppu2c02 {
      */
     fn read_data_register(&self) -> Result<u8, MemoryError> {
         let video_addr = *self.v.borrow();
-        let incr = self.get_v_increment_value() as u16;
-        *self.v.borrow_mut() = self.v_wrapping_add(incr);
+        *self.v.borrow_mut() = self.v_after_data_access();
 
         let data = if video_addr >= PALETTE_ADDRESS_SPACE.0 {
             self.bus.read_byte(video_addr)?
@@ -770,16 +1177,56 @@ impl Ppu2c02 {
     }
 
     fn write_data_register(&mut self, value: u8) -> Result<(), MemoryError> {
-        let incr = self.get_v_increment_value() as u16;
-        let incremented_v = self.v_wrapping_add(incr);
+        let incremented_v = self.v_after_data_access();
 
         //trace!("PPU: writing to PPU data register: 0x{:02X} (v is: 0x{:04X})", value, *self.v.borrow());
         self.bus.write_byte(*self.v.borrow(), value)?;
 
+        // the cached tiles may come from the CHR RAM, the nametables or the palettes just written
+        #[cfg(feature = "ppu_tile_cache")]
+        self.tile_cache.clear();
+
         *self.v.borrow_mut() = incremented_v;
         Ok(())
     }
 
+    fn restore_state(&mut self, reader: &mut StateReader) -> Result<(), MemoryError> {
+        {
+            let mut register = self.register.borrow_mut();
+
+            register.control = reader.byte()?;
+            register.mask = reader.byte()?;
+            register.status = reader.byte()?;
+            register.oam_addr = reader.byte()?;
+            register.scroll = reader.byte()?;
+            register.data = reader.byte()?;
+        }
+
+        *self.v.borrow_mut() = reader.word()?;
+        self.t = reader.word()?;
+        self.x = reader.byte()?;
+        self.fine_x = reader.byte()?;
+        self.latch.borrow_mut().state = if reader.byte()? != 0 { LatchState::LOW } else { LatchState::HIGH };
+        *self.io_latch.borrow_mut() = reader.byte()?;
+        self.pending_v = None;
+
+        for addr in 0..OAM_SIZE {
+            self.oam.write_byte(addr as u8, reader.byte()?);
+        }
+
+        let mut devices = self.bus.serialize_devices();
+
+        if reader.word()? as usize != devices.len() {
+            return Err(MemoryError::IllegalState("PPU state of another PPU bus".to_string()));
+        }
+
+        for (_, device_state) in devices.iter_mut() {
+            *device_state = reader.chunk()?.to_vec();
+        }
+
+        self.bus.deserialize_devices(&devices)
+    }
+
     fn create_mirrored_name_tables_and_connect_to_bus(bus: &mut Box<dyn Bus>, mirroring: Rc<RefCell<PpuNameTableMirroring>>) -> Result<(), PpuError> {
         let ciram_memory = CiramMemory::new(mirroring);
         bus.add_device(Rc::new(RefCell::new(ciram_memory)))?;
@@ -787,7 +1234,7 @@ impl Ppu2c02 {
         Ok(())
     }
 
-    pub fn new(chr_rom: Rc<RefCell<dyn BusDevice>>, mirroring: Rc<RefCell<PpuNameTableMirroring>>, cpu: Rc<RefCell<dyn CPU>>) -> Result<Self, PpuError> {
+    pub fn new(chr_rom: Rc<RefCell<dyn BusDevice>>, mirroring: Rc<RefCell<PpuNameTableMirroring>>, cpu: Rc<RefCell<dyn CPU>>, region: Region) -> Result<Self, PpuError> {
         let mut bus: Box<dyn Bus> = Box::new(NESBus::new());
 
         let palette_table = Rc::new(RefCell::new(
@@ -806,20 +1253,74 @@ impl Ppu2c02 {
             v: RefCell::new(0),
             t: 0,
             x: 0,
+            fine_x: 0,
+            pending_v: None,
             oam: OAM::default(),
             latch: RefCell::new(Latch::new()),
+            io_latch: RefCell::new(0),
             renderer: RefCell::new(Renderer::new()),
             cpu,
-            state: PpuState::VBlank(261),
+            state: PpuState::VBlank(region.pre_render_scanline()),
+            vblank_suppressed: RefCell::new(false),
+            region,
+            clock: Rc::new(RefCell::new(PpuClock::new(region))),
+            cpu_cycles_rendered: 0,
+            scanlines_rendered: 0,
+            timing_spans: TimingSpans::default(),
             #[cfg(feature = "ppu_tile_cache")]
             tile_cache: TileCache::default(),
             background_pixels_line: PixelLines::default(),
-            sprites_pixels_line: PixelLines::default()
+            sprites_pixels_line: PixelLines::default(),
+            background_override: None,
+            sprites_override: None,
+            scanline_hook: None,
+            warming_up: false,
         };
 
         Ok(ppu)
     }
 
+    /***
+     * the hook is called at the start of each visible scanline (0 - 239) with a copy of the PPU state:
+     * the PPU is being run, the hook must not borrow it (through the console or its RefCell).
+     ***/
+    pub fn set_scanline_hook(&mut self, hook: ScanlineHook) {
+        self.scanline_hook = Some(hook);
+    }
+
+    /// OAM decay emulation, off by default: the OAM bytes not refreshed for the given number of frames decay.
+    pub fn set_oam_decay(&mut self, decay_frames: Option<u32>) {
+        self.oam.decay_frames = decay_frames;
+        self.oam.frames_since_refresh = [0; OAM_SIZE];
+    }
+
+    /***
+     * no flicker mode, off by default: all the sprites of a scanline are rendered, not only the first 8.
+     * The sprite overflow flag is still set by the 9th sprite and the sprite 0 is always the first one evaluated,
+     * the game logic sees the 8 sprites limit of the hardware.
+     ***/
+    pub fn set_sprite_limit_disabled(&mut self, disabled: bool) {
+        self.oam.sprite_limit_disabled = disabled;
+    }
+
+    pub fn clear_scanline_hook(&mut self) {
+        self.scanline_hook = None;
+    }
+
+    fn notify_scanline_hook(&mut self, scanline: u16) {
+        if let Some(hook) = self.scanline_hook.as_mut() {
+            let event = ScanlineEvent {
+                scanline,
+                v: *self.v.borrow(),
+                fine_x: self.fine_x,
+                control: self.register.borrow().control,
+                mask: self.register.borrow().mask,
+            };
+
+            hook(event);
+        }
+    }
+
     #[cfg(test)]
     pub fn get_register_value(&self, name: &str) -> u8 {
         match name {
@@ -839,6 +1340,11 @@ impl Ppu2c02 {
         *self.v.borrow()
     }
 
+    #[cfg(test)]
+    pub(crate) fn get_sprites_pixels_line(&self) -> &PixelLines {
+        &self.sprites_pixels_line
+    }
+
     #[cfg(test)]
     pub fn ext_set_flag(&mut self, flag: PpuFlag, value: bool) {
         self.set_flag(flag, value);
@@ -908,23 +1414,22 @@ impl Ppu2c02 {
         *data_plane1 = data_plane1.reverse_bits();
     }
 
-    fn merge_bit_planes(&self, data_plane0: &mut u8, data_plane1: &mut u8) -> Vec<u8> {
-        let mut line_pattern_data = vec![0u8; 8];
+    fn merge_bit_planes(&self, data_plane0: u8, data_plane1: u8) -> [u8; 8] {
+        let mut line_pattern_data = [0u8; 8];
 
-        for bit in 0..=7 {
-            let value0 = (*data_plane0 >> (7 - bit)) & 0x01;
-            let value1 = (*data_plane1 >> (7 - bit)) & 0x01;
-            let combined = (value1 << 1) | value0;
-            line_pattern_data[bit] = combined;
+        for (bit, pixel) in line_pattern_data.iter_mut().enumerate() {
+            let value0 = (data_plane0 >> (7 - bit)) & 0x01;
+            let value1 = (data_plane1 >> (7 - bit)) & 0x01;
+            *pixel = (value1 << 1) | value0;
         }
 
         line_pattern_data
     }
 
-    fn fetch_pattern_data(&self, tile_index: u8, pattern_table_addr: u16, flip_horizontal: bool) -> Result<Vec<u8>, PpuError> {
-        let mut pattern_data= vec![];
+    fn fetch_pattern_data(&self, tile_index: u8, pattern_table_addr: u16, flip_horizontal: bool) -> Result<[u8; MERGED_PATTERN_DATA_SIZE], PpuError> {
+        let mut pattern_data = [0u8; MERGED_PATTERN_DATA_SIZE];
 
-        for line in 0..=7 {
+        for (line, line_pattern_data) in pattern_data.chunks_exact_mut(8).enumerate() {
             let mut pattern_data0 = self.bus.read_byte(pattern_table_addr + (tile_index as u16 * PATTERN_DATA_SIZE as u16) + line as u16)?;
             let mut pattern_data1 = self.bus.read_byte(pattern_table_addr + (tile_index as u16 * PATTERN_DATA_SIZE as u16) + line as u16 + (PATTERN_DATA_SIZE as u16 / 2))?;
 
@@ -932,19 +1437,18 @@ impl Ppu2c02 {
                 self.flip_horizontal(&mut pattern_data0, &mut pattern_data1);
             }
 
-            let mut line_pattern_data = self.merge_bit_planes(&mut pattern_data0, &mut pattern_data1);
-            pattern_data.append(&mut line_pattern_data);
+            line_pattern_data.copy_from_slice(&self.merge_bit_planes(pattern_data0, pattern_data1));
         }
 
         //trace!("PPU: pattern_data: {:?}", pattern_data);
         Ok(pattern_data)
     }
 
-    fn fetch_line_pattern_data(&self, tile: &Tile, line: u8, offset_x: u8, size: usize) -> Vec<u8> {
+    fn fetch_line_pattern_data<'a>(&self, tile: &'a Tile, line: u8, offset_x: u8, size: usize) -> &'a [u8] {
         let a = (line * 8) as usize + offset_x as usize;
         let b = a + size;
 
-        tile.pattern_table[a..b].to_vec()
+        &tile.pattern_table[a..b]
     }
 
     fn fetch_tile_index(&self, tile_x: u8, tile_y: u8, base_name_table_addr: u16) -> Result<u8, PpuError> {
@@ -998,11 +1502,16 @@ impl Ppu2c02 {
         Ok(colors)
     }
 
-    fn detect_sprite_0_hit_and_set_status_flag(&self, pixel_pos_x: u8) {
+    /***
+     * the opacity of the sprite 0 pixel itself is tested, not the one of the sprites line where
+     * another sprite may already have drawn an opaque pixel. the hit never happens at x = 255.
+     * https://www.nesdev.org/wiki/PPU_OAM#Sprite_zero_hits
+     ***/
+    fn detect_sprite_0_hit_and_set_status_flag(&self, pixel_pos_x: u8, sprite_pixel: &Pixel) {
         let background_transparency = self.background_pixels_line.is_transparent(pixel_pos_x);
-        let sprite_transparency = self.sprites_pixels_line.is_transparent(pixel_pos_x);
+        let sprite_transparency = Palette2C02::is_transparent(sprite_pixel.a);
 
-        if sprite_transparency == false && background_transparency == false {
+        if sprite_transparency == false && background_transparency == false && pixel_pos_x != PIXEL_X_MAX {
             self.set_flag(Status(Sprite0Hit), true);
         }
     }
@@ -1010,17 +1519,19 @@ impl Ppu2c02 {
     fn set_pixel(&mut self, pixel_pos_x: u8, _: u8, line_pattern_data: &[u8],
                  palette: (u8, u8, u8, u8), mode: PixelMode, priority: SpritePriority, sprite0_hit_detect: bool) {
 
+        let emphasis = self.register.borrow().mask >> 5;
+
         line_pattern_data.iter().enumerate().for_each(|(pixel_num, color)| {
             //trace!("PPU: x: {}, y: {}, color: {}, mode: {:?}, palette: {:?}", pixel_pos_x, pixel_pos_y, color, mode, palette);
 
-            let (r, g, b, a) = match (color, mode) {
-                (0, PixelMode::Background) => Palette2C02::rgba_transparent(palette.0),
-                (0, PixelMode::Sprite) => Palette2C02::rgba_transparent(palette.0),
-                (1, _) => Palette2C02::rgba_opaque(palette.1),
-                (2, _) => Palette2C02::rgba_opaque(palette.2),
-                (3, _) => Palette2C02::rgba_opaque(palette.3),
+            let (palette_color, a) = match color {
+                0 => (palette.0, Palette2C02::transparent_alpha()),
+                1 => (palette.1, Palette2C02::opaque_alpha()),
+                2 => (palette.2, Palette2C02::opaque_alpha()),
+                3 => (palette.3, Palette2C02::opaque_alpha()),
                 _ => unreachable!("unknown color: {}", color)
             };
+            let (r, g, b) = Palette2C02::rgb_emphasized(palette_color, emphasis);
 
             let pixel_pos_x_plus_pixel = pixel_pos_x + pixel_num as u8;
             let pixel = Pixel::new(r, g, b, a, priority);
@@ -1036,7 +1547,7 @@ impl Ppu2c02 {
                     }
 
                     if sprite0_hit_detect {
-                        self.detect_sprite_0_hit_and_set_status_flag(pixel_pos_x_plus_pixel);
+                        self.detect_sprite_0_hit_and_set_status_flag(pixel_pos_x_plus_pixel, &pixel);
                     }
                 },
             }
@@ -1092,7 +1603,7 @@ impl Ppu2c02 {
         let colors = self.get_background_palette_colors(palette)?;
         let pattern_data = self.fetch_pattern_data(tile_index, pattern_table_addr, false)?;
 
-        let tile = Tile::new(tile_index, colors, vec_to_array::<64>(pattern_data));
+        let tile = Tile::new(tile_index, colors, pattern_data);
 
         //trace!("{}", tile);
         Ok(tile)
@@ -1102,7 +1613,7 @@ impl Ppu2c02 {
     fn get_tile(&mut self, coarse_x: u8, coarse_y: u8, name_table_addr: u16, pattern_table_addr: u16, attribute_table_addr: u16) -> Result<Rc<Tile>, PpuError> {
         let addr = name_table_addr | (coarse_y as u16) << 5  | coarse_x as u16;
 
-        let tile = if let Some(cached_tile) = self.tile_cache.get_cached_tile(addr) {
+        let tile = if let Some(cached_tile) = self.tile_cache.lookup(addr) {
             //trace!("cache hit: coarse_x: {}, coarse_y: {}, tile: 0x{:02X}", coarse_x, coarse_y, cached_tile.index);
             cached_tile
         } else {
@@ -1146,7 +1657,7 @@ impl Ppu2c02 {
     }
 
     fn get_fine_x(&self) -> u8 {
-        self.x
+        self.fine_x
     }
 
     /***
@@ -1198,6 +1709,56 @@ impl Ppu2c02 {
         *self.v.borrow_mut() = v;
     }
 
+    /// A visible scanline is being rendered: the CPU runs the scanline before the PPU renders it at once.
+    fn is_rendering_scanline(&self) -> bool {
+        let is_rendering = self.get_flag(Mask(ShowBackground)) || self.get_flag(Mask(ShowSprites));
+
+        matches!(self.state, PpuState::Rendering(scanline) if scanline <= 239) && is_rendering
+    }
+
+    /***
+     * Scanlines are rendered at once, after the CPU has run for the whole scanline:
+     * the fine X scroll ($2005) and the v address ($2006) written during a visible scanline
+     * are latched and applied at the end of the scanline, so that a split written during HBlank
+     * takes effect on the next scanline, instead of the whole current one.
+     *
+     * Accuracy limits:
+     *   - a write takes effect on the next scanline whatever its dot, mid-scanline splits are not rendered,
+     *     a $2006 write before dot 256 gets the Y increment of the scanline (see v_for_next_scanline),
+     *   - $2007 accesses between a $2006 write and the end of the scanline use the previous v address,
+     *   - the coarse X and Y increments of the dots 0 - 256 are done before the latched v is applied.
+     *
+     * https://www.nesdev.org/wiki/PPU_scrolling#Split_X/Y_scroll
+     ***/
+    fn apply_scroll_writes_latched_during_scanline(&mut self) {
+        self.fine_x = self.x;
+
+        if let Some(v) = self.pending_v.take() {
+            *self.v.borrow_mut() = v;
+        }
+    }
+
+    /***
+     * $2006 written during a visible scanline: on hardware v is reloaded from t at once, then
+     *   - before dot 256, the rest of the scanline is fetched from it, and the Y increment of dot 256
+     *     and the horizontal copy of dot 257 apply to it: the next scanline is one row below the written address,
+     *   - from dot 256, the increments are already done: the next scanline starts at the written address.
+     * The dot is the one of the PPU (see sync_clock), the fetches of the rest of the scanline are not rendered.
+     * https://www.nesdev.org/wiki/PPU_scrolling#$2006_(PPUADDR)_second_write_(w_is_1)
+     * https://www.nesdev.org/wiki/PPU_scrolling#At_dot_256_of_each_scanline
+     ***/
+    fn v_for_next_scanline(&self, v: u16) -> u16 {
+        if self.current_dot() >= Y_INCREMENT_DOT {
+            return v;
+        }
+
+        let fine_y = ((v & 0x7000) >> 12) as u8;
+        let coarse_y = ((v & 0x03E0) >> 5) as u8;
+        let (name_table, fine_y, coarse_y) = self.fine_and_coarse_y_increment(v & 0x0C00, fine_y, coarse_y);
+
+        ((fine_y as u16) << 12) | (name_table & 0x0C00) | ((coarse_y as u16) << 5) | (self.t & 0x001F)
+    }
+
     fn coarse_x_increment(&self, name_table_addr: u16, coarse_x: u8) -> (u16, u8) {
         if coarse_x == 31 {
             let addr = name_table_addr ^ 0x0400;
@@ -1260,7 +1821,7 @@ impl Ppu2c02 {
             let line_pattern_data = self.fetch_line_pattern_data(tile.as_ref(), fine_y, fine_x, size);
             let palette = tile.colors;
 
-            self.set_pixel(pixel_pos_x, pixel_pos_y as u8, &line_pattern_data, palette,
+            self.set_pixel(pixel_pos_x, pixel_pos_y as u8, line_pattern_data, palette,
                            PixelMode::Background, SpritePriority::None, false);
 
             if pixel_pos_x + (size as u8 - 1) == PIXEL_X_MAX {
@@ -1287,11 +1848,19 @@ impl Ppu2c02 {
         Ok(())
     }
 
-    fn is_scanline_in_sprite_range(&self, scanline: u16, sprite: &Sprite, size: u8) -> bool {
-        let sprite_y_min = sprite.y as u16;
-        let sprite_y_max = (sprite_y_min + size as u16).clamp(0, PIXEL_Y_MAX as u16);
+    /***
+     * the row of the sprite drawn on the scanline, if the sprite covers it. all sprites are displayed one scanline
+     * lower than their Y coordinate: Y = 0 starts on scanline 1, Y = 238 only shows its first row on scanline 239
+     * and Y >= 239 hides the sprite. the sprite is clipped at the bottom of the screen, it does not wrap to the top.
+     ***/
+    fn sprite_row(&self, scanline: u16, sprite: &Sprite, size: u8) -> Option<u8> {
+        let sprite_y_min = sprite.y as u16 + 1;
+
+        if scanline > PIXEL_Y_MAX as u16 || scanline < sprite_y_min || scanline - sprite_y_min >= size as u16 {
+            return None;
+        }
 
-        scanline >= sprite_y_min && scanline < sprite_y_max
+        Some((scanline - sprite_y_min) as u8)
     }
 
     fn get_flip_values(&self, sprite: &Sprite) -> (bool, bool) {
@@ -1329,11 +1898,16 @@ impl Ppu2c02 {
         };
 
         let pattern_data = self.fetch_pattern_data(tile_index, fixed_pattern_table_addr, flip_horizontal)?;
-        let tile = Tile::new(tile_index, colors, vec_to_array::<64>(pattern_data));
+        let tile = Tile::new(tile_index, colors, pattern_data);
 
         Ok((tile, tile_offset))
     }
 
+    /***
+     * the sprite overflow flag is set when a 9th sprite is in range of the scanline, in 8x16 mode the whole
+     * 16 rows are tested. the hardware bug of the evaluation (diagonal scan of OAM) is not emulated.
+     * https://www.nesdev.org/wiki/PPU_sprite_evaluation
+     ***/
     fn do_sprite_evaluation(&mut self, scanline: u16) -> Result<(), PpuError> {
         self.oam.clear_secondary();
         let sprite_size = if self.get_flag(Control(SpriteSize)) { 16u8 } else { 8u8 };
@@ -1341,7 +1915,16 @@ impl Ppu2c02 {
         for i in 0..self.oam.primary.len() {
             let sprite = &self.oam.primary[i];
 
-            if self.is_scanline_in_sprite_range(scanline, sprite, sprite_size) {
+            // the sprites are evaluated for the next scanline, the one they are rendered on
+            if self.sprite_row(scanline + 1, sprite, sprite_size).is_some() {
+                if self.oam.sprite_count == SPRITES_PER_SCANLINE {
+                    self.set_flag(Status(SpriteOverflow), true);
+
+                    if !self.oam.sprite_limit_disabled {
+                        break
+                    }
+                }
+
                 //trace!("sprite: {:?}", sprite);
                 self.oam.secondary[self.oam.sprite_count] = sprite.clone();
 
@@ -1350,10 +1933,6 @@ impl Ppu2c02 {
                 }
 
                 self.oam.sprite_count += 1;
-
-                if self.oam.sprite_count == self.oam.secondary.len() {
-                    break
-                }
             }
         }
 
@@ -1380,12 +1959,13 @@ impl Ppu2c02 {
     /***
      * [...] all sprites are displayed one pixel lower than their Y coordinate says [...]
      * https://www.reddit.com/r/EmuDev/comments/x1ol0k/nes_emulator_working_perfectly_except_one/
+     * the offset is applied by ```sprite_row```, for the evaluation as for the rendering.
      */
     fn render_sprites(&mut self, scanline: u16) -> Result<(), PpuError> {
         let is_sprite_8x16  = self.get_flag(Control(SpriteSize));
+        let sprite_size = if is_sprite_8x16 { 16u8 } else { 8u8 };
         //let sprite_pattern_table_addr = self.get_sprites_pattern_table_addr();
 
-        //let sprite_size = if self.get_flag(Control(SpriteSize)) { 16u8 } else { 8u8 };
         //trace!("rendering {} sprites for scanline: {}", self.oam.sprite_count, scanline);
 
         self.sprites_pixels_line.clear();
@@ -1394,8 +1974,11 @@ impl Ppu2c02 {
             let sprite = &self.oam.secondary[i];
             let sprite_pattern_table_addr = self.get_sprites_pattern_table_addr();
 
-            let pixel_pos_y = scanline as u8 - (sprite.y + 1);
-            let width = if PIXEL_X_MAX - sprite.x > SPRITE_WIDTH { SPRITE_WIDTH as usize } else { (PIXEL_X_MAX - sprite.x) as usize };
+            // a sprite evaluated with the other sprite size (the control register written in between) may be out of range
+            let Some(pixel_pos_y) = self.sprite_row(scanline, sprite, sprite_size) else {
+                continue;
+            };
+            let width = if PIXEL_X_MAX - sprite.x >= SPRITE_WIDTH { SPRITE_WIDTH as usize } else { (PIXEL_X_MAX - sprite.x) as usize + 1 };
 
             let (tile, tile_offset) = self.get_tile_by_sprite_definition(sprite, is_sprite_8x16, pixel_pos_y, sprite_pattern_table_addr)?;
 
@@ -1405,7 +1988,7 @@ impl Ppu2c02 {
             let line_pattern_data = self.fetch_line_pattern_data(&tile, tile_offset, 0, width);
             let palette = tile.colors;
 
-            self.set_pixel(sprite.x, scanline as u8, &line_pattern_data, palette, PixelMode::Sprite, priority, sprite0_hit_detect);
+            self.set_pixel(sprite.x, scanline as u8, line_pattern_data, palette, PixelMode::Sprite, priority, sprite0_hit_detect);
         }
 
         Ok(())
@@ -1442,9 +2025,13 @@ impl Ppu2c02 {
          *  v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
          *
          ***/
+        let pre_render_scanline = self.region.pre_render_scanline();
+
         match self.state {
-            PpuState::VBlank(261) => {
+            PpuState::VBlank(scanline) if scanline == pre_render_scanline => {
+                // dot 1 of the pre-render scanline: the flags are cleared whether $2002 was read or not
                 self.set_flag(Status(VBlank), false);
+                *self.vblank_suppressed.borrow_mut() = false;
                 self.set_flag(Status(Sprite0Hit), false);
                 self.set_flag(Status(SpriteOverflow), false);
 
@@ -1458,16 +2045,22 @@ impl Ppu2c02 {
             },
 
             PpuState::Rendering(scanline) if scanline <= 239 => {
+                self.notify_scanline_hook(scanline);
+
                 let show_background = self.get_flag(Mask(ShowBackground));
                 let show_sprites = self.get_flag(Mask(ShowSprites));
 
                 if show_background {
-                    self.render_background(scanline)?;
+                    let (result, elapsed) = timed(|| self.render_background(scanline));
+                    self.timing_spans.background += elapsed;
+                    result?;
                     self.put_horizontal_t_into_v();
                 }
 
                 if show_sprites {
-                    self.render_sprites(scanline)?;
+                    let (result, elapsed) = timed(|| self.render_sprites(scanline));
+                    self.timing_spans.sprites += elapsed;
+                    result?;
                 }
 
                 if show_background || show_sprites {
@@ -1476,13 +2069,26 @@ impl Ppu2c02 {
                     self.oam.clear_secondary();
                 }
 
+                // the layers hidden by the override are drawn transparent
+                if self.background_override == Some(false) {
+                    self.background_pixels_line.clear();
+                }
+
+                if self.sprites_override == Some(false) {
+                    self.sprites_pixels_line.clear();
+                }
+
                 self.write_pixels_lines_to_frame(scanline, show_background, show_sprites);
+                self.apply_scroll_writes_latched_during_scanline();
 
                 self.register.borrow_mut().oam_addr = 0;
                 self.state = PpuState::Rendering(scanline + 1);
             },
 
             PpuState::Rendering(240) => {
+                let is_rendering = self.get_flag(Mask(ShowBackground)) || self.get_flag(Mask(ShowSprites));
+                self.oam.end_frame(is_rendering);
+
                 self.renderer.borrow_mut().update();
                 #[cfg(feature = "ppu_tile_cache")]
                 self.tile_cache.clear();
@@ -1491,15 +2097,20 @@ impl Ppu2c02 {
 
             PpuState::Rendering(241) => {
                 self.renderer.borrow_mut().reset();
-                self.set_flag(Status(VBlank), true);
                 self.state = PpuState::VBlank(242);
 
-                if self.get_flag(Control(GenerateNmi)) {
-                    self.cpu.borrow_mut().signal_nmi()?;
+                let vblank_suppressed = self.vblank_suppressed.replace(false);
+
+                if !vblank_suppressed {
+                    self.set_flag(Status(VBlank), true);
+
+                    if self.get_flag(Control(GenerateNmi)) {
+                        self.cpu.borrow_mut().signal_nmi()?;
+                    }
                 }
             },
 
-            PpuState::VBlank(scanline) if scanline >= 242 && scanline <= 260 => {
+            PpuState::VBlank(scanline) if scanline >= 242 && scanline < pre_render_scanline => {
                 self.state = PpuState::VBlank(scanline + 1);
             },
 
@@ -1513,6 +2124,9 @@ impl Ppu2c02 {
     fn render(&mut self) -> Result<u16, PpuError> {
 
         self.render_scanline()?;
-        Ok(CLOCK_CYCLES_PER_SCANLINE)
+        self.scanlines_rendered += 1;
+        self.cpu_cycles_rendered += self.region.cpu_cycles_per_scanline() as u64;
+
+        Ok(self.region.cpu_cycles_per_scanline())
     }
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use log::info;
 use crate::bus::Bus;
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::dma::{Dma, DmaType};
+use crate::dma_arbiter::DmaArbiter;
 use crate::dma::PpuDmaType::NESPPUDMA;
 use crate::dma_device::DmaDevice;
 use crate::memory::{Memory, MemoryError};
@@ -16,30 +17,25 @@ const PPU_DMA_SIZE: usize = 1;
 pub struct PpuDma {
     device: Rc<RefCell<dyn DmaDevice>>,
     last_transfer_addr: u8,
+    arbiter: DmaArbiter,
     bus: Rc<RefCell<dyn Bus>>
 }
 
 impl Dma for PpuDma {
     fn transfer_memory(&mut self, value: u8) -> Result<u16, MemoryError> {
-        let source = (value as u16) << 8;
-        let last_value = source | 0x00FF;
+        //debug!("DMA: transferring 256 bytes of memory from 0x{:04X} to PPU", (value as u16) << 8);
 
-        //debug!("DMA: transferring 256 bytes of memory from 0x{:04X} to PPU", source);
-
-        let mut index = 0;
         let bus = self.bus.as_ptr();
 
         /***
          * the unsafe call is necessary because in the current design, this
          * code is called as the CPU already holds a mutable reference to the bus.
+         * the parity of the CPU cycle is not known here, the halt is assumed to be on a put cycle (513 cycles).
          */
-        for addr in source..=last_value {
-            let data = unsafe { &*bus }.read_byte(addr)?;
-            self.device.borrow_mut().dma_write(index as u8, data)?;
-            index += 1;
-        }
+        self.arbiter.request_oam(value);
+        let transfer = self.arbiter.run(unsafe { &*bus }, &mut *self.device.borrow_mut(), false)?;
 
-        Ok(index)
+        Ok(transfer.cycles() as u16)
     }
 }
 
@@ -106,6 +102,7 @@ impl PpuDma {
         PpuDma {
             device,
             last_transfer_addr: 0,
+            arbiter: DmaArbiter::new(),
             bus
         }
     }
@@ -0,0 +1,83 @@
+use crate::palette::Palette;
+use crate::palette_2c02::Palette2C02;
+
+pub const PATTERN_TILE_WIDTH: usize = 8;
+pub const PATTERN_TILE_HEIGHT: usize = 8;
+pub const PATTERN_TILES_PER_TABLE: usize = 256;
+pub const PATTERN_TABLES_COUNT: usize = 2;
+pub const PALETTE_RAM_SIZE: usize = 32;
+
+/// A single 8x8 tile of a pattern table, rendered to RGBA.
+#[derive(Debug, Clone)]
+pub struct PatternTile {
+    pixels: Vec<u8>,
+}
+
+impl PatternTile {
+    pub fn new(pixels: Vec<u8>) -> Self {
+        PatternTile {
+            pixels
+        }
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn width(&self) -> usize {
+        PATTERN_TILE_WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        PATTERN_TILE_HEIGHT
+    }
+}
+
+/***
+ * Snapshot of the PPU memory for viewers:
+ * - the 32 bytes of palette RAM ($3F00 - $3F1F),
+ * - the 512 tiles of both pattern tables ($0000 - $1FFF), left table first,
+ *   colorized with one of the 8 palettes (0-3: background, 4-7: sprites).
+ * https://www.nesdev.org/wiki/PPU_pattern_tables
+ * https://www.nesdev.org/wiki/PPU_palettes
+ ***/
+#[derive(Debug, Clone)]
+pub struct PpuMemoryDump {
+    palette: [u8; PALETTE_RAM_SIZE],
+    palette_index: u8,
+    tiles: Vec<PatternTile>,
+}
+
+impl PpuMemoryDump {
+    pub fn new(palette: [u8; PALETTE_RAM_SIZE], palette_index: u8, tiles: Vec<PatternTile>) -> Self {
+        PpuMemoryDump {
+            palette,
+            palette_index,
+            tiles,
+        }
+    }
+
+    pub fn palette(&self) -> &[u8; PALETTE_RAM_SIZE] {
+        &self.palette
+    }
+
+    /// RGB colors of the palette RAM entries, as displayed by the 2C02.
+    pub fn palette_rgb(&self) -> Vec<(u8, u8, u8)> {
+        self.palette.iter().map(|color| Palette2C02::rgb(*color)).collect()
+    }
+
+    /// The palette (0-7) used to colorize the tiles.
+    pub fn palette_index(&self) -> u8 {
+        self.palette_index
+    }
+
+    pub fn tiles(&self) -> &[PatternTile] {
+        &self.tiles
+    }
+
+    /// The tiles of one pattern table (0: $0000, 1: $1000).
+    pub fn pattern_table(&self, table: usize) -> &[PatternTile] {
+        let start = table * PATTERN_TILES_PER_TABLE;
+        &self.tiles[start..start + PATTERN_TILES_PER_TABLE]
+    }
+}
@@ -0,0 +1,61 @@
+/***
+ * Read-only state of the PPU registers and of the rendering position, for the debuggers.
+ * https://www.nesdev.org/wiki/PPU_registers
+ * https://www.nesdev.org/wiki/PPU_scrolling
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PpuSnapshot {
+    pub(crate) control: u8,
+    pub(crate) mask: u8,
+    pub(crate) status: u8,
+    pub(crate) oam_addr: u8,
+    pub(crate) v: u16,
+    pub(crate) t: u16,
+    pub(crate) fine_x: u8,
+    pub(crate) scanline: u16,
+    pub(crate) dot: u16,
+}
+
+impl PpuSnapshot {
+    /// $2000
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    /// $2001
+    pub fn mask(&self) -> u8 {
+        self.mask
+    }
+
+    /// $2002
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    /// $2003
+    pub fn oam_addr(&self) -> u8 {
+        self.oam_addr
+    }
+
+    /// Current VRAM address.
+    pub fn v(&self) -> u16 {
+        self.v
+    }
+
+    /// Temporary VRAM address, the top left onscreen tile.
+    pub fn t(&self) -> u16 {
+        self.t
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.fine_x
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+}
@@ -0,0 +1,67 @@
+/***
+ * Cheat search over the work RAM, to find where a game keeps a value (lives, score, ...):
+ * each search compares the RAM to its snapshot of the previous frame and keeps, among the addresses
+ * still candidates, those matching the criteria. Successive searches narrow the candidates down
+ * until ```reset``` makes the whole RAM a candidate again.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchCriteria {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(u8),
+}
+
+impl SearchCriteria {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match self {
+            SearchCriteria::Changed => current != previous,
+            SearchCriteria::Unchanged => current == previous,
+            SearchCriteria::Increased => current > previous,
+            SearchCriteria::Decreased => current < previous,
+            SearchCriteria::Equals(value) => current == *value,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    previous: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    pub fn new(ram: &[u8]) -> Self {
+        RamSearch {
+            previous: ram.to_vec(),
+            candidates: (0..ram.len() as u16).collect(),
+        }
+    }
+
+    /// Keeps ```ram``` as the reference of the next search, done at the start of each frame.
+    pub fn snapshot(&mut self, ram: &[u8]) {
+        self.previous.clear();
+        self.previous.extend_from_slice(ram);
+    }
+
+    /// The candidates whose value went from the snapshot to ```ram``` as asked, they stay the only candidates.
+    pub fn search(&mut self, ram: &[u8], criteria: SearchCriteria) -> &[u16] {
+        let previous = &self.previous;
+
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            addr < ram.len() && addr < previous.len() && criteria.matches(previous[addr], ram[addr])
+        });
+
+        &self.candidates
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    pub fn reset(&mut self) {
+        self.candidates = (0..self.previous.len() as u16).collect();
+    }
+}
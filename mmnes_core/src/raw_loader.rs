@@ -0,0 +1,175 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
+use log::{debug, info};
+use crate::bus::Bus;
+use crate::bus_device::{BusDevice, BusDeviceType};
+use crate::cartridge::{Cartridge, CartridgeType, CPU_ADDRESS_SPACE, PPU_ADDRESS_SPACE};
+use crate::loader::{Loader, LoaderError};
+use crate::memory::{Memory, MemoryError};
+use crate::memory_bank::MemoryBank;
+use crate::memory_ciram::PpuNameTableMirroring;
+
+const RAW_PRG_RAM_SIZE: usize = 32 * 1024;
+const RAW_CHR_RAM_SIZE: usize = 8 * 1024;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IO_REGISTERS_ADDRESS_SPACE: (u16, u16) = (0x2000, 0x401F);
+const DEVICE_NAME: &str = "Raw Binary RAM";
+
+/***
+ * Headerless binary (hand-assembled programs, 6502 functional tests), copied as is at a load address.
+ * The cartridge space ($8000 - $FFFF) is backed by RAM, with 8 KB of CHR RAM, so that the binary and the reset
+ * vector can be written there or in the work RAM. The bytes over the I/O registers ($2000 - $401F) are dropped.
+ ***/
+#[derive(Debug)]
+pub struct RawLoader {
+    data: Vec<u8>,
+}
+
+impl Loader for RawLoader {
+
+    fn from_file(path: PathBuf) -> Result<RawLoader, LoaderError> {
+        let mut file = File::open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        Ok(RawLoader::from_bytes(&data))
+    }
+
+    fn build_cartridge(self) -> Result<Rc<RefCell<dyn Cartridge>>, LoaderError> {
+        info!("building RAM cartridge for a raw binary of {} bytes...", self.data.len());
+
+        let mut cartridge = RawCartridge::new();
+        cartridge.initialize()?;
+
+        Ok(Rc::new(RefCell::new(cartridge)))
+    }
+}
+
+impl RawLoader {
+
+    pub fn from_bytes(bytes: &[u8]) -> RawLoader {
+        RawLoader {
+            data: bytes.to_vec()
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /***
+     * the binary is written at ```load_addr```, wrapping at $FFFF. The reset vector points to ```reset_vector```,
+     * or to ```load_addr``` when the binary does not provide its own vector at $FFFC.
+     ***/
+    pub fn write_to_bus(&self, bus: &mut dyn Bus, load_addr: u16, reset_vector: Option<u16>) -> Result<(), LoaderError> {
+        if self.data.len() > u16::MAX as usize + 1 {
+            return Err(LoaderError::InvalidRomFormat);
+        }
+
+        let mut covers_reset_vector = false;
+
+        for (offset, byte) in self.data.iter().enumerate() {
+            let addr = load_addr.wrapping_add(offset as u16);
+
+            if addr >= IO_REGISTERS_ADDRESS_SPACE.0 && addr <= IO_REGISTERS_ADDRESS_SPACE.1 {
+                continue;
+            }
+
+            covers_reset_vector |= addr == RESET_VECTOR;
+            bus.write_byte(addr, *byte)?;
+        }
+
+        match reset_vector {
+            Some(vector) => bus.write_word(RESET_VECTOR, vector)?,
+            None if !covers_reset_vector => bus.write_word(RESET_VECTOR, load_addr)?,
+            None => {},
+        }
+
+        debug!("raw binary: {} bytes loaded at 0x{:04X}, reset vector: 0x{:04X}", self.data.len(), load_addr, bus.read_word(RESET_VECTOR)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RawCartridge {
+    prg_ram: Rc<RefCell<MemoryBank>>,
+    chr_ram: Rc<RefCell<MemoryBank>>,
+    mirroring: Rc<RefCell<PpuNameTableMirroring>>,
+}
+
+impl RawCartridge {
+    fn new() -> Self {
+        RawCartridge {
+            prg_ram: Rc::new(RefCell::new(MemoryBank::new(RAW_PRG_RAM_SIZE, CPU_ADDRESS_SPACE))),
+            chr_ram: Rc::new(RefCell::new(MemoryBank::new(RAW_CHR_RAM_SIZE, PPU_ADDRESS_SPACE))),
+            mirroring: Rc::new(RefCell::new(PpuNameTableMirroring::Horizontal)),
+        }
+    }
+}
+
+impl Memory for RawCartridge {
+    fn initialize(&mut self) -> Result<usize, MemoryError> {
+        let mut result = 0;
+
+        result += self.prg_ram.borrow_mut().initialize()?;
+        result += self.chr_ram.borrow_mut().initialize()?;
+
+        Ok(result)
+    }
+
+    fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        self.prg_ram.borrow().read_byte(addr)
+    }
+
+    fn trace_read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        self.read_byte(addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.prg_ram.borrow_mut().write_byte(addr, value)
+    }
+
+    fn read_word(&self, addr: u16) -> Result<u16, MemoryError> {
+        self.prg_ram.borrow().read_word(addr)
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) -> Result<(), MemoryError> {
+        self.prg_ram.borrow_mut().write_word(addr, value)
+    }
+
+    fn dump(&self) {
+        self.prg_ram.borrow().dump();
+    }
+
+    fn size(&self) -> usize {
+        self.prg_ram.borrow().size()
+    }
+}
+
+impl BusDevice for RawCartridge {
+    fn get_name(&self) -> String {
+        DEVICE_NAME.to_string()
+    }
+
+    fn get_device_type(&self) -> BusDeviceType {
+        BusDeviceType::CARTRIDGE(CartridgeType::NESCARTRIDGE)
+    }
+
+    fn get_virtual_address_range(&self) -> (u16, u16) {
+        CPU_ADDRESS_SPACE
+    }
+}
+
+impl Cartridge for RawCartridge {
+    fn get_chr_rom(&self) -> Rc<RefCell<dyn BusDevice>> {
+        self.chr_ram.clone()
+    }
+
+    fn get_mirroring(&self) -> Rc<RefCell<PpuNameTableMirroring>> {
+        self.mirroring.clone()
+    }
+}
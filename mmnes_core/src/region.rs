@@ -0,0 +1,90 @@
+use std::fmt::{Display, Formatter};
+
+const NTSC_SCANLINES_PER_FRAME: u16 = 262;
+const PAL_SCANLINES_PER_FRAME: u16 = 312;
+
+const NTSC_FRAMES_PER_SECOND: f64 = 60.098_8;
+const PAL_FRAMES_PER_SECOND: f64 = 50.007;
+const DENDY_FRAMES_PER_SECOND: f64 = 50.0;
+
+const NTSC_CPU_CLOCK_RATE: f64 = 1_789_773.0;
+const PAL_CPU_CLOCK_RATE: f64 = 1_662_607.0;
+const DENDY_CPU_CLOCK_RATE: f64 = 1_773_448.0;
+
+/// 341 PPU dots per scanline, 3 dots per CPU cycle on NTSC and Dendy, 3.2 on PAL.
+const NTSC_CPU_CYCLES_PER_SCANLINE: u16 = 114;
+const PAL_CPU_CYCLES_PER_SCANLINE: u16 = 107;
+
+/***
+ * https://www.nesdev.org/wiki/Cycle_reference_chart
+ * https://www.nesdev.org/wiki/NES_2.0#CPU/PPU_Timing
+ ***/
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Region {
+    #[default]
+    NTSC,
+    PAL,
+    Multiple,
+    Dendy,
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Region::NTSC => write!(f, "NTSC"),
+            Region::PAL => write!(f, "PAL"),
+            Region::Multiple => write!(f, "Multiple"),
+            Region::Dendy => write!(f, "Dendy"),
+        }
+    }
+}
+
+impl Region {
+
+    /// Total number of scanlines per frame, including the pre-render scanline.
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match self {
+            Region::NTSC | Region::Multiple => NTSC_SCANLINES_PER_FRAME,
+            Region::PAL | Region::Dendy => PAL_SCANLINES_PER_FRAME,
+        }
+    }
+
+    /// Index of the pre-render scanline, which is always the last one of the frame.
+    pub fn pre_render_scanline(&self) -> u16 {
+        self.scanlines_per_frame() - 1
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        match self {
+            Region::NTSC | Region::Multiple => NTSC_FRAMES_PER_SECOND,
+            Region::PAL => PAL_FRAMES_PER_SECOND,
+            Region::Dendy => DENDY_FRAMES_PER_SECOND,
+        }
+    }
+
+    pub fn cpu_clock_rate(&self) -> f64 {
+        match self {
+            Region::NTSC | Region::Multiple => NTSC_CPU_CLOCK_RATE,
+            Region::PAL => PAL_CPU_CLOCK_RATE,
+            Region::Dendy => DENDY_CPU_CLOCK_RATE,
+        }
+    }
+
+    /// The APU is clocked every other CPU cycle.
+    pub fn apu_clock_rate(&self) -> f64 {
+        self.cpu_clock_rate() / 2.0
+    }
+
+    pub fn cpu_cycles_per_scanline(&self) -> u16 {
+        match self {
+            Region::PAL => PAL_CPU_CYCLES_PER_SCANLINE,
+            _ => NTSC_CPU_CYCLES_PER_SCANLINE,
+        }
+    }
+
+    /// Whether the APU frame sequencer runs with the PAL step periods.
+    /// Dendy clones run PAL video timings with an NTSC-like APU.
+    pub fn has_pal_apu(&self) -> bool {
+        *self == Region::PAL
+    }
+}
@@ -3,60 +3,112 @@ use crate::nes_frame::NesFrame;
 const WIDTH: usize = 256;
 const HEIGHT: usize = 240;
 
+/***
+ * Double buffered frame of the PPU: the scanlines are drawn in the back buffer, copied to the front
+ * buffer once the frame is complete (at vblank, see ```update```). Only the front buffer is published,
+ * the consumer never gets a half drawn frame, whatever the moment it asks for one.
+ ***/
 #[cfg(not(test))]
 pub struct Renderer {
-    frame: NesFrame
+    back: NesFrame,
+    front: NesFrame,
+    published: bool,
 }
 
 #[cfg(not(test))]
 impl Renderer {
     pub fn new() -> Self {
         Renderer {
-            frame: NesFrame::new(WIDTH, HEIGHT)
+            back: NesFrame::new(WIDTH, HEIGHT),
+            front: NesFrame::new(WIDTH, HEIGHT),
+            published: false,
         }
     }
 
     pub fn frame_as_mut(&mut self) -> &mut NesFrame {
-        &mut self.frame
+        &mut self.back
     }
 
+    /// The frame being drawn.
     pub fn frame(&self) -> &NesFrame {
-        &self.frame
+        &self.back
+    }
+
+    /// The last completed frame.
+    pub fn front(&self) -> &NesFrame {
+        &self.front
+    }
+
+    /// The last completed frame, once: None until the next frame is completed.
+    pub fn take_completed(&mut self) -> Option<NesFrame> {
+        if !self.published {
+            return None;
+        }
+
+        self.published = false;
+        Some(self.front.clone())
     }
 
     pub fn update(&mut self) {
-        self.frame.finish();
+        self.back.finish();
+        self.front.clone_from(&self.back);
+        self.published = true;
     }
     
     pub fn reset(&mut self) {
-        self.frame.reset();
+        self.back.reset();
     }
 }
 
+/***
+ * The renderer of the tests, its buffers open to the assertions. The frames are completed, counted
+ * and published as by the renderer above: the tests of the publishing and of the frame count rely on it.
+ ***/
 #[cfg(test)]
 pub struct Renderer {
-    pub frame: NesFrame
+    pub back: NesFrame,
+    pub front: NesFrame,
+    pub published: bool,
 }
 
 #[cfg(test)]
 impl Renderer {
     pub fn new() -> Self {
         Renderer {
-            frame: NesFrame::new(WIDTH, HEIGHT)
+            back: NesFrame::new(WIDTH, HEIGHT),
+            front: NesFrame::new(WIDTH, HEIGHT),
+            published: false,
         }
     }
 
     pub fn frame_as_mut(&mut self) -> &mut NesFrame {
-        &mut self.frame
+        &mut self.back
     }
 
     pub fn frame(&self) -> &NesFrame {
-        &self.frame
+        &self.back
+    }
+
+    pub fn front(&self) -> &NesFrame {
+        &self.front
+    }
+
+    pub fn take_completed(&mut self) -> Option<NesFrame> {
+        if !self.published {
+            return None;
+        }
+
+        self.published = false;
+        Some(self.front.clone())
     }
 
     pub fn update(&mut self) {
+        self.back.finish();
+        self.front.clone_from(&self.back);
+        self.published = true;
     }
 
     pub fn reset(&mut self) {
+        self.back.reset();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,68 @@
+/***
+ * The last ```capacity``` samples pushed, oldest first, to draw an oscilloscope.
+ * Each sample is written twice, at its index and ```capacity``` further: the last samples are
+ * then always contiguous, and read as a slice without moving them.
+ * The history is owned by the emulator thread, the UI gets a copy of it (see ```downsampled```),
+ * the audio callback never sees it.
+ ***/
+#[derive(Debug, Clone)]
+pub struct SampleHistory {
+    buffer: Vec<f32>,
+    capacity: usize,
+    next: usize,
+    len: usize,
+}
+
+pub const DEFAULT_HISTORY_SIZE: usize = 2048;
+
+impl SampleHistory {
+    pub fn new(capacity: usize) -> Self {
+        SampleHistory {
+            buffer: vec![0.0; capacity * 2],
+            capacity,
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push_sample(&mut self, sample: f32) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.buffer[self.next] = sample;
+        self.buffer[self.next + self.capacity] = sample;
+        self.next = (self.next + 1) % self.capacity;
+        self.len = (self.len + 1).min(self.capacity);
+    }
+
+    /// At most ```capacity``` samples, the most recent last.
+    pub fn recent_samples(&self) -> &[f32] {
+        let start = self.next + self.capacity - self.len;
+        &self.buffer[start..start + self.len]
+    }
+
+    /// The recent samples reduced to at most ```points``` values, each one the first sample of its slice.
+    pub fn downsampled(&self, points: usize) -> Vec<f32> {
+        let samples = self.recent_samples();
+
+        if points == 0 || samples.len() <= points {
+            return samples.to_vec();
+        }
+
+        (0..points)
+            .map(|point| samples[point * samples.len() / points])
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+    }
+}
+
+impl Default for SampleHistory {
+    fn default() -> Self {
+        SampleHistory::new(DEFAULT_HISTORY_SIZE)
+    }
+}
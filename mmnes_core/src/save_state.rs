@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::memory::MemoryError;
+
+const SAVE_STATE_MAGIC: &[u8; 4] = b"MMNS";
+const SAVE_STATE_VERSION: u8 = 1;
+
+/***
+ * A save state of the console, as written to the disk:
+ *
+ *   "MMNS" | version | CPU state | number of devices (u16) | (device type, device state) for each device
+ *
+ * The devices are the ones of the CPU bus, in address order (see Bus::serialize_devices). The CPU state,
+ * the device types and the device states are chunks: prefixed by their length (u32), all little endian.
+ * The device types are only checked against the devices of the console the state is loaded in.
+ * The APU is not saved, the sound resumes from the channels as they are when the state is loaded.
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveState {
+    cpu: Vec<u8>,
+    devices: Vec<(String, Vec<u8>)>,
+}
+
+impl SaveState {
+    pub fn new(cpu: Vec<u8>, devices: Vec<(String, Vec<u8>)>) -> Self {
+        SaveState {
+            cpu,
+            devices,
+        }
+    }
+
+    pub fn cpu(&self) -> &[u8] {
+        &self.cpu
+    }
+
+    pub fn devices(&self) -> &[(String, Vec<u8>)] {
+        &self.devices
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(SAVE_STATE_MAGIC);
+        bytes.push(SAVE_STATE_VERSION);
+        push_chunk(&mut bytes, &self.cpu);
+        bytes.extend_from_slice(&(self.devices.len() as u16).to_le_bytes());
+
+        for (device_type, state) in &self.devices {
+            push_chunk(&mut bytes, device_type.as_bytes());
+            push_chunk(&mut bytes, state);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, SaveStateError> {
+        let mut reader = StateReader::new(bytes);
+
+        if reader.bytes(SAVE_STATE_MAGIC.len())? != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::InvalidFormat("not a save state".to_string()));
+        }
+
+        let version = reader.byte()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let cpu = reader.chunk()?.to_vec();
+        let count = reader.word()?;
+        let mut devices = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let device_type = String::from_utf8(reader.chunk()?.to_vec())
+                .map_err(|_| SaveStateError::InvalidFormat("device type is not UTF-8".to_string()))?;
+            let state = reader.chunk()?.to_vec();
+
+            devices.push((device_type, state));
+        }
+
+        Ok(SaveState::new(cpu, devices))
+    }
+}
+
+/// Appends ```chunk```, prefixed by its length.
+pub(crate) fn push_chunk(bytes: &mut Vec<u8>, chunk: &[u8]) {
+    bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(chunk);
+}
+
+/// Reads the fields of a state in the order they were written, a state too short is an error.
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        StateReader {
+            bytes,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self.offset + len;
+
+        if end > self.bytes.len() {
+            return Err(SaveStateError::Truncated(self.bytes.len()));
+        }
+
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn word(&mut self) -> Result<u16, SaveStateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn long(&mut self) -> Result<u64, SaveStateError> {
+        let mut long = [0u8; 8];
+        long.copy_from_slice(self.bytes(8)?);
+
+        Ok(u64::from_le_bytes(long))
+    }
+
+    /// A chunk written by ```push_chunk```.
+    pub(crate) fn chunk(&mut self) -> Result<&'a [u8], SaveStateError> {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(self.bytes(4)?);
+
+        self.bytes(u32::from_le_bytes(len) as usize)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    InvalidFormat(String),
+    UnsupportedVersion(u8),
+    Truncated(usize),
+    DeviceMismatch(String),
+}
+
+impl Error for SaveStateError {}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SaveStateError::InvalidFormat(s) => write!(f, "invalid save state: {}", s),
+            SaveStateError::UnsupportedVersion(version) => write!(f, "unsupported save state version: {}", version),
+            SaveStateError::Truncated(len) => write!(f, "save state truncated at {} bytes", len),
+            SaveStateError::DeviceMismatch(s) => write!(f, "save state of another console: {}", s),
+        }
+    }
+}
+
+impl From<SaveStateError> for MemoryError {
+    fn from(error: SaveStateError) -> Self {
+        MemoryError::IllegalState(error.to_string())
+    }
+}
@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use crate::region::Region;
+
+/***
+ * Everything needed to start a game in one shot, from a JSON file:
+ *
+ *   {
+ *     "rom": "games/smb.nes",
+ *     "sram": "saves/smb.sav",
+ *     "cheats": ["SXIOPO", "0075:09"],
+ *     "region": "PAL",
+ *     "palette": "palettes/smooth.pal"
+ *   }
+ *
+ * Only the rom is required. The relative paths of a session file are relative to its directory.
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    rom_file: PathBuf,
+    sram_file: Option<PathBuf>,
+    cheats: Vec<String>,
+    region: Option<Region>,
+    palette_file: Option<PathBuf>,
+}
+
+impl Session {
+
+    pub fn from_file(path: &Path) -> Result<Session, SessionError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| SessionError::IOError(format!("{}: {}", path.display(), e)))?;
+
+        let session = Session::from_json(&text)?;
+
+        match path.parent() {
+            Some(directory) => Ok(session.relative_to(directory)),
+            None => Ok(session),
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Session, SessionError> {
+        let json: Value = serde_json::from_str(text)
+            .map_err(|e| SessionError::InvalidFormat(e.to_string()))?;
+
+        let rom_file = Session::path_field(&json, "rom")?
+            .ok_or(SessionError::MissingField("rom".to_string()))?;
+
+        let cheats = match json.get("cheats") {
+            None => Vec::new(),
+            Some(Value::Array(codes)) => codes.iter()
+                .map(|code| code.as_str()
+                    .map(str::to_string)
+                    .ok_or(SessionError::InvalidField("cheats".to_string())))
+                .collect::<Result<Vec<String>, SessionError>>()?,
+            Some(_) => return Err(SessionError::InvalidField("cheats".to_string())),
+        };
+
+        let region = match Session::string_field(&json, "region")? {
+            Some(name) => Some(Session::parse_region(name)?),
+            None => None,
+        };
+
+        Ok(Session {
+            rom_file,
+            sram_file: Session::path_field(&json, "sram")?,
+            cheats,
+            region,
+            palette_file: Session::path_field(&json, "palette")?,
+        })
+    }
+
+    fn string_field<'a>(json: &'a Value, name: &str) -> Result<Option<&'a str>, SessionError> {
+        match json.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(SessionError::InvalidField(name.to_string())),
+        }
+    }
+
+    fn path_field(json: &Value, name: &str) -> Result<Option<PathBuf>, SessionError> {
+        Ok(Session::string_field(json, name)?.map(PathBuf::from))
+    }
+
+    fn parse_region(name: &str) -> Result<Region, SessionError> {
+        match name.to_ascii_uppercase().as_str() {
+            "NTSC" => Ok(Region::NTSC),
+            "PAL" => Ok(Region::PAL),
+            "DENDY" => Ok(Region::Dendy),
+            _ => Err(SessionError::InvalidField(format!("region {}", name))),
+        }
+    }
+
+    fn relative_to(self, directory: &Path) -> Session {
+        let resolve = |path: PathBuf| if path.is_relative() { directory.join(path) } else { path };
+
+        Session {
+            rom_file: resolve(self.rom_file),
+            sram_file: self.sram_file.map(resolve),
+            palette_file: self.palette_file.map(resolve),
+            ..self
+        }
+    }
+
+    pub fn rom_file(&self) -> &Path {
+        &self.rom_file
+    }
+
+    pub fn sram_file(&self) -> Option<&Path> {
+        self.sram_file.as_deref()
+    }
+
+    pub fn cheats(&self) -> &[String] {
+        &self.cheats
+    }
+
+    pub fn region(&self) -> Option<Region> {
+        self.region
+    }
+
+    pub fn palette_file(&self) -> Option<&Path> {
+        self.palette_file.as_deref()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SessionError {
+    IOError(String),
+    InvalidFormat(String),
+    MissingField(String),
+    InvalidField(String),
+}
+
+impl Error for SessionError {}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SessionError::IOError(s) => write!(f, "cannot read session file: {}", s),
+            SessionError::InvalidFormat(s) => write!(f, "invalid session file: {}", s),
+            SessionError::MissingField(s) => write!(f, "missing field in session file: {}", s),
+            SessionError::InvalidField(s) => write!(f, "invalid field in session file: {}", s),
+        }
+    }
+}
@@ -1,5 +1,9 @@
 use std::fmt::Debug;
 
+/***
+ * Sinks of the APU samples. Implementations hold at most a bounded number of pending samples,
+ * samples pushed while the sink is full are dropped rather than growing the latency.
+ ***/
 pub trait SoundPlayback : Debug {
     fn push_sample(&mut self, sample: f32);
     fn samples(&mut self) -> Vec<f32>;
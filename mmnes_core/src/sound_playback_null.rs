@@ -0,0 +1,15 @@
+use crate::sound_playback::SoundPlayback;
+
+/// Sink of a headless console: the samples are dropped as they are pushed, no audio device is involved.
+#[derive(Debug, Default)]
+pub struct NullSoundPlayback;
+
+impl SoundPlayback for NullSoundPlayback {
+    fn push_sample(&mut self, _sample: f32) {}
+
+    fn samples(&mut self) -> Vec<f32> {
+        Vec::new()
+    }
+
+    fn resume(&self) {}
+}
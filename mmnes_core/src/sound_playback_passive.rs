@@ -1,15 +1,16 @@
 use crate::sound_playback::SoundPlayback;
 
-const BUFFER_SIZE: usize = 1024;
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
 
 #[derive(Debug)]
 pub struct SoundPlaybackPassive {
     buffer: Vec<f32>,
+    buffer_size: usize,
 }
 
 impl SoundPlayback for SoundPlaybackPassive {
     fn push_sample(&mut self, sample: f32) {
-        if self.buffer.len() < BUFFER_SIZE {
+        if self.buffer.len() < self.buffer_size {
             self.buffer.push(sample);
         }
     }
@@ -26,9 +27,16 @@ impl SoundPlayback for SoundPlaybackPassive {
 }
 
 impl SoundPlaybackPassive {
+    #[allow(dead_code)]
     pub fn new() -> Self {
+        SoundPlaybackPassive::with_buffer_size(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Samples pushed once ```buffer_size``` samples are pending are dropped, until the buffer is drained by ```samples()```.
+    pub fn with_buffer_size(buffer_size: usize) -> Self {
         SoundPlaybackPassive {
-            buffer: Vec::with_capacity(BUFFER_SIZE)
+            buffer: Vec::with_capacity(buffer_size),
+            buffer_size,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use crate::sound_playback::SoundPlayback;
+
+/***
+ * Linear resampler between the APU output rate and the host audio device rate, wrapping another playback.
+ * the position between 2 input samples is kept as an exact fraction (numerator over the target rate),
+ * so that the output does not drift from the expected count over long sessions.
+ ***/
+#[derive(Debug)]
+pub struct SoundPlaybackResampler<T: SoundPlayback> {
+    inner: T,
+    source_rate: u64,
+    target_rate: u64,
+    position: u64,
+    previous: f32,
+    current: f32,
+}
+
+impl<T: SoundPlayback> SoundPlayback for SoundPlaybackResampler<T> {
+    fn push_sample(&mut self, sample: f32) {
+        self.previous = self.current;
+        self.current = sample;
+
+        while self.position < self.target_rate {
+            let t = self.position as f32 / self.target_rate as f32;
+            self.inner.push_sample(self.previous + (self.current - self.previous) * t);
+            self.position += self.source_rate;
+        }
+
+        self.position -= self.target_rate;
+    }
+
+    fn samples(&mut self) -> Vec<f32> {
+        self.inner.samples()
+    }
+
+    fn resume(&self) {
+        self.inner.resume()
+    }
+}
+
+impl<T: SoundPlayback> SoundPlaybackResampler<T> {
+    pub fn new(inner: T, source_rate: u32, target_rate: u32) -> Self {
+        SoundPlaybackResampler {
+            inner,
+            source_rate: source_rate as u64,
+            target_rate: target_rate as u64,
+            position: 0,
+            previous: 0.0,
+            current: 0.0,
+        }
+    }
+}
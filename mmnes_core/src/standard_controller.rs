@@ -10,10 +10,12 @@ use crate::key_event::KeyEvents;
 use crate::memory::{Memory, MemoryError};
 
 const DEVICE_NAME: &str = "Standard Controller";
+const FAMICOM_WITH_MIC_DEVICE_NAME: &str = "Famicom Controller with Microphone";
 const CONTROLLER_ADDRESS_SPACE: (u16, u16) = (0x4016, 0x4016);
 const CONTROLLER_MEMORY_SIZE: usize = 1;
 const CONTROLLER_NUM_BUTTONS: usize = 8;
 const DEFAULT_STATE: u8 = 0x01;
+const MICROPHONE_BIT: u8 = 0x04;
 
 #[derive(Debug, PartialEq)]
 enum State {
@@ -25,6 +27,7 @@ enum State {
 #[derive(Debug)]
 pub struct StandardController<T: Input> {
     input: T,
+    controller_type: ControllerType,
     state: RefCell<State>,
     control_states: [u8; CONTROLLER_NUM_BUTTONS],
     control_index: RefCell<usize>,
@@ -43,9 +46,10 @@ impl<T: Input> Memory for StandardController<T> {
         Ok(CONTROLLER_MEMORY_SIZE)
     }
 
-    fn read_byte(&self, _: u16) -> Result<u8, MemoryError> {
+    fn read_byte(&self, addr: u16) -> Result<u8, MemoryError> {
+        let control_state = self.trace_read_byte(addr)?;
 
-        let control_state = if *self.state.borrow() == State::StateReady {
+        if *self.state.borrow() == State::StateReady {
             let index = *self.control_index.borrow();
 
             if index == CONTROLLER_NUM_BUTTONS - 1 {
@@ -53,19 +57,27 @@ impl<T: Input> Memory for StandardController<T> {
             } else {
                 *self.control_index.borrow_mut() = index + 1;
             }
-
-            self.control_states[index]
-        } else {
-            DEFAULT_STATE
-        };
+        }
 
         Ok(control_state)
     }
 
+    /***
+     * while the strobe is high, the shift register is reloaded and reads return the A button,
+     * once the 8 buttons are shifted out, reads return 1 (official controllers).
+     * https://www.nesdev.org/wiki/Standard_controller
+     ***/
     fn trace_read_byte(&self, _: u16) -> Result<u8, MemoryError> {
-        Ok(0)
+        let control_state = match *self.state.borrow() {
+            State::Polling => self.control_states[0],
+            State::StateReady => self.control_states[*self.control_index.borrow()],
+            State::Idle => DEFAULT_STATE,
+        };
+
+        Ok(control_state | self.microphone_bit())
     }
 
+    /// The buttons are latched on each write while the strobe is high, and on the falling edge of the strobe.
     fn write_byte(&mut self, _: u16, value: u8) -> Result<(), MemoryError> {
         let state = value & 0x01;
 
@@ -80,7 +92,10 @@ impl<T: Input> Memory for StandardController<T> {
                 }
             },
 
-            0x01 => *self.state.borrow_mut() = State::Polling,
+            0x01 => {
+                self.input.get_input_state(&mut self.control_states);
+                *self.state.borrow_mut() = State::Polling;
+            },
             _ => unreachable!(),
         };
 
@@ -107,11 +122,14 @@ impl<T: Input> Memory for StandardController<T> {
 
 impl<T: Input> BusDevice for StandardController<T> {
     fn get_name(&self) -> String {
-        DEVICE_NAME.to_string()
+        match self.controller_type {
+            ControllerType::FamicomWithMic => FAMICOM_WITH_MIC_DEVICE_NAME.to_string(),
+            _ => DEVICE_NAME.to_string(),
+        }
     }
 
     fn get_device_type(&self) -> BusDeviceType {
-        CONTROLLER(ControllerType::StandardController)
+        CONTROLLER(self.controller_type.clone())
     }
 
     fn get_virtual_address_range(&self) -> (u16, u16) {
@@ -122,11 +140,32 @@ impl<T: Input> BusDevice for StandardController<T> {
 impl<T: Input> StandardController<T> {
 
     pub fn new(input: T) -> StandardController<T> {
+        StandardController::with_controller_type(input, ControllerType::StandardController)
+    }
+
+    /// Famicom first controller, with the microphone of the second controller reported on bit 2.
+    pub fn with_microphone(input: T) -> StandardController<T> {
+        StandardController::with_controller_type(input, ControllerType::FamicomWithMic)
+    }
+
+    fn with_controller_type(input: T, controller_type: ControllerType) -> StandardController<T> {
         StandardController {
             input,
+            controller_type,
             state: RefCell::new(State::Idle),
             control_states: [0; CONTROLLER_NUM_BUTTONS],
             control_index: RefCell::new(0),
         }
     }
-}
\ No newline at end of file
+
+    /***
+     * the microphone is sampled on each read, whatever the strobe and the shift register.
+     * https://www.nesdev.org/wiki/Controller_port_registers
+     ***/
+    fn microphone_bit(&self) -> u8 {
+        match self.controller_type {
+            ControllerType::FamicomWithMic if self.input.microphone() => MICROPHONE_BIT,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use log::info;
+use crate::custom_io_device::CustomIoDevice;
+use crate::nes_console::{ConsoleState, NesConsole, NesConsoleError};
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_NEEDED: u8 = 0x81;
+const STATUS_PASSED: u8 = 0x00;
+/// Written after the status byte once it is valid, a ROM merely clearing its PRG RAM does not report a result.
+const STATUS_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+/// The exit codes of a process are 0 - 255: the failure codes above are reported as FAILED_MAX_EXIT_CODE.
+const FAILED_MAX_EXIT_CODE: i32 = 253;
+const HALTED_EXIT_CODE: i32 = 254;
+const TIMED_OUT_EXIT_CODE: i32 = 255;
+/// The test ROMs ask to wait at least 100 ms before pressing reset.
+const RESET_DELAY_FRAMES: u32 = 7;
+
+/***
+ * Status of a test ROM, from the byte written to its status port (blargg convention, usually $6000),
+ * valid once the signature DE B0 61 is written in the 3 bytes following it:
+ *   - $80 the test is running, $81 the console must be reset,
+ *   - $00 the test passed, any other value is the failure code.
+ * A halted CPU (JAM) or a test not over after the maximum number of frames ends the test as a failure.
+ * https://www.nesdev.org/wiki/Emulator_tests
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestRomStatus {
+    Running,
+    ResetNeeded,
+    Passed,
+    Failed(u8),
+    Halted(u16),
+    TimedOut,
+}
+
+impl TestRomStatus {
+    pub fn from_status_byte(value: u8) -> TestRomStatus {
+        match value {
+            STATUS_RUNNING => TestRomStatus::Running,
+            STATUS_RESET_NEEDED => TestRomStatus::ResetNeeded,
+            STATUS_PASSED => TestRomStatus::Passed,
+            code => TestRomStatus::Failed(code),
+        }
+    }
+
+    /***
+     * Exit code of the process once the test is over: 0 when passed, the failure code otherwise (1 - 253,
+     * the codes above reported as 253), 254 when the CPU halted and 255 when the test timed out.
+     ***/
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            TestRomStatus::Running | TestRomStatus::ResetNeeded => None,
+            TestRomStatus::Passed => Some(0),
+            TestRomStatus::Failed(code) => Some((*code as i32).min(FAILED_MAX_EXIT_CODE)),
+            TestRomStatus::Halted(_) => Some(HALTED_EXIT_CODE),
+            TestRomStatus::TimedOut => Some(TIMED_OUT_EXIT_CODE),
+        }
+    }
+}
+
+/// The status byte and its signature mapped over the bus with a custom io device, keeping the bytes written.
+pub struct StatusPort {
+    addr: u16,
+    bytes: Rc<RefCell<[u8; 4]>>,
+    written: Rc<Cell<bool>>,
+}
+
+impl StatusPort {
+    pub fn new(addr: u16) -> Self {
+        StatusPort {
+            addr,
+            bytes: Rc::new(RefCell::new([0; 4])),
+            written: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn device(&self) -> CustomIoDevice {
+        let bytes = self.bytes.clone();
+        let written = self.written.clone();
+        let addr = self.addr;
+
+        CustomIoDevice::new("Status Port", (self.addr, self.addr + STATUS_SIGNATURE.len() as u16))
+            .with_write(Box::new(move |cpu_address, value| {
+                let offset = cpu_address.wrapping_sub(addr) as usize;
+                bytes.borrow_mut()[offset] = value;
+                written.set(written.get() || offset == 0);
+            }))
+    }
+
+    /// The status written since the previous call, if any and once the signature is written.
+    pub fn take_status(&self) -> Option<TestRomStatus> {
+        let bytes = self.bytes.borrow();
+
+        if bytes[1..] != STATUS_SIGNATURE || !self.written.take() {
+            return None;
+        }
+
+        Some(TestRomStatus::from_status_byte(bytes[0]))
+    }
+}
+
+/***
+ * Run the console, without pacing, until the test is over: the status port reports a result,
+ * the CPU halts when ```exit_on_halt``` is set (otherwise the PPU and the APU keep running),
+ * or ```max_frames``` frames have run. A reset asked by the test ROM is done after RESET_DELAY_FRAMES frames.
+ ***/
+pub fn run_test_rom(console: &mut NesConsole, status_port: Option<&StatusPort>, exit_on_halt: bool, max_frames: Option<u32>) -> Result<TestRomStatus, NesConsoleError> {
+    let mut frames = 0u32;
+
+    loop {
+        if max_frames.is_some_and(|max_frames| frames >= max_frames) {
+            return Ok(TestRomStatus::TimedOut);
+        }
+
+        let _ = console.step_frame()?;
+        frames += 1;
+
+        if let (ConsoleState::Halted(pc), true) = (console.state(), exit_on_halt) {
+            return Ok(TestRomStatus::Halted(pc));
+        }
+
+        match status_port.and_then(StatusPort::take_status) {
+            Some(TestRomStatus::ResetNeeded) => {
+                info!("test rom asks for a reset");
+
+                for _ in 0..RESET_DELAY_FRAMES {
+                    let _ = console.step_frame()?;
+                }
+
+                frames += RESET_DELAY_FRAMES;
+
+                console.reset()?;
+            },
+
+            Some(status) if status.exit_code().is_some() => return Ok(status),
+
+            _ => {}
+        }
+    }
+}
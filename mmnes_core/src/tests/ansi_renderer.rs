@@ -0,0 +1,55 @@
+use crate::ansi_renderer::frame_to_ansi;
+use crate::nes_frame::NesFrame;
+use crate::tests::init;
+
+fn create_solid_frame(color: (u8, u8, u8)) -> NesFrame {
+    let mut frame = NesFrame::new(256, 240);
+
+    for y in 0..240 {
+        for x in 0..=255 {
+            frame.set_pixel(x, y as u8, color);
+        }
+    }
+
+    frame
+}
+
+#[test]
+fn solid_red_frame_is_rendered_with_uniform_color_codes() {
+    init();
+    let frame = create_solid_frame((0xFF, 0x00, 0x00));
+
+    let ansi = frame_to_ansi(&frame, 80, 24);
+    let expected_line = format!("\x1b[38;2;255;0;0;48;2;255;0;0m{}\x1b[0m", "▀".repeat(80));
+
+    let lines = ansi.lines().collect::<Vec<&str>>();
+
+    assert_eq!(lines.len(), 24);
+    assert!(lines.iter().all(|line| *line == expected_line));
+}
+
+#[test]
+fn colors_are_emitted_when_they_change_along_a_line() {
+    init();
+    let mut frame = create_solid_frame((0x00, 0x00, 0x00));
+
+    for y in 0..240 {
+        for x in 128..=255 {
+            frame.set_pixel(x, y as u8, (0x00, 0x00, 0xFF));
+        }
+    }
+
+    let ansi = frame_to_ansi(&frame, 4, 2);
+    let expected_line = "\x1b[38;2;0;0;0;48;2;0;0;0m▀▀\x1b[38;2;0;0;255;48;2;0;0;255m▀▀\x1b[0m";
+
+    assert_eq!(ansi, format!("{}\n{}\n", expected_line, expected_line));
+}
+
+#[test]
+fn empty_terminal_renders_nothing() {
+    init();
+    let frame = create_solid_frame((0xFF, 0x00, 0x00));
+
+    assert_eq!(frame_to_ansi(&frame, 0, 24), "");
+    assert_eq!(frame_to_ansi(&frame, 80, 0), "");
+}
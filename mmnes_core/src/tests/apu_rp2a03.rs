@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::apu::APU;
+use crate::apu_rp2a03::{ApuClockRates, ApuRp2A03};
+use crate::cpu_6502::Cpu6502;
+use crate::expansion_audio::ExpansionAudio;
+use crate::memory::Memory;
+use crate::nes_bus::NESBus;
+use crate::region::Region;
+use crate::sound_playback_passive::SoundPlaybackPassive;
+use crate::tests::{create_cpu_with_program, init};
+
+const EXPANSION_SAMPLE: f32 = 0.25;
+
+/// expansion channel with a constant output, counting its clocks
+#[derive(Debug, Default)]
+struct ConstantExpansionAudio {
+    clocks: u32,
+}
+
+impl ExpansionAudio for ConstantExpansionAudio {
+    fn clock(&mut self) {
+        self.clocks += 1;
+    }
+
+    fn sample(&self) -> f32 {
+        EXPANSION_SAMPLE
+    }
+}
+
+fn create_apu() -> ApuRp2A03<SoundPlaybackPassive, Cpu6502, NESBus> {
+    let (cpu, _) = create_cpu_with_program(0x8000, &[0xEA]);
+    let mut apu = ApuRp2A03::new(SoundPlaybackPassive::new(), Rc::new(RefCell::new(cpu)), Rc::new(RefCell::new(NESBus::new())), Region::NTSC);
+    apu.initialize().unwrap();
+
+    apu
+}
+
+#[test]
+fn snapshot_reports_pulse1_duty_volume_and_period_written_to_the_registers() {
+    init();
+
+    let mut apu = create_apu();
+
+    apu.write_byte(0x15, 0x01).unwrap();   // $4015: pulse 1 enabled
+    apu.write_byte(0x00, 0xB9).unwrap();   // $4000: duty 50%, length counter halted, constant volume 9
+    apu.write_byte(0x02, 0xAB).unwrap();   // $4002: timer low
+    apu.write_byte(0x03, 0x0B).unwrap();   // $4003: length index 1 (254), timer high 3
+
+    let snapshot = apu.snapshot();
+    let pulse1 = snapshot.pulse1();
+
+    assert!(pulse1.enabled());
+    assert!(!pulse1.muted());
+    assert_eq!(pulse1.duty_cycle(), 2);
+    assert!(pulse1.constant_volume());
+    assert_eq!(pulse1.volume(), 9);
+    assert_eq!(pulse1.timer_period(), 0x3AB);
+    assert_eq!(pulse1.length_counter(), 254);
+
+    assert!(!snapshot.pulse2().enabled());
+    assert_eq!(snapshot.pulse2().length_counter(), 0);
+}
+
+#[test]
+fn expansion_audio_is_clocked_and_mixed_into_the_apu_output() {
+    init();
+
+    let mut apu = create_apu();
+    let (_, samples) = apu.run(0, 1000).unwrap();
+    let apu_samples = samples.unwrap().samples().to_vec();
+
+    let expansion_audio = Rc::new(RefCell::new(ConstantExpansionAudio::default()));
+    let mut apu = create_apu();
+    apu.attach_expansion_audio(expansion_audio.clone());
+
+    let (_, samples) = apu.run(0, 1000).unwrap();
+    let mixed_samples = samples.unwrap().samples().to_vec();
+
+    assert!(!apu_samples.is_empty());
+    assert_eq!(mixed_samples.len(), apu_samples.len());
+
+    for (mixed, apu) in mixed_samples.iter().zip(apu_samples.iter()) {
+        assert_eq!(*mixed, apu + EXPANSION_SAMPLE);
+    }
+
+    assert_eq!(expansion_audio.borrow().clocks, 1000);
+}
+
+/// frame IRQ flag after writing $4017 (4-step, IRQ enabled) once ```before_write``` cycles have run, then ```after_write``` cycles
+fn frame_irq_after_frame_counter_write(before_write: u32, after_write: u32) -> bool {
+    let mut apu = create_apu();
+
+    apu.run(0, before_write).unwrap();
+    apu.write_byte(0x17, 0x00).unwrap();
+    apu.run(0, after_write).unwrap();
+
+    apu.read_byte(0x15).unwrap() & 0x40 != 0
+}
+
+#[test]
+fn frame_counter_write_restarts_the_sequencer_after_a_3_or_4_cycles_delay() {
+    init();
+
+    // the IRQ step is 14914 APU cycles (29828 CPU cycles) after the sequencer restart
+
+    // written between two APU cycles: restart 4 CPU cycles after the write
+    assert!(!frame_irq_after_frame_counter_write(1000, 29830));
+    assert!(frame_irq_after_frame_counter_write(1000, 29831));
+
+    // written on an APU cycle: restart 3 CPU cycles after the write
+    assert!(!frame_irq_after_frame_counter_write(1001, 29829));
+    assert!(frame_irq_after_frame_counter_write(1001, 29830));
+}
+
+#[test]
+fn frame_counter_write_inhibits_the_irq_at_once_but_restarts_the_sequencer_later() {
+    init();
+
+    let mut apu = create_apu();
+
+    // the IRQ step of the power-on sequence is due 2 cycles after the write
+    apu.run(0, 29826).unwrap();
+    apu.write_byte(0x17, 0x00).unwrap();
+    apu.run(0, 2).unwrap();
+    assert_eq!(apu.read_byte(0x15).unwrap() & 0x40, 0x40);
+
+    let mut apu = create_apu();
+
+    apu.run(0, 29826).unwrap();
+    apu.write_byte(0x17, 0x40).unwrap();
+    apu.run(0, 2).unwrap();
+    assert_eq!(apu.read_byte(0x15).unwrap() & 0x40, 0x00);
+}
+
+#[test]
+fn pal_clock_rates_convert_the_cpu_cycles_budget_to_apu_cycles_and_samples() {
+    init();
+
+    let rates = ApuClockRates::new(Region::PAL);
+    let one_second = Region::PAL.cpu_clock_rate() as u32;
+
+    assert_eq!(rates.convert_cpu_cycles_to_apu_cycles(one_second as u64), 831_303);
+    assert_eq!(rates.apu_clock_rate(), 831_303.5);
+
+    let (cpu, _) = create_cpu_with_program(0x8000, &[0xEA]);
+    let mut apu = ApuRp2A03::new_with_clock_rates(SoundPlaybackPassive::new(), Rc::new(RefCell::new(cpu)),
+                                                  Rc::new(RefCell::new(NESBus::new())), Region::PAL, rates);
+    apu.initialize().unwrap();
+
+    apu.write_byte(0x0E, 0x0F).unwrap();   // $400E: noise period 15
+    assert_eq!(apu.snapshot().noise().timer_period(), 3778);
+
+    // the passive playback keeps a bounded buffer, the samples are collected on small budgets
+    let mut count = 0;
+    let mut cycle = 0;
+
+    while cycle < one_second {
+        let credits = (one_second - cycle).min(10_000);
+        let (next_cycle, samples) = apu.run(cycle, credits).unwrap();
+
+        count += samples.map_or(0, |samples| samples.samples().len() as i64);
+        cycle = next_cycle;
+    }
+
+    assert!((count - 44_100).abs() <= 1, "{} samples for one second of PAL CPU cycles", count);
+}
@@ -64,6 +64,15 @@ fn get_chr_memory_size_and_type_returns_chr_ram_size_and_false_when_chr_rom_size
     assert_eq!(result, (chr_ram_size, false));
 }
 
+#[test]
+fn get_chr_memory_size_and_type_returns_8kb_of_chr_ram_when_neither_is_declared() {
+    init();
+
+    let result = get_chr_memory_size_and_type(0, 0);
+
+    assert_eq!(result, (8192, false));
+}
+
 #[test]
 fn create_split_ram_memory_creates_correct_number_of_banks_and_address_ranges() {
     init();
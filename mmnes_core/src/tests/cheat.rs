@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::bus::Bus;
+use crate::cheat::{Cheat, CheatError, Cheats};
+use crate::memory::Memory;
+use crate::memory_bank::MemoryBank;
+use crate::nes_bus::NESBus;
+use crate::tests::init;
+
+#[test]
+fn game_genie_6_letters_code_decodes_to_address_and_value() {
+    init();
+
+    let cheat = Cheat::from_code("GOSSIP").unwrap();
+
+    assert_eq!(cheat.address(), 0xD1DD);
+    assert_eq!(cheat.value(), 0x14);
+    assert_eq!(cheat.compare(), None);
+}
+
+#[test]
+fn game_genie_8_letters_code_decodes_to_address_value_and_compare() {
+    init();
+
+    let cheat = Cheat::from_code("zexpygla").unwrap();
+
+    assert_eq!(cheat.address(), 0x94A7);
+    assert_eq!(cheat.value(), 0x02);
+    assert_eq!(cheat.compare(), Some(0x03));
+}
+
+#[test]
+fn raw_code_decodes_to_address_value_and_compare() {
+    init();
+
+    assert_eq!(Cheat::from_code("075A:09").unwrap(), Cheat::new(0x075A, 0x09, None));
+    assert_eq!(Cheat::from_code("C010:EA:A9").unwrap(), Cheat::new(0xC010, 0xEA, Some(0xA9)));
+}
+
+#[test]
+fn invalid_codes_are_rejected() {
+    init();
+
+    for code in ["GOSSI", "GOSSIPAA0", "GOSSIB", "8000", "8000:1FF", ""] {
+        assert_eq!(Cheat::from_code(code), Err(CheatError::InvalidCode(code.to_string())), "{}", code);
+    }
+}
+
+#[test]
+fn code_with_compare_only_overrides_reads_when_original_byte_matches() {
+    init();
+
+    let prg_rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    prg_rom.borrow_mut().write_byte(0x14A7, 0x03).unwrap();
+
+    let cheats = Rc::new(RefCell::new(Cheats::new()));
+    let mut bus = NESBus::new();
+    bus.add_device(prg_rom.clone()).unwrap();
+    bus.attach_cheats(cheats.clone());
+
+    cheats.borrow_mut().add(Cheat::from_code("ZEXPYGLA").unwrap());
+    assert_eq!(bus.read_byte(0x94A7), Ok(0x02));
+
+    // another bank is mapped: the original byte differs, the read is left untouched
+    prg_rom.borrow_mut().write_byte(0x14A7, 0x55).unwrap();
+    assert_eq!(bus.read_byte(0x94A7), Ok(0x55));
+
+    assert!(cheats.borrow_mut().remove(&Cheat::from_code("ZEXPYGLA").unwrap()));
+    prg_rom.borrow_mut().write_byte(0x14A7, 0x03).unwrap();
+    assert_eq!(bus.read_byte(0x94A7), Ok(0x03));
+}
+
+#[test]
+fn code_without_compare_always_overrides_reads() {
+    init();
+
+    let prg_rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    prg_rom.borrow_mut().write_byte(0x51DD, 0xA9).unwrap();
+
+    let cheats = Rc::new(RefCell::new(Cheats::new()));
+    let mut bus = NESBus::new();
+    bus.add_device(prg_rom).unwrap();
+    bus.attach_cheats(cheats.clone());
+
+    cheats.borrow_mut().add(Cheat::from_code("GOSSIP").unwrap());
+
+    assert_eq!(bus.read_byte(0xD1DD), Ok(0x14));
+    assert_eq!(bus.trace_read_byte(0xD1DD), Ok(0x14));
+    assert_eq!(bus.read_byte(0xD1DE), Ok(0x00));
+}
+
+#[test]
+fn code_aimed_at_the_ram_does_not_patch_it() {
+    init();
+
+    let wram = Rc::new(RefCell::new(MemoryBank::new(2 * 1024, (0x0000, 0x07FF))));
+    wram.borrow_mut().write_byte(0x0300, 0x42).unwrap();
+
+    let cheats = Rc::new(RefCell::new(Cheats::new()));
+    let mut bus = NESBus::new();
+    bus.add_device(wram).unwrap();
+    bus.attach_cheats(cheats.clone());
+
+    cheats.borrow_mut().add(Cheat::from_code("0300:05").unwrap());
+
+    assert_eq!(bus.read_byte(0x0300), Ok(0x42));
+    assert_eq!(bus.trace_read_byte(0x0300), Ok(0x42));
+}
@@ -1,9 +1,13 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use crate::bus::MockBusStub;
-use crate::cpu::{CpuError, Interruptible};
-use crate::cpu_6502::{Cpu6502, APU_DMC_IRQ, APU_FRAME_COUNTER_IRQ, PPU_NMI};
-use crate::tests::init;
+use crate::bus::{Bus, MockBusStub};
+use crate::cpu::{CpuError, Interruptible, CPU};
+use crate::cpu_6502::{Cpu6502, UnstableOpcodeMode, APU_DMC_IRQ, APU_FRAME_COUNTER_IRQ, PPU_NMI};
+use crate::custom_io_device::CustomIoDevice;
+use crate::memory::Memory;
+use crate::memory_bank::MemoryBank;
+use crate::nes_bus::NESBus;
+use crate::tests::{create_cpu_with_program, init};
 
 
 fn create_bus() -> MockBusStub {
@@ -240,4 +244,545 @@ fn assert_irq_works_when_both_nmi_and_irq_are_pending() -> Result<(), CpuError>
     assert_eq!(result, true);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// canonical cycles of the illegal RMW instructions in absolute,X addressing, with or without page crossing
+const ILLEGAL_RMW_ABSOLUTE_X_CYCLES: [(&str, u8, u32); 6] = [
+    ("SLO", 0x1F, 7),
+    ("RLA", 0x3F, 7),
+    ("SRE", 0x5F, 7),
+    ("RRA", 0x7F, 7),
+    ("DCP", 0xDF, 7),
+    ("ISB", 0xFF, 7),
+];
+
+fn run_absolute_x_instruction(opcode: u8, x: u8, base_addr: u16) -> Result<u32, CpuError> {
+    let program = [0xA2, x, opcode, base_addr as u8, (base_addr >> 8) as u8];
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &program);
+
+    cpu.step_instruction()?;
+    cpu.step_instruction()
+}
+
+#[test]
+fn illegal_rmw_absolute_x_takes_the_cycles_of_the_table_without_page_crossing() -> Result<(), CpuError> {
+    init();
+
+    for (mnemonic, opcode, expected) in ILLEGAL_RMW_ABSOLUTE_X_CYCLES {
+        let cycles = run_absolute_x_instruction(opcode, 0x01, 0x0200)?;
+        assert_eq!(cycles, expected, "{} (0x{:02X})", mnemonic, opcode);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn illegal_rmw_absolute_x_does_not_add_a_page_crossing_cycle() -> Result<(), CpuError> {
+    init();
+
+    for (mnemonic, opcode, expected) in ILLEGAL_RMW_ABSOLUTE_X_CYCLES {
+        let cycles = run_absolute_x_instruction(opcode, 0xFF, 0x0201)?;
+        assert_eq!(cycles, expected, "{} (0x{:02X})", mnemonic, opcode);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn las_absolute_y_adds_a_page_crossing_cycle() -> Result<(), CpuError> {
+    init();
+    let program = [0xA0, 0xFF, 0xBB, 0x01, 0x02];
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &program);
+
+    cpu.step_instruction()?;
+    let cycles = cpu.step_instruction()?;
+    assert_eq!(cycles, 5);
+
+    Ok(())
+}
+
+#[test]
+fn unofficial_opcode_stops_the_cpu_when_halt_on_unimplemented_is_set() -> Result<(), CpuError> {
+    init();
+    // DCP $10, LDA #$42
+    let program = [0xC7, 0x10, 0xA9, 0x42];
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &program);
+    cpu.set_halt_on_unimplemented(true);
+
+    let result = cpu.step_instruction();
+    assert!(matches!(result, Err(CpuError::Unimplemented { opcode: 0xC7 })), "{:?}", result);
+    assert_eq!(cpu.snapshot()?.pc(), 0x8000);
+
+    Ok(())
+}
+
+#[test]
+fn unofficial_opcode_is_executed_when_halt_on_unimplemented_is_clear() -> Result<(), CpuError> {
+    init();
+    let program = [0xC7, 0x10, 0xA9, 0x42];
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &program);
+
+    cpu.step_instruction()?;
+    assert_eq!(cpu.snapshot()?.pc(), 0x8002);
+
+    cpu.step_instruction()?;
+    let snapshot = cpu.snapshot()?;
+    assert_eq!(snapshot.pc(), 0x8004);
+    assert_eq!(snapshot.a(), 0x42);
+
+    Ok(())
+}
+
+/***
+ * reference SBC computed on signed 16 bits integers, independently of the CPU implementation:
+ * returns A and the C, Z, N and V flags
+ ***/
+fn reference_sbc(a: u8, value: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let borrow = !carry as i16;
+    let unsigned = a as i16 - value as i16 - borrow;
+    let signed = a as i8 as i16 - value as i8 as i16 - borrow;
+    let result = unsigned as u8;
+
+    (result, unsigned >= 0, result == 0, result & 0x80 != 0, !(-128..=127).contains(&signed))
+}
+
+fn assert_sbc_matches_reference(opcode: u8, step: usize) -> Result<(), CpuError> {
+    // $8000 LDA #a, $8002 SEC / CLC, $8003 SBC #value
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[0xA9, 0x00, 0x38, opcode, 0x00]);
+
+    for a in (0..=0xFFu8).step_by(step) {
+        for value in 0..=0xFFu8 {
+            for carry in [false, true] {
+                ram.borrow_mut().write_byte(0x8001, a)?;
+                ram.borrow_mut().write_byte(0x8002, if carry { 0x38 } else { 0x18 })?;
+                ram.borrow_mut().write_byte(0x8004, value)?;
+
+                cpu.set_pc_immediate(0x8000)?;
+                for _ in 0..3 {
+                    cpu.step_instruction()?;
+                }
+
+                let snapshot = cpu.snapshot()?;
+                let p = snapshot.p();
+                let actual = (snapshot.a(), p & 0x01 != 0, p & 0x02 != 0, p & 0x80 != 0, p & 0x40 != 0);
+
+                assert_eq!(actual, reference_sbc(a, value, carry), "0x{:02X}: A=0x{:02X} M=0x{:02X} C={}", opcode, a, value, carry);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sbc_matches_the_reference_for_all_operands_and_carries() -> Result<(), CpuError> {
+    init();
+    assert_sbc_matches_reference(0xE9, 1)
+}
+
+#[test]
+fn illegal_usbc_matches_the_reference_for_sampled_operands() -> Result<(), CpuError> {
+    init();
+    assert_sbc_matches_reference(0xEB, 15)
+}
+
+/// documented compare: C when register >= value, Z when equal, N is bit 7 of the wrapped register - value
+fn reference_compare(register: u8, value: u8) -> (bool, bool, bool) {
+    let result = register.wrapping_sub(value);
+
+    (register >= value, register == value, result & 0x80 != 0)
+}
+
+/// compares each register value against each operand with ```load``` #register then ```opcode``` #value
+fn assert_compare_matches_reference(load: u8, opcode: u8) -> Result<(), CpuError> {
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[load, 0x00, opcode, 0x00]);
+
+    for register in 0..=0xFFu8 {
+        for value in 0..=0xFFu8 {
+            ram.borrow_mut().write_byte(0x8001, register)?;
+            ram.borrow_mut().write_byte(0x8003, value)?;
+
+            cpu.set_pc_immediate(0x8000)?;
+            cpu.step_instruction()?;
+            cpu.step_instruction()?;
+
+            let p = cpu.snapshot()?.p();
+            let actual = (p & 0x01 != 0, p & 0x02 != 0, p & 0x80 != 0);
+
+            assert_eq!(actual, reference_compare(register, value), "0x{:02X}: R=0x{:02X} M=0x{:02X}", opcode, register, value);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn cmp_cpx_cpy_match_the_reference_for_all_operands() -> Result<(), CpuError> {
+    init();
+
+    assert_compare_matches_reference(0xA9, 0xC9)?;
+    assert_compare_matches_reference(0xA2, 0xE0)?;
+    assert_compare_matches_reference(0xA0, 0xC0)
+}
+
+#[test]
+fn cmp_flags_on_the_boundaries() -> Result<(), CpuError> {
+    init();
+
+    // (A, M) -> (C, Z, N)
+    let expected = [
+        ((0x40, 0x40), (true, true, false)),
+        ((0x80, 0x80), (true, true, false)),
+        ((0x00, 0x00), (true, true, false)),
+        ((0x3F, 0x40), (false, false, true)),
+        ((0x00, 0x01), (false, false, true)),
+        ((0x00, 0x80), (false, false, true)),
+        ((0x01, 0x80), (false, false, true)),
+        ((0x00, 0x81), (false, false, false)),
+        ((0x80, 0x00), (true, false, true)),
+        ((0xFF, 0x00), (true, false, true)),
+        ((0xFF, 0x7F), (true, false, true)),
+        ((0xFF, 0x80), (true, false, false)),
+    ];
+
+    for ((a, value), flags) in expected {
+        let (mut cpu, _) = create_cpu_with_program(0x8000, &[0xA9, a, 0xC9, value]);
+        cpu.step_instruction()?;
+        cpu.step_instruction()?;
+
+        let p = cpu.snapshot()?.p();
+        assert_eq!((p & 0x01 != 0, p & 0x02 != 0, p & 0x80 != 0), flags, "A=0x{:02X} M=0x{:02X}", a, value);
+    }
+
+    Ok(())
+}
+
+/// runs SED, CLC / SEC, LDA #a, ARR #value and returns A and the C, Z, N and V flags
+fn run_arr(decimal_mode: bool, a: u8, value: u8, carry: bool) -> Result<(u8, bool, bool, bool, bool), CpuError> {
+    let set_carry = if carry { 0x38 } else { 0x18 };
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &[0xF8, set_carry, 0xA9, a, 0x6B, value]);
+    cpu.set_decimal_mode(decimal_mode);
+
+    for _ in 0..4 {
+        cpu.step_instruction()?;
+    }
+
+    let snapshot = cpu.snapshot()?;
+    let p = snapshot.p();
+
+    Ok((snapshot.a(), p & 0x01 != 0, p & 0x02 != 0, p & 0x80 != 0, p & 0x40 != 0))
+}
+
+#[test]
+fn arr_in_decimal_mode_applies_the_bcd_fixups() -> Result<(), CpuError> {
+    init();
+
+    // (A, operand, C) -> (A, C, Z, N, V)
+    let expected = [
+        ((0xFF, 0xFF, false), (0xD5, true, false, false, false)),
+        ((0x00, 0xFF, true), (0x80, false, false, true, false)),
+        ((0x44, 0xFF, false), (0x22, false, false, false, true)),
+        ((0x5A, 0xFF, false), (0x83, true, false, false, true)),
+        ((0x01, 0x01, false), (0x00, false, true, false, false)),
+    ];
+
+    for ((a, value, carry), result) in expected {
+        assert_eq!(run_arr(true, a, value, carry)?, result, "A=0x{:02X} M=0x{:02X} C={}", a, value, carry);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn arr_ignores_the_decimal_flag_without_decimal_mode() -> Result<(), CpuError> {
+    init();
+
+    assert_eq!(run_arr(false, 0xFF, 0xFF, false)?, (0x7F, true, false, false, false));
+    assert_eq!(run_arr(false, 0x5A, 0xFF, false)?, (0x2D, false, false, false, true));
+
+    Ok(())
+}
+
+/// SHA $base,Y with A = $FF and X = $0F, returns the RAM at the two candidate targets: the effective address
+/// and the effective address with its high byte ANDed with X
+fn run_sha(mode: UnstableOpcodeMode, rdy_low: bool, base: u16, y: u8) -> Result<(u8, u8), CpuError> {
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[0xA9, 0xFF, 0xA2, 0x0F, 0xA0, y, 0x9F, base as u8, (base >> 8) as u8]);
+    cpu.set_unstable_opcode_mode(mode);
+
+    for _ in 0..3 {
+        cpu.step_instruction()?;
+    }
+
+    if rdy_low {
+        cpu.pull_rdy_low();
+    }
+
+    cpu.step_instruction()?;
+
+    let addr = base.wrapping_add(y as u16);
+    let corrupted_addr = (addr & 0x00FF) | ((((addr >> 8) as u8 & 0x0F) as u16) << 8);
+    let ram = ram.borrow();
+
+    Ok((ram.read_byte(addr)?, ram.read_byte(corrupted_addr)?))
+}
+
+#[test]
+fn sha_ands_with_the_high_byte_unless_the_rdy_line_drops_in_hardware_mode() -> Result<(), CpuError> {
+    init();
+
+    // no page crossed: $1200 + $10, the value is A & X & ($12 + 1)
+    assert_eq!(run_sha(UnstableOpcodeMode::StableHigh, true, 0x1200, 0x10)?.0, 0x03);
+    assert_eq!(run_sha(UnstableOpcodeMode::Hardware, false, 0x1200, 0x10)?.0, 0x03);
+    assert_eq!(run_sha(UnstableOpcodeMode::Hardware, true, 0x1200, 0x10)?.0, 0x0F);
+
+    // page crossed: $12F0 + $20, the high byte of the target is ANDed with X ($0310)
+    assert_eq!(run_sha(UnstableOpcodeMode::StableHigh, true, 0x12F0, 0x20)?, (0x00, 0x13));
+    assert_eq!(run_sha(UnstableOpcodeMode::Hardware, false, 0x12F0, 0x20)?, (0x00, 0x13));
+    assert_eq!(run_sha(UnstableOpcodeMode::Hardware, true, 0x12F0, 0x20)?, (0x0F, 0x00));
+
+    Ok(())
+}
+
+#[test]
+fn run_until_return_stops_right_after_the_rts_of_the_current_subroutine() -> Result<(), CpuError> {
+    init();
+
+    // $8000 JSR $8010, $8003 NOP
+    // $8010 NOP, JSR $8020, RTS; $8020 INX, RTS
+    // NMI handler: $8030 JSR $8020, RTI
+    let mut program = vec![0xEA; 0x40];
+    program[0x00..0x03].copy_from_slice(&[0x20, 0x10, 0x80]);
+    program[0x10..0x15].copy_from_slice(&[0xEA, 0x20, 0x20, 0x80, 0x60]);
+    program[0x20..0x22].copy_from_slice(&[0xE8, 0x60]);
+    program[0x30..0x34].copy_from_slice(&[0x20, 0x20, 0x80, 0x40]);
+
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &program);
+    ram.borrow_mut().write_word(0xFFFA, 0x8030)?;
+
+    cpu.step_instruction()?;
+    let sp = cpu.snapshot()?.sp();
+
+    // taken after the first instruction of the subroutine, its RTS (and the nested ones) do not match
+    cpu.signal_nmi()?;
+    let (_, returned) = cpu.run_until_return(100)?;
+    let snapshot = cpu.snapshot()?;
+
+    assert!(returned);
+    assert_eq!(snapshot.pc(), 0x8003);
+    assert_eq!(snapshot.sp(), sp.wrapping_add(2));
+    assert_eq!(snapshot.x(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn inc_absolute_writes_the_unmodified_value_back_before_the_incremented_one() -> Result<(), CpuError> {
+    init();
+
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let recorder = writes.clone();
+    let register = CustomIoDevice::new("Write Register", (0x5000, 0x5000))
+        .with_read(Box::new(|_, _| 0x41))
+        .with_write(Box::new(move |_, value| recorder.borrow_mut().push(value)));
+
+    // $8000 INC $5000
+    let rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    rom.borrow_mut().write_byte(0x0000, 0xEE)?;
+    rom.borrow_mut().write_word(0x0001, 0x5000)?;
+    rom.borrow_mut().write_word(0x7FFC, 0x8000)?;
+
+    let mut bus = NESBus::new();
+    bus.add_device(rom).unwrap();
+    bus.add_device(Rc::new(RefCell::new(register))).unwrap();
+
+    let mut cpu = Cpu6502::new(Rc::new(RefCell::new(bus)));
+    cpu.reset()?;
+    cpu.step_instruction()?;
+
+    assert_eq!(*writes.borrow(), vec![0x41, 0x42]);
+
+    Ok(())
+}
+
+#[test]
+fn snapshot_reports_the_base_cycles_and_page_cross_penalty() -> Result<(), CpuError> {
+    init();
+    // $8000 LDA $12F0,X, $8003 NOP, $8004 BNE $8004
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &[0xBD, 0xF0, 0x12, 0xEA, 0xD0, 0xFE]);
+
+    let expected = [(4, true), (2, false), (2, true)];
+
+    for (base_cycles, page_cross_penalty) in expected {
+        let snapshot = cpu.snapshot()?;
+        assert_eq!(snapshot.base_cycles(), base_cycles, "{} at 0x{:04X}", snapshot.mnemonic(), snapshot.pc());
+        assert_eq!(snapshot.page_cross_penalty(), page_cross_penalty, "{} at 0x{:04X}", snapshot.mnemonic(), snapshot.pc());
+        cpu.step_instruction()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn every_opcode_is_implemented_with_a_valid_operand() {
+    init();
+
+    for opcode in 0..=0xFFu8 {
+        let (mut cpu, _) = create_cpu_with_program(0x8000, &[opcode, 0x10, 0x20]);
+
+        match cpu.step_instruction() {
+            Err(CpuError::Unimplemented { opcode: code }) => panic!("opcode 0x{:02X} is not implemented", code),
+            Err(CpuError::InvalidOperand { opcode: code, mode }) => panic!("opcode 0x{:02X}: invalid {} operand", code, mode),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn structured_cpu_errors_display_the_opcode() {
+    init();
+
+    assert_eq!(CpuError::Unimplemented { opcode: 0xC7 }.to_string(), "unimplemented: opcode 0xC7");
+    assert_eq!(CpuError::InvalidOperand { opcode: 0xBD, mode: "absolute,X" }.to_string(),
+               "missing or invalid operand: opcode 0xBD, absolute,X addressing");
+}
+
+#[test]
+fn unimplemented_opcode_is_reported_with_its_structured_error() {
+    init();
+    // DCP $10
+    let (mut cpu, _) = create_cpu_with_program(0x8000, &[0xC7, 0x10]);
+    cpu.set_halt_on_unimplemented(true);
+
+    let result = cpu.step_instruction();
+    assert!(matches!(result, Err(CpuError::Unimplemented { opcode: 0xC7 })), "{:?}", result);
+}
+
+/// runs SEC / CLC, LDA #a, BIT $10 with ```value``` at $10 and returns A and the C, Z, N and V flags
+fn run_bit(a: u8, value: u8, carry: bool) -> Result<(u8, bool, bool, bool, bool), CpuError> {
+    let set_carry = if carry { 0x38 } else { 0x18 };
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[set_carry, 0xA9, a, 0x24, 0x10]);
+    ram.borrow_mut().write_byte(0x0010, value)?;
+
+    for _ in 0..3 {
+        cpu.step_instruction()?;
+    }
+
+    let snapshot = cpu.snapshot()?;
+    let p = snapshot.p();
+
+    Ok((snapshot.a(), p & 0x01 != 0, p & 0x02 != 0, p & 0x80 != 0, p & 0x40 != 0))
+}
+
+#[test]
+fn bit_with_a_zero_accumulator_sets_zero_whatever_the_memory() -> Result<(), CpuError> {
+    init();
+
+    for value in [0x00, 0x01, 0x3F, 0xC0, 0xFF] {
+        let (_, _, zero, _, _) = run_bit(0x00, value, false)?;
+        assert!(zero, "M=0x{:02X}", value);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bit_copies_bits_7_and_6_of_the_memory_to_n_and_v() -> Result<(), CpuError> {
+    init();
+
+    // (A, M) -> (A, C, Z, N, V)
+    assert_eq!(run_bit(0xFF, 0xC0, false)?, (0xFF, false, false, true, true));
+    assert_eq!(run_bit(0x00, 0xC0, false)?, (0x00, false, true, true, true));
+    assert_eq!(run_bit(0xFF, 0x3F, false)?, (0xFF, false, false, false, false));
+
+    Ok(())
+}
+
+#[test]
+fn bit_leaves_the_accumulator_and_the_carry_untouched() -> Result<(), CpuError> {
+    init();
+
+    for carry in [false, true] {
+        let (a, c, _, _, _) = run_bit(0x5A, 0xA5, carry)?;
+        assert_eq!((a, c), (0x5A, carry));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn jmp_indirect_on_a_page_boundary_is_disassembled_and_taken_with_the_page_wrap() -> Result<(), CpuError> {
+    init();
+
+    // JMP ($10FF): the high byte of the target is read from $1000, not from $1100
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[0x6C, 0xFF, 0x10]);
+    ram.borrow_mut().write_byte(0x10FF, 0x34)?;
+    ram.borrow_mut().write_byte(0x1000, 0x12)?;
+    ram.borrow_mut().write_byte(0x1100, 0x56)?;
+
+    let snapshot = cpu.snapshot()?;
+    assert_eq!(format!("{} {}", snapshot.mnemonic(), snapshot.operand()), "JMP ($10FF) = 1234");
+
+    cpu.step_instruction()?;
+    assert_eq!(cpu.snapshot()?.pc(), 0x1234);
+
+    Ok(())
+}
+
+/// SEC, SED, LDA #$80 then a NMI to $9000: returns the CPU about to run the RTI of the handler, with the stacked P address
+fn run_to_nmi_handler() -> Result<(Cpu6502, Rc<RefCell<MemoryBank>>, u16), CpuError> {
+    let (mut cpu, ram) = create_cpu_with_program(0x8000, &[0x38, 0xF8, 0xA9, 0x80, 0xEA]);
+    ram.borrow_mut().write_word(0xFFFA, 0x9000)?;
+    ram.borrow_mut().write_byte(0x9000, 0x40)?;
+
+    for _ in 0..3 {
+        cpu.step_instruction()?;
+    }
+
+    cpu.signal_nmi()?;
+    cpu.step_instruction()?;
+
+    let snapshot = cpu.snapshot()?;
+    assert_eq!(snapshot.pc(), 0x9000);
+
+    Ok((cpu, ram, 0x0100 + snapshot.sp() as u16 + 1))
+}
+
+#[test]
+fn interrupt_pushes_b_clear_and_rti_restores_the_other_flags_exactly() -> Result<(), CpuError> {
+    init();
+    let (mut cpu, ram, stacked_p) = run_to_nmi_handler()?;
+
+    // N, U, D, I, C
+    assert_eq!(ram.borrow().read_byte(stacked_p)?, 0xAD);
+
+    cpu.step_instruction()?;
+    let snapshot = cpu.snapshot()?;
+
+    // the NMI is taken at the end of the NOP
+    assert_eq!(snapshot.p(), 0xAD);
+    assert_eq!(snapshot.pc(), 0x8005);
+
+    Ok(())
+}
+
+#[test]
+fn rti_and_plp_clear_b_and_set_unused_whatever_the_stacked_status() -> Result<(), CpuError> {
+    init();
+
+    for stacked in [0x00, 0x10, 0xFF, 0xDF] {
+        let (mut cpu, ram, stacked_p) = run_to_nmi_handler()?;
+        ram.borrow_mut().write_byte(stacked_p, stacked)?;
+
+        cpu.step_instruction()?;
+        assert_eq!(cpu.snapshot()?.p(), (stacked & 0xCF) | 0x20, "RTI, stacked P=0x{:02X}", stacked);
+
+        // PHA of the stacked status, then PLP
+        let (mut cpu, _) = create_cpu_with_program(0x8000, &[0xA9, stacked, 0x48, 0x28]);
+        for _ in 0..3 {
+            cpu.step_instruction()?;
+        }
+        assert_eq!(cpu.snapshot()?.p(), (stacked & 0xCF) | 0x20, "PLP, stacked P=0x{:02X}", stacked);
+    }
+
+    Ok(())
+}
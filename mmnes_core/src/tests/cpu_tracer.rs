@@ -0,0 +1,316 @@
+use std::cell::RefCell;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::cpu_6502::Cpu6502;
+use crate::cpu_tracer::Tracer;
+use crate::ines_loader::INesLoader;
+use crate::loader::Loader;
+use crate::memory::Memory;
+use crate::nes_bus::NESBus;
+use crate::ppu::PpuClock;
+use crate::region::Region;
+use crate::tests::{create_cpu_with_program, create_memory_bank, init, SharedBuffer};
+
+const NESTEST_ROM_ENV: &str = "NESTEST_ROM";
+const NESTEST_LOG_ENV: &str = "NESTEST_LOG";
+const NESTEST_ENTRY_POINT: u16 = 0xC000;
+// the official opcodes, the unofficial ones start at line 5004 and are covered by the tests of the CPU
+const NESTEST_COMPARED_LINES: usize = 5003;
+const TRACE_CONTEXT_LINES: usize = 5;
+const SYNTHETIC_PROGRAM_ORIGIN: u16 = 0xC000;
+// the trace of synthetic_program, checked by hand
+const SYNTHETIC_GOLDEN_LOG: &str = include_str!("assets/synthetic_trace.log");
+
+#[test]
+fn ppu_position_is_derived_from_cpu_cycles() {
+    init();
+
+    assert_eq!(Tracer::ppu_position_from_cycles(0), (0, 0));
+    assert_eq!(Tracer::ppu_position_from_cycles(7), (0, 21));
+    assert_eq!(Tracer::ppu_position_from_cycles(113), (0, 339));
+    assert_eq!(Tracer::ppu_position_from_cycles(114), (1, 1));
+    assert_eq!(Tracer::ppu_position_from_cycles(29781), (0, 1));
+}
+
+#[test]
+fn tracing_writes_nintendulator_lines() {
+    init();
+
+    let program = [
+        0xA2, 0x05,         // LDX #$05
+        0x86, 0x10,         // STX $10
+        0xA9, 0x80,         // LDA #$80
+        0x04, 0xA9,         // NOP $A9 (illegal)
+        0x4C, 0x00, 0xC0,   // JMP $C000
+    ];
+
+    let (mut cpu, _) = create_cpu_with_program(0xC000, &program);
+    let buffer = SharedBuffer::default();
+    cpu.enable_tracing(Box::new(buffer.clone()));
+
+    for _ in 0..6 {
+        cpu.step_instruction().unwrap();
+    }
+
+    let expected = [
+        "C000  A2 05     LDX #$05                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+        "C002  86 10     STX $10 = 00                    A:00 X:05 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9",
+        "C004  A9 80     LDA #$80                        A:00 X:05 Y:00 P:24 SP:FD PPU:  0, 36 CYC:12",
+        "C006  04 A9    *NOP $A9 = 00                    A:80 X:05 Y:00 P:A4 SP:FD PPU:  0, 42 CYC:14",
+        "C008  4C 00 C0  JMP $C000                       A:80 X:05 Y:00 P:A4 SP:FD PPU:  0, 51 CYC:17",
+        "C000  A2 05     LDX #$05                        A:80 X:05 Y:00 P:A4 SP:FD PPU:  0, 60 CYC:20",
+    ];
+
+    assert_eq!(buffer.lines(), expected);
+}
+
+#[test]
+fn tracing_reports_the_attached_ppu_clock_position() {
+    init();
+
+    let (mut cpu, _) = create_cpu_with_program(0xC000, &[0xEA, 0xEA]);
+    let clock = Rc::new(RefCell::new(PpuClock::new(Region::PAL)));
+    cpu.attach_ppu_clock(clock.clone());
+
+    let buffer = SharedBuffer::default();
+    cpu.enable_tracing(Box::new(buffer.clone()));
+
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+
+    let lines = buffer.lines();
+    assert!(lines[0].ends_with("PPU:  0, 22 CYC:7"));
+    assert!(lines[1].ends_with("PPU:  0, 28 CYC:9"));
+    assert_eq!((clock.borrow().scanline(), clock.borrow().dot()), (0, 35));
+}
+
+#[test]
+fn disable_tracing_stops_writing_lines() {
+    init();
+
+    let (mut cpu, _) = create_cpu_with_program(0xC000, &[0xEA, 0xEA, 0xEA]);
+    let buffer = SharedBuffer::default();
+    cpu.enable_tracing(Box::new(buffer.clone()));
+
+    cpu.step_instruction().unwrap();
+    cpu.disable_tracing();
+    cpu.step_instruction().unwrap();
+
+    assert_eq!(buffer.lines().len(), 1);
+}
+
+#[test]
+fn filtered_tracing_only_writes_the_instructions_in_the_pc_range() {
+    init();
+
+    let program = [
+        0x20, 0x10, 0xC0,   // $C000 JSR $C010
+        0xE8,               // $C003 INX
+        0x4C, 0x03, 0xC0,   // $C004 JMP $C003
+    ];
+
+    let subroutine = [
+        0xA9, 0x01,         // $C010 LDA #$01
+        0xA0, 0x02,         // $C012 LDY #$02
+        0x60,               // $C014 RTS
+    ];
+
+    let mut memory = program.to_vec();
+    memory.resize(0x10, 0xEA);
+    memory.extend_from_slice(&subroutine);
+
+    let (mut cpu, _) = create_cpu_with_program(0xC000, &memory);
+    let buffer = SharedBuffer::default();
+    cpu.enable_tracing_filtered(Box::new(buffer.clone()), Some((0xC010, 0xC01F)));
+
+    for _ in 0..7 {
+        cpu.step_instruction().unwrap();
+    }
+
+    let pcs = buffer.lines()
+        .iter()
+        .map(|line| line[..4].to_string())
+        .collect::<Vec<String>>();
+
+    assert_eq!(pcs, ["C010", "C012", "C014"]);
+}
+
+/***
+ * official opcodes in every addressing mode, with the page crossings, the flags of ADC and SBC,
+ * the stack, a subroutine and an indirect jump, ending on JMP $C060 forever
+ ***/
+fn synthetic_program() -> Vec<u8> {
+    let mut program = vec![
+        0xA9, 0x00,         // $C000 LDA #$00
+        0x85, 0x20,         // $C002 STA $20
+        0xA9, 0x03,         // $C004 LDA #$03
+        0x85, 0x21,         // $C006 STA $21
+        0xA2, 0x04,         // $C008 LDX #$04
+        0xA0, 0xFF,         // $C00A LDY #$FF
+        0xA9, 0x7F,         // $C00C LDA #$7F
+        0x9D, 0xFC, 0x02,   // $C00E STA $02FC,X
+        0x69, 0x01,         // $C011 ADC #$01
+        0x91, 0x20,         // $C013 STA ($20),Y
+        0xB1, 0x20,         // $C015 LDA ($20),Y
+        0xC8,               // $C017 INY
+        0xB1, 0x20,         // $C018 LDA ($20),Y
+        0x38,               // $C01A SEC
+        0xE9, 0x80,         // $C01B SBC #$80
+        0x08,               // $C01D PHP
+        0x48,               // $C01E PHA
+        0x68,               // $C01F PLA
+        0x28,               // $C020 PLP
+        0xA1, 0x1C,         // $C021 LDA ($1C,X)
+        0xBD, 0xFF, 0x02,   // $C023 LDA $02FF,X
+        0x0A,               // $C026 ASL A
+        0xE6, 0x30,         // $C027 INC $30
+        0xC6, 0x30,         // $C029 DEC $30
+        0x24, 0x30,         // $C02B BIT $30
+        0x20, 0x40, 0xC0,   // $C02D JSR $C040
+        0xF0, 0x02,         // $C030 BEQ $C034
+        0xEA,               // $C032 NOP
+        0xEA,               // $C033 NOP
+        0x6C, 0x50, 0xC0,   // $C034 JMP ($C050)
+    ];
+
+    program.resize(0x40, 0xEA);
+    program.extend_from_slice(&[
+        0xA2, 0x10,         // $C040 LDX #$10
+        0xCA,               // $C042 DEX
+        0xD0, 0xFD,         // $C043 BNE $C042
+        0x60,               // $C045 RTS
+    ]);
+
+    program.resize(0x50, 0xEA);
+    program.extend_from_slice(&[0x60, 0xC0]);
+
+    program.resize(0x60, 0xEA);
+    program.extend_from_slice(&[0x4C, 0x60, 0xC0]);     // $C060 JMP $C060
+
+    program
+}
+
+/// The index of the first traced line differing from the reference, a missing line is a divergence.
+fn first_divergence(lines: &[String], expected: &[&str]) -> Option<usize> {
+    expected.iter()
+        .enumerate()
+        .position(|(index, expected_line)| lines.get(index).map(String::as_str) != Some(*expected_line))
+}
+
+/// The reference and the traced lines around the divergence at ```index```.
+fn divergence_report(lines: &[String], expected: &[&str], index: usize) -> String {
+    let start = index.saturating_sub(TRACE_CONTEXT_LINES);
+    let mut report = format!("trace differs at line {}:\n", index + 1);
+
+    for line in &lines[start.min(lines.len())..index.min(lines.len())] {
+        report.push_str(&format!("           {}\n", line));
+    }
+
+    report.push_str(&format!("  expected {}\n", expected[index]));
+    report.push_str(&format!("  traced   {}\n", lines.get(index).map_or("(no line)", String::as_str)));
+    report
+}
+
+/***
+ * Traces ```cpu``` and compares its trace, line by line, to the first ```max_lines``` lines of the Nintendulator log
+ * ```golden_log```, including the PPU and CYC columns. The first divergence fails with the lines traced before it,
+ * a CPU error ends the trace where it occurred. The log must have the ```max_lines``` lines, and as many are traced.
+ ***/
+fn assert_cpu_trace_matches(cpu: &mut Cpu6502, golden_log: &str, max_lines: usize) {
+    let expected = golden_log.lines().take(max_lines).map(str::trim_end).collect::<Vec<&str>>();
+    assert_eq!(expected.len(), max_lines, "the log has {} lines, {} are compared", expected.len(), max_lines);
+
+    let buffer = SharedBuffer::default();
+    cpu.enable_tracing(Box::new(buffer.clone()));
+
+    let mut cpu_error = None;
+    for _ in 0..expected.len() {
+        if let Err(e) = cpu.step_instruction() {
+            cpu_error = Some(e);
+            break;
+        }
+    }
+
+    let lines = buffer.lines();
+    if let Some(index) = first_divergence(&lines, &expected) {
+        let cause = cpu_error.map(|e| format!("cpu error: {}\n", e)).unwrap_or_default();
+        panic!("{}{}", cause, divergence_report(&lines, &expected, index));
+    }
+
+    assert_eq!(lines.len(), expected.len(), "{} lines traced, {} in the log", lines.len(), expected.len());
+}
+
+/***
+ * Runs ```rom``` from the nestest entry point (the automated mode of nestest) and compares its trace
+ * to the Nintendulator log ```golden_log```, see assert_cpu_trace_matches.
+ ***/
+fn assert_trace_matches(rom: &Path, golden_log: &Path, max_lines: usize) {
+    let golden = read_to_string(golden_log).unwrap_or_else(|e| panic!("{}: {}", golden_log.display(), e));
+    let mut cpu = create_cpu_with_nestest_rom(rom);
+
+    assert_cpu_trace_matches(&mut cpu, &golden, max_lines);
+}
+
+/// The CPU running ```rom``` from the nestest entry point.
+fn create_cpu_with_nestest_rom(rom: &Path) -> Cpu6502 {
+    let cartridge = INesLoader::from_file(rom.to_path_buf()).unwrap().build_cartridge().unwrap();
+    let wram = Rc::new(RefCell::new(create_memory_bank(2 * 1024, (0x0000, 0x1FFF))));
+    wram.borrow_mut().initialize().unwrap();
+
+    let bus = Rc::new(RefCell::new(NESBus::new()));
+    bus.borrow_mut().add_device(wram).unwrap();
+    bus.borrow_mut().add_device(cartridge).unwrap();
+
+    let mut cpu = Cpu6502::new(bus);
+    cpu.reset().unwrap();
+    cpu.set_pc_immediate(NESTEST_ENTRY_POINT).unwrap();
+    cpu
+}
+
+#[test]
+fn first_divergence_finds_the_first_differing_or_missing_line() {
+    init();
+
+    let lines = ["C000  A", "C002  B", "C004  C"].map(String::from);
+
+    assert_eq!(first_divergence(&lines, &["C000  A", "C002  B", "C004  C"]), None);
+    assert_eq!(first_divergence(&lines, &["C000  A", "C002  X", "C004  C"]), Some(1));
+    assert_eq!(first_divergence(&lines[..2], &["C000  A", "C002  B", "C004  C"]), Some(2));
+}
+
+#[test]
+fn divergence_report_shows_the_expected_and_traced_lines_with_their_context() {
+    init();
+
+    let lines = ["C000  A", "C002  B", "C004  C"].map(String::from);
+    let report = divergence_report(&lines, &["C000  A", "C002  B", "C004  X"], 2);
+
+    assert_eq!(report, "trace differs at line 3:\n           C000  A\n           C002  B\n  expected C004  X\n  traced   C004  C\n");
+}
+
+#[test]
+fn tracing_synthetic_program_matches_its_golden_log() {
+    init();
+
+    let (mut cpu, _) = create_cpu_with_program(SYNTHETIC_PROGRAM_ORIGIN, &synthetic_program());
+
+    assert_cpu_trace_matches(&mut cpu, SYNTHETIC_GOLDEN_LOG, SYNTHETIC_GOLDEN_LOG.lines().count());
+}
+
+/***
+ * requires the nestest rom and its golden log, not distributed with the sources:
+ * NESTEST_ROM=/path/to/nestest.nes NESTEST_LOG=/path/to/nestest.log cargo test --features tracing -- --ignored
+ ***/
+#[test]
+#[ignore]
+fn tracing_nestest_matches_golden_log() {
+    init();
+
+    let rom_file = PathBuf::from(std::env::var(NESTEST_ROM_ENV).expect("NESTEST_ROM not set"));
+    let log_file = PathBuf::from(std::env::var(NESTEST_LOG_ENV).expect("NESTEST_LOG not set"));
+
+    assert_trace_matches(&rom_file, &log_file, NESTEST_COMPARED_LINES);
+}
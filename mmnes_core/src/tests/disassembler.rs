@@ -0,0 +1,50 @@
+use crate::disassembler::disassemble_range;
+use crate::memory::Memory;
+use crate::memory_bank::MemoryBank;
+use crate::tests::init;
+
+#[test]
+fn linear_sweep_lists_each_instruction_with_its_bytes() {
+    init();
+
+    let program = [
+        0x78,                   // SEI
+        0xA2, 0xFF,             // LDX #$FF
+        0x9D, 0x00, 0x02,       // STA $0200,X
+        0xB1, 0x10,             // LDA ($10),Y
+        0xD0, 0xF6,             // BNE $8000
+        0x6C, 0xFC, 0xFF,       // JMP ($FFFC)
+        0x0A,                   // ASL A
+        0x04, 0x20,             // NOP $20 (illegal)
+    ];
+
+    let mut memory = MemoryBank::new(0x10000, (0x0000, 0xFFFF));
+    for (offset, byte) in program.iter().enumerate() {
+        memory.write_byte(0x8000 + offset as u16, *byte).unwrap();
+    }
+
+    let lines = disassemble_range(&memory, 0x8000, 0x800F).unwrap();
+
+    assert_eq!(lines, vec![
+        "8000  78        SEI",
+        "8001  A2 FF     LDX #$FF",
+        "8003  9D 00 02  STA $0200,X",
+        "8006  B1 10     LDA ($10),Y",
+        "8008  D0 F6     BNE $8000",
+        "800A  6C FC FF  JMP ($FFFC)",
+        "800D  0A        ASL A",
+        "800E  04 20    *NOP $20",
+    ]);
+}
+
+#[test]
+fn sweep_stops_at_the_end_of_the_address_space() {
+    init();
+
+    let mut memory = MemoryBank::new(0x10000, (0x0000, 0xFFFF));
+    memory.write_byte(0xFFFE, 0x4C).unwrap();
+
+    // the operand of the JMP wraps to $0000, the sweep does not go on from there
+    let lines = disassemble_range(&memory, 0xFFFE, 0xFFFF).unwrap();
+    assert_eq!(lines, vec!["FFFE  4C 00 00  JMP $0000"]);
+}
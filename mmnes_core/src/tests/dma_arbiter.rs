@@ -0,0 +1,106 @@
+use crate::bus::MockBusStub;
+use crate::dma_arbiter::DmaArbiter;
+use crate::dma_device::DmaDevice;
+use crate::memory::MemoryError;
+use crate::tests::init;
+
+const OAM_PAGE: u8 = 0x02;
+const DMC_SAMPLE_ADDRESS: u16 = 0xC000;
+const DMC_SAMPLE: u8 = 0x5A;
+
+/// OAM recording the DMA writes
+#[derive(Debug)]
+struct Oam {
+    data: [Option<u8>; 256],
+}
+
+impl DmaDevice for Oam {
+    fn dma_write(&mut self, offset: u8, value: u8) -> Result<(), MemoryError> {
+        assert!(self.data[offset as usize].is_none(), "OAM offset {} written twice", offset);
+        self.data[offset as usize] = Some(value);
+        Ok(())
+    }
+}
+
+/// the page reads return the low byte of the address, the DMC sample its own value
+fn create_bus() -> MockBusStub {
+    let mut bus = MockBusStub::new();
+
+    bus.expect_read_byte().returning(|addr| {
+        if addr == DMC_SAMPLE_ADDRESS {
+            Ok(DMC_SAMPLE)
+        } else {
+            assert_eq!(addr >> 8, OAM_PAGE as u16, "unexpected read at 0x{:04X}", addr);
+            Ok(addr as u8)
+        }
+    });
+
+    bus
+}
+
+fn assert_oam_copied(oam: &Oam) {
+    for (offset, value) in oam.data.iter().enumerate() {
+        assert_eq!(*value, Some(offset as u8), "OAM offset {}", offset);
+    }
+}
+
+#[test]
+fn oam_dma_takes_513_or_514_cycles_depending_on_the_alignment() -> Result<(), MemoryError> {
+    init();
+
+    let bus = create_bus();
+
+    for (halt_on_get, expected) in [(false, 513), (true, 514)] {
+        let mut oam = Oam { data: [None; 256] };
+        let mut arbiter = DmaArbiter::new();
+
+        arbiter.request_oam(OAM_PAGE);
+        let transfer = arbiter.run(&bus, &mut oam, halt_on_get)?;
+
+        assert_eq!(transfer.cycles(), expected);
+        assert_eq!(transfer.dmc_sample(), None);
+        assert_oam_copied(&oam);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dmc_dma_alone_takes_3_or_4_cycles() -> Result<(), MemoryError> {
+    init();
+
+    let bus = create_bus();
+
+    for (halt_on_get, expected) in [(true, 3), (false, 4)] {
+        let mut oam = Oam { data: [None; 256] };
+        let mut arbiter = DmaArbiter::new();
+
+        arbiter.request_dmc(DMC_SAMPLE_ADDRESS, 0);
+        let transfer = arbiter.run(&bus, &mut oam, halt_on_get)?;
+
+        assert_eq!(transfer.cycles(), expected);
+        assert_eq!(transfer.dmc_sample(), Some(DMC_SAMPLE));
+        assert!(oam.data.iter().all(|value| value.is_none()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dmc_fetch_during_oam_dma_adds_2_cycles_and_the_copy_completes() -> Result<(), MemoryError> {
+    init();
+
+    let bus = create_bus();
+    let mut oam = Oam { data: [None; 256] };
+    let mut arbiter = DmaArbiter::new();
+
+    arbiter.request_oam(OAM_PAGE);
+    arbiter.request_dmc(DMC_SAMPLE_ADDRESS, 100);
+    let transfer = arbiter.run(&bus, &mut oam, false)?;
+
+    assert_eq!(transfer.cycles(), 513 + 2);
+    assert_eq!(transfer.dmc_sample(), Some(DMC_SAMPLE));
+    assert_oam_copied(&oam);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::io::Write;
+use tempfile::NamedTempFile;
+use crate::fds_loader::{FdsFileKind, FdsLoader};
+use crate::loader::{Loader, LoaderError};
+use crate::tests::init;
+
+const DISK_SIDE_SIZE: usize = 65500;
+
+/***
+ * disk side with the disk info and file amount blocks, followed by files of (id, name, address, kind, size)
+ ***/
+fn create_disk_side(side_number: u8, file_amount: u8, files: &[(u8, &[u8; 8], u16, u8, u16)]) -> Vec<u8> {
+    let mut side = vec![0x01];
+    side.extend_from_slice(b"*NINTENDO-HVC*");
+    side.extend_from_slice(&[0x00, b'T', b'S', b'T', 0x20, 0x00, side_number, 0x00, 0x00, 0x00, 0x0F]);
+    side.resize(56, 0x00);
+
+    side.extend_from_slice(&[0x02, file_amount]);
+
+    for (number, (id, name, address, kind, size)) in files.iter().enumerate() {
+        side.extend_from_slice(&[0x03, number as u8, *id]);
+        side.extend_from_slice(*name);
+        side.extend_from_slice(&address.to_le_bytes());
+        side.extend_from_slice(&size.to_le_bytes());
+        side.push(*kind);
+
+        side.push(0x04);
+        side.extend(vec![0xEA; *size as usize]);
+    }
+
+    side.resize(DISK_SIDE_SIZE, 0x00);
+    side
+}
+
+fn create_fwnes_image(sides: &[Vec<u8>]) -> Vec<u8> {
+    let mut image = vec![0x46, 0x44, 0x53, 0x1A, sides.len() as u8];
+    image.resize(16, 0x00);
+
+    for side in sides {
+        image.extend_from_slice(side);
+    }
+
+    image
+}
+
+fn create_two_sides_image() -> Vec<u8> {
+    let side_a = create_disk_side(0, 3, &[
+        (0x00, b"KYODAKU-", 0x2800, 2, 0xE0),
+        (0x01, b"PROGRAM0", 0x6000, 0, 0x1000),
+        (0x02, b"CHARS000", 0x0000, 1, 0x2000),
+    ]);
+    let side_b = create_disk_side(1, 1, &[
+        (0x00, b"PROGRAM1", 0x6000, 0, 0x0800),
+    ]);
+
+    create_fwnes_image(&[side_a, side_b])
+}
+
+#[test]
+fn fds_image_with_header_reports_its_sides_and_files() {
+    init();
+
+    let loader = FdsLoader::from_bytes(&create_two_sides_image()).unwrap();
+    let sides = loader.sides();
+
+    assert_eq!(sides.len(), 2);
+    assert_eq!(sides[0].game_name(), "TST");
+    assert_eq!(sides[0].file_amount(), 3);
+    assert_eq!(sides[0].files().len(), 3);
+    assert_eq!(sides[1].side_number(), 1);
+    assert_eq!(sides[1].files().len(), 1);
+
+    let program = &sides[0].files()[1];
+    assert_eq!(program.name(), "PROGRAM0");
+    assert_eq!(program.load_address(), 0x6000);
+    assert_eq!(program.kind(), FdsFileKind::Program);
+    assert_eq!(program.data().len(), 0x1000);
+}
+
+#[test]
+fn fds_image_without_header_is_loaded_from_file() {
+    init();
+
+    let image = create_two_sides_image();
+    let mut file = NamedTempFile::new().expect("failed to create temp file");
+    file.write_all(&image[16..]).expect("failed to write disk image");
+    file.flush().expect("failed to flush disk image");
+
+    let loader = FdsLoader::from_file(file.path().to_path_buf()).unwrap();
+
+    assert_eq!(loader.sides().len(), 2);
+    assert_eq!(loader.sides()[0].files().len(), 3);
+}
+
+#[test]
+fn fds_image_with_truncated_side_is_rejected() {
+    init();
+
+    let image = create_two_sides_image();
+    let result = FdsLoader::from_bytes(&image[..image.len() - 1]);
+
+    assert!(matches!(result, Err(LoaderError::InvalidRomFormat)));
+}
@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::bus_device::BusDevice;
+use crate::controller::Controller;
+use crate::four_score::FourScore;
+use crate::input_external::InputExternal;
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_B, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_START, NES_CONTROLLER_PLAYER_KEYS};
+use crate::memory::Memory;
+use crate::memory_bank::MemoryBank;
+use crate::tests::init;
+
+const PORT_1: u16 = 0x4016;
+const PORT_2: u16 = 0x4017;
+
+fn create_four_score(frame_counter: Option<Rc<RefCell<dyn BusDevice>>>) -> FourScore<InputExternal> {
+    let inputs = [InputExternal::new(), InputExternal::new(), InputExternal::new(), InputExternal::new()];
+    FourScore::new(inputs, frame_counter)
+}
+
+/// ```player``` from 1 to 4
+fn press(player: usize, key: usize) -> KeyEvent {
+    KeyEvent { key: (player - 1) * NES_CONTROLLER_PLAYER_KEYS + key, pressed: true }
+}
+
+/// the byte made of 8 reads of ```port```, the first read on bit 0
+fn read_port_byte(four_score: &mut FourScore<InputExternal>, port: u16) -> u8 {
+    (0..8).fold(0, |byte, bit| byte | (four_score.read_byte(port).unwrap() & 0x01) << bit)
+}
+
+#[test]
+fn strobe_then_24_reads_return_the_two_controllers_of_each_port_then_the_signature() {
+    init();
+
+    let mut four_score = create_four_score(None);
+    four_score.set_input(KeyEvents::from_iter([
+        press(1, NES_CONTROLLER_KEY_A),
+        press(2, NES_CONTROLLER_KEY_B),
+        press(3, NES_CONTROLLER_KEY_START),
+        press(4, NES_CONTROLLER_KEY_RIGHT),
+    ])).unwrap();
+
+    four_score.write_byte(PORT_1, 0x01).unwrap();
+    four_score.write_byte(PORT_1, 0x00).unwrap();
+
+    let port_1 = (0..3).map(|_| read_port_byte(&mut four_score, PORT_1)).collect::<Vec<u8>>();
+    let port_2 = (0..3).map(|_| read_port_byte(&mut four_score, PORT_2)).collect::<Vec<u8>>();
+
+    // players 1 and 3 then $10, players 2 and 4 then $20
+    assert_eq!(port_1, vec![0x01, 0x08, 0x10]);
+    assert_eq!(port_2, vec![0x02, 0x80, 0x20]);
+
+    // past the 24 bits, the reads return 1
+    assert_eq!(four_score.read_byte(PORT_1).unwrap(), 1);
+    assert_eq!(four_score.read_byte(PORT_2).unwrap(), 1);
+}
+
+#[test]
+fn writes_to_4017_go_to_the_frame_counter_and_do_not_strobe() {
+    init();
+
+    let frame_counter = Rc::new(RefCell::new(MemoryBank::new(1, (PORT_2, PORT_2))));
+    let mut four_score = create_four_score(Some(frame_counter.clone()));
+    four_score.set_input(KeyEvents::from_iter([press(2, NES_CONTROLLER_KEY_A)])).unwrap();
+
+    four_score.write_byte(PORT_2, 0x41).unwrap();
+
+    assert_eq!(frame_counter.borrow().read_byte(0x0000).unwrap(), 0x41);
+    assert_eq!(four_score.read_byte(PORT_2).unwrap(), 1);
+    assert_eq!(four_score.read_byte(PORT_2).unwrap(), 1);
+}
+
+#[test]
+fn keys_of_a_fifth_player_are_rejected() {
+    init();
+
+    let mut four_score = create_four_score(None);
+
+    assert!(four_score.set_input(KeyEvents::from_iter([press(5, NES_CONTROLLER_KEY_A)])).is_err());
+}
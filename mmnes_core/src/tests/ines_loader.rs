@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use tempfile::NamedTempFile;
+use crate::cpu::MockCpuStub;
+use crate::ines_loader::INesLoader;
+use crate::loader::{Loader, LoaderError};
+use crate::memory::Memory;
+use crate::ppu_2c02::Ppu2c02;
+use crate::region::Region;
+use crate::tests::{init, nrom_image};
+
+const HEADER_SIZE: u64 = 16;
+const PRG_ROM_SIZE: u64 = 16 * 1024;
+const CHR_ROM_SIZE: u64 = 8 * 1024;
+
+fn create_ines_file(bytes: &[u8]) -> NamedTempFile {
+    let mut rom_file = NamedTempFile::new().expect("failed to create temp file");
+    rom_file.write_all(bytes).expect("failed to write rom file");
+    rom_file.flush().expect("failed to flush rom file");
+
+    rom_file
+}
+
+fn create_nrom_128_image() -> Vec<u8> {
+    nrom_image(&[0xEA; PRG_ROM_SIZE as usize])
+}
+
+#[test]
+fn ines_file_shorter_than_its_header_declares_is_a_truncated_rom() {
+    init();
+
+    let rom_file = create_ines_file(&create_nrom_128_image()[..(HEADER_SIZE + PRG_ROM_SIZE) as usize]);
+
+    let result = INesLoader::from_file(rom_file.path().to_path_buf());
+    let expected = HEADER_SIZE + PRG_ROM_SIZE + CHR_ROM_SIZE;
+    let actual = HEADER_SIZE + PRG_ROM_SIZE;
+
+    assert!(matches!(result, Err(LoaderError::TruncatedRom { expected: e, actual: a }) if e == expected && a == actual));
+}
+
+#[test]
+fn ines_file_shorter_than_a_header_or_without_the_magic_is_rejected() {
+    init();
+
+    let rom_file = create_ines_file(&create_nrom_128_image()[..8]);
+    let result = INesLoader::from_file(rom_file.path().to_path_buf());
+    assert!(matches!(result, Err(LoaderError::TruncatedRom { expected: HEADER_SIZE, actual: 8 })));
+
+    let mut bytes = create_nrom_128_image();
+    bytes[3] = 0x00;
+    let rom_file = create_ines_file(&bytes);
+    let result = INesLoader::from_file(rom_file.path().to_path_buf());
+    assert!(matches!(result, Err(LoaderError::InvalidRomFormat)));
+}
+
+#[test]
+fn ines_file_without_chr_rom_has_pattern_tables_written_through_ppudata() {
+    init();
+
+    let mut bytes = create_nrom_128_image();
+    bytes[5] = 0x00;
+    let rom_file = create_ines_file(&bytes[..(HEADER_SIZE + PRG_ROM_SIZE) as usize]);
+
+    let cartridge = INesLoader::from_file(rom_file.path().to_path_buf()).unwrap().build_cartridge().unwrap();
+    let chr_memory = cartridge.borrow().get_chr_rom();
+    let mirroring = cartridge.borrow().get_mirroring();
+    let mut ppu = Ppu2c02::new(chr_memory, mirroring, Rc::new(RefCell::new(MockCpuStub::new())), Region::NTSC).unwrap();
+
+    ppu.write_byte(0x06, 0x1F).unwrap();
+    ppu.write_byte(0x06, 0xF0).unwrap();
+    for value in [0x3C, 0x66] {
+        ppu.write_byte(0x07, value).unwrap();
+    }
+
+    // the reads below the palettes are delayed by one
+    ppu.write_byte(0x06, 0x1F).unwrap();
+    ppu.write_byte(0x06, 0xF0).unwrap();
+    let _ = ppu.read_byte(0x07).unwrap();
+
+    assert_eq!(ppu.read_byte(0x07).unwrap(), 0x3C);
+    assert_eq!(ppu.read_byte(0x07).unwrap(), 0x66);
+}
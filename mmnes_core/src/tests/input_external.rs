@@ -1,6 +1,6 @@
 use crate::input_external::InputExternal;
-use crate::input::Input;
-use crate::key_event::{KeyEvent, KeyEvents};
+use crate::input::{Input, SocdMode};
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_UP};
 use crate::tests::init;
 
 
@@ -119,3 +119,67 @@ fn should_process_all_available_key_events_until_none_remain() {
     assert_eq!(control_states_second[7], 0);
 }
 
+
+/// Delivers the key events, one batch per poll of the controller, and returns the states of the last poll.
+fn poll_key_events(input_external: &mut InputExternal, batches: &[&[(usize, bool)]]) -> [u8; 8] {
+    let mut control_states = [0u8; 8];
+
+    for batch in batches {
+        let key_events = batch.iter()
+            .map(|(key, pressed)| KeyEvent { key: *key, pressed: *pressed })
+            .collect::<KeyEvents>();
+
+        input_external.set_input_state(key_events);
+        input_external.get_input_state(&mut control_states);
+    }
+
+    control_states
+}
+
+#[test]
+fn socd_off_reports_left_and_right_held_together() {
+    init();
+
+    let mut input_external = create_input_external();
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_LEFT, true)], &[(NES_CONTROLLER_KEY_RIGHT, true)]]);
+
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 1);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 1);
+}
+
+#[test]
+fn socd_neutral_reports_neither_direction_while_left_and_right_are_held() {
+    init();
+
+    let mut input_external = create_input_external().with_socd_mode(SocdMode::Neutral);
+
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_LEFT, true), (NES_CONTROLLER_KEY_UP, true)], &[(NES_CONTROLLER_KEY_RIGHT, true)]]);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 0);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 0);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_UP], 1);
+
+    // left is reported again once right is released
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_RIGHT, false)]]);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 1);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 0);
+}
+
+#[test]
+fn socd_last_wins_reports_the_direction_pressed_last() {
+    init();
+
+    let mut input_external = create_input_external().with_socd_mode(SocdMode::LastWins);
+
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_LEFT, true)], &[(NES_CONTROLLER_KEY_RIGHT, true)]]);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 0);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 1);
+
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_LEFT, false), (NES_CONTROLLER_KEY_LEFT, true)]]);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 1);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 0);
+
+    // right, still held, is reported again once left is released
+    let control_states = poll_key_events(&mut input_external, &[&[(NES_CONTROLLER_KEY_LEFT, false)]]);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_LEFT], 0);
+    assert_eq!(control_states[NES_CONTROLLER_KEY_RIGHT], 1);
+}
@@ -1,4 +1,5 @@
 use crate::bus::MockBusStub;
+use crate::bus_device::BusDevice;
 use crate::memory::{Memory, MemoryError};
 use crate::memory_bank::MemoryBank;
 use crate::tests::{create_memory_bank, init};
@@ -82,6 +83,29 @@ fn read_and_write_word_correctly_at_specific_address() {
     assert_eq!(read_value, test_value);
 }
 
+#[test]
+fn memory_bank_round_trips_its_contents_through_its_state() {
+    init();
+
+    let mut memory_bank = create_memory_bank(DEFAULT_MEMORY_SIZE, DEFAULT_MEMORY_RANGE);
+    memory_bank.write_byte(0x0000, 0x12).unwrap();
+    memory_bank.write_byte(0x0FFF, 0x34).unwrap();
+
+    let state = memory_bank.serialize_state();
+    assert_eq!(state.len(), DEFAULT_MEMORY_SIZE);
+
+    let mut restored = create_memory_bank(DEFAULT_MEMORY_SIZE, DEFAULT_MEMORY_RANGE);
+    restored.deserialize_state(&state).unwrap();
 
+    assert_eq!(restored.bytes(), memory_bank.bytes());
+    assert_eq!(restored.read_byte(0x0FFF).unwrap(), 0x34);
+}
+
+#[test]
+fn memory_bank_rejects_a_state_of_another_size() {
+    init();
 
+    let mut memory_bank = create_memory_bank(DEFAULT_MEMORY_SIZE, DEFAULT_MEMORY_RANGE);
 
+    assert!(matches!(memory_bank.deserialize_state(&[0x00; 16]), Err(MemoryError::IllegalState(_))));
+}
@@ -1,9 +1,11 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use crate::bus::Bus;
 use crate::bus_device::BusDevice;
 use crate::memory::{Memory, MemoryError};
 use crate::memory_bank::MemoryBank;
 use crate::memory_mirror::MemoryMirror;
+use crate::nes_bus::NESBus;
 use crate::tests::{create_memory_bank, init};
 
 const VIRTUAL_MEMORY_RANGE: (u16, u16) = (0x8000, 0x9FFF);
@@ -166,4 +168,38 @@ fn test_size_returns_correct_memory_size_from_underlying_memory_bank() -> Result
     assert_eq!(result, PHYSICAL_MEMORY_SIZE);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn device_mirrored_with_mask_07ff_returns_the_same_byte_for_0000_and_0800() -> Result<(), MemoryError> {
+    init();
+
+    let mut memory_bank = create_memory_bank(2 * 1024, (0x0000, 0x07FF));
+    memory_bank.initialize()?;
+
+    let mirror = MemoryMirror::with_mask(Rc::new(RefCell::new(memory_bank)), (0x0000, 0x1FFF), 0x07FF)?;
+    let mut bus = NESBus::new();
+    bus.add_device(Rc::new(RefCell::new(mirror))).unwrap();
+
+    bus.write_byte(0x0800, 0x5A)?;
+
+    assert_eq!(bus.read_byte(0x0000)?, 0x5A);
+    assert_eq!(bus.read_byte(0x0800)?, 0x5A);
+    assert_eq!(bus.read_byte(0x1800)?, 0x5A);
+
+    Ok(())
+}
+
+#[test]
+fn mirror_mask_out_of_the_device_is_rejected() -> Result<(), MemoryError> {
+    init();
+
+    let mut memory_bank = create_memory_bank(2 * 1024, (0x0000, 0x07FF));
+    memory_bank.initialize()?;
+
+    let result = MemoryMirror::with_mask(Rc::new(RefCell::new(memory_bank)), (0x0000, 0x1FFF), 0x0FFF);
+
+    assert!(matches!(result, Err(MemoryError::InvalidAddressSpace(_))));
+
+    Ok(())
+}
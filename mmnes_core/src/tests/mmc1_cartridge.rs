@@ -0,0 +1,48 @@
+use std::io::Cursor;
+use crate::cartridge::{BankState, Cartridge};
+use crate::memory::Memory;
+use crate::memory_ciram::PpuNameTableMirroring;
+use crate::mmc1_cartridge::Mmc1Cartridge;
+use crate::tests::init;
+
+const PRG_ROM_SIZE: usize = 128 * 1024;
+const CHR_ROM_SIZE: usize = 32 * 1024;
+
+fn create_mmc1_cartridge() -> Mmc1Cartridge {
+    let data = Cursor::new(vec![0x00; PRG_ROM_SIZE + CHR_ROM_SIZE]);
+
+    Mmc1Cartridge::new(data, 0, PRG_ROM_SIZE, 8 * 1024, PRG_ROM_SIZE as u64, CHR_ROM_SIZE, 0, PpuNameTableMirroring::Horizontal)
+        .expect("failed to create the cartridge")
+}
+
+/// the 5 bits of ```value``` shifted in, low bit first, the last write selecting the register at ```addr```
+fn write_register(cartridge: &mut Mmc1Cartridge, addr: u16, value: u8) {
+    for bit in 0..5 {
+        cartridge.write_byte(addr, (value >> bit) & 0x01).unwrap();
+    }
+}
+
+#[test]
+fn mmc1_powers_on_with_the_last_prg_bank_fixed_at_c000() {
+    init();
+    let cartridge = create_mmc1_cartridge();
+
+    assert_eq!(cartridge.bank_state(), BankState { prg_banks: [0, 1, 14, 15], chr_banks: [0, 1] });
+}
+
+#[test]
+fn mmc1_bank_state_follows_the_bank_select_writes() {
+    init();
+    let mut cartridge = create_mmc1_cartridge();
+
+    write_register(&mut cartridge, 0xE000, 0x03);
+    assert_eq!(cartridge.bank_state().prg_banks, [6, 7, 14, 15]);
+
+    // 4 KB CHR banks, first PRG bank fixed at $8000
+    write_register(&mut cartridge, 0x8000, 0x18);
+    write_register(&mut cartridge, 0xA000, 0x05);
+    write_register(&mut cartridge, 0xC000, 0x02);
+
+    assert_eq!(cartridge.bank_state(), BankState { prg_banks: [0, 1, 6, 7], chr_banks: [5, 2] });
+    assert_eq!(cartridge.bank_state().to_string(), "PRG $8000: 0, $A000: 1, $C000: 6, $E000: 7 | CHR $0000: 5, $1000: 2");
+}
@@ -1,20 +1,53 @@
 use log::LevelFilter;
 use simplelog::{Config, TestLogger};
+use std::cell::RefCell;
+#[cfg(feature = "tracing")]
+use std::io::Write;
+use std::rc::Rc;
 use std::sync::Once;
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::cpu_6502::Cpu6502;
+use crate::memory::Memory;
 use crate::memory_bank::MemoryBank;
+use crate::nes_bus::NESBus;
 
 mod nes_bus;
 mod memory_bank;
 mod ppu_2c02;
 mod ppu_dma;
+mod dma_arbiter;
 mod cpu_6502;
 mod memory_mirror;
 mod input_external;
 mod key_events;
 mod sound_playback_passive;
+mod sound_playback_resampler;
 mod nes_samples;
 mod cartridge;
 mod memory_ciram;
+mod nes_console;
+#[cfg(feature = "tracing")]
+mod cpu_tracer;
+mod fds_loader;
+mod ines_loader;
+mod cheat;
+mod apu_rp2a03;
+mod standard_controller;
+mod four_score;
+mod palette_2c02;
+mod ntsc_filter;
+mod ansi_renderer;
+mod renderer;
+mod nes;
+mod mmc1_cartridge;
+mod unrom_cartridge;
+mod session;
+mod test_rom;
+mod sample_history;
+mod ram_search;
+mod disassembler;
+mod wav_sink;
 
 static START: Once = Once::new();
 
@@ -26,10 +59,74 @@ pub fn init() {
     init_logger_for_test();
 }
 
+const INES_HEADER_SIZE: usize = 16;
+const NROM_PRG_ROM_BANK_SIZE: usize = 16 * 1024;
+const NROM_CHR_ROM_SIZE: usize = 8 * 1024;
+
 fn create_memory_bank(size: usize, address_range: (u16, u16)) -> MemoryBank {
     MemoryBank::new(size, address_range)
 }
 
+/// iNES image of a NROM cartridge of ```prg_rom``` (a multiple of 16 KB) and 8 KB of blank CHR ROM.
+fn nrom_image(prg_rom: &[u8]) -> Vec<u8> {
+    let mut image = vec![0x4E, 0x45, 0x53, 0x1A, (prg_rom.len() / NROM_PRG_ROM_BANK_SIZE) as u8, 0x01, 0x00, 0x00];
+    image.resize(INES_HEADER_SIZE, 0x00);
+
+    image.extend_from_slice(prg_rom);
+    image.extend(vec![0x00; NROM_CHR_ROM_SIZE]);
+    image
+}
+
+
+
+
+
+/***
+ * CPU wired to a flat 64 KB RAM, with the program copied at origin and the reset vector pointing to it
+ ***/
+fn create_cpu_with_program(origin: u16, program: &[u8]) -> (Cpu6502, Rc<RefCell<MemoryBank>>) {
+    let ram = Rc::new(RefCell::new(create_memory_bank(64 * 1024, (0x0000, 0xFFFF))));
+
+    for (offset, byte) in program.iter().enumerate() {
+        ram.borrow_mut().write_byte(origin.wrapping_add(offset as u16), *byte).unwrap();
+    }
 
+    ram.borrow_mut().write_word(0xFFFC, origin).unwrap();
 
+    let mut bus = NESBus::new();
+    bus.add_device(ram.clone()).unwrap();
 
+    let mut cpu = Cpu6502::new(Rc::new(RefCell::new(bus)));
+    cpu.reset().unwrap();
+
+    (cpu, ram)
+}
+
+/***
+ * in-memory writer shared with the test, to inspect what has been written
+ ***/
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(feature = "tracing")]
+impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.0.borrow())
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
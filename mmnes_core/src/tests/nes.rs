@@ -0,0 +1,73 @@
+use crate::nes::{ButtonState, Nes, NesError};
+use crate::tests::{init, nrom_image};
+
+const PRG_ROM_SIZE: usize = 16 * 1024;
+
+/// iNES image of a NROM-128 cartridge running ```program``` from $8000.
+fn create_nrom_image(program: &[u8]) -> Vec<u8> {
+    let mut prg_rom = vec![0xEA; PRG_ROM_SIZE];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    nrom_image(&prg_rom)
+}
+
+/// strobes the controllers and keeps the A button of the players 1 and 2 at $10 and $11
+fn create_controller_reader_image() -> Vec<u8> {
+    create_nrom_image(&[
+        0xA9, 0x01,         // $8000 LDA #$01
+        0x8D, 0x16, 0x40,   // $8002 STA $4016
+        0xA9, 0x00,         // $8005 LDA #$00
+        0x8D, 0x16, 0x40,   // $8007 STA $4016
+        0xAD, 0x16, 0x40,   // $800A LDA $4016
+        0x29, 0x01,         // $800D AND #$01
+        0x85, 0x10,         // $800F STA $10
+        0xAD, 0x17, 0x40,   // $8011 LDA $4017
+        0x29, 0x01,         // $8014 AND #$01
+        0x85, 0x11,         // $8016 STA $11
+        0x4C, 0x00, 0x80,   // $8018 JMP $8000
+    ])
+}
+
+#[test]
+fn rom_loaded_from_bytes_steps_one_frame() {
+    init();
+
+    let mut nes = Nes::new(&create_nrom_image(&[0x4C, 0x00, 0x80])).expect("failed to create the console");
+    assert!(nes.frame().is_none());
+
+    let frame = nes.step_frame().expect("failed to step a frame");
+    assert_eq!((frame.width(), frame.height()), (256, 240));
+    assert_eq!(frame.count(), 1);
+
+    assert_eq!(nes.frame().map(|frame| frame.count()), Some(1));
+    assert!(!nes.audio_samples().is_empty());
+}
+
+#[test]
+fn buttons_are_read_by_the_game_for_each_player() {
+    init();
+
+    let mut nes = Nes::new(&create_controller_reader_image()).expect("failed to create the console");
+
+    nes.set_buttons(2, ButtonState { a: true, ..Default::default() }).unwrap();
+    nes.step_frame().unwrap();
+    assert_eq!((nes.console().peek(0x10).unwrap(), nes.console().peek(0x11).unwrap()), (0, 1));
+
+    nes.set_buttons(1, ButtonState { a: true, ..Default::default() }).unwrap();
+    nes.set_buttons(2, ButtonState::default()).unwrap();
+    nes.step_frame().unwrap();
+    assert_eq!((nes.console().peek(0x10).unwrap(), nes.console().peek(0x11).unwrap()), (1, 0));
+}
+
+#[test]
+fn invalid_player_and_truncated_rom_are_rejected() {
+    init();
+
+    let image = create_nrom_image(&[0x4C, 0x00, 0x80]);
+    let mut nes = Nes::new(&image).unwrap();
+
+    assert!(matches!(nes.set_buttons(0, ButtonState::default()), Err(NesError::InvalidPlayer(0))));
+    assert!(matches!(nes.set_buttons(5, ButtonState::default()), Err(NesError::InvalidPlayer(5))));
+    assert!(matches!(Nes::new(&image[..image.len() / 2]), Err(NesError::ConsoleError(_))));
+}
@@ -1,10 +1,20 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use crate::memory::{Memory, MemoryType};
+use crate::memory::{Memory, MemoryError, MemoryType};
 use mockall::predicate::eq;
 use crate::bus::Bus;
 use crate::bus_device::{BusDeviceType, MockBusDeviceStub};
-use crate::nes_bus::{BUS_ADDRESSABLE_SIZE, NESBus};
+use crate::cpu::{MockCpuStub, CPU};
+use crate::cpu_6502::Cpu6502;
+use crate::custom_io_device::CustomIoDevice;
+use crate::dma_device::MockDmaDeviceStub;
+use crate::memory_bank::MemoryBank;
+use crate::memory_ciram::PpuNameTableMirroring;
+use crate::nes_bus::{BUS_ADDRESSABLE_SIZE, NESBus, WriteSource};
+use crate::ppu::{PPU, PpuType};
+use crate::ppu_2c02::Ppu2c02;
+use crate::ppu_dma::PpuDma;
+use crate::region::Region;
 use crate::tests::init;
 
 const DEFAULT_MEMORY_SIZE: usize = 2048;
@@ -33,6 +43,7 @@ fn create_bus_device_with_expectations(memory_size: usize, memory_range: (u16, u
 
     device.expect_size().returning(move || memory_size);
     device.expect_get_virtual_address_range().returning(move || memory_range);
+    device.expect_get_address_ranges().returning(move || vec![memory_range]);
 
     match (request, length) {
         (RequestType::Read, RequestData::Byte(value)) => {
@@ -185,6 +196,28 @@ fn read_write_word_request_for_valid_address() {
     assert_eq!(result1, Ok(expected_value));
 }
 
+#[test]
+fn device_with_two_disjoint_ranges_is_mapped_in_both() {
+    init();
+
+    let mut device = MockBusDeviceStub::new();
+
+    device.expect_get_name().returning(|| DEFAULT_DEVICE_NAME.to_string());
+    device.expect_get_device_type().returning(|| BusDeviceType::WRAM(MemoryType::StandardMemory));
+    device.expect_size().returning(|| 0x20);
+    device.expect_get_virtual_address_range().returning(|| (0x4000, 0x4017));
+    device.expect_get_address_ranges().returning(|| vec![(0x4000, 0x4003), (0x4015, 0x4015)]);
+    device.expect_read_byte().with(eq(0x0001)).times(1).returning(|_| Ok(0x11));
+    device.expect_read_byte().with(eq(0x0015)).times(1).returning(|_| Ok(0x15));
+
+    let mut nes_bus = create_nes_bus();
+    nes_bus.add_device(Rc::new(RefCell::new(device))).unwrap();
+
+    assert_eq!(nes_bus.read_byte(0x4001), Ok(0x11));
+    assert_eq!(nes_bus.read_byte(0x4015), Ok(0x15));
+    assert_eq!(nes_bus.describe_mapping(), vec![((0x4000, 0x4003), DEFAULT_DEVICE_NAME.to_string()), ((0x4015, 0x4015), DEFAULT_DEVICE_NAME.to_string())]);
+}
+
 #[test]
 fn returns_size() {
     init();
@@ -253,4 +286,142 @@ fn read_is_routed_to_right_device() {
     let result = nes_bus.read_byte(virtual_addr);
 
     assert_eq!(result, Ok(expected_value));
-}
\ No newline at end of file
+}
+
+/***
+ * 2 KB of WRAM mirrored up to $1FFF, and the PPU registers with the vblank flag set
+ ***/
+fn create_nes_bus_with_wram_and_ppu() -> (NESBus, Rc<RefCell<Ppu2c02>>) {
+    let wram = Rc::new(RefCell::new(MemoryBank::new(DEFAULT_MEMORY_SIZE, DEFAULT_MEMORY_RANGE)));
+
+    let mut ppu = Ppu2c02::new(
+        Rc::new(RefCell::new(MemoryBank::new(8192, (0x0000, 0x1FFF)))),
+        Rc::new(RefCell::new(PpuNameTableMirroring::Horizontal)),
+        Rc::new(RefCell::new(MockCpuStub::new())),
+        Region::NTSC
+    ).expect("failed to create ppu");
+    ppu.reset().expect("failed to reset ppu");
+    let ppu = Rc::new(RefCell::new(ppu));
+
+    let mut nes_bus = create_nes_bus();
+    nes_bus.add_device(wram).expect("failed to add bus device");
+    nes_bus.add_device(ppu.clone()).expect("failed to add bus device");
+
+    (nes_bus, ppu)
+}
+
+#[test]
+fn poke_writes_ram_without_disturbing_ppu_state() {
+    init();
+
+    let (mut nes_bus, ppu) = create_nes_bus_with_wram_and_ppu();
+
+    nes_bus.poke_byte(0x0200, 0x42).unwrap();
+    nes_bus.poke_byte(0x0201, 0x80).unwrap();
+
+    assert_eq!(nes_bus.trace_read_byte(0x0200), Ok(0x42));
+    assert_eq!(nes_bus.trace_read_byte(0x0A00), Ok(0x42));
+    assert_eq!(nes_bus.peek_word(0x0200), Ok(0x8042));
+
+    assert_eq!(nes_bus.trace_read_byte(0x2002).unwrap() & 0x80, 0x80);
+    assert_eq!(ppu.borrow().get_register_value("status") & 0x80, 0x80);
+}
+
+#[test]
+fn poke_to_ppu_registers_is_refused() {
+    init();
+
+    let (mut nes_bus, ppu) = create_nes_bus_with_wram_and_ppu();
+
+    let result = nes_bus.poke_byte(0x2000, 0x80);
+
+    assert!(matches!(result, Err(MemoryError::IllegalState(_))));
+    assert_eq!(ppu.borrow().get_register_value("controller"), 0x00);
+}
+
+#[test]
+fn removed_devices_are_released_and_left_to_the_open_bus() {
+    init();
+
+    let wram = Rc::new(RefCell::new(MemoryBank::new(DEFAULT_MEMORY_SIZE, DEFAULT_MEMORY_RANGE)));
+    let mut nes_bus = create_nes_bus();
+
+    nes_bus.add_device(wram.clone()).expect("failed to add bus device");
+    assert!(Rc::strong_count(&wram) > 1);
+
+    nes_bus.remove_devices();
+
+    assert_eq!(Rc::strong_count(&wram), 1);
+    assert!(nes_bus.describe_mapping().is_empty());
+}
+
+#[test]
+fn device_states_restore_the_ram_behind_the_bus() {
+    init();
+
+    let (mut nes_bus, _) = create_nes_bus_with_wram_and_ppu();
+    nes_bus.write_byte(0x0200, 0x42).unwrap();
+
+    let states = nes_bus.serialize_devices();
+
+    assert_eq!(states.len(), 2);
+    assert_eq!(states[0].0, BusDeviceType::WRAM(MemoryType::StandardMemory));
+    assert_eq!(states[0].1.len(), DEFAULT_MEMORY_SIZE);
+    assert_eq!(states[1].0, BusDeviceType::PPU(PpuType::NES2C02));
+
+    nes_bus.write_byte(0x0200, 0x00).unwrap();
+    nes_bus.deserialize_devices(&states).unwrap();
+
+    assert_eq!(nes_bus.read_byte(0x0200), Ok(0x42));
+    assert!(matches!(nes_bus.deserialize_devices(&states[..1]), Err(MemoryError::IllegalState(_))));
+}
+
+#[test]
+fn writes_in_the_logged_range_are_logged_with_their_source() {
+    init();
+
+    // $8000 LDA #$80, STA $2000, LDX #$1E, STX $2001, STA $0300, LDA #$02, STA $4014 (OAM DMA of page $02)
+    let program = [0xA9, 0x80, 0x8D, 0x00, 0x20, 0xA2, 0x1E, 0x8E, 0x01, 0x20, 0x8D, 0x00, 0x03, 0xA9, 0x02, 0x8D, 0x14, 0x40];
+
+    let rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    for (offset, byte) in program.iter().enumerate() {
+        rom.borrow_mut().write_byte(offset as u16, *byte).unwrap();
+    }
+    rom.borrow_mut().write_word(0x7FFC, 0x8000).unwrap();
+
+    let ram = Rc::new(RefCell::new(MemoryBank::new(DEFAULT_MEMORY_SIZE, (0x0000, 0x07FF))));
+    ram.borrow_mut().write_byte(0x0200, 0x11).unwrap();
+    ram.borrow_mut().write_byte(0x02FF, 0x22).unwrap();
+
+    let mut oam = MockDmaDeviceStub::new();
+    oam.expect_dma_write().times(256).returning(|_, _| Ok(()));
+
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let log = writes.clone();
+
+    let bus = Rc::new(RefCell::new(NESBus::new()));
+    bus.borrow_mut().log_writes_in_range((0x2000, 0x2007), Box::new(move |addr, value, source| log.borrow_mut().push((addr, value, source))));
+
+    let ppu_registers = CustomIoDevice::new("PPU registers", (0x2000, 0x2007));
+    let dma = PpuDma::new(Rc::new(RefCell::new(oam)), bus.clone());
+
+    bus.borrow_mut().add_device(ram).unwrap();
+    bus.borrow_mut().add_device(rom).unwrap();
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(ppu_registers))).unwrap();
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(dma))).unwrap();
+
+    let mut cpu = Cpu6502::new(bus);
+    cpu.reset().unwrap();
+
+    for _ in 0..7 {
+        cpu.step_instruction().unwrap();
+    }
+
+    let writes = writes.borrow();
+
+    assert_eq!(writes.len(), 2 + 256);
+    assert_eq!(writes[0..2], [(0x2000, 0x80, WriteSource::Cpu), (0x2001, 0x1E, WriteSource::Cpu)]);
+    assert_eq!(writes[2], (0x2004, 0x11, WriteSource::Dma));
+    assert_eq!(writes[257], (0x2004, 0x22, WriteSource::Dma));
+    assert!(writes[2..].iter().all(|(addr, _, source)| *addr == 0x2004 && *source == WriteSource::Dma));
+}
@@ -0,0 +1,988 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+use log::info;
+use tempfile::NamedTempFile;
+use crate::apu::ApuType::RP2A03;
+use crate::apu_rp2a03::AUDIO_RATE;
+use crate::benchmark::run_benchmark;
+use crate::bus::BusType;
+use crate::bus_device::BusDeviceType::{APU, CARTRIDGE, CONTROLLER, PPU, WRAM};
+use crate::cartridge::CartridgeType::NROM;
+use crate::controller::ControllerType::StandardController;
+use crate::cpu::{CpuType, InterruptKind};
+use crate::cpu_6502::APU_FRAME_COUNTER_IRQ;
+use crate::custom_io_device::CustomIoDevice;
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A};
+use crate::loader::LoaderType;
+use crate::loader::LoaderType::INESV2;
+use crate::memory::MemoryType::StandardMemory;
+use crate::memory_bank::RamInit;
+use crate::nes_console::{ConsoleState, NesConsole, NesConsoleBuilder, NesConsoleError};
+use crate::ppu::PpuType::NES2C02;
+use crate::ram_search::SearchCriteria;
+use crate::region::Region;
+use crate::save_state::SaveState;
+use crate::wav_sink::{MultiChannelWavSink, APU_CHANNELS};
+use crate::tests::{init, nrom_image};
+
+const PRG_ROM_SIZE: usize = 16 * 1024;
+
+/***
+ * NROM-128 image looping forever on JMP $8000, all vectors pointing to $8000
+ ***/
+fn create_nrom_file(program: &[u8]) -> NamedTempFile {
+    let mut prg_rom = vec![0xEA; PRG_ROM_SIZE];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    create_nrom_file_with_prg_rom(&prg_rom)
+}
+
+/// NROM image of the given PRG ROM, a multiple of 16 KB.
+fn create_nrom_file_with_prg_rom(prg_rom: &[u8]) -> NamedTempFile {
+    let mut rom_file = NamedTempFile::new().expect("failed to create temp file");
+    rom_file.write_all(&nrom_image(prg_rom)).expect("failed to write rom file");
+    rom_file.flush().expect("failed to flush rom file");
+
+    rom_file
+}
+
+fn create_console(rom_file: &NamedTempFile, region: Region) -> NesConsole {
+    create_console_with_ram_init(rom_file, region, RamInit::default())
+}
+
+fn create_console_with_ram_init(rom_file: &NamedTempFile, region: Region, ram_init: RamInit) -> NesConsole {
+    create_console_with(rom_file.path(), |builder| builder.with_region(region).with_ram_init(ram_init))
+}
+
+/// Powered on NES of the standard devices loading the iNES ```rom_file```, ```customize``` sets up the rest.
+fn create_console_with(rom_file: &Path, customize: impl FnOnce(NesConsoleBuilder) -> NesConsoleBuilder) -> NesConsole {
+    let builder = NesConsoleBuilder::new()
+        .with_cpu(CpuType::NES6502)
+        .with_bus_type(BusType::NESBus)
+        .with_bus_device_type(WRAM(StandardMemory))
+        .with_bus_device_type(CARTRIDGE(NROM))
+        .with_bus_device_type(APU(RP2A03))
+        .with_bus_device_type(PPU(NES2C02))
+        .with_bus_device_type(CONTROLLER(StandardController))
+        .with_loader_type(INESV2)
+        .with_rom_file(rom_file.to_path_buf());
+
+    let mut console = customize(builder)
+        .build()
+        .expect("failed to build console");
+
+    console.power_on().expect("failed to power on console");
+    console
+}
+
+#[test]
+fn console_defaults_to_ntsc_timings() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let console = create_console(&rom_file, Region::NTSC);
+
+    assert_eq!(console.region(), Region::NTSC);
+    assert_eq!(console.get_ppu().borrow().scanlines_per_frame(), 262);
+}
+
+#[test]
+fn pal_console_ppu_reports_312_scanlines_per_frame() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::PAL);
+
+    assert_eq!(console.region(), Region::PAL);
+    assert_eq!(console.get_ppu().borrow().scanlines_per_frame(), 312);
+
+    console.step_frame().expect("failed to step frame");
+    let scanlines = console.perf_counters().scanlines();
+
+    console.step_frame().expect("failed to step frame");
+    assert_eq!(console.perf_counters().scanlines() - scanlines, 312);
+}
+
+#[test]
+fn reset_preserves_ram_and_reloads_pc_from_reset_vector() {
+    init();
+
+    let program = [
+        0xA5, 0x10,         // $8000 LDA $10
+        0xD0, 0x04,         // $8002 BNE $8008
+        0xA9, 0x42,         // $8004 LDA #$42
+        0x85, 0x10,         // $8006 STA $10
+        0x4C, 0x08, 0x80,   // $8008 JMP $8008
+    ];
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    for _ in 0..10 {
+        console.step_instruction().expect("failed to step instruction");
+    }
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x8008);
+    let sp = snapshot.sp();
+
+    console.reset().expect("failed to reset console");
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x8002);
+    assert_eq!(snapshot.a(), 0x42);
+    assert_eq!(snapshot.sp(), sp.wrapping_sub(3));
+}
+
+#[test]
+fn run_instructions_executes_exactly_the_given_number_of_instructions() {
+    init();
+
+    let program = [
+        0xA9, 0x01,         // $8000 LDA #$01
+        0x85, 0x10,         // $8002 STA $10
+        0xE8,               // $8004 INX
+    ].repeat(4);
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let instructions_executed = console.instructions_executed();
+
+    let cycles = console.run_instructions(10).expect("failed to run instructions");
+    let snapshot = console.cpu_snapshot().expect("failed to get cpu snapshot");
+
+    assert_eq!(console.instructions_executed() - instructions_executed, 10);
+    assert_eq!(cycles, 3 * (2 + 3 + 2) + 2);
+    assert_eq!(snapshot.pc(), 0x8011);
+    assert_eq!(snapshot.x(), 3);
+}
+
+#[test]
+fn state_summary_reports_the_cpu_ppu_and_apu_state() {
+    init();
+
+    let program = [
+        0xA9, 0x01,         // $8000 LDA #$01
+        0x8D, 0x15, 0x40,   // $8002 STA $4015
+        0xA2, 0x34,         // $8005 LDX #$34
+        0xA0, 0x56,         // $8007 LDY #$56
+    ];
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    console.run_instructions(4).expect("failed to run instructions");
+
+    let summary = console.state_summary().expect("failed to build the state summary");
+    let ppu = console.ppu_snapshot();
+
+    assert_eq!(summary["cpu"]["pc"], 0x8009);
+    assert_eq!(summary["cpu"]["a"], 0x01);
+    assert_eq!(summary["cpu"]["x"], 0x34);
+    assert_eq!(summary["cpu"]["y"], 0x56);
+    assert_eq!(summary["ppu"]["scanline"], ppu.scanline());
+    assert_eq!(summary["ppu"]["mask"], ppu.mask());
+    assert_eq!(summary["apu"]["pulse1"]["enabled"], true);
+    assert_eq!(summary["apu"]["pulse2"]["enabled"], false);
+    assert!(summary["apu"]["dmc"].is_object());
+}
+
+#[test]
+fn benchmark_reports_emulated_frames_per_second() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    let report = run_benchmark(&mut console, Duration::from_millis(200)).expect("failed to run benchmark");
+    info!("benchmark: {}", report);
+
+    assert!(report.frames() > 0);
+    assert!(report.instructions() > report.frames());
+    assert!(report.frames_per_second() > 0.0);
+}
+
+#[test]
+fn jam_opcode_halts_the_console_until_a_reset() {
+    init();
+
+    // LDA #$42, JAM
+    let rom_file = create_nrom_file(&[0xA9, 0x42, 0x02]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    assert_eq!(console.state(), ConsoleState::Running);
+
+    console.step_frame().expect("a halted CPU must not fail the frame");
+    assert_eq!(console.state(), ConsoleState::Halted(0x8002));
+
+    // the PPU keeps rendering frames while the CPU is frozen
+    console.step_frame().expect("failed to step frame");
+    assert_eq!(console.state(), ConsoleState::Halted(0x8002));
+
+    console.reset().expect("failed to reset console");
+    assert_eq!(console.state(), ConsoleState::Running);
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x8002);
+}
+
+#[test]
+fn unofficial_opcode_stops_the_console_built_with_halt_on_unimplemented() {
+    init();
+
+    // DCP $10
+    let rom_file = create_nrom_file(&[0xC7, 0x10]);
+    let mut console = create_console_with(rom_file.path(), |builder| builder.with_halt_on_unimplemented(true));
+
+    console.step_instruction().expect("a stopped CPU must not fail the step");
+
+    assert_eq!(console.state(), ConsoleState::Unimplemented { pc: 0x8000, opcode: 0xC7 });
+    assert!(console.state().is_cpu_stopped());
+}
+
+#[test]
+fn memory_map_lists_the_devices_in_decode_order() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let console = create_console(&rom_file, Region::NTSC);
+
+    let memory_map = console.describe_memory_map();
+    for ((start, end), name) in &memory_map {
+        info!("0x{:04X} - 0x{:04X}: {}", start, end, name);
+    }
+
+    // the APU leaves $4014 to the PPU (OAM DMA) and $4016 to the controller
+    let expected = [
+        ((0x0000, 0x1FFF), "Memory Bank"),
+        ((0x2000, 0x3FFF), "PPU 2C02"),
+        ((0x4000, 0x4013), "APU RP2A03"),
+        ((0x4014, 0x4014), "PPU DMA"),
+        ((0x4015, 0x4015), "APU RP2A03"),
+        ((0x4016, 0x4016), "Standard Controller"),
+        ((0x4017, 0x4017), "APU RP2A03"),
+        ((0x8000, 0xFFFF), "NROM-16384"),
+    ].map(|(range, name)| (range, name.to_string()));
+
+    assert_eq!(memory_map, expected);
+}
+
+#[test]
+fn devices_given_in_any_order_are_mapped_with_the_oam_dma_and_controller_over_the_apu() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let console = NesConsoleBuilder::new()
+        .with_cpu(CpuType::NES6502)
+        .with_bus_type(BusType::NESBus)
+        .with_bus_device_type(CONTROLLER(StandardController))
+        .with_bus_device_type(PPU(NES2C02))
+        .with_bus_device_type(APU(RP2A03))
+        .with_bus_device_type(CARTRIDGE(NROM))
+        .with_bus_device_type(WRAM(StandardMemory))
+        .with_loader_type(INESV2)
+        .with_rom_file(rom_file.path().to_path_buf())
+        .build()
+        .expect("failed to build console");
+
+    let memory_map = console.describe_memory_map();
+    let device_at = |addr: u16| memory_map.iter()
+        .find(|((start, end), _)| (*start..=*end).contains(&addr))
+        .map(|(_, name)| name.as_str());
+
+    assert_eq!(device_at(0x4014), Some("PPU DMA"));
+    assert_eq!(device_at(0x4016), Some("Standard Controller"));
+    assert_eq!(device_at(0x4015), Some("APU RP2A03"));
+}
+
+#[test]
+fn builder_rejects_two_devices_of_the_same_kind() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let result = NesConsoleBuilder::new()
+        .with_cpu(CpuType::NES6502)
+        .with_bus_type(BusType::NESBus)
+        .with_bus_device_type(WRAM(StandardMemory))
+        .with_bus_device_type(CARTRIDGE(NROM))
+        .with_bus_device_type(APU(RP2A03))
+        .with_bus_device_type(PPU(NES2C02))
+        .with_bus_device_type(PPU(NES2C02))
+        .with_bus_device_type(CONTROLLER(StandardController))
+        .with_loader_type(INESV2)
+        .with_rom_file(rom_file.path().to_path_buf())
+        .build();
+
+    assert!(result.is_err());
+}
+
+/// LDA $00, LDX $01, STA $00 (the RAM is read before being written), JMP $8006
+const READ_RAM_BEFORE_WRITE_PROGRAM: [u8; 9] = [0xA5, 0x00, 0xA6, 0x01, 0x85, 0x00, 0x4C, 0x06, 0x80];
+
+#[test]
+fn ram_is_zeroed_at_power_on_by_default() {
+    init();
+
+    let rom_file = create_nrom_file(&READ_RAM_BEFORE_WRITE_PROGRAM);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    console.step_instruction().expect("failed to step instruction");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+    assert_eq!((snapshot.a(), snapshot.x()), (0x00, 0x00));
+}
+
+#[test]
+fn ram_init_ones_reads_ff_before_the_program_writes_it() {
+    init();
+
+    let rom_file = create_nrom_file(&READ_RAM_BEFORE_WRITE_PROGRAM);
+    let mut console = create_console_with_ram_init(&rom_file, Region::NTSC, RamInit::Ones);
+
+    console.step_instruction().expect("failed to step instruction");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+    assert_eq!((snapshot.a(), snapshot.x()), (0xFF, 0xFF));
+}
+
+#[test]
+fn power_cycle_fills_the_ram_again_while_a_reset_leaves_it_untouched() {
+    init();
+
+    let program = [
+        0xA5, 0x10,         // $8000 LDA $10
+        0xA2, 0xAA,         // $8002 LDX #$AA
+        0x86, 0x10,         // $8004 STX $10
+    ];
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console_with_ram_init(&rom_file, Region::NTSC, RamInit::Ones);
+    console.run_instructions(3).expect("failed to run instructions");
+
+    console.reset().expect("failed to reset console");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.a(), 0xAA);
+
+    console.power_cycle().expect("failed to power cycle console");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.a(), 0xFF);
+    assert_eq!(snapshot.sp(), 0xFD);
+}
+
+#[test]
+fn ram_init_pattern_fills_the_ram_from_the_offsets() {
+    init();
+
+    let rom_file = create_nrom_file(&READ_RAM_BEFORE_WRITE_PROGRAM);
+    let checkerboard = RamInit::Pattern(|offset| if offset % 2 == 0 { 0x00 } else { 0xFF });
+    let mut console = create_console_with_ram_init(&rom_file, Region::NTSC, checkerboard);
+
+    console.step_instruction().expect("failed to step instruction");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+    assert_eq!((snapshot.a(), snapshot.x()), (0x00, 0xFF));
+}
+
+#[test]
+fn raw_binary_is_loaded_at_its_address_and_reset_vector_points_to_it() {
+    init();
+
+    let program = [
+        0xA9, 0x42,         // $0600 LDA #$42
+        0xAA,               // $0602 TAX
+        0xE8,               // $0603 INX
+        0x4C, 0x04, 0x06,   // $0604 JMP $0604
+    ];
+
+    let mut raw_file = NamedTempFile::new().expect("failed to create temp file");
+    raw_file.write_all(&program).expect("failed to write program");
+    raw_file.flush().expect("failed to flush raw file");
+
+    let mut console = create_console_with(raw_file.path(), |builder|
+        builder.with_loader_type(LoaderType::RawBinary { load_addr: 0x0600, reset_vector: None }));
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x0602);
+    assert_eq!(snapshot.a(), 0x42);
+
+    console.step_instruction().expect("failed to step instruction");
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x0604);
+    assert_eq!(snapshot.x(), 0x43);
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x0604);
+}
+
+#[test]
+fn headless_console_runs_frames_without_samples_nor_input() {
+    init();
+
+    // strobes the controller and stores the A button at $00, forever
+    let rom_file = create_nrom_file(&[
+        0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+        0xAD, 0x16, 0x40, 0x85, 0x00, 0x4C, 0x00, 0x80,
+    ]);
+    let mut console = create_console_with(rom_file.path(), |builder| builder.with_headless(true));
+    console.set_input(KeyEvents::from_iter([KeyEvent { key: NES_CONTROLLER_KEY_A, pressed: true }])).expect("failed to set input");
+
+    let mut last_count = 0;
+    for _ in 0..100 {
+        let (frame, samples) = console.step_frame().expect("failed to step frame");
+
+        assert!(samples.samples().is_empty());
+        last_count = frame.count();
+    }
+
+    assert_eq!(last_count, 100);
+    assert_eq!(console.peek(0x0000).unwrap() & 0x01, 0);
+}
+
+#[test]
+fn channel_wav_sink_records_each_apu_channel_at_each_sample_tick() {
+    init();
+
+    let sink = Rc::new(RefCell::new(MultiChannelWavSink::new(AUDIO_RATE as u32)));
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let mut console = create_console_with(rom_file.path(), |builder| builder.with_channel_wav_sink(sink.clone()));
+    console.step_frame().expect("failed to step frame");
+
+    let sink = sink.borrow();
+    assert!(!sink.is_empty());
+
+    for channel in 0..APU_CHANNELS {
+        assert_eq!(sink.channel(channel).len(), sink.len());
+    }
+}
+
+#[test]
+fn custom_io_device_captures_the_cpu_writes_and_serves_the_reads() {
+    init();
+
+    let program = [
+        0xA9, 0x41,         // $8000 LDA #$41
+        0x8D, 0x00, 0x50,   // $8002 STA $5000
+        0xA9, 0x42,         // $8005 LDA #$42
+        0x8D, 0x00, 0x50,   // $8007 STA $5000
+        0xAD, 0x01, 0x50,   // $800A LDA $5001
+        0x4C, 0x0D, 0x80,   // $800D JMP $800D
+    ];
+
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let recorder = written.clone();
+
+    let device = CustomIoDevice::new("Print Port", (0x5000, 0x5001))
+        .with_write(Box::new(move |addr, value| recorder.borrow_mut().push((addr, value))))
+        .with_read(Box::new(|addr, _| if addr == 0x5001 { 0x80 } else { 0x00 }));
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console_with(rom_file.path(), |builder| builder.with_custom_io_device(device));
+
+    let mut snapshot = None;
+    for _ in 0..5 {
+        let (_, _, s) = console.step_instruction().expect("failed to step instruction");
+        snapshot = Some(s);
+    }
+
+    assert_eq!(*written.borrow(), vec![(0x5000, 0x41), (0x5000, 0x42)]);
+    assert_eq!(snapshot.expect("no instruction executed").a(), 0x80);
+}
+
+const BLARGG_ROM_ENV: &str = "BLARGG_ROM";
+const BLARGG_MAX_FRAMES: usize = 60 * 60;
+const BLARGG_RESET_DELAY_FRAMES: usize = 10;
+const BLARGG_SRAM_RANGE: (u16, u16) = (0x6000, 0x7FFF);
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_STATUS_RUNNING: u8 = 0x80;
+const BLARGG_STATUS_RESET_REQUESTED: u8 = 0x81;
+const BLARGG_STATUS_PASSED: u8 = 0x00;
+
+/***
+ * blargg's test ROMs protocol, in the SRAM:
+ *   - $6001 - $6003: DE B0 61 once the status is valid,
+ *   - $6000: status, $80 while running, $81 when the reset button must be pressed (after 100 ms or more),
+ *     the result code otherwise ($00: passed),
+ *   - $6004: zero terminated text output.
+ *
+ * The SRAM is a custom I/O device recording the writes, mapped over the cartridge.
+ * Runs until the result code is written or max_frames, returns the status and the text.
+ *
+ * https://github.com/christopherpow/nes-test-roms/blob/master/instr_test-v5/readme.txt
+ ***/
+fn run_blargg_rom(rom_file: &Path, max_frames: usize) -> (u8, String) {
+    let sram = Rc::new(RefCell::new(vec![0x00; (BLARGG_SRAM_RANGE.1 - BLARGG_SRAM_RANGE.0) as usize + 1]));
+    let recorder = sram.clone();
+
+    let device = CustomIoDevice::new("Blargg SRAM", BLARGG_SRAM_RANGE)
+        .with_write(Box::new(move |addr, value| recorder.borrow_mut()[(addr - BLARGG_SRAM_RANGE.0) as usize] = value));
+
+    let mut console = create_console_with(rom_file, |builder| builder.with_custom_io_device(device));
+
+    let mut reset_in = None;
+
+    for _ in 0..max_frames {
+        console.step_frame().expect("failed to step frame");
+
+        let status = {
+            let sram = sram.borrow();
+            if sram[1..4] != BLARGG_SIGNATURE { continue; }
+            sram[0]
+        };
+
+        match (status, reset_in) {
+            (BLARGG_STATUS_RUNNING, _) => {},
+            (BLARGG_STATUS_RESET_REQUESTED, None) => reset_in = Some(BLARGG_RESET_DELAY_FRAMES),
+            (BLARGG_STATUS_RESET_REQUESTED, Some(0)) => {
+                console.reset().expect("failed to reset console");
+                reset_in = None;
+            },
+            (BLARGG_STATUS_RESET_REQUESTED, Some(frames)) => reset_in = Some(frames - 1),
+            _ => break,
+        }
+    }
+
+    let sram = sram.borrow();
+    let text = sram[4..].iter()
+        .take_while(|&&byte| byte != 0x00)
+        .map(|&byte| byte as char)
+        .collect::<String>();
+
+    (sram[0], text)
+}
+
+#[test]
+fn blargg_harness_reads_the_status_and_the_text_written_by_the_rom() {
+    init();
+
+    let mut writes = vec![(0x6000, BLARGG_STATUS_RUNNING), (0x6001, 0xDE), (0x6002, 0xB0), (0x6003, 0x61)];
+    writes.extend("Passed\n".bytes().enumerate().map(|(index, byte)| (0x6004 + index as u16, byte)));
+    writes.push((0x6004 + 7, 0x00));
+    writes.push((0x6000, BLARGG_STATUS_PASSED));
+
+    // LDA #value, STA addr for each write, then JMP to itself
+    let mut program = writes.iter()
+        .flat_map(|&(addr, value)| [0xA9, value, 0x8D, addr as u8, (addr >> 8) as u8])
+        .collect::<Vec<u8>>();
+    let end = 0x8000 + program.len() as u16;
+    program.extend_from_slice(&[0x4C, end as u8, (end >> 8) as u8]);
+
+    let rom_file = create_nrom_file(&program);
+    let (status, text) = run_blargg_rom(rom_file.path(), 10);
+
+    assert_eq!(status, BLARGG_STATUS_PASSED);
+    assert_eq!(text, "Passed\n");
+}
+
+/***
+ * requires one of blargg's cpu_instrs / instr_test ROMs, not distributed with the sources:
+ * BLARGG_ROM=/path/to/01-implied.nes cargo test -- --ignored
+ ***/
+#[test]
+#[ignore]
+fn blargg_cpu_instrs_rom_passes() {
+    init();
+
+    let rom_file = PathBuf::from(std::env::var(BLARGG_ROM_ENV).expect("BLARGG_ROM not set"));
+    let (status, text) = run_blargg_rom(&rom_file, BLARGG_MAX_FRAMES);
+
+    info!("{}", text);
+    assert_eq!(status, BLARGG_STATUS_PASSED, "{}", text);
+    assert!(text.contains("Passed"), "{}", text);
+}
+
+#[test]
+fn ram_search_finds_the_byte_written_during_the_last_frame() {
+    init();
+
+    // LDA #$42, STA $10, JMP $8004
+    let rom_file = create_nrom_file(&[0xA9, 0x42, 0x85, 0x10, 0x4C, 0x04, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    console.step_frame().expect("failed to run a frame");
+    assert_eq!(console.ram_search(SearchCriteria::Changed), vec![0x0010]);
+
+    console.step_frame().expect("failed to run a frame");
+    assert!(console.ram_search(SearchCriteria::Changed).is_empty());
+
+    console.reset_ram_search();
+    assert_eq!(console.ram_search(SearchCriteria::Equals(0x42)), vec![0x0010]);
+}
+
+#[test]
+fn nrom_128_prg_rom_is_mirrored_at_c000_with_the_reset_vector() {
+    init();
+
+    let rom_file = create_nrom_file(&[0xA9, 0x42, 0x4C, 0x00, 0x80]);
+    let console = create_console(&rom_file, Region::NTSC);
+
+    for offset in [0x0000, 0x0001, 0x1234, 0x3FFF] {
+        assert_eq!(console.peek(0x8000 + offset).unwrap(), console.peek(0xC000 + offset).unwrap(), "offset: 0x{:04X}", offset);
+    }
+
+    assert_eq!(console.peek(0x8001).unwrap(), 0x42);
+    assert_eq!((console.peek(0xFFFC).unwrap(), console.peek(0xFFFD).unwrap()), (0x00, 0x80));
+    assert_eq!(console.cpu_snapshot().unwrap().pc(), 0x8000);
+}
+
+#[test]
+fn nrom_256_maps_32kb_of_prg_rom_without_mirror() {
+    init();
+
+    // the first 16 KB filled with $11, the last 16 KB with $22 and the vectors to $C000
+    let mut prg_rom = vec![0x11; PRG_ROM_SIZE * 2];
+    prg_rom[PRG_ROM_SIZE..].fill(0x22);
+    prg_rom[PRG_ROM_SIZE * 2 - 6..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    let rom_file = create_nrom_file_with_prg_rom(&prg_rom);
+    let console = create_console(&rom_file, Region::NTSC);
+
+    assert_eq!(console.peek(0x8000).unwrap(), 0x11);
+    assert_eq!(console.peek(0xC000).unwrap(), 0x22);
+    assert_eq!(console.cpu_snapshot().unwrap().pc(), 0xC000);
+}
+
+#[test]
+fn prg_disassembly_starts_at_the_reset_vector() {
+    init();
+
+    let rom_file = create_nrom_file(&[0xA9, 0x42, 0x4C, 0x00, 0x80]);
+    let console = create_console(&rom_file, Region::NTSC);
+    let lines = console.disassemble_prg().expect("failed to disassemble");
+
+    assert_eq!(lines[0], "; NMI: $8000, RESET: $8000, IRQ: $8000");
+    assert_eq!(lines[1], "8000  A9 42     LDA #$42");
+    assert_eq!(lines[2], "8002  4C 00 80  JMP $8000");
+    assert_eq!(lines.last().unwrap(), "FFF9  EA        NOP");
+}
+
+#[test]
+fn loading_a_save_state_restores_the_ram_and_the_cpu() {
+    init();
+
+    // INC $10, JMP $8000
+    let rom_file = create_nrom_file(&[0xE6, 0x10, 0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    console.step_frame().expect("failed to run a frame");
+    let state = console.save_state();
+    let (counter, pc) = (console.peek(0x0010).unwrap(), console.cpu_snapshot().unwrap().pc());
+
+    console.step_frame().expect("failed to run a frame");
+    assert_ne!(console.peek(0x0010).unwrap(), counter);
+
+    console.load_state(&state).expect("failed to load the state");
+
+    assert_eq!(console.peek(0x0010).unwrap(), counter);
+    assert_eq!(console.cpu_snapshot().unwrap().pc(), pc);
+}
+
+#[test]
+fn truncated_save_state_is_rejected() {
+    init();
+
+    let rom_file = create_nrom_file(&[0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let state = console.save_state();
+
+    assert!(matches!(console.load_state(&state[..state.len() / 2]), Err(NesConsoleError::SaveStateError(_))));
+    assert!(matches!(console.load_state(b"not a state"), Err(NesConsoleError::SaveStateError(_))));
+}
+
+#[test]
+fn save_state_with_a_section_of_another_size_leaves_the_console_untouched() {
+    init();
+
+    // INC $10, JMP $8000
+    let rom_file = create_nrom_file(&[0xE6, 0x10, 0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    let saved = SaveState::from_bytes(&console.save_state()).unwrap();
+    let mut devices = saved.devices().to_vec();
+    devices.last_mut().unwrap().1.push(0x00);
+    let state = SaveState::new(saved.cpu().to_vec(), devices).to_bytes();
+
+    console.step_frame().expect("failed to run a frame");
+    let (counter, pc) = (console.peek(0x0010).unwrap(), console.cpu_snapshot().unwrap().pc());
+
+    assert!(matches!(console.load_state(&state), Err(NesConsoleError::SaveStateError(_))));
+    assert_eq!(console.peek(0x0010).unwrap(), counter);
+    assert_eq!(console.cpu_snapshot().unwrap().pc(), pc);
+}
+
+/// NROM image running ```program``` from $8000, the NMI vector pointing to $9000 and the IRQ vector to $A000
+fn create_nrom_file_with_interrupt_handlers(program: &[u8]) -> NamedTempFile {
+    let mut prg_rom = vec![0xEA; PRG_ROM_SIZE];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x1000..0x1003].copy_from_slice(&[0x4C, 0x00, 0x90]);
+    prg_rom[0x2000..0x2003].copy_from_slice(&[0x4C, 0x00, 0xA0]);
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x90, 0x00, 0x80, 0x00, 0xA0]);
+
+    create_nrom_file_with_prg_rom(&prg_rom)
+}
+
+#[test]
+fn signaled_nmi_vectors_through_fffa_on_the_next_step() {
+    init();
+
+    let rom_file = create_nrom_file_with_interrupt_handlers(&[0xEA, 0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x8001);
+
+    console.signal_interrupt(InterruptKind::Nmi).expect("failed to signal the nmi");
+
+    let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+    assert_eq!(snapshot.pc(), 0x9000);
+}
+
+#[test]
+fn signaled_irq_waits_for_the_interrupt_disable_flag_to_be_cleared() {
+    init();
+
+    let program = [
+        0xEA,               // $8000 NOP
+        0xEA,               // $8001 NOP
+        0x58,               // $8002 CLI
+        0xEA,               // $8003 NOP
+        0x4C, 0x03, 0x80,   // $8004 JMP $8003
+    ];
+
+    let rom_file = create_nrom_file_with_interrupt_handlers(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+
+    console.signal_interrupt(InterruptKind::Irq(APU_FRAME_COUNTER_IRQ)).expect("failed to signal the irq");
+
+    for pc in [0x8001, 0x8002, 0x8003, 0xA000] {
+        let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+        assert_eq!(snapshot.pc(), pc);
+    }
+}
+
+/// The scanline the PPU renders next, the one the CPU is running: the first one is the pre-render scanline.
+fn scanline_rendered_next(console: &NesConsole) -> u16 {
+    let scanlines_per_frame = console.get_ppu().borrow().scanlines_per_frame() as u64;
+
+    ((scanlines_per_frame - 1 + console.perf_counters().scanlines()) % scanlines_per_frame) as u16
+}
+
+#[test]
+fn ppu_position_follows_the_scanlines_rendered_across_frames_dma_and_reset() {
+    init();
+
+    // LDA #$02, STA $4014 (OAM DMA), JMP $8000
+    let rom_file = create_nrom_file(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0x4C, 0x00, 0x80]);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let ppu = console.get_ppu();
+    let clock = ppu.borrow().clock();
+
+    for frame in 0..5 {
+        if frame == 2 {
+            console.reset().expect("failed to reset console");
+        }
+
+        let mut position = (ppu.borrow().current_scanline(), ppu.borrow().current_dot());
+        assert_eq!(position.0, scanline_rendered_next(&console));
+
+        for _ in 0..29781 / 3 {
+            let cycles = console.run_instructions(1).expect("failed to run instruction") as u16;
+            let next_position = (ppu.borrow().current_scanline(), ppu.borrow().current_dot());
+
+            assert_eq!(next_position.0, scanline_rendered_next(&console));
+            assert_eq!(next_position, (clock.borrow().scanline(), clock.borrow().dot()));
+
+            if next_position.0 == position.0 {
+                assert_eq!(next_position.1, position.1 + 3 * cycles);
+            }
+
+            position = next_position;
+        }
+    }
+}
+
+const VBLANK_SET_SCANLINE: u16 = 241;
+const STATUS_READ_ADDR: u16 = 0x800F;
+const NMI_COUNT_ADDR: u16 = 0x0010;
+
+/***
+ * NROM image waiting for the warm-up (3 vblanks), enabling the NMI, then reading $2002 forever at $800F,
+ * the NMI handler at $8100 counts the NMIs at $10.
+ ***/
+fn create_nrom_file_reading_the_status_with_nmi() -> NamedTempFile {
+    let program = [
+        0xA2, 0x03,         // $8000 LDX #$03
+        0x2C, 0x02, 0x20,   // $8002 BIT $2002
+        0x10, 0xFB,         // $8005 BPL $8002
+        0xCA,               // $8007 DEX
+        0xD0, 0xF8,         // $8008 BNE $8002
+        0xA9, 0x80,         // $800A LDA #$80
+        0x8D, 0x00, 0x20,   // $800C STA $2000
+        0xAD, 0x02, 0x20,   // $800F LDA $2002
+        0x4C, 0x0F, 0x80,   // $8012 JMP $800F
+    ];
+
+    let mut prg_rom = vec![0xEA; PRG_ROM_SIZE];
+    prg_rom[..program.len()].copy_from_slice(&program);
+    prg_rom[0x0100..0x0103].copy_from_slice(&[0xE6, 0x10, 0x40]);    // $8100 INC $10, RTI
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x81, 0x00, 0x80, 0x00, 0x80]);
+
+    create_nrom_file_with_prg_rom(&prg_rom)
+}
+
+#[test]
+fn status_read_by_the_cpu_one_dot_before_vblank_suppresses_the_flag_and_the_nmi() {
+    init();
+
+    let rom_file = create_nrom_file_reading_the_status_with_nmi();
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let ppu = console.get_ppu();
+    let mut pc = console.cpu_snapshot().unwrap().pc();
+
+    // the read of $2002 starting at dot 0 of scanline 241, as the phase of the loop drifts from frame to frame
+    let mut racing_read = false;
+    for _ in 0..60 * 29781 / 3 {
+        let position = (ppu.borrow().current_scanline(), ppu.borrow().current_dot());
+        let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+        if pc == STATUS_READ_ADDR && position == (VBLANK_SET_SCANLINE, 0) {
+            assert_eq!(snapshot.a() & 0x80, 0);
+            racing_read = true;
+            pc = snapshot.pc();
+            break;
+        }
+
+        pc = snapshot.pc();
+    }
+
+    assert!(racing_read, "no read of $2002 at dot 0 of scanline 241");
+
+    let nmi_count = console.peek(NMI_COUNT_ADDR).unwrap();
+    assert_ne!(nmi_count, 0);
+
+    // the flag is never set for this frame: no read sees it, the NMI is not generated
+    while ppu.borrow().current_scanline() != 0 {
+        let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+        if pc == STATUS_READ_ADDR {
+            assert_eq!(snapshot.a() & 0x80, 0);
+        }
+
+        pc = snapshot.pc();
+    }
+
+    assert_eq!(console.peek(NMI_COUNT_ADDR).unwrap(), nmi_count);
+}
+
+const OAM_DATA_READ_ADDR: u16 = 0x801E;
+
+#[test]
+fn oam_data_read_by_the_cpu_returns_ff_while_the_secondary_oam_is_cleared() {
+    init();
+
+    let program = [
+        0xA9, 0x00,         // $8000 LDA #$00
+        0x8D, 0x03, 0x20,   // $8002 STA $2003
+        0xA9, 0x42,         // $8005 LDA #$42
+        0x8D, 0x04, 0x20,   // $8007 STA $2004
+        0xA2, 0x03,         // $800A LDX #$03
+        0x2C, 0x02, 0x20,   // $800C BIT $2002
+        0x10, 0xFB,         // $800F BPL $800C
+        0xCA,               // $8011 DEX
+        0xD0, 0xF8,         // $8012 BNE $800C
+        0xA9, 0x18,         // $8014 LDA #$18
+        0x8D, 0x01, 0x20,   // $8016 STA $2001
+        0xA9, 0x00,         // $8019 LDA #$00
+        0x8D, 0x03, 0x20,   // $801B STA $2003
+        0xAD, 0x04, 0x20,   // $801E LDA $2004
+        0x4C, 0x19, 0x80,   // $8021 JMP $8019
+    ];
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let ppu = console.get_ppu();
+    let mut pc = console.cpu_snapshot().unwrap().pc();
+    let (mut cleared_reads, mut oam_reads) = (0, 0);
+
+    for _ in 0..6 * 29781 / 3 {
+        let (scanline, dot) = (ppu.borrow().current_scanline(), ppu.borrow().current_dot());
+        let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+        if pc == OAM_DATA_READ_ADDR {
+            if scanline <= 239 && (1..=64).contains(&dot) {
+                assert_eq!(snapshot.a(), 0xFF, "scanline {}, dot {}", scanline, dot);
+                cleared_reads += 1;
+            } else {
+                assert_eq!(snapshot.a(), 0x42, "scanline {}, dot {}", scanline, dot);
+                oam_reads += 1;
+            }
+        }
+
+        pc = snapshot.pc();
+    }
+
+    assert_ne!(cleared_reads, 0);
+    assert_ne!(oam_reads, 0);
+}
+
+const ADDR_SECOND_WRITE_ADDR: u16 = 0x8016;
+
+#[test]
+fn addr_written_by_the_cpu_before_dot_256_gets_the_y_increment_of_the_scanline() {
+    init();
+
+    // $2006 written with $2100 (fine Y 2, coarse Y 8), then a wait longer than a scanline, with the background on
+    let program = [
+        0xA2, 0x03,         // $8000 LDX #$03
+        0x2C, 0x02, 0x20,   // $8002 BIT $2002
+        0x10, 0xFB,         // $8005 BPL $8002
+        0xCA,               // $8007 DEX
+        0xD0, 0xF8,         // $8008 BNE $8002
+        0xA9, 0x08,         // $800A LDA #$08
+        0x8D, 0x01, 0x20,   // $800C STA $2001
+        0xA9, 0x21,         // $800F LDA #$21
+        0x8D, 0x06, 0x20,   // $8011 STA $2006
+        0xA9, 0x00,         // $8014 LDA #$00
+        0x8D, 0x06, 0x20,   // $8016 STA $2006
+        0xA0, 0x20,         // $8019 LDY #$20
+        0x88,               // $801B DEY
+        0xD0, 0xFD,         // $801C BNE $801B
+        0x4C, 0x0F, 0x80,   // $801E JMP $800F
+    ];
+
+    let rom_file = create_nrom_file(&program);
+    let mut console = create_console(&rom_file, Region::NTSC);
+    let ppu = console.get_ppu();
+    let mut pc = console.cpu_snapshot().unwrap().pc();
+    let mut written_at = None;
+    let (mut incremented, mut not_incremented) = (0, 0);
+
+    for _ in 0..6 * 29781 / 3 {
+        let (scanline, dot) = (ppu.borrow().current_scanline(), ppu.borrow().current_dot());
+        let (_, _, snapshot) = console.step_instruction().expect("failed to step instruction");
+
+        if pc == ADDR_SECOND_WRITE_ADDR && scanline <= 239 {
+            written_at = Some((scanline, dot));
+        }
+
+        // the write is applied once the scanline is rendered
+        if let Some((scanline, dot)) = written_at && ppu.borrow().current_scanline() != scanline {
+            if dot < 256 {
+                assert_eq!(console.ppu_snapshot().v(), 0x3100, "scanline {}, dot {}", scanline, dot);
+                incremented += 1;
+            } else {
+                assert_eq!(console.ppu_snapshot().v(), 0x2100, "scanline {}, dot {}", scanline, dot);
+                not_incremented += 1;
+            }
+
+            written_at = None;
+        }
+
+        pc = snapshot.pc();
+    }
+
+    assert_ne!(incremented, 0);
+    assert_ne!(not_incremented, 0);
+}
@@ -0,0 +1,56 @@
+use crate::nes_frame::NesFrame;
+use crate::ntsc_filter::{NtscFilter, NTSC_FILTER_SCALE};
+use crate::tests::init;
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 240;
+
+fn create_frame(color: impl Fn(u8, u8) -> (u8, u8, u8)) -> NesFrame {
+    let mut frame = NesFrame::new(WIDTH, HEIGHT);
+
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            frame.set_pixel(x as u8, y as u8, color(x as u8, y as u8));
+        }
+    }
+
+    frame
+}
+
+#[test]
+fn ntsc_filter_widens_the_frame_and_keeps_its_height() {
+    init();
+    let frame = create_frame(|_, _| (0x00, 0x00, 0x00));
+
+    let filtered = NtscFilter::apply(&frame);
+
+    assert_eq!(filtered.width(), WIDTH * NTSC_FILTER_SCALE);
+    assert_eq!(filtered.height(), HEIGHT);
+    assert_eq!(filtered.pixels().len(), WIDTH * NTSC_FILTER_SCALE * HEIGHT * 4);
+    assert_eq!(filtered.count(), frame.count());
+}
+
+#[test]
+fn ntsc_filter_leaves_a_solid_color_frame_untouched() {
+    init();
+    let frame = create_frame(|_, _| (0x21, 0x55, 0xFF));
+
+    let filtered = NtscFilter::apply(&frame);
+
+    for (index, rgba) in filtered.pixels().chunks_exact(4).enumerate() {
+        assert_eq!(rgba, [0x21, 0x55, 0xFF, 0xFF], "sample {}", index);
+    }
+}
+
+#[test]
+fn ntsc_filter_bleeds_colors_over_the_edges() {
+    init();
+    let frame = create_frame(|x, _| if x < 128 { (0xFF, 0x29, 0x50) } else { (0x00, 0x77, 0xFF) });
+
+    let filtered = NtscFilter::apply(&frame);
+    let edge = 128 * NTSC_FILTER_SCALE * 4;
+
+    assert_ne!(filtered.pixels()[edge - 4..edge], [0xFF, 0x29, 0x50, 0xFF]);
+    assert_ne!(filtered.pixels()[edge..edge + 4], [0x00, 0x77, 0xFF, 0xFF]);
+    assert_eq!(filtered.pixels()[0..4], [0xFF, 0x29, 0x50, 0xFF]);
+}
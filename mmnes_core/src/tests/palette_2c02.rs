@@ -0,0 +1,49 @@
+use crate::palette::Palette;
+use crate::palette_2c02::{Palette2C02, PaletteError, SYSTEM_PALETTE};
+use crate::tests::init;
+
+/***
+ * 64 colors where color n is (n, 2n, 255 - n), each emphasis variant adds its number to the red component
+ ***/
+fn create_pal_file(variants: u8) -> Vec<u8> {
+    (0..variants).flat_map(|variant| {
+        (0..64u8).flat_map(move |color| [color + variant, color * 2, 255 - color])
+    }).collect()
+}
+
+#[test]
+fn load_from_pal_replaces_the_system_palette() {
+    init();
+
+    Palette2C02::load_from_pal(&create_pal_file(1)).unwrap();
+
+    assert_eq!(Palette2C02::rgba_opaque(0x00), (0, 0, 255, 255));
+    assert_eq!(Palette2C02::rgba_opaque(0x16), (0x16, 0x2C, 255 - 0x16, 255));
+    assert_eq!(Palette2C02::rgba_opaque(0x3F), (0x3F, 0x7E, 255 - 0x3F, 255));
+    assert_eq!(Palette2C02::rgb_emphasized(0x16, 0x05), (0x16, 0x2C, 255 - 0x16));
+
+    Palette2C02::reset_to_system_palette();
+    assert_eq!(Palette2C02::rgb(0x16), SYSTEM_PALETTE[0x16]);
+}
+
+#[test]
+fn load_from_extended_pal_selects_the_emphasis_variant() {
+    init();
+
+    Palette2C02::load_from_pal(&create_pal_file(8)).unwrap();
+
+    assert_eq!(Palette2C02::rgb(0x20), (0x20, 0x40, 255 - 0x20));
+    assert_eq!(Palette2C02::rgb_emphasized(0x20, 0x01), (0x21, 0x40, 255 - 0x20));
+    assert_eq!(Palette2C02::rgb_emphasized(0x20, 0x07), (0x27, 0x40, 255 - 0x20));
+}
+
+#[test]
+fn load_from_pal_rejects_other_sizes() {
+    init();
+
+    for size in [0, 191, 193, 1535] {
+        assert_eq!(Palette2C02::load_from_pal(&vec![0; size]), Err(PaletteError::InvalidSize(size)));
+    }
+
+    assert_eq!(Palette2C02::rgb(0x16), SYSTEM_PALETTE[0x16]);
+}
@@ -1,13 +1,21 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use log::debug;
-use crate::bus::MockBusStub;
-use crate::bus_device::{BusDeviceType, MockBusDeviceStub};
-use crate::cpu::MockCpuStub;
+use log::{debug, info};
+use crate::bus::{Bus, MockBusStub};
+use crate::bus_device::{BusDevice, BusDeviceType, MockBusDeviceStub};
+use crate::cpu::{Interruptible, MockCpuStub, CPU};
+use crate::cpu_6502::Cpu6502;
 use crate::memory::{Memory, MemoryError, MemoryType};
+use crate::memory_bank::MemoryBank;
 use crate::memory_ciram::PpuNameTableMirroring;
-use crate::ppu_2c02::Ppu2c02;
+use crate::nes_bus::NESBus;
+use crate::ppu::PPU;
+use crate::palette::Palette;
+use crate::palette_2c02::Palette2C02;
+use crate::ppu_2c02::{Pixel, PixelLines, Ppu2c02, ScanlineEvent, SpritePriority};
+use crate::region::Region;
 use crate::tests::init;
+use crate::util::measure_exec_time;
 
 const CHR_MEMORY_RANGE: (u16, u16) = (0x0000, 0x1FFF);
 const CHR_MEMORY_SIZE: usize = 8192;
@@ -36,13 +44,23 @@ fn create_ppu() -> Ppu2c02 {
 }
 
 fn create_ppu_with_nametable_mirroring(mirroring: PpuNameTableMirroring) -> Ppu2c02 {
+    create_ppu_with_nametable_mirroring_and_region(mirroring, Region::NTSC)
+}
+
+fn create_ppu_with_nametable_mirroring_and_region(mirroring: PpuNameTableMirroring, region: Region) -> Ppu2c02 {
+    create_ppu_with_cpu(mirroring, region, create_cpu())
+}
+
+fn create_ppu_with_cpu(mirroring: PpuNameTableMirroring, region: Region, cpu: MockCpuStub) -> Ppu2c02 {
     let mut chr_rom = MockBusDeviceStub::new();
-    let cpu = create_cpu();
 
     chr_rom.expect_size().returning(|| CHR_MEMORY_SIZE);
     chr_rom.expect_get_virtual_address_range().returning(|| CHR_MEMORY_RANGE);
+    chr_rom.expect_get_address_ranges().returning(|| vec![CHR_MEMORY_RANGE]);
     chr_rom.expect_get_device_type().returning(|| BusDeviceType::WRAM(MemoryType::StandardMemory));
     chr_rom.expect_get_name().returning(|| CHR_NAME.to_string());
+    chr_rom.expect_serialize_state().returning(Vec::new);
+    chr_rom.expect_deserialize_state().returning(|_| Ok(()));
     chr_rom.expect_read_byte().returning(move |addr| {
         if addr == VALID_CH_ROM_ADDRESS || addr == VALID_CH_ROM_ADDRESS + 1 {
             Ok(VALID_DATA_VALUE) }
@@ -54,7 +72,8 @@ fn create_ppu_with_nametable_mirroring(mirroring: PpuNameTableMirroring) -> Ppu2
     Ppu2c02::new(
         Rc::new(RefCell::new(chr_rom)),
         Rc::new(RefCell::new(mirroring)),
-        Rc::new(RefCell::new(cpu))
+        Rc::new(RefCell::new(cpu)),
+        region
     ).unwrap()
 }
 
@@ -99,6 +118,23 @@ fn read_write_byte_works() {
     assert_eq!(ppu.read_byte(address).unwrap(), value);
 }
 
+#[test]
+fn write_only_registers_read_the_open_bus_latch() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    ppu.write_byte(0x00, 0x80).unwrap();
+    ppu.write_byte(0x03, 0x5A).unwrap();
+
+    assert_eq!(ppu.get_register_value("controller"), 0x80);
+    for address in [0x00, 0x01, 0x03, 0x05, 0x06] {
+        assert_eq!(ppu.read_byte(address).unwrap(), 0x5A);
+    }
+
+    assert_eq!(ppu.read_byte(0x02).unwrap() & 0x1F, 0x1A);
+}
+
 #[test]
 fn read_write_word_raise_error() {
     init();
@@ -178,6 +214,32 @@ fn write_to_addr_and_data_and_read_to_data_registers_to_palette_works() {
     assert_eq!(result, VALID_DATA_VALUE);
 }
 
+#[test]
+fn sprite_palette_backdrops_alias_the_background_palette_backdrops() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    for (sprite_backdrop, background_backdrop) in [(0x3F10, 0x3F00), (0x3F14, 0x3F04), (0x3F18, 0x3F08), (0x3F1C, 0x3F0C)] {
+        write_address_to_addr_register(&mut ppu, sprite_backdrop).unwrap();
+        write_data_to_data_register(&mut ppu, 0x21).unwrap();
+
+        write_address_to_addr_register(&mut ppu, background_backdrop).unwrap();
+        assert_eq!(ppu.read_byte(0x07).unwrap(), 0x21);
+
+        write_address_to_addr_register(&mut ppu, background_backdrop).unwrap();
+        write_data_to_data_register(&mut ppu, 0x0F).unwrap();
+        write_address_to_addr_register(&mut ppu, sprite_backdrop).unwrap();
+        assert_eq!(ppu.read_byte(0x07).unwrap(), 0x0F);
+    }
+
+    // the other entries of the sprite palettes are not mirrored
+    write_address_to_addr_register(&mut ppu, 0x3F11).unwrap();
+    write_data_to_data_register(&mut ppu, 0x30).unwrap();
+    write_address_to_addr_register(&mut ppu, 0x3F01).unwrap();
+    assert_ne!(ppu.read_byte(0x07).unwrap(), 0x30);
+}
+
 #[test]
 fn read_to_data_registers_with_increments_to_name_tables_works() {
     init();
@@ -292,12 +354,13 @@ fn test_read_to_status_clears_vblank_and_reset_latch() {
     let result0 = ppu.read_byte(status).unwrap();
     let result1 = ppu.read_byte(status).unwrap();
 
-    assert_eq!(result0, status_value);
-    assert_eq!(result1, status_value & 0x7F );
+    // the low 5 bits are the open bus, last written by $2006
+    assert_eq!(result0, (status_value & 0xE0) | (addr_value & 0x1F));
+    assert_eq!(result1, result0 & 0x7F);
 
     ppu.write_byte(addr, addr_value).unwrap();
-    let result2 = ppu.read_byte(addr).unwrap();
-    assert_eq!(result2, 0x00);
+    ppu.write_byte(addr, 0xCD).unwrap();
+    assert_eq!(ppu.get_v_value(), ((addr_value as u16) << 8 | 0xCD) & 0x3FFF);
 }
 
 #[test]
@@ -315,3 +378,1136 @@ fn v_wraps_to_0x0000_when_incrementing_from_0x3fff() {
     println!("V: 0x{:04X}", v);
     assert_eq!(ppu.get_v_value(), 0x0000);
 }
+
+fn count_scanlines_between_vblanks(ppu: &mut Ppu2c02) -> u16 {
+    let mut cycles = 0;
+    let mut scanlines = 0;
+    let mut vblank_edges = 0;
+    let mut previous_vblank = false;
+
+    while vblank_edges < 2 {
+        let (next_cycles, _) = ppu.run(cycles, 1).unwrap();
+        cycles = next_cycles;
+
+        let vblank = ppu.get_register_value("status") & 0x80 != 0;
+        if vblank && !previous_vblank {
+            vblank_edges += 1;
+        }
+
+        if vblank_edges == 1 {
+            scanlines += 1;
+        }
+
+        previous_vblank = vblank;
+    }
+
+    scanlines
+}
+
+#[test]
+fn ntsc_ppu_renders_262_scanlines_per_frame() {
+    init();
+
+    let mut ppu = create_ppu_with_nametable_mirroring_and_region(PpuNameTableMirroring::Horizontal, Region::NTSC);
+
+    assert_eq!(ppu.scanlines_per_frame(), 262);
+    assert_eq!(count_scanlines_between_vblanks(&mut ppu), 262);
+}
+
+#[test]
+fn pal_ppu_renders_312_scanlines_per_frame() {
+    init();
+
+    let mut ppu = create_ppu_with_nametable_mirroring_and_region(PpuNameTableMirroring::Horizontal, Region::PAL);
+
+    assert_eq!(ppu.scanlines_per_frame(), 312);
+    assert_eq!(count_scanlines_between_vblanks(&mut ppu), 312);
+}
+
+/***
+ * the status register is never read: the vblank flag must be cleared by the pre-render scanline alone.
+ * each frame starts with the pre-render scanline and the 241 rendered scanlines (0 - 240) with the flag low,
+ * followed by the vblank scanlines (241 up to the pre-render one) with the flag high.
+ ***/
+fn assert_vblank_cleared_without_status_reads(region: Region, vblank_scanlines: usize) {
+    let mut ppu = create_ppu_with_nametable_mirroring_and_region(PpuNameTableMirroring::Horizontal, region);
+    let scanlines_per_frame = ppu.scanlines_per_frame() as usize;
+
+    let flags = (0..3 * scanlines_per_frame).map(|_| {
+        ppu.run(0, 1).unwrap();
+        ppu.get_register_value("status") & 0x80 != 0
+    }).collect::<Vec<bool>>();
+
+    for (number, frame) in flags.chunks(scanlines_per_frame).enumerate() {
+        let (rendering, vblank) = frame.split_at(scanlines_per_frame - vblank_scanlines);
+
+        assert!(rendering.iter().all(|flag| !flag), "frame {}: vblank flag set while rendering", number);
+        assert!(vblank.iter().all(|flag| *flag), "frame {}: vblank flag clear during vblank", number);
+    }
+}
+
+#[test]
+fn ntsc_vblank_flag_is_cleared_by_the_pre_render_scanline_without_status_reads() {
+    init();
+
+    assert_vblank_cleared_without_status_reads(Region::NTSC, 20);
+}
+
+#[test]
+fn pal_vblank_flag_is_cleared_by_the_pre_render_scanline_without_status_reads() {
+    init();
+
+    assert_vblank_cleared_without_status_reads(Region::PAL, 70);
+}
+
+#[test]
+fn scanline_hook_is_called_at_the_start_of_each_visible_scanline() {
+    init();
+
+    let mut ppu = create_ppu();
+    let events = Rc::new(RefCell::new(Vec::<ScanlineEvent>::new()));
+    let recorder = events.clone();
+
+    ppu.set_scanline_hook(Box::new(move |event| recorder.borrow_mut().push(event)));
+    ppu.write_byte(0x00, 0x10).unwrap();
+
+    for _ in 0..ppu.scanlines_per_frame() {
+        ppu.run(0, 1).unwrap();
+    }
+
+    let scanlines = events.borrow().iter().map(|event| event.scanline()).collect::<Vec<u16>>();
+
+    assert_eq!(scanlines, (0..240).collect::<Vec<u16>>());
+    assert!(events.borrow().iter().all(|event| event.control() == 0x10));
+
+    ppu.clear_scanline_hook();
+    ppu.run(0, 1).unwrap();
+
+    assert_eq!(events.borrow().len(), 240);
+}
+
+#[test]
+fn register_writes_are_ignored_during_the_warm_up_after_reset() {
+    init();
+
+    let mut ppu = create_ppu();
+    let clock = ppu.clock();
+    ppu.reset().unwrap();
+
+    clock.borrow_mut().advance(29657);
+    ppu.write_byte(0x00, 0x10).unwrap();
+    ppu.write_byte(0x01, 0x1E).unwrap();
+
+    assert_eq!(ppu.get_register_value("controller"), 0x00);
+    assert_eq!(ppu.get_register_value("mask"), 0x00);
+
+    clock.borrow_mut().advance(1);
+    ppu.write_byte(0x00, 0x10).unwrap();
+    ppu.write_byte(0x01, 0x1E).unwrap();
+
+    assert_eq!(ppu.get_register_value("controller"), 0x10);
+    assert_eq!(ppu.get_register_value("mask"), 0x1E);
+}
+
+#[test]
+fn ppu_clock_advances_3_dots_per_cpu_cycle_and_wraps_at_341() {
+    init();
+
+    let ppu = create_ppu();
+    let clock = ppu.clock();
+
+    assert_eq!((ppu.current_scanline(), ppu.current_dot()), (0, 0));
+
+    clock.borrow_mut().advance(1);
+    assert_eq!((ppu.current_scanline(), ppu.current_dot()), (0, 3));
+
+    clock.borrow_mut().advance(112);
+    assert_eq!((ppu.current_scanline(), ppu.current_dot()), (0, 339));
+
+    clock.borrow_mut().advance(1);
+    assert_eq!((ppu.current_scanline(), ppu.current_dot()), (1, 1));
+
+    // 29781 cpu cycles are 89343 dots, 1 dot more than a 262 scanlines frame
+    clock.borrow_mut().advance(29781 - 114);
+    assert_eq!((ppu.current_scanline(), ppu.current_dot()), (0, 1));
+}
+
+#[test]
+fn pal_ppu_clock_advances_16_dots_every_5_cpu_cycles() {
+    init();
+
+    let ppu = create_ppu_with_nametable_mirroring_and_region(PpuNameTableMirroring::Horizontal, Region::PAL);
+    let clock = ppu.clock();
+
+    clock.borrow_mut().advance(2);
+    assert_eq!(ppu.current_dot(), 6);
+
+    clock.borrow_mut().advance(3);
+    assert_eq!(ppu.current_dot(), 16);
+}
+
+/// A CPU running ```program``` from $8000, its bus has the PPU.
+fn create_cpu_with_ppu(ppu: Rc<RefCell<Ppu2c02>>, program: &[u8]) -> Cpu6502 {
+    let rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    for (offset, byte) in program.iter().enumerate() {
+        rom.borrow_mut().write_byte(offset as u16, *byte).unwrap();
+    }
+    rom.borrow_mut().write_word(0x7FFC, 0x8000).unwrap();
+
+    let mut bus = NESBus::new();
+    bus.add_device(rom).unwrap();
+    bus.add_device(ppu).unwrap();
+
+    let mut cpu = Cpu6502::new(Rc::new(RefCell::new(bus)));
+    cpu.reset().unwrap();
+
+    cpu
+}
+
+#[test]
+fn indexed_store_dummy_read_hits_ppu_status_register() {
+    init();
+
+    let ppu = Rc::new(RefCell::new(create_ppu()));
+    ppu.borrow_mut().reset().unwrap();
+
+    let program = [
+        0xA2, 0x08,         // LDX #$08
+        0x9D, 0xFA, 0x3F,   // STA $3FFA,X: dummy read at $3F02 (mirror of $2002), write at $4002
+    ];
+    let mut cpu = create_cpu_with_ppu(ppu.clone(), &program);
+
+    assert_ne!(ppu.borrow().get_register_value("status") & 0x80, 0);
+
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+
+    assert_eq!(ppu.borrow().get_register_value("status") & 0x80, 0);
+}
+
+#[test]
+fn snapshot_of_an_indexed_load_crossing_a_page_leaves_the_vblank_flag_set() {
+    init();
+
+    let ppu = Rc::new(RefCell::new(create_ppu()));
+    ppu.borrow_mut().reset().unwrap();
+
+    let program = [
+        0xA2, 0x12,         // LDX #$12
+        0xBD, 0xF0, 0x20,   // LDA $20F0,X: page crossed, dummy read at $2002, read at $2102
+    ];
+    let mut cpu = create_cpu_with_ppu(ppu.clone(), &program);
+    cpu.step_instruction().unwrap();
+
+    let snapshot = cpu.snapshot().unwrap();
+    assert_eq!(snapshot.pc(), 0x8002);
+    assert_ne!(ppu.borrow().get_register_value("status") & 0x80, 0);
+
+    // executed, the dummy read and the read of $2102 (mirror of $2002) clear it
+    cpu.step_instruction().unwrap();
+    assert_eq!(ppu.borrow().get_register_value("status") & 0x80, 0);
+}
+
+/***
+ * a CPU whose bus has the PPU, the PPU signaling its NMI to that CPU. the program at $8000:
+ * LDA #$80, STA $2000 (NMI enabled by the write, on the last cycle of the STA), NOP, NOP.
+ * the NMI handler is at $9000
+ ***/
+fn create_cpu_writing_the_nmi_enable(vblank: bool) -> Rc<RefCell<Cpu6502>> {
+    let program = [0xA9, 0x80, 0x8D, 0x00, 0x20, 0xEA, 0xEA];
+
+    let rom = Rc::new(RefCell::new(MemoryBank::new(32 * 1024, (0x8000, 0xFFFF))));
+    for (offset, byte) in program.iter().enumerate() {
+        rom.borrow_mut().write_byte(offset as u16, *byte).unwrap();
+    }
+    rom.borrow_mut().write_word(0x7FFA, 0x9000).unwrap();
+    rom.borrow_mut().write_word(0x7FFC, 0x8000).unwrap();
+
+    let bus = Rc::new(RefCell::new(NESBus::new()));
+    let cpu = Rc::new(RefCell::new(Cpu6502::new(bus.clone())));
+
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+    let mut ppu = Ppu2c02::new(chr_memory, Rc::new(RefCell::new(PpuNameTableMirroring::Horizontal)), cpu.clone(), Region::NTSC).unwrap();
+
+    if vblank {
+        run_ppu_until_vblank_set_scanline(&mut ppu, 0);
+        ppu.run(0, 1).unwrap();
+    }
+
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(MemoryBank::new(2 * 1024, (0x0000, 0x07FF))))).unwrap();
+    bus.borrow_mut().add_device(rom).unwrap();
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(ppu))).unwrap();
+
+    cpu.borrow_mut().reset().unwrap();
+    cpu
+}
+
+fn step_to_pc(cpu: &Rc<RefCell<Cpu6502>>) -> u16 {
+    cpu.borrow_mut().step_instruction().unwrap();
+    cpu.borrow().snapshot().unwrap().pc()
+}
+
+#[test]
+fn nmi_raised_on_the_last_cycle_of_an_instruction_is_taken_after_the_next_one() {
+    init();
+
+    // the write of the STA enables the NMI during vblank: too late for the poll of the STA, taken after the NOP
+    let cpu = create_cpu_writing_the_nmi_enable(true);
+
+    assert_eq!(step_to_pc(&cpu), 0x8002);
+    assert_eq!(step_to_pc(&cpu), 0x8005);
+    assert_eq!(step_to_pc(&cpu), 0x9000);
+
+    // raised on the cycle after the LDA, the NMI is polled by the STA and taken at its end
+    let cpu = create_cpu_writing_the_nmi_enable(false);
+
+    assert_eq!(step_to_pc(&cpu), 0x8002);
+    cpu.borrow_mut().signal_nmi().unwrap();
+    assert_eq!(step_to_pc(&cpu), 0x9000);
+}
+
+fn create_ppu_with_chr_memory(chr_memory: Rc<RefCell<MemoryBank>>) -> Ppu2c02 {
+    Ppu2c02::new(
+        chr_memory,
+        Rc::new(RefCell::new(PpuNameTableMirroring::Horizontal)),
+        Rc::new(RefCell::new(create_cpu())),
+        Region::NTSC
+    ).unwrap()
+}
+
+#[test]
+fn dump_pattern_tables_produces_512_tiles_of_8x8_rgba_pixels() {
+    init();
+
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+    let ppu = create_ppu_with_chr_memory(chr_memory);
+
+    let dump = ppu.dump_pattern_tables(0).unwrap();
+
+    assert_eq!(dump.palette().len(), 32);
+    assert_eq!(dump.tiles().len(), 512);
+    assert_eq!(dump.pattern_table(1).len(), 256);
+
+    for tile in dump.tiles() {
+        assert_eq!((tile.width(), tile.height()), (8, 8));
+        assert_eq!(tile.pixels().len(), 8 * 8 * 4);
+    }
+}
+
+#[test]
+fn dump_pattern_tables_colorizes_tiles_with_the_selected_palette() {
+    init();
+
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+
+    // tile 1 of the right pattern table: first pixel uses color 3 (both bit planes set)
+    chr_memory.borrow_mut().write_byte(0x1010, 0x80).unwrap();
+    chr_memory.borrow_mut().write_byte(0x1018, 0x80).unwrap();
+
+    let mut ppu = create_ppu_with_chr_memory(chr_memory);
+
+    // sprite palette 1 ($3F14 - $3F17), color 3 is $16
+    write_address_to_addr_register(&mut ppu, 0x3F17).unwrap();
+    write_data_to_data_register(&mut ppu, 0x16).unwrap();
+
+    let dump = ppu.dump_pattern_tables(5).unwrap();
+    let (r, g, b) = Palette2C02::rgb(0x16);
+
+    assert_eq!(dump.palette_index(), 5);
+    assert_eq!(dump.palette()[0x17], 0x16);
+    assert_eq!(&dump.pattern_table(1)[1].pixels()[0..4], &[r, g, b, 0xFF]);
+}
+
+const VBLANK_SET_SCANLINE: u16 = 241;
+const GENERATE_NMI: u8 = 0x80;
+
+/***
+ * render up to the scanline where vblank is set (pre-render, 0 - 240), and place the PPU clock at ```dot``` of scanline 241
+ ***/
+fn run_ppu_until_vblank_set_scanline(ppu: &mut Ppu2c02, dot: u16) {
+    let mut cycles = 0;
+
+    for _ in 0..242 {
+        let (next_cycles, _) = ppu.run(cycles, 1).unwrap();
+        cycles = next_cycles;
+    }
+
+    ppu.clock().borrow_mut().set_position(VBLANK_SET_SCANLINE, dot);
+}
+
+fn create_ppu_with_nmi_expectation(times: usize) -> Ppu2c02 {
+    let mut cpu = create_cpu();
+    cpu.expect_signal_nmi().times(times).returning(|| Ok(()));
+
+    let mut ppu = create_ppu_with_cpu(PpuNameTableMirroring::Horizontal, Region::NTSC, cpu);
+    ppu.write_byte(0x00, GENERATE_NMI).unwrap();
+    ppu
+}
+
+#[test]
+fn vblank_sets_flag_and_signals_nmi() {
+    init();
+
+    let mut ppu = create_ppu_with_nmi_expectation(1);
+    run_ppu_until_vblank_set_scanline(&mut ppu, 0);
+
+    ppu.run(0, 1).unwrap();
+
+    assert_ne!(ppu.get_register_value("status") & 0x80, 0);
+}
+
+#[test]
+fn status_read_at_vblank_set_dot_reads_flag_and_suppresses_nmi() {
+    init();
+
+    let mut ppu = create_ppu_with_nmi_expectation(0);
+    run_ppu_until_vblank_set_scanline(&mut ppu, 1);
+
+    assert_ne!(ppu.read_byte(0x02).unwrap() & 0x80, 0);
+
+    ppu.run(0, 1).unwrap();
+
+    assert_eq!(ppu.get_register_value("status") & 0x80, 0);
+}
+
+#[test]
+fn status_read_one_dot_before_vblank_set_reads_clear_and_suppresses_nmi() {
+    init();
+
+    let mut ppu = create_ppu_with_nmi_expectation(0);
+    run_ppu_until_vblank_set_scanline(&mut ppu, 0);
+
+    assert_eq!(ppu.read_byte(0x02).unwrap() & 0x80, 0);
+
+    ppu.run(0, 1).unwrap();
+
+    assert_eq!(ppu.get_register_value("status") & 0x80, 0);
+}
+
+const SPRITE_SIZE_8X16: u8 = 0x20;
+const SHOW_BACKGROUND_AND_SPRITES: u8 = 0x1E;
+const SPRITE_0_HIT: u8 = 0x40;
+const SPRITE_OVERFLOW: u8 = 0x20;
+
+/// CHR memory whose tile 1 is opaque, color 1 on its 8 rows
+fn create_chr_memory_with_opaque_tile_1() -> Rc<RefCell<MemoryBank>> {
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+    for addr in 0x0010..0x0018 {
+        chr_memory.borrow_mut().write_byte(addr, 0xFF).unwrap();
+    }
+
+    chr_memory
+}
+
+/// Y = $FF for the 64 sprites
+fn move_sprites_off_screen(ppu: &mut Ppu2c02) {
+    ppu.write_byte(0x03, 0x00).unwrap();
+    for _ in 0..256 {
+        ppu.write_byte(0x04, 0xFF).unwrap();
+    }
+}
+
+/// opaque sprite tile 1 (see create_chr_memory_with_opaque_tile_1), all the sprites moved off screen
+fn create_ppu_with_opaque_sprite_tile() -> Ppu2c02 {
+    let mut ppu = create_ppu_with_chr_memory(create_chr_memory_with_opaque_tile_1());
+    move_sprites_off_screen(&mut ppu);
+    ppu
+}
+
+/***
+ * opaque background (tile 0 filled with color 1 on the whole nametable), 8x16 sprites,
+ * all the sprites moved off screen
+ ***/
+fn create_ppu_with_opaque_background_and_8x16_sprites(chr_memory: Rc<RefCell<MemoryBank>>) -> Ppu2c02 {
+    for addr in 0x0000..0x0008 {
+        chr_memory.borrow_mut().write_byte(addr, 0xFF).unwrap();
+    }
+
+    let mut ppu = create_ppu_with_chr_memory(chr_memory);
+    move_sprites_off_screen(&mut ppu);
+
+    ppu.write_byte(0x00, SPRITE_SIZE_8X16).unwrap();
+    ppu.write_byte(0x01, SHOW_BACKGROUND_AND_SPRITES).unwrap();
+    ppu
+}
+
+fn write_sprite(ppu: &mut Ppu2c02, index: u8, y: u8, tile_index: u8, x: u8) {
+    ppu.write_byte(0x03, index * 4).unwrap();
+
+    for value in [y, tile_index, 0x00, x] {
+        ppu.write_byte(0x04, value).unwrap();
+    }
+}
+
+/// the first run renders the pre-render scanline, the next ones render the visible scanlines from 0
+fn run_ppu_scanlines(ppu: &mut Ppu2c02, count: usize) {
+    for _ in 0..count {
+        ppu.run(0, 1).unwrap();
+    }
+}
+
+#[test]
+fn sprite_0_hit_fires_on_the_bottom_half_of_a_8x16_sprite() {
+    init();
+
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+
+    // sprite tiles 2 (top, transparent) and 3 (bottom): only row 4 of tile 3, row 12 of the sprite, is opaque
+    chr_memory.borrow_mut().write_byte(0x0034, 0xFF).unwrap();
+
+    let mut ppu = create_ppu_with_opaque_background_and_8x16_sprites(chr_memory);
+    write_sprite(&mut ppu, 0, 50, 0x02, 100);
+
+    // sprites are displayed 1 scanline lower: row 12 of the sprite is on scanline 63
+    run_ppu_scanlines(&mut ppu, 1 + 63);
+    assert_eq!(ppu.get_register_value("status") & SPRITE_0_HIT, 0);
+
+    run_ppu_scanlines(&mut ppu, 1);
+    assert_ne!(ppu.get_register_value("status") & SPRITE_0_HIT, 0);
+}
+
+#[test]
+fn sprites_hidden_by_the_layer_override_leave_the_sprites_line_clear() {
+    init();
+
+    let render_sprite_line = |sprites: Option<bool>| {
+        let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+        chr_memory.borrow_mut().write_byte(0x0034, 0xFF).unwrap();
+
+        let mut ppu = create_ppu_with_opaque_background_and_8x16_sprites(chr_memory);
+        ppu.set_layer_override(None, sprites);
+        write_sprite(&mut ppu, 0, 50, 0x02, 100);
+
+        // row 12 of the sprite, on scanline 63
+        run_ppu_scanlines(&mut ppu, 1 + 64);
+        let pixel = *ppu.get_sprites_pixels_line().get_pixel_rgba(100);
+
+        (pixel, ppu.get_register_value("status") & SPRITE_0_HIT)
+    };
+
+    let (pixel, _) = render_sprite_line(None);
+    assert_ne!(pixel, Pixel::default());
+
+    // the mask enables the sprites, the sprite 0 hit is still detected
+    let (pixel, sprite_0_hit) = render_sprite_line(Some(false));
+    assert_eq!(pixel, Pixel::default());
+    assert_ne!(sprite_0_hit, 0);
+}
+
+/// 10 8x8 opaque sprites on scanlines 51 - 58, returns the sprites rendered on scanline 51 and the overflow flag
+fn render_10_sprites_line(sprite_limit_disabled: bool) -> (usize, u8) {
+    let mut ppu = create_ppu_with_opaque_sprite_tile();
+    ppu.set_sprite_limit_disabled(sprite_limit_disabled);
+
+    for index in 0..10u8 {
+        write_sprite(&mut ppu, index, 50, 0x01, index * 16);
+    }
+
+    ppu.write_byte(0x01, SHOW_BACKGROUND_AND_SPRITES).unwrap();
+    run_ppu_scanlines(&mut ppu, 1 + 52);
+
+    let line = ppu.get_sprites_pixels_line();
+    let rendered = (0..10u8)
+        .filter(|index| *line.get_pixel_rgba(index * 16) != Pixel::default())
+        .count();
+
+    (rendered, ppu.get_register_value("status") & SPRITE_OVERFLOW)
+}
+
+#[test]
+fn all_the_sprites_of_a_scanline_are_rendered_when_the_sprite_limit_is_disabled() {
+    init();
+
+    let (rendered, overflow) = render_10_sprites_line(false);
+    assert_eq!(rendered, 8);
+    assert_ne!(overflow, 0);
+
+    // the overflow flag still reflects the 8 sprites limit
+    let (rendered, overflow) = render_10_sprites_line(true);
+    assert_eq!(rendered, 10);
+    assert_ne!(overflow, 0);
+}
+
+#[test]
+fn sprite_overflow_uses_the_16_rows_of_8x16_sprites() {
+    init();
+
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+    let mut ppu = create_ppu_with_opaque_background_and_8x16_sprites(chr_memory);
+
+    // 8 sprites from scanline 50, a 9th one whose bottom half only reaches scanlines 50 - 57
+    for index in 0..8 {
+        write_sprite(&mut ppu, index, 50, 0x02, index * 16);
+    }
+    write_sprite(&mut ppu, 8, 42, 0x02, 200);
+
+    run_ppu_scanlines(&mut ppu, 1 + 50);
+    assert_eq!(ppu.get_register_value("status") & SPRITE_OVERFLOW, 0);
+
+    run_ppu_scanlines(&mut ppu, 1);
+    assert_ne!(ppu.get_register_value("status") & SPRITE_OVERFLOW, 0);
+}
+
+/// an opaque 8x8 sprite at (```y```, 100), all the other sprites moved off screen
+fn create_ppu_with_8x8_sprite(y: u8) -> Ppu2c02 {
+    let mut ppu = create_ppu_with_opaque_sprite_tile();
+
+    write_sprite(&mut ppu, 0, y, 0x01, 100);
+    ppu.write_byte(0x01, SHOW_BACKGROUND_AND_SPRITES).unwrap();
+    ppu
+}
+
+fn is_sprite_drawn(ppu: &Ppu2c02) -> bool {
+    *ppu.get_sprites_pixels_line().get_pixel_rgba(100) != Pixel::default()
+}
+
+#[test]
+fn sprite_at_y_0_appears_from_scanline_1() {
+    init();
+
+    let mut ppu = create_ppu_with_8x8_sprite(0);
+
+    // scanline 0
+    run_ppu_scanlines(&mut ppu, 1 + 1);
+    assert!(!is_sprite_drawn(&ppu));
+
+    // scanlines 1 - 8
+    for _ in 1..=8 {
+        run_ppu_scanlines(&mut ppu, 1);
+        assert!(is_sprite_drawn(&ppu));
+    }
+
+    // scanline 9
+    run_ppu_scanlines(&mut ppu, 1);
+    assert!(!is_sprite_drawn(&ppu));
+}
+
+#[test]
+fn sprite_at_y_238_only_shows_its_first_row_on_the_last_scanline() {
+    init();
+
+    let mut ppu = create_ppu_with_8x8_sprite(238);
+
+    // scanline 238
+    run_ppu_scanlines(&mut ppu, 1 + 239);
+    assert!(!is_sprite_drawn(&ppu));
+
+    // scanline 239
+    run_ppu_scanlines(&mut ppu, 1);
+    assert!(is_sprite_drawn(&ppu));
+
+    // the rest of the sprite is clipped, it does not wrap to the top of the next frame:
+    // post-render, vblank and pre-render scanlines (240 - 261), then scanline 0
+    run_ppu_scanlines(&mut ppu, 22 + 1);
+    assert!(!is_sprite_drawn(&ppu));
+}
+
+/// Y = 255 puts the first row of the sprite on scanline 256: below the screen, it must not wrap to the top.
+fn assert_sprite_at_y_255_is_never_drawn(sprite_size: u8) {
+    let mut ppu = create_ppu_with_8x8_sprite(255);
+    ppu.write_byte(0x00, sprite_size).unwrap();
+
+    // pre-render scanline, then two frames of 262 scanlines
+    run_ppu_scanlines(&mut ppu, 1);
+    for scanline in 0..2 * 262 {
+        run_ppu_scanlines(&mut ppu, 1);
+        assert!(!is_sprite_drawn(&ppu), "sprite drawn on scanline {}", scanline % 262);
+    }
+}
+
+#[test]
+fn sprite_at_y_255_is_never_drawn() {
+    init();
+    assert_sprite_at_y_255_is_never_drawn(0x00);
+}
+
+#[test]
+fn sprite_8x16_at_y_255_is_never_drawn() {
+    init();
+    assert_sprite_at_y_255_is_never_drawn(SPRITE_SIZE_8X16);
+}
+
+const SHOW_BACKGROUND: u8 = 0x0A;
+const WHITE: u8 = 0x30;
+const BLACK: u8 = 0x0F;
+
+/***
+ * tile 0: left half opaque (white), right half transparent (black), tile 1: transparent.
+ * nametable $2000 is filled with tile 0, nametable $2800 with tile 1
+ ***/
+fn create_ppu_with_striped_background() -> Ppu2c02 {
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+
+    for addr in 0x0000..0x0010 {
+        chr_memory.borrow_mut().write_byte(addr, 0xF0).unwrap();
+    }
+
+    let mut ppu = create_ppu_with_chr_memory(chr_memory);
+    set_v_increment(&mut ppu, 1);
+
+    write_address_to_addr_register(&mut ppu, 0x3F00).unwrap();
+    for color in [BLACK, WHITE, WHITE, WHITE] {
+        write_data_to_data_register(&mut ppu, color).unwrap();
+    }
+
+    write_address_to_addr_register(&mut ppu, 0x2800).unwrap();
+    for _ in 0..960 {
+        write_data_to_data_register(&mut ppu, 0x01).unwrap();
+    }
+
+    write_address_to_addr_register(&mut ppu, 0x2000).unwrap();
+    ppu.write_byte(0x05, 0x00).unwrap();
+    ppu.write_byte(0x05, 0x00).unwrap();
+    ppu.write_byte(0x01, SHOW_BACKGROUND).unwrap();
+
+    ppu
+}
+
+#[test]
+fn rendered_nametable_shows_the_tile_written_in_the_nametable() {
+    init();
+
+    let mut ppu = create_ppu_with_chr_memory(create_chr_memory_with_opaque_tile_1());
+    set_v_increment(&mut ppu, 1);
+
+    write_address_to_addr_register(&mut ppu, 0x3F00).unwrap();
+    for color in [BLACK, WHITE, WHITE, WHITE] {
+        write_data_to_data_register(&mut ppu, color).unwrap();
+    }
+
+    // tile 1 (color 1) at column 5, row 3 of the nametable 0
+    write_address_to_addr_register(&mut ppu, 0x2000 + 3 * 32 + 5).unwrap();
+    write_data_to_data_register(&mut ppu, 0x01).unwrap();
+
+    let v = ppu.get_v_value();
+    let nametable = ppu.render_nametable(0).unwrap();
+
+    assert_eq!((nametable.width(), nametable.height()), (256, 240));
+    assert_eq!(nametable.get_pixel(5 * 8, 3 * 8), Palette2C02::rgb_emphasized(WHITE, 0));
+    assert_eq!(nametable.get_pixel(5 * 8 + 7, 3 * 8 + 7), Palette2C02::rgb_emphasized(WHITE, 0));
+    assert_eq!(nametable.get_pixel(5 * 8 + 8, 3 * 8), Palette2C02::rgb_emphasized(BLACK, 0));
+    assert_eq!(nametable.get_pixel(0, 0), Palette2C02::rgb_emphasized(BLACK, 0));
+    assert_eq!(ppu.get_v_value(), v);
+}
+
+fn pixel_color(ppu: &Ppu2c02, x: u8, y: u8) -> (u8, u8, u8) {
+    ppu.frame().get_pixel(x, y)
+}
+
+#[test]
+fn scroll_x_written_during_a_scanline_applies_from_the_next_scanline() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    run_ppu_scanlines(&mut ppu, 1 + 10);
+
+    // the CPU runs scanline 10 before it is rendered: fine X 4 moves the transparent half of the tiles to x = 0
+    ppu.write_byte(0x05, 0x04).unwrap();
+    ppu.write_byte(0x05, 0x00).unwrap();
+    run_ppu_scanlines(&mut ppu, 2);
+
+    assert_eq!(pixel_color(&ppu, 0, 9), Palette2C02::rgb(WHITE));
+    assert_eq!(pixel_color(&ppu, 0, 10), Palette2C02::rgb(WHITE));
+    assert_eq!(pixel_color(&ppu, 0, 11), Palette2C02::rgb(BLACK));
+    assert_eq!(pixel_color(&ppu, 4, 11), Palette2C02::rgb(WHITE));
+}
+
+#[test]
+fn addr_written_during_a_scanline_applies_from_the_next_scanline() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    run_ppu_scanlines(&mut ppu, 1 + 10);
+
+    // status bar split: the nametable $2800 is displayed from scanline 11
+    write_address_to_addr_register(&mut ppu, 0x2800).unwrap();
+    assert_ne!(ppu.get_v_value(), 0x2800);
+
+    run_ppu_scanlines(&mut ppu, 3);
+
+    assert_eq!(pixel_color(&ppu, 0, 10), Palette2C02::rgb(WHITE));
+    assert_eq!(pixel_color(&ppu, 0, 11), Palette2C02::rgb(BLACK));
+    assert_eq!(pixel_color(&ppu, 0, 12), Palette2C02::rgb(BLACK));
+}
+
+#[test]
+fn perf_counters_count_the_scanlines_and_the_tile_cache_hits_of_a_frame() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    run_ppu_scanlines(&mut ppu, 1 + 240);
+
+    let counters = ppu.perf_counters();
+    assert_eq!(counters.scanlines(), 241);
+
+    // each tile of the nametable is looked up again on the 7 scanlines below its first one
+    #[cfg(feature = "ppu_tile_cache")]
+    assert!(counters.tile_cache_hits() > 0);
+}
+
+#[test]
+fn addr_written_during_hblank_is_the_name_table_base_of_the_next_scanline() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    run_ppu_scanlines(&mut ppu, 1 + 10);
+
+    // between scanlines 10 and 11: the Y increment of the scanline is already done
+    ppu.clock().borrow_mut().set_position(10, 300);
+    write_address_to_addr_register(&mut ppu, 0x2800).unwrap();
+    run_ppu_scanlines(&mut ppu, 1);
+
+    assert_eq!(ppu.get_v_value(), 0x2800);
+
+    run_ppu_scanlines(&mut ppu, 1);
+
+    assert_eq!(ppu.get_v_value() & 0x0C00, 0x0800);
+    assert_eq!(pixel_color(&ppu, 0, 11), Palette2C02::rgb(BLACK));
+}
+
+#[test]
+fn data_access_during_rendering_increments_coarse_x_and_y() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    run_ppu_scanlines(&mut ppu, 1 + 10);
+
+    // scanline 10: fine Y 2, coarse Y 1, coarse X 0 of the nametable 0
+    assert_eq!(ppu.get_v_value(), 0x2020);
+
+    ppu.read_byte(0x07).unwrap();
+    assert_eq!(ppu.get_v_value(), 0x3021);
+
+    // the increment of $2000 (+1 or +32) does not apply
+    set_v_increment(&mut ppu, 32);
+    write_data_to_data_register(&mut ppu, 0x00).unwrap();
+    assert_eq!(ppu.get_v_value(), 0x4022);
+}
+
+#[test]
+fn data_access_during_rendering_wraps_coarse_x_and_y_to_the_next_nametables() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+
+    // scroll to the last column and the last row of the tiles: coarse X 31, coarse Y 29, fine Y 7
+    ppu.write_byte(0x05, 31 * 8).unwrap();
+    ppu.write_byte(0x05, 29 * 8 + 7).unwrap();
+    run_ppu_scanlines(&mut ppu, 1);
+    assert_eq!(ppu.get_v_value(), 0x73BF);
+
+    ppu.read_byte(0x07).unwrap();
+
+    // coarse X and Y back to 0, in the nametable $2C00
+    assert_eq!(ppu.get_v_value(), 0x0C00);
+}
+
+#[test]
+fn data_access_outside_rendering_adds_the_vram_increment() {
+    init();
+
+    let mut ppu = create_ppu_with_striped_background();
+    ppu.write_byte(0x01, 0x00).unwrap();
+    run_ppu_scanlines(&mut ppu, 1 + 10);
+
+    write_address_to_addr_register(&mut ppu, 0x2020).unwrap();
+    ppu.read_byte(0x07).unwrap();
+    assert_eq!(ppu.get_v_value(), 0x2021);
+}
+
+const SPRITE_SIZE_8X8: u8 = 0x00;
+const DENSE_FRAME_HASH_8X8: u64 = 0x3063_CCCF_BB02_39E2;
+const DENSE_FRAME_HASH_8X16: u64 = 0x1BC4_737D_DE93_0C21;
+
+/***
+ * pseudo-random pattern tables, palettes and nametable, 64 sprites with every combination of
+ * palette, priority and flips, spread so that most scanlines hold 8 sprites
+ ***/
+fn create_ppu_with_dense_sprites(sprite_size: u8) -> Ppu2c02 {
+    let chr_memory = Rc::new(RefCell::new(MemoryBank::new(CHR_MEMORY_SIZE, CHR_MEMORY_RANGE)));
+    let mut seed = 0x1234_5678u32;
+
+    for addr in CHR_MEMORY_RANGE.0..=CHR_MEMORY_RANGE.1 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        chr_memory.borrow_mut().write_byte(addr, seed as u8).unwrap();
+    }
+
+    let mut ppu = create_ppu_with_chr_memory(chr_memory);
+    set_v_increment(&mut ppu, 1);
+
+    write_address_to_addr_register(&mut ppu, 0x3F00).unwrap();
+    for color in 0..32u8 {
+        write_data_to_data_register(&mut ppu, (color * 7) & 0x3F).unwrap();
+    }
+
+    write_address_to_addr_register(&mut ppu, 0x2000).unwrap();
+    for tile in 0..1024u16 {
+        write_data_to_data_register(&mut ppu, (tile * 13) as u8).unwrap();
+    }
+
+    ppu.write_byte(0x03, 0x00).unwrap();
+    for index in 0..64u8 {
+        let attributes = (index & 0x03) | ((index & 0x04) << 3) | ((index & 0x18) << 3);
+
+        for value in [(index * 3) % 230, index.wrapping_mul(5), attributes, index.wrapping_mul(37)] {
+            ppu.write_byte(0x04, value).unwrap();
+        }
+    }
+
+    write_address_to_addr_register(&mut ppu, 0x2000).unwrap();
+    ppu.write_byte(0x05, 0x00).unwrap();
+    ppu.write_byte(0x05, 0x00).unwrap();
+    ppu.write_byte(0x00, sprite_size | 0x08).unwrap();
+    ppu.write_byte(0x01, SHOW_BACKGROUND_AND_SPRITES).unwrap();
+
+    ppu
+}
+
+/// FNV-1a, stable across Rust versions unlike the hasher of the standard library.
+fn frame_hash(ppu: &Ppu2c02) -> u64 {
+    ppu.frame().pixels().iter().fold(0xCBF2_9CE4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+fn render_dense_sprites_frame(sprite_size: u8) -> u64 {
+    let mut ppu = create_ppu_with_dense_sprites(sprite_size);
+
+    let (_, duration) = measure_exec_time(|| run_ppu_scanlines(&mut ppu, 1 + 240));
+    info!("dense sprites frame (control: 0x{:02X}) rendered in {:?}", sprite_size, duration);
+
+    frame_hash(&ppu)
+}
+
+#[test]
+fn dense_8x8_sprites_frame_output_is_unchanged() {
+    init();
+
+    assert_eq!(render_dense_sprites_frame(SPRITE_SIZE_8X8), DENSE_FRAME_HASH_8X8);
+}
+
+#[test]
+fn dense_8x16_sprites_frame_output_is_unchanged() {
+    init();
+
+    assert_eq!(render_dense_sprites_frame(SPRITE_SIZE_8X16), DENSE_FRAME_HASH_8X16);
+}
+
+#[test]
+fn oam_attribute_bits_2_to_4_read_back_as_zero() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    ppu.write_byte(0x03, 0x00).unwrap();
+    for value in [0x10, 0x20, 0xFF, 0x30] {
+        ppu.write_byte(0x04, value).unwrap();
+    }
+
+    let values = (0..4).map(|offset| {
+        ppu.write_byte(0x03, offset).unwrap();
+        ppu.read_byte(0x04).unwrap()
+    }).collect::<Vec<u8>>();
+
+    assert_eq!(values, vec![0x10, 0x20, 0xE3, 0x30]);
+}
+
+fn read_oam_byte(ppu: &mut Ppu2c02, addr: u8) -> u8 {
+    ppu.write_byte(0x03, addr).unwrap();
+    ppu.read_byte(0x04).unwrap()
+}
+
+#[test]
+fn oam_byte_not_refreshed_decays_after_the_configured_number_of_frames() {
+    init();
+
+    let mut ppu = create_ppu();
+    ppu.set_oam_decay(Some(3));
+
+    write_sprite(&mut ppu, 0, 0x10, 0x20, 0x30);
+
+    // 2 full frames with rendering disabled, back on the pre-render scanline: the OAM writes are not ignored
+    run_ppu_scanlines(&mut ppu, 262 * 2);
+    assert_eq!(read_oam_byte(&mut ppu, 0), 0x10);
+
+    write_sprite(&mut ppu, 1, 0x40, 0x50, 0x60);
+    run_ppu_scanlines(&mut ppu, 262);
+
+    assert_eq!(read_oam_byte(&mut ppu, 0), 0xFF);
+    assert_eq!(read_oam_byte(&mut ppu, 3), 0xFF);
+    assert_eq!(read_oam_byte(&mut ppu, 4), 0x40);
+}
+
+#[test]
+fn oam_bytes_do_not_decay_without_the_oam_decay_mode() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    write_sprite(&mut ppu, 0, 0x10, 0x20, 0x30);
+    run_ppu_scanlines(&mut ppu, 262 * 4);
+
+    assert_eq!(read_oam_byte(&mut ppu, 0), 0x10);
+}
+
+#[test]
+fn oam_data_reads_ff_while_the_secondary_oam_is_cleared() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    write_sprite(&mut ppu, 0, 0x10, 0x20, 0x30);
+    ppu.write_byte(0x01, SHOW_BACKGROUND_AND_SPRITES).unwrap();
+    ppu.write_byte(0x03, 0x00).unwrap();
+
+    ppu.clock().borrow_mut().set_position(10, 30);
+    assert_eq!(ppu.read_byte(0x04).unwrap(), 0xFF);
+
+    ppu.clock().borrow_mut().set_position(10, 100);
+    assert_eq!(ppu.read_byte(0x04).unwrap(), 0x10);
+
+    ppu.clock().borrow_mut().set_position(241, 30);
+    assert_eq!(ppu.read_byte(0x04).unwrap(), 0x10);
+}
+
+const OPAQUE_BACKGROUND: Pixel = Pixel::new(0x10, 0x20, 0x30, 0xFF, SpritePriority::None);
+const TRANSPARENT_BACKGROUND: Pixel = Pixel::new(0x01, 0x02, 0x03, 0x00, SpritePriority::None);
+const FRONT_SPRITE: Pixel = Pixel::new(0xA0, 0xB0, 0xC0, 0xFF, SpritePriority::Front);
+const BACK_SPRITE: Pixel = Pixel::new(0xD0, 0xE0, 0xF0, 0xFF, SpritePriority::Back);
+const TRANSPARENT_FRONT_SPRITE: Pixel = Pixel::new(0xAA, 0xBB, 0xCC, 0x00, SpritePriority::Front);
+
+/// the background pixel and the sprite pixel are set at x and merged, the other pixels stay transparent.
+fn merge_pixel(x: u8, background: Pixel, sprite: Option<Pixel>) -> Pixel {
+    let mut background_line = PixelLines::default();
+    let mut sprites_line = PixelLines::default();
+
+    background_line.set_pixel_rgba(x, background);
+    if let Some(sprite) = sprite {
+        sprites_line.set_pixel_rgba(x, sprite);
+    }
+
+    *background_line.merge(&sprites_line).get_pixel_rgba(x)
+}
+
+#[test]
+fn front_priority_sprite_is_drawn_over_the_background() {
+    init();
+
+    assert_eq!(merge_pixel(0, OPAQUE_BACKGROUND, Some(FRONT_SPRITE)), FRONT_SPRITE);
+    assert_eq!(merge_pixel(128, TRANSPARENT_BACKGROUND, Some(FRONT_SPRITE)), FRONT_SPRITE);
+    assert_eq!(merge_pixel(255, OPAQUE_BACKGROUND, Some(FRONT_SPRITE)), FRONT_SPRITE);
+}
+
+#[test]
+fn back_priority_sprite_is_hidden_by_an_opaque_background() {
+    init();
+
+    assert_eq!(merge_pixel(0, OPAQUE_BACKGROUND, Some(BACK_SPRITE)), OPAQUE_BACKGROUND);
+    assert_eq!(merge_pixel(255, OPAQUE_BACKGROUND, Some(BACK_SPRITE)), OPAQUE_BACKGROUND);
+}
+
+#[test]
+fn back_priority_sprite_shows_through_a_transparent_background() {
+    init();
+
+    assert_eq!(merge_pixel(0, TRANSPARENT_BACKGROUND, Some(BACK_SPRITE)), BACK_SPRITE);
+    assert_eq!(merge_pixel(255, TRANSPARENT_BACKGROUND, Some(BACK_SPRITE)), BACK_SPRITE);
+}
+
+#[test]
+fn transparent_or_missing_sprite_keeps_the_background() {
+    init();
+
+    assert_eq!(merge_pixel(10, OPAQUE_BACKGROUND, None), OPAQUE_BACKGROUND);
+    assert_eq!(merge_pixel(10, TRANSPARENT_BACKGROUND, None), TRANSPARENT_BACKGROUND);
+    assert_eq!(merge_pixel(10, OPAQUE_BACKGROUND, Some(TRANSPARENT_FRONT_SPRITE)), OPAQUE_BACKGROUND);
+    assert_eq!(merge_pixel(10, TRANSPARENT_BACKGROUND, Some(TRANSPARENT_FRONT_SPRITE)), TRANSPARENT_BACKGROUND);
+}
+
+#[test]
+fn merge_resolves_every_pixel_of_the_line_independently() {
+    init();
+
+    let cases = [
+        (OPAQUE_BACKGROUND, Some(FRONT_SPRITE), FRONT_SPRITE),
+        (OPAQUE_BACKGROUND, Some(BACK_SPRITE), OPAQUE_BACKGROUND),
+        (TRANSPARENT_BACKGROUND, Some(BACK_SPRITE), BACK_SPRITE),
+        (TRANSPARENT_BACKGROUND, Some(FRONT_SPRITE), FRONT_SPRITE),
+        (OPAQUE_BACKGROUND, None, OPAQUE_BACKGROUND),
+        (TRANSPARENT_BACKGROUND, None, TRANSPARENT_BACKGROUND),
+    ];
+
+    let mut background_line = PixelLines::default();
+    let mut sprites_line = PixelLines::default();
+
+    for x in 0..=255u8 {
+        let (background, sprite, _) = cases[x as usize % cases.len()];
+
+        background_line.set_pixel_rgba(x, background);
+        if let Some(sprite) = sprite {
+            sprites_line.set_pixel_rgba(x, sprite);
+        }
+    }
+
+    let merged = background_line.merge(&sprites_line);
+
+    for x in 0..=255u8 {
+        let (_, _, expected) = cases[x as usize % cases.len()];
+        assert_eq!(*merged.get_pixel_rgba(x), expected, "x: {}", x);
+    }
+}
+
+#[test]
+#[cfg(feature = "timing_spans")]
+fn timing_spans_accumulate_the_background_rendering_of_a_frame() {
+    init();
+    let mut ppu = create_ppu_with_striped_background();
+
+    run_ppu_scanlines(&mut ppu, 1 + 240);
+
+    assert!(ppu.timing_spans().background() > std::time::Duration::ZERO);
+}
+
+#[test]
+#[cfg(not(feature = "timing_spans"))]
+fn timing_spans_are_not_measured_without_the_feature() {
+    init();
+    let mut ppu = create_ppu_with_striped_background();
+
+    run_ppu_scanlines(&mut ppu, 1 + 240);
+
+    assert_eq!(ppu.timing_spans(), crate::timing_spans::TimingSpans::default());
+}
+
+#[test]
+fn ppu_state_restores_the_registers_oam_and_nametables() {
+    init();
+
+    let mut ppu = create_ppu();
+
+    write_address_to_addr_register(&mut ppu, VALID_NAME_TABLE_ADDRESS).unwrap();
+    write_data_to_data_register(&mut ppu, 0x11).unwrap();
+    ppu.write_byte(0x03, 0x08).unwrap();
+    ppu.write_byte(0x04, 0x42).unwrap();
+    ppu.write_byte(0x00, 0x80).unwrap();
+
+    let state = ppu.serialize_state();
+
+    write_address_to_addr_register(&mut ppu, VALID_NAME_TABLE_ADDRESS).unwrap();
+    write_data_to_data_register(&mut ppu, 0x22).unwrap();
+    ppu.write_byte(0x03, 0x08).unwrap();
+    ppu.write_byte(0x04, 0x00).unwrap();
+    ppu.write_byte(0x00, 0x00).unwrap();
+
+    ppu.deserialize_state(&state).unwrap();
+
+    assert_eq!(ppu.get_register_value("controller"), 0x80);
+    ppu.write_byte(0x03, 0x08).unwrap();
+    assert_eq!(ppu.read_byte(0x04).unwrap(), 0x42);
+
+    write_address_to_addr_register(&mut ppu, VALID_NAME_TABLE_ADDRESS).unwrap();
+    ppu.read_byte(0x07).unwrap();
+    assert_eq!(ppu.read_byte(0x07).unwrap(), 0x11);
+}
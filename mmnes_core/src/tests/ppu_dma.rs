@@ -45,7 +45,8 @@ fn dma_transfer_works() {
     let mut ppu_dma = create_ppu_dma();
     let value = 0x20;
 
-    ppu_dma.transfer_memory(value).unwrap();
+    let cycles = ppu_dma.transfer_memory(value).unwrap();
+    assert_eq!(cycles, 513);
 }
 
 #[test]
@@ -0,0 +1,32 @@
+use crate::ram_search::{RamSearch, SearchCriteria};
+
+#[test]
+fn changed_keeps_only_the_mutated_addresses() {
+    let mut ram = vec![0x10u8; 0x800];
+    let mut search = RamSearch::new(&ram);
+
+    ram[0x0042] = 0x11;
+    ram[0x0300] = 0x0F;
+
+    assert_eq!(search.search(&ram, SearchCriteria::Changed), &[0x0042, 0x0300]);
+}
+
+#[test]
+fn successive_searches_narrow_the_candidates_until_reset() {
+    let mut ram = vec![0x00u8; 0x800];
+    let mut search = RamSearch::new(&ram);
+
+    ram[0x0010] = 0x03;
+    ram[0x0020] = 0x05;
+    assert_eq!(search.search(&ram, SearchCriteria::Increased), &[0x0010, 0x0020]);
+
+    search.snapshot(&ram);
+    ram[0x0010] = 0x02;
+    ram[0x0030] = 0x02;
+    assert_eq!(search.search(&ram, SearchCriteria::Decreased), &[0x0010]);
+    assert_eq!(search.search(&ram, SearchCriteria::Equals(0x02)), &[0x0010]);
+
+    search.reset();
+    assert_eq!(search.candidates().len(), 0x800);
+    assert_eq!(search.search(&ram, SearchCriteria::Equals(0x02)), &[0x0010, 0x0030]);
+}
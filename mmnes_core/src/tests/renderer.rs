@@ -0,0 +1,65 @@
+use crate::nes_frame::FrameState;
+use crate::renderer::Renderer;
+use crate::tests::init;
+
+const RED: (u8, u8, u8) = (0xFF, 0x00, 0x00);
+const BLUE: (u8, u8, u8) = (0x00, 0x00, 0xFF);
+
+fn draw_scanlines(renderer: &mut Renderer, scanlines: std::ops::Range<u8>, color: (u8, u8, u8)) {
+    for y in scanlines {
+        for x in 0..=255 {
+            renderer.frame_as_mut().set_pixel(x, y, color);
+        }
+    }
+}
+
+#[test]
+fn nothing_is_published_before_the_first_frame_is_completed() {
+    init();
+    let mut renderer = Renderer::new();
+
+    draw_scanlines(&mut renderer, 0..120, RED);
+
+    assert!(renderer.take_completed().is_none());
+}
+
+#[test]
+fn completed_frame_is_published_once() {
+    init();
+    let mut renderer = Renderer::new();
+
+    draw_scanlines(&mut renderer, 0..240, RED);
+    renderer.update();
+
+    let frame = renderer.take_completed().unwrap();
+    assert_eq!(frame.state(), FrameState::Completed);
+    assert_eq!(frame.count(), 1);
+    assert_eq!(frame.get_pixel(0, 239), RED);
+    assert!(renderer.take_completed().is_none());
+}
+
+#[test]
+fn frame_published_mid_render_is_the_last_completed_one() {
+    init();
+    let mut renderer = Renderer::new();
+
+    draw_scanlines(&mut renderer, 0..240, RED);
+    renderer.update();
+    renderer.take_completed().unwrap();
+    renderer.reset();
+
+    // half of the next frame drawn: the consumer still sees the whole previous frame
+    draw_scanlines(&mut renderer, 0..120, BLUE);
+    assert!(renderer.take_completed().is_none());
+
+    let front = renderer.front();
+    assert_eq!(front.count(), 1);
+    assert!((0..240).all(|y| front.get_pixel(128, y) == RED));
+
+    draw_scanlines(&mut renderer, 120..240, BLUE);
+    renderer.update();
+
+    let frame = renderer.take_completed().unwrap();
+    assert_eq!(frame.count(), 2);
+    assert!((0..240).all(|y| frame.get_pixel(128, y) == BLUE));
+}
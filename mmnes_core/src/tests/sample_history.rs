@@ -0,0 +1,30 @@
+use crate::sample_history::SampleHistory;
+
+#[test]
+fn recent_samples_are_the_last_pushed_ones_in_order() {
+    let mut history = SampleHistory::new(4);
+
+    for sample in [0.1, 0.2, 0.3] {
+        history.push_sample(sample);
+    }
+
+    assert_eq!(history.recent_samples(), &[0.1, 0.2, 0.3]);
+
+    for sample in [0.4, 0.5, 0.6, -0.7] {
+        history.push_sample(sample);
+    }
+
+    assert_eq!(history.recent_samples(), &[0.4, 0.5, 0.6, -0.7]);
+}
+
+#[test]
+fn downsampled_keeps_one_sample_out_of_each_slice() {
+    let mut history = SampleHistory::new(8);
+
+    for sample in 0..10 {
+        history.push_sample(sample as f32);
+    }
+
+    assert_eq!(history.downsampled(4), vec![2.0, 4.0, 6.0, 8.0]);
+    assert_eq!(history.downsampled(16), history.recent_samples().to_vec());
+}
@@ -0,0 +1,34 @@
+use std::io::Write;
+use tempfile::NamedTempFile;
+use crate::nes_console::NesConsoleBuilder;
+use crate::region::Region;
+use crate::session::Session;
+use crate::tests::init;
+
+const SESSION: &str = r#"{
+    "rom": "games/smb.nes",
+    "sram": "/saves/smb.sav",
+    "cheats": ["SXIOPO", "0075:09"],
+    "region": "pal"
+}"#;
+
+#[test]
+fn session_file_configures_the_builder_with_its_rom_region_and_cheats() {
+    init();
+
+    let mut session_file = NamedTempFile::new().expect("failed to create temp file");
+    session_file.write_all(SESSION.as_bytes()).expect("failed to write session file");
+    session_file.flush().expect("failed to flush session file");
+
+    let session = Session::from_file(session_file.path()).expect("failed to parse session file");
+    let directory = session_file.path().parent().unwrap();
+
+    assert_eq!(session.sram_file(), Some(std::path::Path::new("/saves/smb.sav")));
+    assert_eq!(session.palette_file(), None);
+
+    let builder = NesConsoleBuilder::new().with_session(&session);
+
+    assert_eq!(builder.rom_file(), Some(&directory.join("games/smb.nes")));
+    assert_eq!(builder.region(), Region::PAL);
+    assert_eq!(builder.cheat_codes(), &["SXIOPO".to_string(), "0075:09".to_string()]);
+}
@@ -66,4 +66,20 @@ fn test_samples_returns_all_buffered_samples() {
 
     assert_eq!(returned_samples.len(), test_samples.len());
     assert_eq!(returned_samples, test_samples);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_push_sample_never_grows_beyond_configured_buffer_size() {
+    let buffer_size = 256;
+    let mut sound_playback = SoundPlaybackPassive::with_buffer_size(buffer_size);
+
+    for round in 0..4 {
+        for i in 0..buffer_size * 10 {
+            sound_playback.push_sample(i as f32);
+        }
+
+        let samples = sound_playback.samples();
+        assert_eq!(samples.len(), buffer_size, "round {}", round);
+        assert_eq!(samples[buffer_size - 1], (buffer_size - 1) as f32);
+    }
+}
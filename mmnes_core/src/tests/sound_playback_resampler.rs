@@ -0,0 +1,60 @@
+use crate::sound_playback::SoundPlayback;
+use crate::sound_playback_passive::SoundPlaybackPassive;
+use crate::sound_playback_resampler::SoundPlaybackResampler;
+use crate::tests::init;
+
+const APU_SAMPLE_RATE: u32 = 44_100;
+const HOST_SAMPLE_RATE: u32 = 48_000;
+
+fn create_resampler(target_rate: u32) -> SoundPlaybackResampler<SoundPlaybackPassive> {
+    SoundPlaybackResampler::new(SoundPlaybackPassive::with_buffer_size(100_000), APU_SAMPLE_RATE, target_rate)
+}
+
+#[test]
+fn resampling_1_second_from_44100_to_48000_produces_48000_samples() {
+    init();
+
+    let mut resampler = create_resampler(HOST_SAMPLE_RATE);
+
+    for i in 0..APU_SAMPLE_RATE {
+        resampler.push_sample((i % 100) as f32 / 100.0);
+    }
+
+    let count = resampler.samples().len() as i64;
+    assert!((count - HOST_SAMPLE_RATE as i64).abs() <= 1, "got {} samples", count);
+}
+
+#[test]
+fn resampling_does_not_drift_over_long_sessions() {
+    init();
+
+    let mut resampler = create_resampler(HOST_SAMPLE_RATE);
+    let mut count = 0;
+
+    // 10 minutes of audio, drained every 735 samples (one NTSC frame)
+    for i in 0..(APU_SAMPLE_RATE as usize * 600) {
+        resampler.push_sample(0.5);
+
+        if i % 735 == 0 {
+            count += resampler.samples().len();
+        }
+    }
+
+    count += resampler.samples().len();
+
+    let expected = HOST_SAMPLE_RATE as i64 * 600;
+    assert!((count as i64 - expected).abs() <= 1, "got {} samples, expected {}", count, expected);
+}
+
+#[test]
+fn resampling_interpolates_between_input_samples() {
+    init();
+
+    let mut resampler = create_resampler(APU_SAMPLE_RATE * 2);
+
+    resampler.push_sample(0.0);
+    resampler.push_sample(1.0);
+    resampler.push_sample(0.0);
+
+    assert_eq!(resampler.samples(), vec![0.0, 0.0, 0.0, 0.5, 1.0, 0.5]);
+}
@@ -0,0 +1,119 @@
+use crate::controller::Controller;
+use crate::input::Input;
+use crate::input_external::InputExternal;
+use crate::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_MICROPHONE};
+use crate::memory::Memory;
+use crate::standard_controller::StandardController;
+use crate::tests::init;
+
+const CONTROLLER_PORT: u16 = 0x4016;
+const BUTTON_A: usize = 0;
+const BUTTON_SELECT: usize = 2;
+const BUTTON_RIGHT: usize = 7;
+const MICROPHONE_BIT: u8 = 0x04;
+
+fn create_controller_with_pressed_buttons(buttons: &[usize]) -> StandardController<InputExternal> {
+    let mut input = InputExternal::new();
+    let mut key_events = KeyEvents::new();
+
+    for button in buttons {
+        key_events.push_back(KeyEvent { key: *button, pressed: true });
+    }
+
+    input.set_input_state(key_events);
+    StandardController::new(input)
+}
+
+#[test]
+fn strobe_then_9_reads_return_the_8_buttons_then_1() {
+    init();
+
+    let mut controller = create_controller_with_pressed_buttons(&[BUTTON_A, BUTTON_SELECT, BUTTON_RIGHT]);
+
+    controller.write_byte(CONTROLLER_PORT, 0x01).unwrap();
+    controller.write_byte(CONTROLLER_PORT, 0x00).unwrap();
+
+    let reads = (0..9)
+        .map(|_| controller.read_byte(CONTROLLER_PORT).unwrap())
+        .collect::<Vec<u8>>();
+
+    assert_eq!(reads, vec![1, 0, 1, 0, 0, 0, 0, 1, 1]);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 1);
+}
+
+#[test]
+fn reads_while_the_strobe_is_high_return_the_a_button() {
+    init();
+
+    let mut controller = create_controller_with_pressed_buttons(&[BUTTON_SELECT]);
+    controller.write_byte(CONTROLLER_PORT, 0x01).unwrap();
+
+    for _ in 0..10 {
+        assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+    }
+
+    controller.write_byte(CONTROLLER_PORT, 0x00).unwrap();
+
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 1);
+}
+
+#[test]
+fn trace_read_does_not_shift_the_buttons() {
+    init();
+
+    let mut controller = create_controller_with_pressed_buttons(&[BUTTON_A]);
+
+    controller.write_byte(CONTROLLER_PORT, 0x01).unwrap();
+    controller.write_byte(CONTROLLER_PORT, 0x00).unwrap();
+
+    assert_eq!(controller.trace_read_byte(CONTROLLER_PORT).unwrap(), 1);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 1);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+}
+
+fn set_microphone(controller: &mut StandardController<InputExternal>, pressed: bool) {
+    let key_events = KeyEvents::from_iter([KeyEvent { key: NES_CONTROLLER_KEY_MICROPHONE, pressed }]);
+    controller.set_input(key_events).unwrap();
+}
+
+#[test]
+fn famicom_microphone_is_reported_on_bit_2_without_affecting_the_buttons() {
+    init();
+
+    let mut input = InputExternal::new();
+    input.set_input_state(KeyEvents::from_iter([KeyEvent { key: BUTTON_A, pressed: true }]));
+    let mut controller = StandardController::with_microphone(input);
+
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 1);
+
+    set_microphone(&mut controller, true);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), MICROPHONE_BIT | 1);
+
+    controller.write_byte(CONTROLLER_PORT, 0x01).unwrap();
+    controller.write_byte(CONTROLLER_PORT, 0x00).unwrap();
+
+    let reads = (0..9)
+        .map(|_| controller.read_byte(CONTROLLER_PORT).unwrap())
+        .collect::<Vec<u8>>();
+
+    assert_eq!(reads, vec![5, 4, 4, 4, 4, 4, 4, 4, 5]);
+
+    set_microphone(&mut controller, false);
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 1);
+}
+
+#[test]
+fn standard_controller_ignores_the_microphone() {
+    init();
+
+    let mut controller = create_controller_with_pressed_buttons(&[]);
+    set_microphone(&mut controller, true);
+
+    controller.write_byte(CONTROLLER_PORT, 0x01).unwrap();
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+
+    controller.write_byte(CONTROLLER_PORT, 0x00).unwrap();
+    assert_eq!(controller.read_byte(CONTROLLER_PORT).unwrap(), 0);
+}
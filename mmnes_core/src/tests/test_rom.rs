@@ -0,0 +1,124 @@
+use crate::apu::ApuType::RP2A03;
+use crate::bus::BusType;
+use crate::bus_device::BusDeviceType::{APU, CARTRIDGE, CONTROLLER, PPU, WRAM};
+use crate::cartridge::CartridgeType::NROM;
+use crate::controller::ControllerType::StandardController;
+use crate::cpu::CpuType;
+use crate::loader::LoaderType::INESV2;
+use crate::memory::Memory;
+use crate::memory::MemoryType::StandardMemory;
+use crate::nes_console::{NesConsole, NesConsoleBuilder};
+use crate::ppu::PpuType::NES2C02;
+use crate::test_rom::{run_test_rom, StatusPort, TestRomStatus};
+use crate::tests::{init, nrom_image};
+
+const PRG_ROM_SIZE: usize = 16 * 1024;
+const STATUS_PORT_ADDR: u16 = 0x6000;
+
+/// NROM-128 running ```program``` from $8000, the status port mapped at $6000.
+fn create_console_with_status_port(program: &[u8], status_port: &StatusPort) -> NesConsole {
+    let mut prg_rom = vec![0xEA; PRG_ROM_SIZE];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    let mut console = NesConsoleBuilder::new()
+        .with_cpu(CpuType::NES6502)
+        .with_bus_type(BusType::NESBus)
+        .with_bus_device_type(WRAM(StandardMemory))
+        .with_bus_device_type(CARTRIDGE(NROM))
+        .with_bus_device_type(APU(RP2A03))
+        .with_bus_device_type(PPU(NES2C02))
+        .with_bus_device_type(CONTROLLER(StandardController))
+        .with_loader_type(INESV2)
+        .with_rom_bytes(nrom_image(&prg_rom))
+        .with_custom_io_device(status_port.device())
+        .build()
+        .expect("failed to build console");
+
+    console.power_on().expect("failed to power on console");
+    console
+}
+
+#[test]
+fn status_byte_maps_to_the_exit_code_of_the_test() {
+    init();
+
+    assert_eq!(TestRomStatus::from_status_byte(0x00).exit_code(), Some(0));
+    assert_eq!(TestRomStatus::from_status_byte(0x03).exit_code(), Some(3));
+    assert_eq!(TestRomStatus::from_status_byte(0x80).exit_code(), None);
+    assert_eq!(TestRomStatus::from_status_byte(0x81).exit_code(), None);
+    assert_ne!(TestRomStatus::Halted(0x8000).exit_code(), Some(0));
+}
+
+#[test]
+fn exit_codes_of_the_halt_and_the_timeout_are_distinct_from_the_failure_codes() {
+    init();
+
+    let halted = TestRomStatus::Halted(0x8000).exit_code();
+    let timed_out = TestRomStatus::TimedOut.exit_code();
+
+    for code in 0x01..=0xFF {
+        let failed = TestRomStatus::Failed(code).exit_code();
+
+        assert!(failed.is_some_and(|failed| failed > 0 && failed < 256));
+        assert_ne!(failed, halted);
+        assert_ne!(failed, timed_out);
+    }
+
+    assert_ne!(halted, timed_out);
+}
+
+#[test]
+fn status_port_reports_the_last_byte_written_once_signed() {
+    init();
+
+    let port = StatusPort::new(STATUS_PORT_ADDR);
+    let mut device = port.device();
+
+    assert_eq!(port.take_status(), None);
+
+    device.write_byte(0x0000, 0x80).unwrap();
+    device.write_byte(0x0000, 0x02).unwrap();
+    assert_eq!(port.take_status(), None);
+
+    device.write_byte(0x0001, 0xDE).unwrap();
+    device.write_byte(0x0002, 0xB0).unwrap();
+    device.write_byte(0x0003, 0x61).unwrap();
+
+    assert_eq!(port.take_status(), Some(TestRomStatus::Failed(0x02)));
+    assert_eq!(port.take_status(), None);
+}
+
+#[test]
+fn rom_clearing_the_status_byte_without_the_signature_times_out() {
+    init();
+
+    // LDA #$00, STA $6000, JMP *
+    let program = [0xA9, 0x00, 0x8D, 0x00, 0x60, 0x4C, 0x05, 0x80];
+    let port = StatusPort::new(STATUS_PORT_ADDR);
+    let mut console = create_console_with_status_port(&program, &port);
+
+    let status = run_test_rom(&mut console, Some(&port), false, Some(10)).unwrap();
+
+    assert_eq!(status, TestRomStatus::TimedOut);
+}
+
+#[test]
+fn rom_writing_the_signature_and_a_status_of_0_passes() {
+    init();
+
+    // the signature DE B0 61 at $6001 - $6003, then $00 at $6000, JMP *
+    let program = [
+        0xA9, 0xDE, 0x8D, 0x01, 0x60,
+        0xA9, 0xB0, 0x8D, 0x02, 0x60,
+        0xA9, 0x61, 0x8D, 0x03, 0x60,
+        0xA9, 0x00, 0x8D, 0x00, 0x60,
+        0x4C, 0x14, 0x80,
+    ];
+    let port = StatusPort::new(STATUS_PORT_ADDR);
+    let mut console = create_console_with_status_port(&program, &port);
+
+    let status = run_test_rom(&mut console, Some(&port), false, Some(10)).unwrap();
+
+    assert_eq!(status, TestRomStatus::Passed);
+}
@@ -0,0 +1,57 @@
+use std::io::Cursor;
+use crate::memory::Memory;
+use crate::memory_ciram::PpuNameTableMirroring;
+use crate::unrom_cartridge::UnromCartridge;
+use crate::tests::init;
+
+const PRG_BANK_SIZE: usize = 16 * 1024;
+const PRG_BANKS: usize = 4;
+const BANK_NUMBER_ADDR: u16 = 0x0100;
+
+/// 4 PRG banks holding their number at $8100, the first bank holds $01 at $8000, the fixed one $FF at $C000.
+fn create_unrom_cartridge(bus_conflicts: bool) -> UnromCartridge {
+    let mut prg_rom = vec![0x00; PRG_BANKS * PRG_BANK_SIZE];
+
+    for bank in 0..PRG_BANKS {
+        prg_rom[bank * PRG_BANK_SIZE + BANK_NUMBER_ADDR as usize] = bank as u8;
+    }
+
+    prg_rom[0] = 0x01;
+    prg_rom[(PRG_BANKS - 1) * PRG_BANK_SIZE] = 0xFF;
+
+    UnromCartridge::new(Cursor::new(prg_rom), 0, PRG_BANKS * PRG_BANK_SIZE, 0, 0, 8 * 1024, PpuNameTableMirroring::Vertical)
+        .expect("failed to create the cartridge")
+        .with_bus_conflicts(bus_conflicts)
+}
+
+#[test]
+fn bank_write_with_bus_conflicts_selects_the_value_anded_with_the_rom_byte() {
+    init();
+    let mut cartridge = create_unrom_cartridge(true);
+
+    // $03 written over $01 in the ROM: the latch gets $01
+    cartridge.write_byte(0x0000, 0x03).unwrap();
+
+    assert_eq!(cartridge.read_byte(BANK_NUMBER_ADDR).unwrap(), 1);
+}
+
+#[test]
+fn bank_write_over_a_matching_rom_byte_is_not_affected_by_bus_conflicts() {
+    init();
+    let mut cartridge = create_unrom_cartridge(true);
+
+    // $FF in the fixed bank at $C000, the usual bank table trick of the games
+    cartridge.write_byte(0x4000, 0x03).unwrap();
+
+    assert_eq!(cartridge.read_byte(BANK_NUMBER_ADDR).unwrap(), 3);
+}
+
+#[test]
+fn bank_write_without_bus_conflicts_selects_the_value_written() {
+    init();
+    let mut cartridge = create_unrom_cartridge(false);
+
+    cartridge.write_byte(0x0000, 0x03).unwrap();
+
+    assert_eq!(cartridge.read_byte(BANK_NUMBER_ADDR).unwrap(), 3);
+}
@@ -0,0 +1,34 @@
+use crate::tests::init;
+use crate::wav_sink::{wav_bytes, MultiChannelWavSink};
+
+#[test]
+fn pushed_samples_are_split_into_one_buffer_per_channel() {
+    init();
+    let mut sink = MultiChannelWavSink::new(44_100);
+
+    sink.push_samples([1.0, 2.0, 3.0, 4.0, 5.0]);
+    sink.push_samples([15.0, 0.0, 7.0, 12.0, 127.0]);
+
+    assert_eq!(sink.len(), 2);
+    assert_eq!(sink.channel(0), &[1.0, 15.0]);
+    assert_eq!(sink.channel(1), &[2.0, 0.0]);
+    assert_eq!(sink.channel(2), &[3.0, 7.0]);
+    assert_eq!(sink.channel(3), &[4.0, 12.0]);
+    assert_eq!(sink.channel(4), &[5.0, 127.0]);
+
+    sink.clear();
+    assert!(sink.is_empty());
+}
+
+#[test]
+fn wav_bytes_spread_the_channel_output_over_the_pcm_range() {
+    init();
+    let bytes = wav_bytes(&[0.0, 15.0], 15.0, 44_100);
+
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[8..12], b"WAVE");
+    assert_eq!(u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]), 44_100);
+    assert_eq!(u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]), 4);
+    assert_eq!(i16::from_le_bytes([bytes[44], bytes[45]]), -i16::MAX);
+    assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), i16::MAX);
+}
@@ -0,0 +1,58 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+#[cfg(feature = "timing_spans")]
+use crate::util::measure_exec_time;
+
+/***
+ * Time spent in the hot paths of the emulator since it was built, to find where a frame goes.
+ * The spans are only measured with the timing_spans feature: without it ```timed``` just runs
+ * the closure, and the spans stay at 0.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TimingSpans {
+    pub(crate) cpu: Duration,
+    pub(crate) background: Duration,
+    pub(crate) sprites: Duration,
+    pub(crate) apu: Duration,
+}
+
+impl TimingSpans {
+    /// CPU stepping, the PPU and APU catch up excluded.
+    pub fn cpu(&self) -> Duration {
+        self.cpu
+    }
+
+    pub fn background(&self) -> Duration {
+        self.background
+    }
+
+    pub fn sprites(&self) -> Duration {
+        self.sprites
+    }
+
+    pub fn apu(&self) -> Duration {
+        self.apu
+    }
+}
+
+impl Display for TimingSpans {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cpu: {:.3} ms, background: {:.3} ms, sprites: {:.3} ms, apu: {:.3} ms",
+               self.cpu.as_secs_f64() * 1000.0,
+               self.background.as_secs_f64() * 1000.0,
+               self.sprites.as_secs_f64() * 1000.0,
+               self.apu.as_secs_f64() * 1000.0)
+    }
+}
+
+#[cfg(feature = "timing_spans")]
+#[inline]
+pub(crate) fn timed<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    measure_exec_time(f)
+}
+
+#[cfg(not(feature = "timing_spans"))]
+#[inline(always)]
+pub(crate) fn timed<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    (f(), Duration::ZERO)
+}
@@ -1,14 +1,13 @@
 use std::cell::RefCell;
-use std::fs::File;
-use std::io::{BufReader};
+use std::io::{BufReader, Read, Seek};
 use std::rc::Rc;
 use log::debug;
 use crate::bus_device::{BusDevice, BusDeviceType};
 use crate::cartridge;
-use crate::cartridge::{Cartridge, CartridgeError, CPU_ADDRESS_SPACE, PPU_ADDRESS_SPACE};
+use crate::cartridge::{BankState, Cartridge, CartridgeError, CPU_ADDRESS_SPACE, PPU_ADDRESS_SPACE};
 use crate::cartridge::CartridgeType::UNROM;
 use crate::ines_loader::{FromINes, INesRomHeader};
-use crate::loader::LoaderError;
+use crate::loader::{LoaderError, RomData};
 use crate::memory::{Memory, MemoryError};
 use crate::memory_bank::MemoryBank;
 use crate::memory_ciram::PpuNameTableMirroring;
@@ -17,6 +16,8 @@ const UNROM_PRG_MEMORY_BANK_SIZE: usize = 16 * 1024;
 const UNROM_CHR_MEMORY_BANK_SIZE: usize = 8 * 1024;
 const MEMORY_FIXED_BANK_PHYS_ADDR: u16 = 0x3FFF; // 0xFFFF - 0x4000 (16 KB);
 const MAPPER_NAME: &str = "UNROM";
+/// NES 2.0 submapper of the UNROM boards with bus conflicts (1: without, 0: unspecified, taken as without).
+const BUS_CONFLICTS_SUB_MAPPER: u8 = 2;
 
 #[derive(Debug)]
 pub struct UnromCartridge {
@@ -27,7 +28,8 @@ pub struct UnromCartridge {
     prg_rom_size: usize,
     chr_rom: Rc<RefCell<MemoryBank>>,
     device_type: BusDeviceType,
-    mirroring: Rc<RefCell<PpuNameTableMirroring>>
+    mirroring: Rc<RefCell<PpuNameTableMirroring>>,
+    bus_conflicts: bool,
 }
 
 impl UnromCartridge {
@@ -37,7 +39,7 @@ impl UnromCartridge {
      * the underlying memory mapping is made by multiple 16 KB memory banks, switched by writes.
      * https://www.nesdev.org/wiki/UxROM
      ***/
-    pub fn new(mut data: BufReader<File>,
+    pub fn new(mut data: impl Read + Seek,
                prg_rom_offset: u64, prg_rom_size: usize,
                chr_rom_offset: u64, chr_rom_size: usize,
                chr_ram_size: usize, mirroring: PpuNameTableMirroring) -> Result<UnromCartridge, CartridgeError> {
@@ -64,17 +66,28 @@ impl UnromCartridge {
             device_type: BusDeviceType::CARTRIDGE(UNROM),
             mirroring: Rc::new(RefCell::new(mirroring)),
             chr_rom: Rc::new(RefCell::new(chr_mem)),
+            bus_conflicts: false,
         };
 
         Ok(cartridge)
     }
 
-    fn build(file: File,
+    /***
+     * The bank register is a discrete latch: on the boards with bus conflicts the ROM drives the data bus
+     * during the write too, the latch gets the value written ANDed with the ROM byte at the address.
+     * The games avoid it by writing to a byte of the ROM holding the same value.
+     ***/
+    pub fn with_bus_conflicts(mut self, bus_conflicts: bool) -> Self {
+        self.bus_conflicts = bus_conflicts;
+        self
+    }
+
+    fn build(data: Box<dyn RomData>,
              prg_rom_offset: u64, prg_rom_size: usize,
              chr_rom_offset: Option<u64>, chr_rom_size: usize, chr_ram_size: usize, mirroring: PpuNameTableMirroring) -> Result<UnromCartridge, LoaderError> {
         debug!("creating UNROM cartridge");
 
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(data);
         let chr_rom_offset = if let Some(chr_rom_offset_unwrapped) = chr_rom_offset { chr_rom_offset_unwrapped } else { 0 };
 
         let cartridge = UnromCartridge::new(reader, prg_rom_offset, prg_rom_size, chr_rom_offset, chr_rom_size, chr_ram_size, mirroring)?;
@@ -84,14 +97,15 @@ impl UnromCartridge {
 
 impl FromINes for UnromCartridge {
     #[allow(refining_impl_trait)]
-    fn from_ines(file: File, header: INesRomHeader) -> Result<UnromCartridge, LoaderError>
+    fn from_ines(data: Box<dyn RomData>, header: INesRomHeader) -> Result<UnromCartridge, LoaderError>
     where
         Self: Sized
     {
-        let cartridge = UnromCartridge::build(file,
+        let cartridge = UnromCartridge::build(data,
                                               header.prg_offset(), header.prg_rom_size,
                                               header.chr_offset(), header.chr_rom_size,
-                                              header.chr_ram_size, header.nametables_layout)?;
+                                              header.chr_ram_size, header.nametables_layout)?
+            .with_bus_conflicts(header.ines2 && header.sub_mapper == BUS_CONFLICTS_SUB_MAPPER);
 
         Ok(cartridge)
     }
@@ -117,7 +131,12 @@ impl Memory for UnromCartridge {
         self.read_byte(addr)
     }
 
-    fn write_byte(&mut self, _: u16, value: u8) -> Result<(), MemoryError> {
+    fn poke_byte(&mut self, addr: u16, _: u8) -> Result<(), MemoryError> {
+        Err(MemoryError::ReadOnly(addr))
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        let value = if self.bus_conflicts { value & self.read_byte(addr)? } else { value };
         let previous_bank = self.current_bank;
         self.current_bank = (value & 0x0F) as usize % self.num_memory_banks;
         debug!("UNROM: switching to bank: was: {}, now: {} (raw write: 0x{:04X})", previous_bank, self.current_bank, value);
@@ -160,6 +179,20 @@ impl BusDevice for UnromCartridge {
     fn get_virtual_address_range(&self) -> (u16, u16) {
         CPU_ADDRESS_SPACE
     }
+
+    fn serialize_state(&self) -> Vec<u8> {
+        vec![self.current_bank as u8]
+    }
+
+    fn deserialize_state(&mut self, state: &[u8]) -> Result<(), MemoryError> {
+        match state {
+            [bank] => {
+                self.current_bank = *bank as usize % self.num_memory_banks;
+                Ok(())
+            },
+            _ => Err(MemoryError::IllegalState(format!("UNROM state of {} bytes, expected 1 byte", state.len()))),
+        }
+    }
 }
 
 impl Cartridge for UnromCartridge {
@@ -170,4 +203,8 @@ impl Cartridge for UnromCartridge {
     fn get_mirroring(&self) -> Rc<RefCell<PpuNameTableMirroring>> {
         self.mirroring.clone()
     }
+
+    fn bank_state(&self) -> BankState {
+        BankState::from_16k_prg_banks(self.current_bank, self.fixed_bank, 0, 1)
+    }
 }
\ No newline at end of file
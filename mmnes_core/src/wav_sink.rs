@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const APU_CHANNELS: usize = 5;
+pub const APU_CHANNEL_NAMES: [&str; APU_CHANNELS] = ["pulse1", "pulse2", "triangle", "noise", "dmc"];
+
+/// Highest output of each channel: 4 bits for the pulses, the triangle and the noise, 7 bits for the DMC.
+const APU_CHANNEL_FULL_SCALES: [f32; APU_CHANNELS] = [15.0, 15.0, 15.0, 15.0, 127.0];
+
+const WAV_HEADER_SIZE: u32 = 44;
+const WAV_BITS_PER_SAMPLE: u16 = 16;
+
+/***
+ * The output of each APU channel before the mixer, one buffer per channel, in the order of APU_CHANNEL_NAMES.
+ * The APU pushes the samples of its five channels at each sample tick, at the rate of the mixer.
+ * The mixer is not linear: the channels written as WAV files do not sum up to the master output,
+ * they are meant to be analysed one by one.
+ ***/
+#[derive(Debug, Clone)]
+pub struct MultiChannelWavSink {
+    buffers: [Vec<f32>; APU_CHANNELS],
+    sample_rate: u32,
+}
+
+impl MultiChannelWavSink {
+    pub fn new(sample_rate: u32) -> Self {
+        MultiChannelWavSink {
+            buffers: Default::default(),
+            sample_rate,
+        }
+    }
+
+    /// The outputs of the channels at a sample tick, as given by the channels (0 - 15, 0 - 127 for the DMC).
+    pub fn push_samples(&mut self, samples: [f32; APU_CHANNELS]) {
+        for (buffer, sample) in self.buffers.iter_mut().zip(samples) {
+            buffer.push(sample);
+        }
+    }
+
+    /// The samples of ```channel```, an index in APU_CHANNEL_NAMES.
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        &self.buffers[channel]
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.buffers.iter_mut().for_each(Vec::clear);
+    }
+
+    /// One mono WAV file per channel, next to ```prefix```: smb gives smb_pulse1.wav, smb_pulse2.wav...
+    pub fn write_files(&self, prefix: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+        let stem = prefix.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+        self.buffers.iter()
+            .zip(APU_CHANNEL_NAMES)
+            .zip(APU_CHANNEL_FULL_SCALES)
+            .map(|((buffer, name), full_scale)| {
+                let path = prefix.with_file_name(format!("{}_{}.wav", stem, name));
+                fs::write(&path, wav_bytes(buffer, full_scale, self.sample_rate))?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// A mono 16 bits PCM WAV file, the samples from 0 to ```full_scale``` are spread over the whole range.
+pub fn wav_bytes(samples: &[f32], full_scale: f32, sample_rate: u32) -> Vec<u8> {
+    let block_align = WAV_BITS_PER_SAMPLE / 8;
+    let data_size = samples.len() as u32 * block_align as u32;
+    let mut bytes = Vec::with_capacity((WAV_HEADER_SIZE + data_size) as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&WAV_BITS_PER_SAMPLE.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+
+    for sample in samples {
+        let normalized = (sample / full_scale).clamp(0.0, 1.0) * 2.0 - 1.0;
+        bytes.extend_from_slice(&((normalized * i16::MAX as f32) as i16).to_le_bytes());
+    }
+
+    bytes
+}
@@ -0,0 +1,231 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use eframe::egui;
+use eframe::egui::{pos2, vec2, Color32, Context, Pos2, RichText, Sense, Stroke, Ui};
+use log::warn;
+use mmnes_core::apu_snapshot::{ApuChannelSnapshot, ApuSnapshot};
+use mmnes_core::nes_console::NesConsoleError;
+use crate::helpers_ui::HelpersUI;
+use crate::nes_front_ui::{NesButton, NesButtonId};
+use crate::nes_mediator::NesMediator;
+use crate::nes_message::NesMessage;
+use crate::nes_ui_widget::NesUiWidget;
+
+const WINDOW_NAME: &str = "NES APU Viewer";
+const BAR_WIDTH: f32 = 160.0;
+const BAR_HEIGHT: f32 = 12.0;
+const MAX_VOLUME: f32 = 15.0;
+const MAX_DMC_OUTPUT_LEVEL: f32 = 127.0;
+const DUTY_CYCLES_NAMES: [&str; 4] = ["12.5%", "25%", "50%", "75%"];
+const STATE_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const SCOPE_WIDTH: f32 = 420.0;
+const SCOPE_HEIGHT: f32 = 64.0;
+
+pub struct ApuViewerWidget {
+    visible: bool,
+    error: Option<NesConsoleError>,
+    nes_mediator: Rc<RefCell<NesMediator>>,
+    is_state_requested: bool,
+    last_request: Instant,
+    snapshot: ApuSnapshot,
+    waveform: Vec<f32>,
+    buttons: Vec<NesButton>,
+}
+
+impl NesUiWidget for ApuViewerWidget {
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_error(&mut self, error: Option<NesConsoleError>) {
+        self.error = error;
+    }
+
+    fn menu_buttons(&self) -> &[NesButton] {
+        &self.buttons
+    }
+
+    fn on_button(&mut self, id: NesButtonId) -> Result<(), NesConsoleError> {
+        match id {
+            NesButtonId(0) => self.switch_visible(),
+            _ => return Err(NesConsoleError::InternalError("unknown button".to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn footer(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn draw(&mut self, ctx: &Context) -> Result<(), NesConsoleError> {
+        if self.visible {
+            self.apu_viewer_window(ctx)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ApuViewerWidget {
+
+    pub fn new(cc: &eframe::CreationContext<'_>, nes_mediator: Rc<RefCell<NesMediator>>) -> Result<ApuViewerWidget, NesConsoleError> {
+        let button = NesButton::new(cc, NesButtonId(0), "APU VIEWER", "Show the state of the sound channels", include_bytes!("assets/apu_viewer.png"))?;
+        let buttons = vec![button];
+
+        let widget = ApuViewerWidget {
+            visible: false,
+            error: None,
+            nes_mediator,
+            is_state_requested: false,
+            last_request: Instant::now(),
+            snapshot: ApuSnapshot::default(),
+            waveform: Vec::new(),
+            buttons,
+        };
+
+        Ok(widget)
+    }
+
+    fn switch_visible(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    fn read_apu_viewer_messages(&mut self) -> Result<(), NesConsoleError> {
+        let messages = self.nes_mediator.borrow().read_apu_viewer_messages()?;
+
+        for message in messages {
+            match message {
+                NesMessage::ApuState(snapshot) => {
+                    self.is_state_requested = false;
+                    self.snapshot = snapshot;
+                },
+                NesMessage::Waveform(waveform) => self.waveform = waveform,
+                _ => warn!("unexpected message: {:?}", message),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Requests are dropped when the channels are full, a pending request is retried after a timeout.
+    fn request_state(&mut self) -> Result<(), NesConsoleError> {
+        let is_expired = self.last_request.elapsed() > STATE_REQUEST_TIMEOUT;
+
+        if (!self.is_state_requested || is_expired) && self.error.is_none() {
+            self.nes_mediator.borrow_mut().send_message(NesMessage::ApuStateRequest)?;
+            self.is_state_requested = true;
+            self.last_request = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    fn level_bar(ui: &mut Ui, level: f32, color: Color32) {
+        let (rect, _) = ui.allocate_exact_size(vec2(BAR_WIDTH, BAR_HEIGHT), Sense::hover());
+        let mut filled = rect;
+        filled.set_width(BAR_WIDTH * level.clamp(0.0, 1.0));
+
+        ui.painter().rect_filled(rect, 0.0, Color32::DARK_GRAY);
+        ui.painter().rect_filled(filled, 0.0, color);
+    }
+
+    fn channel_row(ui: &mut Ui, name: &str, channel: &ApuChannelSnapshot, duty: Option<&str>) {
+        let color = if channel.muted() { Color32::GRAY } else { Color32::LIGHT_GREEN };
+
+        ui.horizontal(|ui| {
+            ui.label(HelpersUI::monospace(&format!("{:<8}", name)));
+            ApuViewerWidget::level_bar(ui, channel.volume() as f32 / MAX_VOLUME, color);
+            ui.label(HelpersUI::monospace(&format!("VOL:{:>2}{} PER:{:03X} LEN:{:>3} {:>5}",
+                                                   channel.volume(),
+                                                   if channel.constant_volume() { "C" } else { "E" },
+                                                   channel.timer_period(),
+                                                   channel.length_counter(),
+                                                   duty.unwrap_or(""))));
+        });
+    }
+
+    /// The mixed output of the APU, from 0.0 at the bottom to 1.0 at the top.
+    fn oscilloscope(ui: &mut Ui, waveform: &[f32]) {
+        let (rect, _) = ui.allocate_exact_size(vec2(SCOPE_WIDTH, SCOPE_HEIGHT), Sense::hover());
+        ui.painter().rect_filled(rect, 0.0, Color32::BLACK);
+
+        if waveform.len() < 2 {
+            return;
+        }
+
+        let step = rect.width() / (waveform.len() - 1) as f32;
+        let points = waveform.iter()
+            .enumerate()
+            .map(|(index, sample)| Pos2::new(
+                rect.left() + index as f32 * step,
+                rect.bottom() - sample.clamp(0.0, 1.0) * rect.height()))
+            .collect::<Vec<Pos2>>();
+
+        ui.painter().line(points, Stroke::new(1.0, Color32::LIGHT_GREEN));
+    }
+
+    fn apu_viewer_window_inner(&mut self, ui: &mut Ui) -> Result<(), NesConsoleError> {
+        self.read_apu_viewer_messages()?;
+        self.request_state()?;
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("  APU Viewer").strong());
+        });
+
+        ui.separator();
+
+        let snapshot = self.snapshot;
+        let pulse1_duty = DUTY_CYCLES_NAMES[snapshot.pulse1().duty_cycle() as usize % DUTY_CYCLES_NAMES.len()];
+        let pulse2_duty = DUTY_CYCLES_NAMES[snapshot.pulse2().duty_cycle() as usize % DUTY_CYCLES_NAMES.len()];
+
+        ApuViewerWidget::channel_row(ui, "PULSE 1", snapshot.pulse1(), Some(pulse1_duty));
+        ApuViewerWidget::channel_row(ui, "PULSE 2", snapshot.pulse2(), Some(pulse2_duty));
+        ApuViewerWidget::channel_row(ui, "TRIANGLE", snapshot.triangle(), None);
+        ApuViewerWidget::channel_row(ui, "NOISE", snapshot.noise(), None);
+
+        ui.horizontal(|ui| {
+            let color = if snapshot.dmc_enabled() { Color32::LIGHT_GREEN } else { Color32::GRAY };
+
+            ui.label(HelpersUI::monospace(&format!("{:<8}", "DMC")));
+            ApuViewerWidget::level_bar(ui, snapshot.dmc_output_level() as f32 / MAX_DMC_OUTPUT_LEVEL, color);
+            ui.label(HelpersUI::monospace(&format!("OUT:{:>3}", snapshot.dmc_output_level())));
+        });
+
+        ui.separator();
+        ApuViewerWidget::oscilloscope(ui, &self.waveform);
+
+        Ok(())
+    }
+
+    fn apu_viewer_window(&mut self, ctx: &Context) -> Result<(), NesConsoleError> {
+        let response = egui::Window::new(WINDOW_NAME)
+            .title_bar(false)
+            .default_pos(pos2(760.0, 420.0))
+            .resizable(false)
+            .show(ctx, |ui| {
+                let result = self.apu_viewer_window_inner(ui);
+
+                if let Err(error) = &result {
+                    self.set_error(Some(error.clone()));
+                }
+
+                if let Some(error) = &self.error {
+                    ui.separator();
+                    ui.label(HelpersUI::error(&error.to_string()));
+                }
+
+                result
+            });
+
+        match response.and_then(|response| response.inner) {
+            Some(Err(error)) => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
@@ -4,6 +4,9 @@ use eframe::egui;
 use eframe::egui::{pos2, vec2, Button, Color32, Context, Grid, Key, Response, RichText, Shadow, Stroke, TextStyle, Ui};
 use egui_extras::{Column, TableBody, TableBuilder, TableRow};
 use log::warn;
+use mmnes_core::cartridge::BankState;
+use mmnes_core::cpu::InterruptKind;
+use mmnes_core::cpu_6502::APU_FRAME_COUNTER_IRQ;
 use mmnes_core::cpu_debugger::{CpuSnapshot, DebugCommand};
 use mmnes_core::nes_console::NesConsoleError;
 use crate::helpers_ui::HelpersUI;
@@ -23,6 +26,7 @@ pub struct DebuggerWidget {
     error: Option<NesConsoleError>,
     nes_mediator: Rc<RefCell<NesMediator>>,
     cpu_snapshots: Vec<Box<dyn CpuSnapshot>>,
+    bank_state: Option<BankState>,
     buttons: Vec<NesButton>,
 }
 
@@ -74,6 +78,7 @@ impl DebuggerWidget {
             error: None,
             nes_mediator,
             cpu_snapshots: Vec::new(),
+            bank_state: None,
             buttons,
         };
 
@@ -119,6 +124,12 @@ impl DebuggerWidget {
             ui.separator();
             ui.label(RichText::new(format!("ATTACHED: {}", self.is_debugger_attached.to_string().to_uppercase())).monospace());
         });
+
+        if let Some(bank_state) = &self.bank_state {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("  BANKS: {}", bank_state)).monospace());
+            });
+        }
     }
 
     fn debugger_icon_button(&self, ui: &mut Ui, glyph: &str, tooltip: &str, fill: Color32) -> Response {
@@ -147,6 +158,9 @@ impl DebuggerWidget {
             if i.key_pressed(Key::F7) {
                 self.nes_mediator.borrow_mut().send_message(Debug(DebugCommand::StepInstruction))?;
             }
+            if i.key_pressed(Key::F8) {
+                self.nes_mediator.borrow_mut().send_message(Debug(DebugCommand::DumpTimings))?;
+            }
 
             Ok(())
         })?;
@@ -231,6 +245,14 @@ impl DebuggerWidget {
                         if self.debugger_icon_button(ui, "✨", "Explain", default_fill).clicked() {
                         }
 
+                        if self.debugger_icon_button(ui, "⚡", "Signal NMI", default_fill).clicked() {
+                            self.nes_mediator.borrow_mut().send_message(NesMessage::SignalInterrupt(InterruptKind::Nmi))?;
+                        }
+
+                        if self.debugger_icon_button(ui, "↯", "Signal IRQ (APU frame counter)", default_fill).clicked() {
+                            self.nes_mediator.borrow_mut().send_message(NesMessage::SignalInterrupt(InterruptKind::Irq(APU_FRAME_COUNTER_IRQ)))?;
+                        }
+
                         Ok(())
                     });
 
@@ -302,7 +324,7 @@ impl DebuggerWidget {
             let y = format!("{:02X}", snapshot.y());
             let p = format!("{:02X}", snapshot.p());
             let sp = format!("{:02X}", snapshot.sp());
-            let cycles = format!("{}", snapshot.cycles());
+            let cycles = format!("{} ({}{})", snapshot.cycles(), snapshot.base_cycles(), if snapshot.page_cross_penalty() { "+" } else { "" });
 
             row.col(|ui| {
                 ui.label(HelpersUI::monospace(if is_current { "▶" } else { " " }));
@@ -378,6 +400,7 @@ impl DebuggerWidget {
             match message {
                 NesMessage::CpuSnapshot(snap) => self.cpu_snapshots.push(snap),
                 NesMessage::CpuSnapshotSet(snaps) => self.cpu_snapshots.extend(snaps),
+                NesMessage::BankState(bank_state) => self.bank_state = Some(bank_state),
                 _ => warn!("unexpected message: {:?}", message),
             };
         }
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 8.0;
+const FAST_FORWARD_SPEEDS: [f64; 3] = [1.0, 2.0, 4.0];
+const SLOW_MOTION_SPEEDS: [f64; 3] = [1.0, 0.5, 0.25];
+
+/***
+ * Emulation speed multiplier: above 1x several emulated frames are run per real frame (frame budget),
+ * only the samples of the first one are played to keep the audio queue from growing.
+ * Below 1x (slow motion) one emulated frame lasts longer than a real frame and the sound is muted:
+ * the samples would not be enough to feed the audio device anyway.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmulationSpeed {
+    multiplier: f64,
+}
+
+impl Default for EmulationSpeed {
+    fn default() -> Self {
+        EmulationSpeed {
+            multiplier: 1.0
+        }
+    }
+}
+
+impl EmulationSpeed {
+    pub fn new(multiplier: f64) -> Self {
+        EmulationSpeed {
+            multiplier: multiplier.clamp(MIN_SPEED, MAX_SPEED)
+        }
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    /// Number of emulated frames to run per real frame, and the duration of the real frame.
+    /// ```frame_duration```: duration of a frame at 1x (region dependent).
+    pub fn frame_budget(&self, frame_duration: Duration) -> (u32, Duration) {
+        let frames = self.multiplier.floor().max(1.0) as u32;
+        let duration = frame_duration.mul_f64(frames as f64 / self.multiplier);
+
+        (frames, duration)
+    }
+
+    /// Whether the samples of the ```frame```-th emulated frame of a real frame are played, the others are dropped.
+    pub fn plays_samples(&self, frame: u32) -> bool {
+        frame == 0 && !self.is_slow_motion()
+    }
+
+    pub fn is_slow_motion(&self) -> bool {
+        self.multiplier < 1.0
+    }
+
+    /// Next fast-forward speed (1x, 2x, 4x, then back to 1x).
+    pub fn next_fast_forward(&self) -> Self {
+        let next = FAST_FORWARD_SPEEDS.iter()
+            .find(|speed| **speed > self.multiplier)
+            .copied()
+            .unwrap_or(FAST_FORWARD_SPEEDS[0]);
+
+        EmulationSpeed::new(next)
+    }
+
+    /// Next slow motion speed (1x, 0.5x, 0.25x, then back to 1x).
+    pub fn next_slow_motion(&self) -> Self {
+        let next = SLOW_MOTION_SPEEDS.iter()
+            .find(|speed| **speed < self.multiplier)
+            .copied()
+            .unwrap_or(SLOW_MOTION_SPEEDS[0]);
+
+        EmulationSpeed::new(next)
+    }
+}
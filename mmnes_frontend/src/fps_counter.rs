@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+pub const FPS_WINDOW: usize = 60;
+
+/***
+ * Frames per second averaged over the last ticks (a rolling window), steadier than the rate
+ * of the last tick alone. A tick counts the frames done since the previous one: 1 for a repaint
+ * of the UI, any number for the emulator, which can run several frames between two repaints.
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FpsCounter {
+    ticks: VecDeque<(Instant, u64)>,
+    frames: u64,
+    window: usize,
+}
+
+impl FpsCounter {
+    /// ```window```: number of ticks averaged, at least 2.
+    pub fn new(window: usize) -> Self {
+        FpsCounter {
+            ticks: VecDeque::with_capacity(window.max(2)),
+            frames: 0,
+            window: window.max(2),
+        }
+    }
+
+    /// ```frames``` done since the previous tick, at ```at```.
+    pub fn tick(&mut self, at: Instant, frames: u64) {
+        self.frames += frames;
+
+        if self.ticks.len() == self.window {
+            self.ticks.pop_front();
+        }
+
+        self.ticks.push_back((at, self.frames));
+    }
+
+    /// The frames over the time spanned by the window, 0 until two ticks apart are recorded.
+    pub fn fps(&self) -> f32 {
+        let (Some((first_at, first_frames)), Some((last_at, last_frames))) = (self.ticks.front(), self.ticks.back()) else {
+            return 0.0;
+        };
+
+        let duration = last_at.duration_since(*first_at).as_secs_f32();
+
+        if duration > 0.0 {
+            (last_frames - first_frames) as f32 / duration
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        FpsCounter::new(FPS_WINDOW)
+    }
+}
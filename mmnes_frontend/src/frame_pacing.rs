@@ -0,0 +1,63 @@
+use std::hint::spin_loop;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use clap::ValueEnum;
+
+const SPIN_BEFORE: Duration = Duration::from_micros(500);
+
+/***
+ * How the emulator thread waits for the next frame:
+ *   - Spin: sleeps, then spin-waits the last SPIN_BEFORE for an accurate deadline (burns CPU),
+ *   - Sleep: only sleeps, the deadline can be missed by the scheduler granularity,
+ *   - VSync: the UI repaints (synchronized to the display) drive the frames, the audio queue keeps
+ *     the emulation at its nominal speed on a display whose refresh rate is not the NES one.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum FramePacing {
+    #[default]
+    Spin,
+    Sleep,
+    #[value(name = "vsync")]
+    VSync,
+}
+
+impl FramePacing {
+
+    /// Duration of a frame at ```frames_per_second```, the deadline of the clock driven modes.
+    pub fn frame_budget(frames_per_second: f64) -> Duration {
+        Duration::from_secs_f64(1.0 / frames_per_second)
+    }
+
+    /// Waits for the ```next``` frame deadline and returns the following one, one ```frame``` later.
+    /// A missed deadline is skipped rather than caught up. VSync waits like Sleep: it is used when the
+    /// frames are not driven by the display (debugger).
+    pub fn sleep_until_next_frame(&self, next: Instant, frame: Duration) -> Instant {
+        let now = Instant::now();
+        let mut next = next;
+
+        if next > now {
+            let mut to_sleep = next - now;
+
+            if *self == FramePacing::Spin {
+                if to_sleep > SPIN_BEFORE {
+                    to_sleep -= SPIN_BEFORE;
+                    sleep(to_sleep);
+                }
+
+                while Instant::now() < next {
+                    spin_loop();
+                }
+            } else {
+                sleep(to_sleep);
+            }
+
+            next + frame
+        } else {
+            while next <= now {
+                next += frame;
+            }
+
+            next
+        }
+    }
+}
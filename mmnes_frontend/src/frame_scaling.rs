@@ -0,0 +1,73 @@
+use std::fmt::{Display, Formatter};
+use eframe::egui::{vec2, Rect, Vec2};
+
+pub const MAX_INTEGER_SCALE: u32 = 3;
+/// Pixel aspect ratio of the NTSC NES, its pixels are slightly wider than tall.
+pub const PIXEL_ASPECT_RATIO: f32 = 8.0 / 7.0;
+
+/***
+ * How the NES frame is drawn in the viewport:
+ *   - Fit: stretched to the largest size fitting the viewport, the frame ratio kept,
+ *   - Integer(n): each NES pixel is n x n screen pixels. When n does not fit, the largest scale
+ *     fitting is used (at least 1x), the frame is then letterboxed in the viewport.
+ * The 8:7 aspect correction widens the frame before it is scaled.
+ ***/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScaleMode {
+    #[default]
+    Fit,
+    Integer(u32),
+}
+
+impl Display for ScaleMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScaleMode::Fit => write!(f, "fit"),
+            ScaleMode::Integer(scale) => write!(f, "{}x", scale),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameScaling {
+    mode: ScaleMode,
+    aspect_correction: bool,
+}
+
+impl FrameScaling {
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    pub fn aspect_correction(&self) -> bool {
+        self.aspect_correction
+    }
+
+    /// Next scale mode (fit, 1x, 2x, 3x, then back to fit).
+    pub fn next_mode(&self) -> Self {
+        let mode = match self.mode {
+            ScaleMode::Fit => ScaleMode::Integer(1),
+            ScaleMode::Integer(scale) if scale < MAX_INTEGER_SCALE => ScaleMode::Integer(scale + 1),
+            ScaleMode::Integer(_) => ScaleMode::Fit,
+        };
+
+        FrameScaling { mode, ..*self }
+    }
+
+    pub fn toggle_aspect_correction(&self) -> Self {
+        FrameScaling { aspect_correction: !self.aspect_correction, ..*self }
+    }
+
+    /// Where the ```frame``` (in NES pixels) is drawn, centered in the ```viewport```.
+    pub fn destination(&self, frame: Vec2, viewport: Rect) -> Rect {
+        let frame = if self.aspect_correction { vec2(frame.x * PIXEL_ASPECT_RATIO, frame.y) } else { frame };
+        let fit = (viewport.width() / frame.x).min(viewport.height() / frame.y);
+
+        let scale = match self.mode {
+            ScaleMode::Fit => fit,
+            ScaleMode::Integer(scale) => (scale as f32).min(fit.floor()).max(1.0),
+        };
+
+        Rect::from_center_size(viewport.center(), frame * scale)
+    }
+}
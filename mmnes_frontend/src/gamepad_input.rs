@@ -0,0 +1,157 @@
+use gilrs::{Axis, Button, Event, EventType, GamepadId, Gilrs};
+use log::{info, warn};
+use mmnes_core::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_B, NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_SELECT, NES_CONTROLLER_KEY_START, NES_CONTROLLER_KEY_UP};
+use mmnes_core::nes_console::NesConsoleError;
+use crate::input_source::InputSource;
+
+/// Analog values below it (in absolute value) are the center of the stick.
+pub const GAMEPAD_DEADZONE: f32 = 0.5;
+const NES_CONTROLLER_KEYS: usize = 8;
+
+/***
+ * Gamepad buttons and axes to the NES controller keys:
+ *   - South / West are A / B (the NES pad layout on a modern pad), Start / Select are Start / Select,
+ *   - the d-pad buttons, the d-pad axes and the left stick are the directions,
+ *     the analog values are pressed beyond GAMEPAD_DEADZONE.
+ *
+ * The state of each key is kept, only the changes are reported: a stick moving within a direction does not
+ * repeat the key, and the keys still pressed can be released when the gamepad is disconnected.
+ ***/
+#[derive(Debug, Default)]
+pub struct GamepadMapping {
+    pressed: [bool; NES_CONTROLLER_KEYS]
+}
+
+impl GamepadMapping {
+
+    pub fn new() -> Self {
+        GamepadMapping::default()
+    }
+
+    pub fn map_button(&mut self, button: Button, pressed: bool) -> Option<KeyEvent> {
+        let key = match button {
+            Button::South => NES_CONTROLLER_KEY_A,
+            Button::West => NES_CONTROLLER_KEY_B,
+            Button::Start => NES_CONTROLLER_KEY_START,
+            Button::Select => NES_CONTROLLER_KEY_SELECT,
+            Button::DPadUp => NES_CONTROLLER_KEY_UP,
+            Button::DPadDown => NES_CONTROLLER_KEY_DOWN,
+            Button::DPadLeft => NES_CONTROLLER_KEY_LEFT,
+            Button::DPadRight => NES_CONTROLLER_KEY_RIGHT,
+            _ => return None,
+        };
+
+        self.set_key(key, pressed)
+    }
+
+    /// The Y axes are positive upward.
+    pub fn map_axis(&mut self, axis: Axis, value: f32) -> Vec<KeyEvent> {
+        let (negative, positive) = match axis {
+            Axis::LeftStickX | Axis::DPadX => (NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT),
+            Axis::LeftStickY | Axis::DPadY => (NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_UP),
+            _ => return Vec::new(),
+        };
+
+        [(negative, value < -GAMEPAD_DEADZONE), (positive, value > GAMEPAD_DEADZONE)]
+            .into_iter()
+            .filter_map(|(key, pressed)| self.set_key(key, pressed))
+            .collect()
+    }
+
+    pub fn release_all(&mut self) -> Vec<KeyEvent> {
+        (0..NES_CONTROLLER_KEYS)
+            .filter_map(|key| self.set_key(key, false))
+            .collect()
+    }
+
+    fn set_key(&mut self, key: usize, pressed: bool) -> Option<KeyEvent> {
+        if self.pressed[key] == pressed {
+            return None;
+        }
+
+        self.pressed[key] = pressed;
+        Some(KeyEvent { key, pressed })
+    }
+}
+
+/***
+ * Gamepads through gilrs: the first gamepad connected drives the controller.
+ * When it is disconnected, its keys are released and the next connected gamepad (if any) takes over.
+ ***/
+pub struct GamepadInput {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+    mapping: GamepadMapping
+}
+
+impl GamepadInput {
+
+    pub fn new() -> Result<Self, NesConsoleError> {
+        let gilrs = Gilrs::new()
+            .map_err(|e| NesConsoleError::ControllerError(format!("gamepad support unavailable: {}", e)))?;
+
+        let active = gilrs.gamepads().next().map(|(id, gamepad)| {
+            info!("using gamepad {}", gamepad.name());
+            id
+        });
+
+        Ok(GamepadInput {
+            gilrs,
+            active,
+            mapping: GamepadMapping::new()
+        })
+    }
+
+    fn on_connected(&mut self, id: GamepadId) {
+        let name = self.gilrs.gamepad(id).name().to_string();
+
+        if self.active.is_none() {
+            info!("gamepad connected, using it: {}", name);
+            self.active = Some(id);
+        } else {
+            info!("gamepad connected: {}", name);
+        }
+    }
+
+    fn on_disconnected(&mut self, id: GamepadId, input: &mut KeyEvents) {
+        if self.active != Some(id) {
+            return;
+        }
+
+        self.mapping.release_all().into_iter().for_each(|event| input.push_back(event));
+        self.active = self.gilrs.gamepads()
+            .map(|(other, _)| other)
+            .find(|other| *other != id);
+
+        match self.active {
+            Some(other) => warn!("gamepad disconnected, using {}", self.gilrs.gamepad(other).name()),
+            None => warn!("gamepad disconnected"),
+        }
+    }
+}
+
+impl InputSource for GamepadInput {
+    fn poll(&mut self, input: &mut KeyEvents) {
+        while let Some(Event { id, event, .. }) = self.gilrs.next_event() {
+            match event {
+                EventType::Connected => self.on_connected(id),
+                EventType::Disconnected => self.on_disconnected(id, input),
+                _ if self.active != Some(id) => {},
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(event) = self.mapping.map_button(button, true) {
+                        input.push_back(event);
+                    }
+                },
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(event) = self.mapping.map_button(button, false) {
+                        input.push_back(event);
+                    }
+                },
+                EventType::AxisChanged(axis, value, _) => {
+                    self.mapping.map_axis(axis, value).into_iter().for_each(|event| input.push_back(event));
+                },
+                _ => {},
+            }
+        }
+    }
+}
@@ -2,6 +2,8 @@ use eframe::egui::{Color32, ColorImage, RichText};
 use image::codecs::jpeg::JpegEncoder;
 use image::{ExtendedColorType, ImageError};
 
+const ERROR_COLOR: Color32 = Color32::from_rgb(230, 75, 75);
+
 pub struct HelpersUI;
 
 impl HelpersUI {
@@ -13,6 +15,10 @@ impl HelpersUI {
         RichText::new(s).monospace().strong()
     }
 
+    pub fn error(s: &str) -> RichText {
+        RichText::new(s).monospace().color(ERROR_COLOR)
+    }
+
     pub fn color_image_to_jpeg_bytes(image: &ColorImage, quality: u8, background: [u8; 3]) -> Result<Vec<u8>, ImageError> {
         let (width, height) = (image.size[0], image.size[1]);
 
@@ -0,0 +1,6 @@
+use mmnes_core::key_event::KeyEvents;
+
+/// Source of NES controller inputs other than the keyboard, polled by the UI on each update.
+pub trait InputSource {
+    fn poll(&mut self, input: &mut KeyEvents);
+}
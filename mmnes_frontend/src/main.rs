@@ -1,17 +1,27 @@
-use std::path::PathBuf;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
-use std::thread::{spawn, JoinHandle};
-use std::time::{Duration};
-use log::{error, LevelFilter};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::{Duration, Instant};
+use log::{error, info, LevelFilter};
 use simplelog::{Config, SimpleLogger};
-use clap::{Parser};
+use clap::{ArgGroup, Parser};
 use clap_num::maybe_hex;
 use eframe::egui::{vec2, ViewportBuilder};
 use eframe::NativeOptions;
+use mmnes_core::ansi_renderer::frame_to_ansi;
+use mmnes_core::benchmark::run_benchmark;
+use mmnes_core::controller::ControllerType;
 use mmnes_core::nes_console::NesConsoleError;
+use mmnes_core::palette_2c02::Palette2C02;
+use mmnes_core::session::Session;
+use mmnes_core::test_rom::{run_test_rom, StatusPort};
+use crate::frame_pacing::FramePacing;
 use crate::nes_front_end::NesFrontEnd;
 use crate::nes_front_ui::NesFrontUI;
 use crate::nes_message::NesMessage;
+use crate::sound_player::{DEFAULT_AUDIO_BUFFER_SIZE, DEFAULT_SAMPLE_RATE};
 
 mod nes_front_ui;
 mod sound_player;
@@ -33,6 +43,16 @@ pub mod tests;
 mod ai_worker;
 mod nes_rom_metadata_widget;
 mod nes_rom_metadata_worker;
+mod ppu_viewer_widget;
+mod apu_viewer_widget;
+mod emulation_speed;
+mod frame_pacing;
+mod frame_scaling;
+mod save_slots;
+mod recent_roms;
+mod fps_counter;
+mod input_source;
+mod gamepad_input;
 
 const APP_NAME: &str = "MMNES";
 
@@ -41,14 +61,20 @@ const FRAME_BUFFER_HEIGHT: usize = 240;
 const CHANNEL_BOUND_SIZE: usize = 10;
 const DEBUG_CHANNEL_BOUND_SIZE: usize = 100;
 const ERROR_BOUND_SIZE: usize = 10;
+const PPU_VIEWER_BOUND_SIZE: usize = 2;
+const APU_VIEWER_BOUND_SIZE: usize = 2;
 const FRAMES_PER_SECOND: f64 = 60.098_8;
-const SPIN_BEFORE: Duration = Duration::from_micros(500);
+
+const TUI_FRAMES_PER_SECOND: f64 = 30.0;
+const TUI_DEFAULT_SIZE: (u16, u16) = (80, 24);
 
 const VIEWPORT_HEIGHT: f32 = 600.0;
 const VIEWPORT_WIDTH: f32 = 900.0;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("rom").args(["rom_file", "session_file"]).multiple(true)))]
+#[command(group(ArgGroup::new("test_rom").args(["exit_on_halt", "status_port"]).multiple(true)))]
 pub struct Args {
     #[arg(
         short = 'd',
@@ -71,9 +97,165 @@ pub struct Args {
         long = "rom-file",
         help = "rom file to immediately load",
     )]
-    rom_file: Option<PathBuf>
+    rom_file: Option<PathBuf>,
+
+    #[arg(
+        short = 's',
+        long = "session",
+        help = "session file (JSON) with the rom, sram, cheats, region and palette to start with, the other flags take precedence",
+    )]
+    session_file: Option<PathBuf>,
+
+    #[arg(skip)]
+    session: Option<Session>,
+
+    #[arg(
+        short = 'b',
+        long = "audio-buffer",
+        help = "audio buffer size in samples (latency), at least 1024",
+        default_value_t = DEFAULT_AUDIO_BUFFER_SIZE as u32,
+        value_parser = clap::value_parser!(u32).range(1024..=65536)
+    )]
+    audio_buffer_size: u32,
+
+    #[arg(
+        short = 'r',
+        long = "sample-rate",
+        help = "audio sample rate in Hz, the APU output is resampled to it",
+        default_value_t = DEFAULT_SAMPLE_RATE,
+        value_parser = clap::value_parser!(u32).range(22_050..=192_000)
+    )]
+    sample_rate: u32,
+
+    #[arg(
+        long = "benchmark",
+        help = "run the rom headless, unthrottled and without audio for the given number of seconds, then print the emulated speed",
+        requires = "rom"
+    )]
+    benchmark: Option<u64>,
+
+    #[arg(
+        short = 'p',
+        long = "palette",
+        help = "palette file (.pal, 192 bytes or 1536 bytes with the emphasis variants) used instead of the built-in palette",
+    )]
+    palette_file: Option<PathBuf>,
+
+    #[arg(
+        long = "ntsc-filter",
+        help = "apply the NTSC composite video filter (color bleeding and dot crawl) to the frames",
+    )]
+    ntsc_filter: bool,
+
+    #[arg(
+        long = "microphone",
+        help = "plug a Famicom controller, its microphone (key M) is reported on bit 2 of $4016",
+    )]
+    microphone: bool,
+
+    #[arg(
+        long = "four-score",
+        help = "plug a Four Score for 4 players, players 2 and 3 on the keyboard too (IJKL + O U P Y, TFGH + V C B X)",
+        conflicts_with = "microphone"
+    )]
+    four_score: bool,
+
+    #[arg(
+        long = "tui",
+        help = "run the rom headless and draw the frames in the terminal with ANSI colors (no audio, no input)",
+        requires = "rom",
+        conflicts_with = "benchmark"
+    )]
+    tui: bool,
+
+    #[arg(
+        long = "headless",
+        help = "run the rom without audio, video nor input devices (servers, CI), unthrottled, until the CPU halts or for --frames frames",
+        requires = "rom",
+        conflicts_with_all = ["benchmark", "tui"]
+    )]
+    headless: bool,
+
+    #[arg(
+        long = "frames",
+        help = "with --headless: stop after this number of frames",
+        requires = "headless"
+    )]
+    frames: Option<u64>,
+
+    #[arg(
+        long = "pacing",
+        help = "frame pacing: spin (accurate, burns CPU), sleep, or vsync (driven by the display refresh)",
+        value_enum,
+        default_value_t = FramePacing::Spin
+    )]
+    pacing: FramePacing,
+
+    #[arg(
+        long = "exit-on-halt",
+        help = "run the rom headless as a test and exit with a nonzero code when the CPU halts (JAM)",
+        requires = "rom",
+        conflicts_with_all = ["benchmark", "tui", "headless"]
+    )]
+    exit_on_halt: bool,
+
+    #[arg(
+        long = "status-port",
+        help = "run the rom headless as a test and exit with the status written at this address, followed by the signature DE B0 61: 0 on success ($00), the failure code otherwise",
+        value_parser = maybe_hex::<u16>,
+        requires = "rom",
+        conflicts_with_all = ["benchmark", "tui", "headless"]
+    )]
+    status_port: Option<u16>,
+
+    #[arg(
+        long = "timeout-frames",
+        help = "with --exit-on-halt or --status-port: fail the test (exit code 255) when it is not over after this number of frames",
+        requires = "test_rom"
+    )]
+    timeout_frames: Option<u32>,
+
+    #[arg(
+        long = "disassemble",
+        help = "write the disassembly of the PRG ROM, from the reset vector, to this file and exit",
+        requires = "rom",
+        conflicts_with_all = ["benchmark", "tui", "headless", "exit_on_halt", "status_port", "timeout_frames"]
+    )]
+    disassembly_file: Option<PathBuf>,
 }
 
+impl Args {
+    /// The rom and palette flags take precedence over the ones of the session.
+    fn load_session(&mut self) -> Result<(), NesConsoleError> {
+        let session = match &self.session_file {
+            Some(path) => Session::from_file(path)?,
+            None => return Ok(()),
+        };
+
+        info!("session loaded from {}", self.session_file.as_ref().unwrap().display());
+
+        if self.rom_file.is_none() {
+            self.rom_file = Some(session.rom_file().to_path_buf());
+        }
+
+        if self.palette_file.is_none() {
+            self.palette_file = session.palette_file().map(Path::to_path_buf);
+        }
+
+        self.session = Some(session);
+        Ok(())
+    }
+
+    fn controller_type(&self) -> ControllerType {
+        if self.microphone {
+            ControllerType::FamicomWithMic
+        } else if self.four_score {
+            ControllerType::FourScore
+        } else {
+            ControllerType::StandardController
+        }
+    }
+}
 
 fn logger_init(debug: u8) {
 
@@ -86,10 +268,33 @@ fn logger_init(debug: u8) {
     SimpleLogger::init(log_level, Config::default()).unwrap();
 }
 
-fn spawn_emulator_thread(_args: &Args, frame_tx: SyncSender<NesMessage>, command_rx: Receiver<NesMessage>, debug_tx: SyncSender<NesMessage>, error_tx: SyncSender<NesMessage>) -> Result<JoinHandle<Result<(), NesConsoleError>>, NesConsoleError> {
+/// The palette is per thread, it must be loaded by the thread running the console.
+fn load_palette_file(palette_file: &Option<PathBuf>) -> Result<(), NesConsoleError> {
+    if let Some(path) = palette_file {
+        let bytes = fs::read(path)?;
+        Palette2C02::load_from_pal(&bytes)?;
+        info!("palette loaded from {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn spawn_emulator_thread(args: &Args, frame_tx: SyncSender<NesMessage>, command_rx: Receiver<NesMessage>, debug_tx: SyncSender<NesMessage>, error_tx: SyncSender<NesMessage>, ppu_viewer_tx: SyncSender<NesMessage>, apu_viewer_tx: SyncSender<NesMessage>) -> Result<JoinHandle<Result<(), NesConsoleError>>, NesConsoleError> {
+
+    let audio_buffer_size = args.audio_buffer_size as usize;
+    let sample_rate = args.sample_rate;
+    let palette_file = args.palette_file.clone();
+    let controller_type = args.controller_type();
+    let pacing = args.pacing;
+    let session = args.session.clone();
 
     let handle = spawn(move || -> Result<(), NesConsoleError> {
-        let mut front = NesFrontEnd::new(frame_tx, command_rx, debug_tx, error_tx).map_err(|e| {
+        load_palette_file(&palette_file).map_err(|e| {
+            error!("fatal error while loading palette: {}", e);
+            e
+        })?;
+
+        let mut front = NesFrontEnd::new(frame_tx, command_rx, debug_tx, error_tx, ppu_viewer_tx, apu_viewer_tx, audio_buffer_size, sample_rate, controller_type, pacing, session).map_err(|e| {
             error!("fatal error while creating emulator: {}", e);
             e
         })?;
@@ -103,15 +308,175 @@ fn spawn_emulator_thread(_args: &Args, frame_tx: SyncSender<NesMessage>, command
     Ok(handle)
 }
 
+fn run_benchmark_mode(args: &Args, seconds: u64) -> Result<(), NesConsoleError> {
+    let rom_file = args.rom_file.clone()
+        .ok_or_else(|| NesConsoleError::InternalError("benchmark needs a rom file".to_string()))?;
+
+    load_palette_file(&args.palette_file)?;
+
+    let mut console = NesFrontEnd::create_emulator(rom_file, args.pc, args.audio_buffer_size as usize, args.sample_rate, args.controller_type(), args.session.as_ref())?;
+    let report = run_benchmark(&mut console, Duration::from_secs(seconds))?;
+
+    println!("benchmark: {}", report);
+
+    Ok(())
+}
+
+/***
+ * Test ROMs in CI: the process exits with the code of the test result, the status port is mapped
+ * over the devices already decoding its address (e.g. the PRG RAM at $6000).
+ ***/
+fn run_test_rom_mode(args: &Args) -> Result<(), NesConsoleError> {
+    let rom_file = args.rom_file.clone()
+        .ok_or_else(|| NesConsoleError::InternalError("test rom mode needs a rom file".to_string()))?;
+
+    let status_port = args.status_port.map(StatusPort::new);
+    let mut builder = NesFrontEnd::emulator_builder(rom_file, args.pc, args.audio_buffer_size as usize, args.sample_rate, args.controller_type(), args.session.as_ref());
+
+    if let Some(port) = &status_port {
+        builder = builder.with_custom_io_device(port.device());
+    }
+
+    let mut console = NesFrontEnd::power_on_emulator(builder)?;
+    let status = run_test_rom(&mut console, status_port.as_ref(), args.exit_on_halt, args.timeout_frames)?;
+    let code = status.exit_code().unwrap_or_default();
+
+    info!("test rom over: {:?}, exit code: {}", status, code);
+    std::process::exit(code)
+}
+
+/***
+ * Pure computation: the console drops its samples and reads no button, no SDL nor window is opened.
+ * The frames are not paced, the run is over when the CPU halts or after ```--frames``` frames.
+ ***/
+fn run_headless_mode(args: &Args) -> Result<(), NesConsoleError> {
+    let rom_file = args.rom_file.clone()
+        .ok_or_else(|| NesConsoleError::InternalError("headless needs a rom file".to_string()))?;
+
+    load_palette_file(&args.palette_file)?;
+
+    let builder = NesFrontEnd::emulator_builder(rom_file, args.pc, args.audio_buffer_size as usize, args.sample_rate, args.controller_type(), args.session.as_ref())
+        .with_headless(true);
+    let mut console = NesFrontEnd::power_on_emulator(builder)?;
+    let mut frames = 0;
+
+    while !console.state().is_cpu_stopped() && args.frames.is_none_or(|max_frames| frames < max_frames) {
+        console.step_frame()?;
+        frames += 1;
+    }
+
+    info!("headless run over after {} frames: {:?}", frames, console.state());
+    Ok(())
+}
+
+/// A linear sweep of the PRG space: the data between the routines is disassembled as code.
+fn run_disassemble_mode(args: &Args, output: &Path) -> Result<(), NesConsoleError> {
+    let rom_file = args.rom_file.clone()
+        .ok_or_else(|| NesConsoleError::InternalError("disassembly needs a rom file".to_string()))?;
+
+    let console = NesFrontEnd::create_emulator(rom_file, args.pc, args.audio_buffer_size as usize, args.sample_rate, args.controller_type(), args.session.as_ref())?;
+    let lines = console.disassemble_prg()?;
+
+    fs::write(output, lines.join("\n") + "\n")?;
+    info!("{} lines of disassembly written to {}", lines.len(), output.display());
+
+    Ok(())
+}
+
+/// Terminal size from the shell, minus the last line for the cursor.
+fn terminal_size() -> (u16, u16) {
+    let read = |name: &str, default: u16| std::env::var(name).ok()
+        .and_then(|value| value.parse::<u16>().ok())
+        .unwrap_or(default);
+
+    let cols = read("COLUMNS", TUI_DEFAULT_SIZE.0);
+    let rows = read("LINES", TUI_DEFAULT_SIZE.1).saturating_sub(1);
+
+    (cols, rows)
+}
+
+/// The largest area of the terminal with the aspect ratio of the frame, each cell being 1 x 2 pixels.
+fn fit_to_terminal(cols: u16, rows: u16) -> (u16, u16) {
+    let width = FRAME_BUFFER_WIDTH as u32;
+    let height = FRAME_BUFFER_HEIGHT as u32;
+
+    let fitted_cols = (cols as u32).min(rows as u32 * 2 * width / height);
+    let fitted_rows = (rows as u32).min(cols as u32 * height / width / 2);
+
+    (fitted_cols as u16, fitted_rows as u16)
+}
+
+/***
+ * Run the emulation at its own pace and draw the frames in the terminal, at most TUI_FRAMES_PER_SECOND:
+ * the frames in between are emulated but not printed, to avoid flooding the terminal.
+ * The terminal size is read again before each frame.
+ ***/
+fn run_tui_mode(args: &Args) -> Result<(), NesConsoleError> {
+    let rom_file = args.rom_file.clone()
+        .ok_or_else(|| NesConsoleError::InternalError("tui needs a rom file".to_string()))?;
+
+    load_palette_file(&args.palette_file)?;
+
+    let mut console = NesFrontEnd::create_emulator(rom_file, args.pc, args.audio_buffer_size as usize, args.sample_rate, args.controller_type(), args.session.as_ref())?;
+    let frame_duration = Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND);
+    let print_interval = Duration::from_secs_f64(1.0 / TUI_FRAMES_PER_SECOND);
+    let mut last_print = Instant::now() - print_interval;
+    let mut stdout = std::io::stdout();
+
+    // clear the screen
+    write!(stdout, "\x1b[2J")?;
+
+    loop {
+        let start = Instant::now();
+        let (frame, _) = console.step_frame()?;
+
+        if last_print.elapsed() >= print_interval {
+            let (cols, rows) = terminal_size();
+            let (cols, rows) = fit_to_terminal(cols, rows);
+
+            // cursor to the top left corner, the previous frame is overwritten
+            write!(stdout, "\x1b[H{}", frame_to_ansi(&frame, cols, rows))?;
+            stdout.flush()?;
+            last_print = Instant::now();
+        }
+
+        if let Some(remaining) = frame_duration.checked_sub(start.elapsed()) {
+            sleep(remaining);
+        }
+    }
+}
+
 fn main() -> Result<(), NesConsoleError> {
-    let args: Args = Args::parse();
+    let mut args: Args = Args::parse();
 
     logger_init(args.debug);
+    args.load_session()?;
+
+    if let Some(seconds) = args.benchmark {
+        return run_benchmark_mode(&args, seconds);
+    }
+
+    if args.tui {
+        return run_tui_mode(&args);
+    }
+
+    if args.headless {
+        return run_headless_mode(&args);
+    }
+
+    if args.exit_on_halt || args.status_port.is_some() {
+        return run_test_rom_mode(&args);
+    }
+
+    if let Some(output) = &args.disassembly_file {
+        return run_disassemble_mode(&args, output);
+    }
 
     let native_options = NativeOptions {
         viewport: ViewportBuilder::default()
             .with_inner_size(vec2(VIEWPORT_WIDTH, VIEWPORT_HEIGHT))
             .with_resizable(true),
+        vsync: true,
         ..Default::default()
     };
 
@@ -119,8 +484,10 @@ fn main() -> Result<(), NesConsoleError> {
     let (command_tx, command_rx) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
     let (debug_tx, debug_rx) = sync_channel::<NesMessage>(DEBUG_CHANNEL_BOUND_SIZE);
     let (error_tx, error_rx) = sync_channel::<NesMessage>(ERROR_BOUND_SIZE);
+    let (ppu_viewer_tx, ppu_viewer_rx) = sync_channel::<NesMessage>(PPU_VIEWER_BOUND_SIZE);
+    let (apu_viewer_tx, apu_viewer_rx) = sync_channel::<NesMessage>(APU_VIEWER_BOUND_SIZE);
 
-    let _ = spawn_emulator_thread(&args, frame_tx, command_rx, debug_tx, error_tx)?;
+    let _ = spawn_emulator_thread(&args, frame_tx, command_rx, debug_tx, error_tx, ppu_viewer_tx, apu_viewer_tx)?;
 
     let _ = eframe::run_native(
         APP_NAME,
@@ -128,7 +495,7 @@ fn main() -> Result<(), NesConsoleError> {
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
 
-            let nes_front_ui = NesFrontUI::new(args, cc, command_tx, frame_rx, debug_rx, error_rx, FRAME_BUFFER_WIDTH, FRAME_BUFFER_HEIGHT);
+            let nes_front_ui = NesFrontUI::new(args, cc, command_tx, frame_rx, debug_rx, error_rx, ppu_viewer_rx, apu_viewer_rx, FRAME_BUFFER_WIDTH, FRAME_BUFFER_HEIGHT);
             if let Err(error) = nes_front_ui {
                 panic!("failed to initialize NES front UI: {}", error);
             }
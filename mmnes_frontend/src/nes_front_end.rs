@@ -1,36 +1,45 @@
-use std::hint::spin_loop;
 use std::ops::ControlFlow;
 use std::ops::ControlFlow::{Break, Continue};
 use std::path::PathBuf;
-use std::sync::mpsc::TrySendError;
+use std::sync::mpsc::{RecvTimeoutError, TrySendError};
 use std::sync::mpsc::{Receiver, SyncSender};
-use std::thread::sleep;
 use std::time::{Duration, Instant};
 use log::{info, warn};
 use mmnes_core::apu::ApuType::RP2A03;
 use mmnes_core::bus::BusType;
 use mmnes_core::bus_device::BusDeviceType::{APU, CARTRIDGE, CONTROLLER, PPU, WRAM};
 use mmnes_core::cartridge::CartridgeType::NROM;
-use mmnes_core::controller::ControllerType::StandardController;
-use mmnes_core::cpu::CpuType;
+use mmnes_core::controller::ControllerType;
+use mmnes_core::cpu::{CpuError, CpuType};
 use mmnes_core::cpu_debugger::DebugCommand;
 use mmnes_core::loader::LoaderType::INESV2;
 use mmnes_core::memory::MemoryType::StandardMemory;
-use mmnes_core::nes_console::{NesConsole, NesConsoleBuilder, NesConsoleError};
+use mmnes_core::nes_console::{ConsoleState, NesConsole, NesConsoleBuilder, NesConsoleError};
 use mmnes_core::nes_frame::NesFrame;
 use mmnes_core::nes_samples::NesSamples;
+use mmnes_core::perf_counters::PerfCounters;
 use mmnes_core::ppu::PpuType::NES2C02;
-use crate::{FRAMES_PER_SECOND, SPIN_BEFORE};
+use mmnes_core::sample_history::SampleHistory;
+use mmnes_core::session::Session;
+use crate::FRAMES_PER_SECOND;
+use crate::emulation_speed::EmulationSpeed;
+use crate::frame_pacing::FramePacing;
 use crate::nes_message::NesMessage;
+use crate::save_slots::SaveSlots;
 use crate::sound_player::SoundPlayer;
 
+const VSYNC_TIMEOUT_FRAMES: u32 = 4;
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+const WAVEFORM_POINTS: usize = 512;
+
 #[derive(Debug, Clone, PartialEq)]
-enum NesFrontEndState {
+pub(crate) enum NesFrontEndState {
     Running,
     Debug(DebugCommand),
     Paused,
     Idle,
     Halted,
+    CpuHalted(u16),
 }
 
 impl NesFrontEndState {
@@ -48,8 +57,19 @@ pub struct NesFrontEnd {
     frame_tx: SyncSender<NesMessage>,
     debug_tx: SyncSender<NesMessage>,
     error_tx: SyncSender<NesMessage>,
+    ppu_viewer_tx: SyncSender<NesMessage>,
+    apu_viewer_tx: SyncSender<NesMessage>,
     nes: Option<NesConsole>,
-    state: NesFrontEndState
+    state: NesFrontEndState,
+    audio_buffer_size: usize,
+    sample_rate: u32,
+    controller_type: ControllerType,
+    speed: EmulationSpeed,
+    pacing: FramePacing,
+    session: Option<Session>,
+    last_stats: (Instant, PerfCounters),
+    sample_history: SampleHistory,
+    save_slots: Option<SaveSlots>,
 }
 
 impl NesFrontEnd {
@@ -60,29 +80,42 @@ impl NesFrontEnd {
             .ok_or_else(|| NesConsoleError::InternalError("emulator is not created".to_string()))
     }
 
-    fn create_emulator(rom_file: PathBuf, pc: Option<u16>) -> Result<NesConsole, NesConsoleError> {
-        let builder = NesConsoleBuilder::new();
+    pub(crate) fn create_emulator(rom_file: PathBuf, pc: Option<u16>, audio_buffer_size: usize, sample_rate: u32, controller_type: ControllerType, session: Option<&Session>) -> Result<NesConsole, NesConsoleError> {
+        let builder = NesFrontEnd::emulator_builder(rom_file, pc, audio_buffer_size, sample_rate, controller_type, session);
+
+        NesFrontEnd::power_on_emulator(builder)
+    }
+
+    /// Extra devices can be mapped over the bus before the console is powered on.
+    pub(crate) fn emulator_builder(rom_file: PathBuf, pc: Option<u16>, audio_buffer_size: usize, sample_rate: u32, controller_type: ControllerType, session: Option<&Session>) -> NesConsoleBuilder {
+        let builder = match session {
+            Some(session) => NesConsoleBuilder::new().with_session(session),
+            None => NesConsoleBuilder::new(),
+        };
 
         info!("emulator bootstrapping...");
 
         /***
-         * order of initialization is important:
-         * 1. APU covers a single range from 0x4000 to 0x4017, because of the default bus implementation that does not support multiple ranges.
-         * 2. PPU (OAM DMA) and CONTROLLER overwrite part of the APU range with their own memory spaces.
-         * /!\ Changing the order will result in PPU and CONTROLLER having no mapping to the bus.
+         * the builder maps the devices in the order they depend on each other (the PPU needs the cartridge),
+         * whatever the order they are given in. The APU leaves $4014 and $4016 to the OAM DMA and the controller.
          ***/
-        let mut console = builder
+        builder
             .with_cpu(CpuType::NES6502)
             .with_bus_type(BusType::NESBus)
             .with_bus_device_type(WRAM(StandardMemory))
             .with_bus_device_type(CARTRIDGE(NROM))
             .with_bus_device_type(APU(RP2A03))
             .with_bus_device_type(PPU(NES2C02))
-            .with_bus_device_type(CONTROLLER(StandardController))
+            .with_bus_device_type(CONTROLLER(controller_type))
             .with_loader_type(INESV2)
             .with_rom_file(rom_file)
             .with_entry_point(pc)
-            .build()?;
+            .with_sound_buffer_size(audio_buffer_size)
+            .with_sample_rate(sample_rate)
+    }
+
+    pub(crate) fn power_on_emulator(builder: NesConsoleBuilder) -> Result<NesConsole, NesConsoleError> {
+        let mut console = builder.build()?;
 
         console.power_on()?;
         info!("emulator ready");
@@ -90,7 +123,7 @@ impl NesFrontEnd {
         Ok(console)
     }
 
-    pub fn new(frame_tx: SyncSender<NesMessage>, command_rx: Receiver<NesMessage>, debug_tx: SyncSender<NesMessage>, error_tx: SyncSender<NesMessage>) -> Result<NesFrontEnd, NesConsoleError> {
+    pub fn new(frame_tx: SyncSender<NesMessage>, command_rx: Receiver<NesMessage>, debug_tx: SyncSender<NesMessage>, error_tx: SyncSender<NesMessage>, ppu_viewer_tx: SyncSender<NesMessage>, apu_viewer_tx: SyncSender<NesMessage>, audio_buffer_size: usize, sample_rate: u32, controller_type: ControllerType, pacing: FramePacing, session: Option<Session>) -> Result<NesFrontEnd, NesConsoleError> {
 
         let front = NesFrontEnd {
             nes: None,
@@ -98,36 +131,74 @@ impl NesFrontEnd {
             command_rx,
             debug_tx,
             error_tx,
-            state: NesFrontEndState::Halted
+            ppu_viewer_tx,
+            apu_viewer_tx,
+            state: NesFrontEndState::Halted,
+            audio_buffer_size,
+            sample_rate,
+            controller_type,
+            speed: EmulationSpeed::default(),
+            pacing,
+            session,
+            last_stats: (Instant::now(), PerfCounters::default()),
+            sample_history: SampleHistory::default(),
+            save_slots: None,
         };
 
         Ok(front)
     }
 
-    fn sleep_until_next_frame(next: Instant, frame: Duration) -> Instant {
-        let now = Instant::now();
-        let mut next = next;
+    #[cfg(test)]
+    pub(crate) fn nes(&self) -> Option<&NesConsole> {
+        self.nes.as_ref()
+    }
 
-        if next > now {
-            let mut to_sleep = next - now;
-            if to_sleep > SPIN_BEFORE {
-                to_sleep -= SPIN_BEFORE;
-                sleep(to_sleep);
-            }
+    fn frame_duration(&self) -> Duration {
+        let frames_per_second = self.nes
+            .as_ref()
+            .map(|nes| nes.region().frames_per_second())
+            .unwrap_or(FRAMES_PER_SECOND);
 
-            while Instant::now() < next {
-                spin_loop();
-            }
+        FramePacing::frame_budget(frames_per_second)
+    }
+
+    /***
+     * VSync pacing: a frame is run on each repaint of the UI (VSync message). The audio queue is the reference:
+     *   - on a display faster than the NES, the repaints are skipped while more than half of the audio buffer is queued,
+     *   - on a slower one, the frame is run without waiting once less than a frame of samples is queued.
+     * Without repaint for VSYNC_TIMEOUT_FRAMES frames (hidden window), the frame is run anyway.
+     ***/
+    fn wait_for_vsync(&mut self, sound_player: &SoundPlayer, frame_duration: Duration) -> Result<(), NesConsoleError> {
+        let frame_samples = (self.sample_rate as f64 * frame_duration.as_secs_f64()) as usize;
 
-            next + frame
-        } else {
-            while next <= now {
-                next += frame;
+        loop {
+            if sound_player.queued_samples() < frame_samples {
+                return Ok(());
             }
 
-            next
+            match self.command_rx.recv_timeout(frame_duration * VSYNC_TIMEOUT_FRAMES) {
+                Ok(NesMessage::VSync) => {
+                    if sound_player.queued_samples() <= self.audio_buffer_size / 2 {
+                        return Ok(());
+                    }
+                },
+
+                Ok(message) => {
+                    if let Break(next_state) = self.process_message(message)? {
+                        self.state = next_state;
+                        return Ok(());
+                    }
+                },
+
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(NesConsoleError::ChannelCommunication("NES UI is gone ...".to_string()));
+                }
+            }
         }
     }
+
     fn try_send_common(tx: &SyncSender<NesMessage>, label: &str, message: NesMessage) -> Result<(), NesConsoleError> {
         match tx.try_send(message) {
             Ok(()) => Ok(()),
@@ -149,23 +220,94 @@ impl NesFrontEnd {
         NesFrontEnd::try_send_common(&self.debug_tx, "debug", message)
     }
 
+    /// The banks of the cartridge go with the CPU snapshots, the debugger shows them as they are switched.
+    fn send_bank_state(&self) -> Result<(), NesConsoleError> {
+        match self.nes.as_ref().and_then(|nes| nes.bank_state()) {
+            Some(bank_state) => self.send_debug_message(NesMessage::BankState(bank_state)),
+            None => Ok(()),
+        }
+    }
+
     fn send_error_message(&self, error: NesConsoleError) -> Result<(), NesConsoleError> { 
         NesFrontEnd::try_send_common(&self.error_tx, "error", NesMessage::Error(error))
     }
 
+    fn send_ppu_viewer_message(&self, message: NesMessage) -> Result<(), NesConsoleError> {
+        NesFrontEnd::try_send_common(&self.ppu_viewer_tx, "ppu viewer", message)
+    }
+
+    fn send_apu_viewer_message(&self, message: NesMessage) -> Result<(), NesConsoleError> {
+        NesFrontEnd::try_send_common(&self.apu_viewer_tx, "apu viewer", message)
+    }
+
     fn process_frame(&self, frame: NesFrame) -> Result<(), NesConsoleError> {
         self.send_message(NesMessage::Frame(frame))
     }
 
-    fn process_samples(&self, samples: NesSamples, sound_player: &mut SoundPlayer) -> Result<(), NesConsoleError> {
+    fn process_samples(&mut self, samples: NesSamples, sound_player: &mut SoundPlayer) -> Result<(), NesConsoleError> {
         for sample in samples.samples() {
+            self.sample_history.push_sample(*sample);
             sound_player.push_sample(*sample)
         }
 
         Ok(())
     }
 
-    fn process_message(&mut self, message: NesMessage) -> Result<ControlFlow<NesFrontEndState, ()>, NesConsoleError> {
+    /// The work done by the console over the last STATS_INTERVAL, sent along with the frames,
+    /// with the fill level of the audio queue (0.0 - 1.0 of the audio buffer size).
+    fn send_stats(&mut self, sound_player: &SoundPlayer) -> Result<(), NesConsoleError> {
+        let (since, previous) = self.last_stats;
+
+        if since.elapsed() < STATS_INTERVAL {
+            return Ok(());
+        }
+
+        let counters = match &self.nes {
+            Some(nes) => nes.perf_counters(),
+            None => return Ok(()),
+        };
+
+        self.last_stats = (Instant::now(), counters);
+        self.send_message(NesMessage::Stats(counters.since(&previous)))?;
+
+        let fill = sound_player.queued_samples() as f32 / self.audio_buffer_size.max(1) as f32;
+        self.send_message(NesMessage::AudioBufferFill(fill.min(1.0)))
+    }
+
+    /// The toast telling how the save went, a failure does not stop the emulator.
+    fn save_state_to_slot(&self, slot: u8) -> String {
+        let result = match (&self.nes, &self.save_slots) {
+            (Some(nes), Some(save_slots)) => save_slots.save(slot, &nes.save_state()),
+            _ => Err(NesConsoleError::InternalError("no ROM loaded".to_string())),
+        };
+
+        match result {
+            Ok(()) => format!("state saved to slot {}", slot),
+            Err(e) => {
+                warn!("failed to save slot {}: {}", slot, e);
+                format!("unable to save slot {}: {}", slot, e)
+            }
+        }
+    }
+
+    /// The toast telling how the load went: an empty slot, or a state of another ROM, leaves the console as it is.
+    fn load_state_from_slot(&mut self, slot: u8) -> String {
+        let result = match (&mut self.nes, &self.save_slots) {
+            (Some(nes), Some(save_slots)) => save_slots.load(slot).and_then(|state| nes.load_state(&state)),
+            _ => Err(NesConsoleError::InternalError("no ROM loaded".to_string())),
+        };
+
+        match result {
+            Ok(()) => format!("state loaded from slot {}", slot),
+            Err(NesConsoleError::SaveStateError(e)) => e,
+            Err(e) => {
+                warn!("failed to load slot {}: {}", slot, e);
+                format!("unable to load slot {}: {}", slot, e)
+            }
+        }
+    }
+
+    pub(crate) fn process_message(&mut self, message: NesMessage) -> Result<ControlFlow<NesFrontEndState, ()>, NesConsoleError> {
 
         match (self.nes.as_mut(), message) {
             (Some(nes), NesMessage::Keys(key_events)) => {
@@ -175,7 +317,11 @@ impl NesFrontEnd {
 
             (Some(nes), NesMessage::Reset) => {
                 nes.reset()?;
-                Ok(Break(self.state.clone()))
+
+                match self.state {
+                    NesFrontEndState::CpuHalted(_) => Ok(Break(NesFrontEndState::Running)),
+                    _ => Ok(Break(self.state.clone())),
+                }
             },
 
             (Some(_), NesMessage::Pause) => {
@@ -190,6 +336,7 @@ impl NesFrontEnd {
 
             (Some(_), NesMessage::PowerOff) => {
                 self.nes = None;
+                self.save_slots = None;
                 Ok(Break(NesFrontEndState::Halted))
             },
 
@@ -197,13 +344,27 @@ impl NesFrontEnd {
                 Ok(Break(NesFrontEndState::Running))
             },
 
+            /***
+             * a ROM that fails to load (bad magic, truncated file, unsupported mapper...) is reported to the UI
+             * as an error dialog, the emulator thread goes on idle until another ROM is loaded.
+             * The session given at startup only goes with the first ROM loaded.
+             * A ROM loaded while another one runs replaces the whole console (a power cycle with another cartridge):
+             * the previous console is dropped first, its devices are unmapped from its bus and released.
+             ***/
             (_, NesMessage::LoadRom(rom_file)) => {
-                match NesFrontEnd::create_emulator(rom_file, None) {
+                let session = self.session.take();
+                self.nes = None;
+                self.save_slots = None;
+                self.sample_history.clear();
+
+                match NesFrontEnd::create_emulator(rom_file.clone(), None, self.audio_buffer_size, self.sample_rate, self.controller_type.clone(), session.as_ref()) {
                     Ok(nes) => {
                         self.nes = Some(nes);
+                        self.save_slots = Some(SaveSlots::new(rom_file));
                         Ok(Break(NesFrontEndState::Running))
                     }
                     Err(e) => {
+                        warn!("failed to load {}: {}", rom_file.display(), e);
                         self.nes = None;
                         self.send_error_message(e)?;
                         Ok(Break(NesFrontEndState::Idle))
@@ -211,6 +372,57 @@ impl NesFrontEnd {
                 }
             },
 
+            (Some(nes), NesMessage::PpuMemoryDumpRequest(palette_index)) => {
+                let dump = nes.dump_pattern_tables(palette_index)?;
+                self.send_ppu_viewer_message(NesMessage::PpuMemoryDump(dump))?;
+                Ok(Continue(()))
+            },
+
+            (Some(nes), NesMessage::SetLayerOverride(background, sprites)) => {
+                nes.set_layer_override(background, sprites);
+                Ok(Continue(()))
+            },
+
+            (Some(nes), NesMessage::ApuStateRequest) => {
+                let snapshot = nes.apu_snapshot();
+                let waveform = self.sample_history.downsampled(WAVEFORM_POINTS);
+                self.send_apu_viewer_message(NesMessage::ApuState(snapshot))?;
+                self.send_apu_viewer_message(NesMessage::Waveform(waveform))?;
+                Ok(Continue(()))
+            },
+
+            (Some(_), NesMessage::SaveState(slot)) => {
+                let toast = self.save_state_to_slot(slot);
+                self.send_message(NesMessage::Toast(toast))?;
+                Ok(Continue(()))
+            },
+
+            (Some(_), NesMessage::LoadState(slot)) => {
+                let toast = self.load_state_from_slot(slot);
+                self.send_message(NesMessage::Toast(toast))?;
+                Ok(Continue(()))
+            },
+
+            (_, NesMessage::SetSpeed(multiplier)) => {
+                self.speed = EmulationSpeed::new(multiplier);
+                info!("emulation speed: {}x", self.speed.multiplier());
+                Ok(Continue(()))
+            },
+
+            (_, NesMessage::VSync) => {
+                Ok(Continue(()))
+            },
+
+            (Some(nes), NesMessage::SignalInterrupt(kind)) => {
+                nes.signal_interrupt(kind)?;
+                Ok(Continue(()))
+            },
+
+            (Some(nes), NesMessage::Debug(DebugCommand::DumpTimings)) => {
+                info!("timing spans: {}", nes.timing_spans());
+                Ok(Continue(()))
+            },
+
             (Some(_), NesMessage::Debug(command)) => {
                 let state = NesFrontEndState::Debug(command);
                 Ok(Break(state))
@@ -238,21 +450,56 @@ impl NesFrontEnd {
     }
 
 
+    /// A JAM opcode halts the console without failing: the error is shown and the thread waits for a reset.
+    /// So does an unimplemented opcode, when the console is built to stop on them.
+    fn check_cpu_halted(&mut self) -> Result<(), NesConsoleError> {
+        let (pc, error) = match self.nes_mut()?.state() {
+            ConsoleState::Running => return Ok(()),
+            ConsoleState::Halted(pc) => (pc, CpuError::Halted(pc)),
+            ConsoleState::Unimplemented { pc, opcode } => (pc, CpuError::Unimplemented { opcode }),
+        };
+
+        warn!("CPU stopped at 0x{:04X} ({}), press reset to recover", pc, error);
+        self.send_error_message(NesConsoleError::CpuError(error))?;
+        self.state = NesFrontEndState::CpuHalted(pc);
+
+        Ok(())
+    }
+
     pub fn run(&mut self) -> Result<(), NesConsoleError> {
-        let frame_duration = Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND);
+        let mut frame_duration = self.frame_duration();
         let mut next_frame = Instant::now() + frame_duration;
-        let mut sound_player = SoundPlayer::new().map_err(|e| NesConsoleError::ControllerError(e.to_string()))?;
+        let mut sound_player = SoundPlayer::new(self.audio_buffer_size, self.sample_rate).map_err(|e| NesConsoleError::ControllerError(e.to_string()))?;
 
         loop {
             self.state = self.read_and_process_messages()?;
+            frame_duration = self.frame_duration();
 
             match self.state {
                 NesFrontEndState::Running => {
-                    let (frame, samples) = self.nes_mut()?.step_frame()?;
-                    self.process_frame(frame)?;
-                    self.process_samples(samples, &mut sound_player)?;
+                    let (frames, tick_duration) = self.speed.frame_budget(frame_duration);
+
+                    // only the last frame is displayed, and the samples of the first one played
+                    for index in 0..frames {
+                        let (frame, samples) = self.nes_mut()?.step_frame()?;
 
-                    next_frame = NesFrontEnd::sleep_until_next_frame(next_frame, frame_duration);
+                        if index + 1 == frames {
+                            self.process_frame(frame)?;
+                        }
+
+                        if self.speed.plays_samples(index) {
+                            self.process_samples(samples, &mut sound_player)?;
+                        }
+                    }
+
+                    self.check_cpu_halted()?;
+
+                    // muted in slow motion, the audio queue cannot pace the frames
+                    if self.pacing == FramePacing::VSync && !self.speed.is_slow_motion() {
+                        self.wait_for_vsync(&sound_player, tick_duration)?;
+                    } else {
+                        next_frame = self.pacing.sleep_until_next_frame(next_frame, tick_duration);
+                    }
                 },
 
                 NesFrontEndState::Debug(DebugCommand::StepInstruction) => {
@@ -260,7 +507,7 @@ impl NesFrontEnd {
 
                     if let Some(frame) = frame {
                         self.process_frame(frame)?;
-                        next_frame = NesFrontEnd::sleep_until_next_frame(next_frame, frame_duration);
+                        next_frame = self.pacing.sleep_until_next_frame(next_frame, frame_duration);
                     }
 
                     if let Some(samples) = samples {
@@ -268,7 +515,9 @@ impl NesFrontEnd {
                     }
 
                     self.send_debug_message(NesMessage::CpuSnapshot(snapshot))?;
+                    self.send_bank_state()?;
                     self.state = NesFrontEndState::Debug(DebugCommand::Paused);
+                    self.check_cpu_halted()?;
                 },
 
                 NesFrontEndState::Debug(DebugCommand::Paused) => {},
@@ -278,8 +527,10 @@ impl NesFrontEnd {
                     self.process_frame(frame)?;
                     self.process_samples(samples, &mut sound_player)?;
 
-                    next_frame = NesFrontEnd::sleep_until_next_frame(next_frame, frame_duration);
+                    next_frame = self.pacing.sleep_until_next_frame(next_frame, frame_duration);
                     self.send_debug_message(NesMessage::CpuSnapshotSet(snapshots))?;
+                    self.send_bank_state()?;
+                    self.check_cpu_halted()?;
                 },
 
                 NesFrontEndState::Debug(DebugCommand::Detach) => {
@@ -293,8 +544,11 @@ impl NesFrontEnd {
 
                 NesFrontEndState::Paused => {},
                 NesFrontEndState::Halted => {},
+                NesFrontEndState::CpuHalted(_) => {},
                 NesFrontEndState::Idle => {}
             }
+
+            self.send_stats(&sound_player)?;
         }
     }
 }
\ No newline at end of file
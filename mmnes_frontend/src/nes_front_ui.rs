@@ -1,26 +1,43 @@
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::mpsc::{Receiver, SyncSender};
 use eframe::{egui, App, Frame};
 use eframe::egui::{vec2, Align, Align2, Button, CentralPanel, Color32, ColorImage, Context, Event, Grid, Image, Key, Layout, Margin, RawInput, RichText, Stroke, TextureHandle, TopBottomPanel, Vec2};
 use egui_file_dialog::FileDialog;
 use log::warn;
-use mmnes_core::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_B, NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_SELECT, NES_CONTROLLER_KEY_START, NES_CONTROLLER_KEY_UP};
+use mmnes_core::key_event::{KeyEvent, KeyEvents, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_B, NES_CONTROLLER_KEY_DOWN, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_MICROPHONE, NES_CONTROLLER_KEY_RIGHT, NES_CONTROLLER_KEY_SELECT, NES_CONTROLLER_KEY_START, NES_CONTROLLER_KEY_UP, NES_CONTROLLER_PLAYER_KEYS};
+use mmnes_core::controller::ControllerType;
 use mmnes_core::nes_console::NesConsoleError;
 use crate::ai_widget::AiWidget;
 use crate::ai_worker::AiWorker;
 use crate::Args;
 use crate::debugger_widget::DebuggerWidget;
+use crate::frame_pacing::FramePacing;
+use crate::gamepad_input::GamepadInput;
 use crate::image_text_button::{ButtonKind, ImageTextButton};
+use crate::input_source::InputSource;
 use crate::nes_mediator::NesMediator;
 use crate::nes_message::NesMessage;
-use crate::nes_message::NesMessage::{Keys, LoadRom};
+use crate::nes_message::NesMessage::{Keys, LoadRom, VSync};
 use crate::nes_ui_widget::NesUiWidget;
+use crate::ppu_viewer_widget::PpuViewerWidget;
+use crate::apu_viewer_widget::ApuViewerWidget;
 use crate::renderer_widget::RendererWidget;
+use crate::recent_roms::{RecentRoms, RECENT_ROMS_MAX};
+use crate::save_slots::SaveSlots;
 
 const OPENAI_API_URL: &str = "https://api.openai.com/v1/responses";
 const OPENAI_MODEL: &str = "gpt-5-nano";
 
+/// Players 2 and 3 of the Four Score on the keyboard: the directions, then A, B, Start and Select.
+const FOUR_SCORE_KEY_SETS: [(usize, [(Key, usize); 8]); 2] = [
+    (2, [(Key::I, NES_CONTROLLER_KEY_UP), (Key::K, NES_CONTROLLER_KEY_DOWN), (Key::J, NES_CONTROLLER_KEY_LEFT), (Key::L, NES_CONTROLLER_KEY_RIGHT),
+         (Key::O, NES_CONTROLLER_KEY_A), (Key::U, NES_CONTROLLER_KEY_B), (Key::P, NES_CONTROLLER_KEY_START), (Key::Y, NES_CONTROLLER_KEY_SELECT)]),
+    (3, [(Key::T, NES_CONTROLLER_KEY_UP), (Key::G, NES_CONTROLLER_KEY_DOWN), (Key::F, NES_CONTROLLER_KEY_LEFT), (Key::H, NES_CONTROLLER_KEY_RIGHT),
+         (Key::V, NES_CONTROLLER_KEY_A), (Key::C, NES_CONTROLLER_KEY_B), (Key::B, NES_CONTROLLER_KEY_START), (Key::X, NES_CONTROLLER_KEY_SELECT)]),
+];
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct NesButtonId(pub u16);
 
@@ -76,12 +93,16 @@ pub struct NesFrontUI {
     widgets: Vec<Box<dyn NesUiWidget>>,
     nes_mediator: Rc<RefCell<NesMediator>>,
     menu_buttons: Vec<NesButton>,
+    vsync: bool,
+    gamepad: Option<GamepadInput>,
+    four_score: bool,
+    recent_roms: RecentRoms,
 }
 
 impl NesFrontUI {
 
     pub fn new(args: Args, cc: &eframe::CreationContext<'_>,
-               command_tx: SyncSender<NesMessage>, frame_rx: Receiver<NesMessage>, debug_rx: Receiver<NesMessage>, error_rx: Receiver<NesMessage>,
+               command_tx: SyncSender<NesMessage>, frame_rx: Receiver<NesMessage>, debug_rx: Receiver<NesMessage>, error_rx: Receiver<NesMessage>, ppu_viewer_rx: Receiver<NesMessage>, apu_viewer_rx: Receiver<NesMessage>,
                width: usize, height: usize) -> Result<NesFrontUI, NesConsoleError> {
 
         let button = NesButton::new(cc, NesButtonId(0), "OPEN ROM", "Load a ROM file", include_bytes!("assets/load_rom.png"))?;
@@ -99,22 +120,42 @@ impl NesFrontUI {
         let api_key = std::env::var("OPENAI_API_KEY")
             .map_err(|e| NesConsoleError::InternalError(format!("OpenAI API key (OPENAI_API_KEY) not set: {}", e)))?;
 
-        let nes_mediator = Rc::new(RefCell::new(NesMediator::new(frame_rx, command_tx, debug_rx, error_rx)));
+        let nes_mediator = Rc::new(RefCell::new(NesMediator::new(frame_rx, command_tx, debug_rx, error_rx, ppu_viewer_rx, apu_viewer_rx)));
 
         let ai_worker = AiWorker::spawn(api_key, OPENAI_API_URL, OPENAI_MODEL)
             .map_err(|e| NesConsoleError::InternalError(format!("unable to spawn AI worker: {}", e)))?;
 
         let mut widgets = Vec::<Box<dyn NesUiWidget>>::new();
 
-        let renderer_ui =  RendererWidget::new(height, width, cc, nes_mediator.clone())?;
+        let renderer_ui =  RendererWidget::new(height, width, args.ntsc_filter, cc, nes_mediator.clone())?;
         let debugger_ui = DebuggerWidget::new(cc, nes_mediator.clone())?;
         let ai_ui = AiWidget::new(cc, nes_mediator.clone(), ai_worker)?;
+        let ppu_viewer_ui = PpuViewerWidget::new(cc, nes_mediator.clone())?;
+        let apu_viewer_ui = ApuViewerWidget::new(cc, nes_mediator.clone())?;
 
         widgets.push(Box::new(renderer_ui));
         widgets.push(Box::new(debugger_ui));
         widgets.push(Box::new(ai_ui));
+        widgets.push(Box::new(ppu_viewer_ui));
+        widgets.push(Box::new(apu_viewer_ui));
+
+        let gamepad = match GamepadInput::new() {
+            Ok(gamepad) => Some(gamepad),
+            Err(e) => {
+                warn!("{}, only the keyboard is available", e);
+                None
+            }
+        };
+
+        let recent_roms = match RecentRoms::default_file() {
+            Some(file) => RecentRoms::load(file, RECENT_ROMS_MAX).unwrap_or_else(|e| {
+                warn!("unable to load the recent roms: {}", e);
+                RecentRoms::new(RecentRoms::default_file(), RECENT_ROMS_MAX)
+            }),
+            None => RecentRoms::new(None, RECENT_ROMS_MAX),
+        };
 
-        let nes_front_ui = NesFrontUI {
+        let mut nes_front_ui = NesFrontUI {
             emulator_viewport_frame: frame,
             input: KeyEvents::new(),
             rom_file_dialog: FileDialog::new(),
@@ -122,22 +163,37 @@ impl NesFrontUI {
             nes_mediator,
             widgets,
             menu_buttons,
+            vsync: args.pacing == FramePacing::VSync,
+            gamepad,
+            four_score: args.controller_type() == ControllerType::FourScore,
+            recent_roms,
         };
 
-        if let Some(rom_file) = &args.rom_file {
-            let mut nes_mediator = nes_front_ui.nes_mediator.borrow_mut();
-
-            nes_mediator.send_message(LoadRom(rom_file.clone()))?;
-            nes_mediator.set_rom_file(args.rom_file);
+        if let Some(rom_file) = args.rom_file {
+            nes_front_ui.open_rom(rom_file)?;
         }
 
         Ok(nes_front_ui)
     }
 
+    /// The key of a Four Score player 2 or 3, offset as the core expects it.
+    fn four_score_key(key: Key) -> Option<usize> {
+        FOUR_SCORE_KEY_SETS.iter()
+            .find_map(|(player, key_set)| key_set.iter()
+                .find(|(set_key, _)| *set_key == key)
+                .map(|(_, nes_key)| (player - 1) * NES_CONTROLLER_PLAYER_KEYS + nes_key))
+    }
+
     fn is_halted(&self) -> bool {
         self.nes_mediator.borrow().rom_file().is_none()
     }
 
+    fn poll_gamepad(&mut self) {
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            gamepad.poll(&mut self.input);
+        }
+    }
+
     fn send_input_to_emulator(&mut self) -> Result<(), NesConsoleError> {
         if self.input.is_empty() {
             return Ok(());
@@ -147,6 +203,15 @@ impl NesFrontUI {
         self.nes_mediator.borrow_mut().send_message(Keys(inputs))
     }
 
+    /// With the VSync pacing, each repaint lets the emulator run a frame.
+    fn send_vsync_to_emulator(&mut self) -> Result<(), NesConsoleError> {
+        if !self.vsync {
+            return Ok(());
+        }
+
+        self.nes_mediator.borrow_mut().send_message(VSync)
+    }
+
     pub fn read_error_messages(&mut self) -> Result<(), NesConsoleError> {
         let messages = self.nes_mediator.borrow().read_error_messages()?;
 
@@ -166,11 +231,24 @@ impl NesFrontUI {
 
     fn load_rom_file(&mut self) -> Result<(), NesConsoleError> {
         if let Some(path) = self.rom_file_dialog.take_picked() {
-            let rom_file = Some(path.clone());
+            self.open_rom(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// The ROM is loaded by the emulator thread and becomes the most recent one.
+    fn open_rom(&mut self, path: PathBuf) -> Result<(), NesConsoleError> {
+        {
             let mut nes_mediator = self.nes_mediator.borrow_mut();
 
-            nes_mediator.set_rom_file(rom_file);
-            nes_mediator.send_message(LoadRom(path))?;
+            nes_mediator.set_rom_file(Some(path.clone()));
+            nes_mediator.send_message(LoadRom(path.clone()))?;
+        }
+
+        self.recent_roms.add(&path);
+        if let Err(e) = self.recent_roms.save() {
+            warn!("unable to save the recent roms: {}", e);
         }
 
         Ok(())
@@ -277,7 +355,9 @@ impl NesFrontUI {
 impl App for NesFrontUI {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
         let _ = self.read_error_messages();
+        self.poll_gamepad();
         let _ = self.send_input_to_emulator();
+        let _ = self.send_vsync_to_emulator();
 
         NesFrontUI::install_theme(ctx);
         ctx.request_repaint();
@@ -294,6 +374,23 @@ impl App for NesFrontUI {
                 let _ = self.load_rom_file();
                 self.rom_file_dialog.update(ctx);
 
+                let mut recent_rom: Option<PathBuf> = None;
+                ui.add_enabled_ui(!self.recent_roms.paths().is_empty(), |ui| {
+                    ui.menu_button("RECENT", |ui| {
+                        for path in self.recent_roms.paths() {
+                            let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+
+                            if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                                recent_rom = Some(path.clone());
+                            }
+                        }
+                    });
+                });
+
+                if let Some(path) = recent_rom {
+                    let _ = self.open_rom(path);
+                }
+
                 for widget in &mut self.widgets {
                     let mut clicked: Option<NesButtonId> = None;
                     let buttons = widget.menu_buttons();
@@ -348,7 +445,19 @@ impl App for NesFrontUI {
     fn raw_input_hook(&mut self, ctx: &Context, raw_input: &mut RawInput) {
         if ctx.wants_keyboard_input() { return; }
 
+        let four_score = self.four_score;
+
         raw_input.events.retain(|event| {
+            if let Event::Key { key, pressed: true, repeat: false, modifiers, .. } = event
+                && let Some(slot) = SaveSlots::slot_for_key(*key) {
+                let message = if modifiers.shift { NesMessage::LoadState(slot) } else { NesMessage::SaveState(slot) };
+
+                if let Err(e) = self.nes_mediator.borrow_mut().send_message(message) {
+                    warn!("failed to send save state message: {}", e);
+                }
+                return false;
+            }
+
             if let Event::Key { key, pressed, .. } = event {
                 let handled = match key {
                     Key::Z => { self.input.push_back( KeyEvent { key: NES_CONTROLLER_KEY_A, pressed: *pressed }); true }
@@ -359,6 +468,11 @@ impl App for NesFrontUI {
                     Key::ArrowDown => { self.input.push_back(KeyEvent { key: NES_CONTROLLER_KEY_DOWN, pressed: *pressed }); true }
                     Key::ArrowLeft => { self.input.push_back(KeyEvent { key: NES_CONTROLLER_KEY_LEFT, pressed: *pressed }); true }
                     Key::ArrowRight => { self.input.push_back(KeyEvent { key: NES_CONTROLLER_KEY_RIGHT, pressed: *pressed }); true }
+                    Key::M => { self.input.push_back(KeyEvent { key: NES_CONTROLLER_KEY_MICROPHONE, pressed: *pressed }); true }
+                    _ if four_score => match NesFrontUI::four_score_key(*key) {
+                        Some(nes_key) => { self.input.push_back(KeyEvent { key: nes_key, pressed: *pressed }); true }
+                        None => false,
+                    },
                     _ => false,
                 };
                 return !handled;
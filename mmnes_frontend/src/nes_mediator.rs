@@ -16,18 +16,22 @@ pub struct NesMediator {
     command_tx: SyncSender<NesMessage>,
     debug_rx: Receiver<NesMessage>,
     error_rx: Receiver<NesMessage>,
+    ppu_viewer_rx: Receiver<NesMessage>,
+    apu_viewer_rx: Receiver<NesMessage>,
     rom_file: Option<PathBuf>,
     request: Option<NesMediatorRequest>,
 }
 
 impl NesMediator {
 
-    pub fn new(frame_rx: Receiver<NesMessage>, command_tx: SyncSender<NesMessage>, debug_rx: Receiver<NesMessage>, error_rx: Receiver<NesMessage>) -> NesMediator {
+    pub fn new(frame_rx: Receiver<NesMessage>, command_tx: SyncSender<NesMessage>, debug_rx: Receiver<NesMessage>, error_rx: Receiver<NesMessage>, ppu_viewer_rx: Receiver<NesMessage>, apu_viewer_rx: Receiver<NesMessage>) -> NesMediator {
         NesMediator {
             frame_rx,
             command_tx,
             debug_rx,
             error_rx,
+            ppu_viewer_rx,
+            apu_viewer_rx,
             rom_file: None,
             request: None,
         }
@@ -72,7 +76,10 @@ impl NesMediator {
             match self.frame_rx.try_recv() {
                 Ok(message) => match message {
                     NesMessage::Error(_) |
-                    NesMessage::Frame(_) => {
+                    NesMessage::Frame(_) |
+                    NesMessage::Stats(_) |
+                    NesMessage::AudioBufferFill(_) |
+                    NesMessage::Toast(_) => {
                         messages.push(message);
                     },
 
@@ -97,7 +104,8 @@ impl NesMediator {
             match self.debug_rx.try_recv() {
                 Ok(message) => match message {
                     NesMessage::CpuSnapshot(_) |
-                    NesMessage::CpuSnapshotSet(_) => {
+                    NesMessage::CpuSnapshotSet(_) |
+                    NesMessage::BankState(_) => {
                         messages.push(message);
                     },
 
@@ -136,6 +144,48 @@ impl NesMediator {
         Ok(messages)
     }
 
+    pub fn read_ppu_viewer_messages(&self) -> Result<Vec<NesMessage>, NesConsoleError> {
+        let mut messages = Vec::new();
+
+        loop {
+            match self.ppu_viewer_rx.try_recv() {
+                Ok(message) => match message {
+                    NesMessage::PpuMemoryDump(_) => messages.push(message),
+                    other => warn!("unexpected ppu viewer message: {:?}", other),
+                },
+
+                Err(TryRecvError::Empty) => break,
+
+                Err(TryRecvError::Disconnected) => {
+                    return Err(NesConsoleError::ChannelCommunication("NES backend is gone ...".to_string()));
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+
+    pub fn read_apu_viewer_messages(&self) -> Result<Vec<NesMessage>, NesConsoleError> {
+        let mut messages = Vec::new();
+
+        loop {
+            match self.apu_viewer_rx.try_recv() {
+                Ok(message) => match message {
+                    NesMessage::ApuState(_) | NesMessage::Waveform(_) => messages.push(message),
+                    other => warn!("unexpected apu viewer message: {:?}", other),
+                },
+
+                Err(TryRecvError::Empty) => break,
+
+                Err(TryRecvError::Disconnected) => {
+                    return Err(NesConsoleError::ChannelCommunication("NES backend is gone ...".to_string()));
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+
     pub fn send_message(&mut self, message: NesMessage) -> Result<(), NesConsoleError> {
         match self.command_tx.try_send(message) {
             Ok(()) => Ok(()),
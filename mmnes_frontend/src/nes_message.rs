@@ -2,7 +2,12 @@ use std::path::PathBuf;
 use mmnes_core::key_event::KeyEvents;
 use mmnes_core::nes_console::NesConsoleError;
 use mmnes_core::nes_frame::NesFrame;
+use mmnes_core::cartridge::BankState;
+use mmnes_core::cpu::InterruptKind;
 use mmnes_core::cpu_debugger::{CpuSnapshot, DebugCommand};
+use mmnes_core::ppu_memory_dump::PpuMemoryDump;
+use mmnes_core::apu_snapshot::ApuSnapshot;
+use mmnes_core::perf_counters::PerfCounters;
 
 #[derive(Debug)]
 pub enum NesMessage {
@@ -13,8 +18,23 @@ pub enum NesMessage {
     Pause,
     Reset,
     PowerOff,
+    SetSpeed(f64),
+    VSync,
     Debug(DebugCommand),
     Error(NesConsoleError),
     CpuSnapshot(Box<dyn CpuSnapshot>),
-    CpuSnapshotSet(Vec<Box<dyn CpuSnapshot>>)
+    CpuSnapshotSet(Vec<Box<dyn CpuSnapshot>>),
+    PpuMemoryDumpRequest(u8),
+    PpuMemoryDump(PpuMemoryDump),
+    SetLayerOverride(Option<bool>, Option<bool>),
+    ApuStateRequest,
+    ApuState(ApuSnapshot),
+    Waveform(Vec<f32>),
+    Stats(PerfCounters),
+    AudioBufferFill(f32),
+    SaveState(u8),
+    LoadState(u8),
+    Toast(String),
+    SignalInterrupt(InterruptKind),
+    BankState(BankState)
 }
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use eframe::egui;
+use eframe::egui::{pos2, vec2, Color32, ColorImage, Context, Image, RichText, Sense, TextureHandle, TextureOptions, Ui};
+use log::warn;
+use mmnes_core::nes_console::NesConsoleError;
+use mmnes_core::ppu_memory_dump::{PatternTile, PpuMemoryDump, PATTERN_TABLES_COUNT, PATTERN_TILES_PER_TABLE, PATTERN_TILE_HEIGHT, PATTERN_TILE_WIDTH};
+use crate::helpers_ui::HelpersUI;
+use crate::nes_front_ui::{NesButton, NesButtonId};
+use crate::nes_mediator::NesMediator;
+use crate::nes_message::NesMessage;
+use crate::nes_ui_widget::NesUiWidget;
+
+const WINDOW_NAME: &str = "NES PPU Viewer";
+const TILES_PER_ROW: usize = 16;
+const PATTERN_TABLE_SIZE: usize = TILES_PER_ROW * PATTERN_TILE_WIDTH;
+const PATTERN_TABLE_SCALE: f32 = 2.0;
+const PALETTES_COUNT: u8 = 8;
+const PALETTE_SWATCH_SIZE: f32 = 14.0;
+const DUMP_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct PpuViewerWidget {
+    visible: bool,
+    error: Option<NesConsoleError>,
+    nes_mediator: Rc<RefCell<NesMediator>>,
+    palette_index: u8,
+    show_background: bool,
+    show_sprites: bool,
+    is_dump_requested: bool,
+    last_request: Instant,
+    palette: Vec<Color32>,
+    textures: Vec<TextureHandle>,
+    texture_options: TextureOptions,
+    buttons: Vec<NesButton>,
+}
+
+impl NesUiWidget for PpuViewerWidget {
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_error(&mut self, error: Option<NesConsoleError>) {
+        self.error = error;
+    }
+
+    fn menu_buttons(&self) -> &[NesButton] {
+        &self.buttons
+    }
+
+    fn on_button(&mut self, id: NesButtonId) -> Result<(), NesConsoleError> {
+        match id {
+            NesButtonId(0) => self.switch_visible(),
+            _ => return Err(NesConsoleError::InternalError("unknown button".to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn footer(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn draw(&mut self, ctx: &Context) -> Result<(), NesConsoleError> {
+        if self.visible {
+            self.ppu_viewer_window(ctx)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PpuViewerWidget {
+
+    pub fn new(cc: &eframe::CreationContext<'_>, nes_mediator: Rc<RefCell<NesMediator>>) -> Result<PpuViewerWidget, NesConsoleError> {
+        let button = NesButton::new(cc, NesButtonId(0), "PPU VIEWER", "Show palettes and pattern tables", include_bytes!("assets/ppu_viewer.png"))?;
+        let buttons = vec![button];
+
+        let texture_options = TextureOptions {
+            minification: egui::TextureFilter::Nearest,
+            wrap_mode: Default::default(),
+            magnification: egui::TextureFilter::Nearest,
+            mipmap_mode: None,
+        };
+
+        let textures = (0..PATTERN_TABLES_COUNT).map(|table| {
+            cc.egui_ctx.load_texture(
+                format!("nes-pattern-table-{}", table),
+                ColorImage::new([PATTERN_TABLE_SIZE, PATTERN_TABLE_SIZE], HelpersUI::create_default_texture(PATTERN_TABLE_SIZE, PATTERN_TABLE_SIZE, Color32::DARK_GRAY)),
+                texture_options
+            )
+        }).collect();
+
+        let widget = PpuViewerWidget {
+            visible: false,
+            error: None,
+            nes_mediator,
+            palette_index: 0,
+            show_background: true,
+            show_sprites: true,
+            is_dump_requested: false,
+            last_request: Instant::now(),
+            palette: Vec::new(),
+            textures,
+            texture_options,
+            buttons,
+        };
+
+        Ok(widget)
+    }
+
+    fn switch_visible(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /***
+     * assemble the 256 tiles of a pattern table in a 16x16 tiles grid (128x128 pixels)
+     ***/
+    fn pattern_table_image(tiles: &[PatternTile]) -> ColorImage {
+        let mut pixels = vec![0u8; PATTERN_TABLE_SIZE * PATTERN_TABLE_SIZE * 4];
+
+        for (index, tile) in tiles.iter().enumerate() {
+            let tile_x = (index % TILES_PER_ROW) * PATTERN_TILE_WIDTH;
+            let tile_y = (index / TILES_PER_ROW) * PATTERN_TILE_HEIGHT;
+
+            for line in 0..PATTERN_TILE_HEIGHT {
+                let src = line * PATTERN_TILE_WIDTH * 4;
+                let dst = ((tile_y + line) * PATTERN_TABLE_SIZE + tile_x) * 4;
+
+                pixels[dst..dst + PATTERN_TILE_WIDTH * 4].copy_from_slice(&tile.pixels()[src..src + PATTERN_TILE_WIDTH * 4]);
+            }
+        }
+
+        ColorImage::from_rgba_unmultiplied([PATTERN_TABLE_SIZE, PATTERN_TABLE_SIZE], &pixels)
+    }
+
+    fn apply_dump(&mut self, dump: PpuMemoryDump) {
+        self.palette = dump.palette_rgb()
+            .iter()
+            .map(|(r, g, b)| Color32::from_rgb(*r, *g, *b))
+            .collect();
+
+        for (table, texture) in self.textures.iter_mut().enumerate() {
+            let image = PpuViewerWidget::pattern_table_image(dump.pattern_table(table));
+            texture.set(image, self.texture_options);
+        }
+    }
+
+    fn read_ppu_viewer_messages(&mut self) -> Result<(), NesConsoleError> {
+        let messages = self.nes_mediator.borrow().read_ppu_viewer_messages()?;
+
+        for message in messages {
+            match message {
+                NesMessage::PpuMemoryDump(dump) => {
+                    self.is_dump_requested = false;
+
+                    // a dump colorized with a previous palette is dropped, a new one will be requested
+                    if dump.palette_index() == self.palette_index {
+                        self.apply_dump(dump);
+                    }
+                },
+                _ => warn!("unexpected message: {:?}", message),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Requests are dropped when the channels are full, a pending request is retried after a timeout.
+    fn request_dump(&mut self) -> Result<(), NesConsoleError> {
+        let is_expired = self.last_request.elapsed() > DUMP_REQUEST_TIMEOUT;
+
+        if (self.is_dump_requested == false || is_expired) && self.error.is_none() {
+            self.nes_mediator.borrow_mut().send_message(NesMessage::PpuMemoryDumpRequest(self.palette_index))?;
+            self.is_dump_requested = true;
+            self.last_request = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    /// The hidden layers are overridden, the visible ones are left to the game.
+    fn layer_toggles(&mut self, ui: &mut Ui) -> Result<(), NesConsoleError> {
+        let background_changed = ui.checkbox(&mut self.show_background, "BG").changed();
+        let sprites_changed = ui.checkbox(&mut self.show_sprites, "SPR").changed();
+
+        if background_changed || sprites_changed {
+            let background = (!self.show_background).then_some(false);
+            let sprites = (!self.show_sprites).then_some(false);
+            self.nes_mediator.borrow_mut().send_message(NesMessage::SetLayerOverride(background, sprites))?;
+        }
+
+        Ok(())
+    }
+
+    fn palette_swatches(&mut self, ui: &mut Ui) {
+        for (row, label) in ["BG", "SPR"].iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(HelpersUI::monospace(&format!("{:<3}", label)));
+
+                for palette in 0..(PALETTES_COUNT / 2) {
+                    let palette_index = row as u8 * (PALETTES_COUNT / 2) + palette;
+
+                    for color in 0..4 {
+                        let fill = self.palette
+                            .get(palette_index as usize * 4 + color)
+                            .copied()
+                            .unwrap_or(Color32::DARK_GRAY);
+
+                        let (rect, response) = ui.allocate_exact_size(vec2(PALETTE_SWATCH_SIZE, PALETTE_SWATCH_SIZE), Sense::click());
+                        ui.painter().rect_filled(rect, 0.0, fill);
+
+                        if response.on_hover_text(format!("palette {}", palette_index)).clicked() {
+                            self.palette_index = palette_index;
+                        }
+                    }
+
+                    ui.add_space(6.0);
+                }
+            });
+        }
+    }
+
+    fn ppu_viewer_window_inner(&mut self, ui: &mut Ui) -> Result<(), NesConsoleError> {
+        self.read_ppu_viewer_messages()?;
+        self.request_dump()?;
+
+        let result = ui.horizontal(|ui| {
+            ui.label(RichText::new("  PPU Viewer").strong());
+            ui.separator();
+            ui.label(HelpersUI::monospace(&format!("PALETTE: {}", self.palette_index)));
+            ui.separator();
+            self.layer_toggles(ui)
+        });
+
+        result.inner?;
+
+        ui.separator();
+        self.palette_swatches(ui);
+        ui.separator();
+
+        let size = vec2(PATTERN_TABLE_SIZE as f32, PATTERN_TABLE_SIZE as f32) * PATTERN_TABLE_SCALE;
+
+        ui.horizontal(|ui| {
+            for (table, texture) in self.textures.iter().enumerate() {
+                ui.vertical(|ui| {
+                    ui.label(HelpersUI::monospace(&format!("${:04X}", table * PATTERN_TILES_PER_TABLE * 16)));
+                    ui.add(Image::new((texture.id(), size)));
+                });
+            }
+        });
+
+        Ok(())
+    }
+
+    fn ppu_viewer_window(&mut self, ctx: &Context) -> Result<(), NesConsoleError> {
+        let response = egui::Window::new(WINDOW_NAME)
+            .title_bar(false)
+            .default_pos(pos2(760.0, 60.0))
+            .resizable(false)
+            .show(ctx, |ui| {
+                let result = self.ppu_viewer_window_inner(ui);
+
+                if let Err(error) = &result {
+                    self.set_error(Some(error.clone()));
+                }
+
+                if let Some(error) = &self.error {
+                    ui.separator();
+                    ui.label(HelpersUI::error(&error.to_string()));
+                }
+
+                result
+            });
+
+        match response.and_then(|response| response.inner) {
+            Some(Err(error)) => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
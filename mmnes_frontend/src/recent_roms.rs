@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use mmnes_core::nes_console::NesConsoleError;
+
+pub const RECENT_ROMS_MAX: usize = 10;
+const RECENT_ROMS_FILE: &str = ".mmnes_recent_roms";
+
+/***
+ * The ROMs opened last, the most recent first, for the RECENT menu. The list is kept in a file of the
+ * home directory, one path per line: the ROMs no longer on the disk are pruned when it is loaded,
+ * and a ROM opened again moves to the top instead of being listed twice.
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentRoms {
+    file: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+    max: usize,
+}
+
+impl RecentRoms {
+    pub fn new(file: Option<PathBuf>, max: usize) -> Self {
+        RecentRoms {
+            file,
+            paths: Vec::new(),
+            max,
+        }
+    }
+
+    /// ~/.mmnes_recent_roms, none without a home directory: the list is then not persisted.
+    pub fn default_file() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(RECENT_ROMS_FILE))
+    }
+
+    /// The list saved in ```file```, empty if there is none yet.
+    pub fn load(file: PathBuf, max: usize) -> Result<Self, NesConsoleError> {
+        let mut recent_roms = RecentRoms::new(Some(file.clone()), max);
+
+        match fs::read_to_string(&file) {
+            Ok(content) => {
+                recent_roms.paths = content.lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(PathBuf::from)
+                    .collect();
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(NesConsoleError::IOError(format!("{}: {}", file.display(), e))),
+        }
+
+        recent_roms.prune();
+        recent_roms.paths.truncate(max);
+
+        Ok(recent_roms)
+    }
+
+    /// ```path``` becomes the most recent ROM, the oldest one is dropped past the maximum.
+    pub fn add(&mut self, path: &Path) {
+        self.paths.retain(|recent| recent != path);
+        self.paths.insert(0, path.to_path_buf());
+        self.paths.truncate(self.max);
+    }
+
+    /// Drops the ROMs no longer on the disk.
+    pub fn prune(&mut self) {
+        self.paths.retain(|path| path.is_file());
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn save(&self) -> Result<(), NesConsoleError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let content = self.paths.iter()
+            .map(|path| format!("{}\n", path.display()))
+            .collect::<String>();
+
+        fs::write(file, content)
+            .map_err(|e| NesConsoleError::IOError(format!("{}: {}", file.display(), e)))
+    }
+}
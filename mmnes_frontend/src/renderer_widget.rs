@@ -1,29 +1,46 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use eframe::egui;
-use eframe::egui::{pos2, vec2, Color32, ColorImage, Context, Image, TextureHandle, TextureOptions, Ui};
+use eframe::egui::{pos2, vec2, Align2, Color32, ColorImage, Context, FontId, Key, Rect, Sense, TextureHandle, TextureOptions, Ui};
 use log::warn;
 use mmnes_core::nes_console::NesConsoleError;
+use mmnes_core::ntsc_filter::NtscFilter;
+use mmnes_core::perf_counters::PerfCounters;
 use mmnes_core::util::measure_exec_time;
+use crate::emulation_speed::EmulationSpeed;
+use crate::fps_counter::FpsCounter;
+use crate::frame_scaling::FrameScaling;
 use crate::helpers_ui::HelpersUI;
 use crate::nes_front_ui::{NesButton, NesButtonId};
 use crate::nes_mediator::NesMediator;
 use crate::nes_message::NesMessage;
-use crate::nes_message::NesMessage::{Pause, Play, PowerOff, Reset};
+use crate::nes_message::NesMessage::{Pause, Play, PowerOff, Reset, SetSpeed};
 use crate::nes_ui_widget::NesUiWidget;
 use crate::text_8x8_generator::Test8x8Generator;
 
 const WINDOW_NAME: &str = "NES Emulator";
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+const FPS_OVERLAY_KEY: Key = Key::F9;
 const RENDERER_PLAY_BUTTON: NesButtonId = NesButtonId(0);
 const RENDERER_PAUSE_BUTTON: NesButtonId = NesButtonId(1);
 const RENDERER_RESET_BUTTON: NesButtonId = NesButtonId(2);
 const RENDERER_POWER_OFF_BUTTON: NesButtonId = NesButtonId(3);
-const RENDERER_BUTTONS: [(NesButtonId, &str, &str, &[u8]); 4] = [
+const RENDERER_FAST_FORWARD_BUTTON: NesButtonId = NesButtonId(4);
+const RENDERER_NTSC_FILTER_BUTTON: NesButtonId = NesButtonId(5);
+const RENDERER_SLOW_MOTION_BUTTON: NesButtonId = NesButtonId(6);
+const RENDERER_SCALE_BUTTON: NesButtonId = NesButtonId(7);
+const RENDERER_ASPECT_BUTTON: NesButtonId = NesButtonId(8);
+const RENDERER_BUTTONS: [(NesButtonId, &str, &str, &[u8]); 9] = [
     (RENDERER_PLAY_BUTTON, "PLAY", "Run emulator", include_bytes!("assets/play.png")),
     (RENDERER_PAUSE_BUTTON, "PAUSE", "Pause/Run emulator", include_bytes!("assets/pause.png")),
     (RENDERER_RESET_BUTTON, "RESET", "Reset emulator", include_bytes!("assets/reset.png")),
     (RENDERER_POWER_OFF_BUTTON, "POWER OFF", "Power off emulator", include_bytes!("assets/poweroff.png")),
+    (RENDERER_FAST_FORWARD_BUTTON, "FAST FWD", "Cycle emulation speed (1x, 2x, 4x)", include_bytes!("assets/fast_forward.png")),
+    (RENDERER_SLOW_MOTION_BUTTON, "SLOW MO", "Cycle slow motion speed (1x, 0.5x, 0.25x), muted below 1x", include_bytes!("assets/slow_motion.png")),
+    (RENDERER_NTSC_FILTER_BUTTON, "NTSC", "Enable/Disable the NTSC composite video filter", include_bytes!("assets/ntsc_filter.png")),
+    (RENDERER_SCALE_BUTTON, "SCALE", "Cycle frame scaling (fit, 1x, 2x, 3x)", include_bytes!("assets/scale.png")),
+    (RENDERER_ASPECT_BUTTON, "8:7", "Enable/Disable the 8:7 pixel aspect ratio correction", include_bytes!("assets/aspect.png")),
 ];
 
 
@@ -35,15 +52,21 @@ pub struct RendererWidget {
     width: usize,
     texture: TextureHandle,
     texture_options: TextureOptions,
-    last_tick: Instant,
     last_frame_counter: u32,
     frame_counter: u32,
     rendering_duration_ms: f64,
-    ui_fps: f32,
-    emulator_fps: f32,
+    ui_fps: FpsCounter,
+    emulator_fps: FpsCounter,
+    fps_overlay: bool,
+    audio_buffer_fill: Option<f32>,
     nes_frame: Option<ColorImage>,
     nes_mediator: Rc<RefCell<NesMediator>>,
     menu_buttons: Vec<NesButton>,
+    speed: EmulationSpeed,
+    ntsc_filter: bool,
+    scaling: FrameScaling,
+    stats: Option<PerfCounters>,
+    toast: Option<(String, Instant)>,
 }
 
 impl NesUiWidget for RendererWidget {
@@ -68,6 +91,26 @@ impl NesUiWidget for RendererWidget {
             RENDERER_PLAY_BUTTON => self.nes_mediator.borrow_mut().send_message(Play),
             RENDERER_PAUSE_BUTTON => self.nes_mediator.borrow_mut().send_message(Pause),
             RENDERER_RESET_BUTTON => self.nes_mediator.borrow_mut().send_message(Reset),
+            RENDERER_FAST_FORWARD_BUTTON => {
+                self.speed = self.speed.next_fast_forward();
+                self.nes_mediator.borrow_mut().send_message(SetSpeed(self.speed.multiplier()))
+            },
+            RENDERER_SLOW_MOTION_BUTTON => {
+                self.speed = self.speed.next_slow_motion();
+                self.nes_mediator.borrow_mut().send_message(SetSpeed(self.speed.multiplier()))
+            },
+            RENDERER_NTSC_FILTER_BUTTON => {
+                self.ntsc_filter = !self.ntsc_filter;
+                Ok(())
+            },
+            RENDERER_SCALE_BUTTON => {
+                self.scaling = self.scaling.next_mode();
+                Ok(())
+            },
+            RENDERER_ASPECT_BUTTON => {
+                self.scaling = self.scaling.toggle_aspect_correction();
+                Ok(())
+            },
             RENDERER_POWER_OFF_BUTTON => {
                 let mut nes_mediator = self.nes_mediator.borrow_mut();
 
@@ -84,8 +127,22 @@ impl NesUiWidget for RendererWidget {
         let mut fields = Vec::<String>::new();
 
         fields.push(format!("rendering: {:.3} ms", self.rendering_duration_ms));
-        fields.push(format!("UI: {:>5.1} fps", self.ui_fps));
-        fields.push(format!("emulator: {:>5.1} fps", self.emulator_fps));
+        fields.push(format!("UI: {:>5.1} fps", self.ui_fps.fps()));
+        fields.push(format!("emulator: {:>5.1} fps", self.emulator_fps.fps()));
+        fields.push(format!("speed: {}x", self.speed.multiplier()));
+        fields.push(format!("scale: {}{}", self.scaling.mode(), if self.scaling.aspect_correction() { " 8:7" } else { "" }));
+
+        if let Some(stats) = &self.stats {
+            fields.push(format!("{} instr/s", stats.instructions()));
+
+            if let Some(ratio) = stats.tile_cache_hit_ratio() {
+                fields.push(format!("tile cache: {:.0}% hits", ratio * 100.0));
+            }
+        }
+
+        if self.ntsc_filter {
+            fields.push("NTSC filter".to_string());
+        }
 
         fields
     }
@@ -108,7 +165,7 @@ impl RendererWidget {
         Ok(buttons)
     }
 
-    pub fn new(height: usize, width: usize, cc: &eframe::CreationContext<'_>, nes_mediator: Rc<RefCell<NesMediator>>) -> Result<RendererWidget, NesConsoleError> {
+    pub fn new(height: usize, width: usize, ntsc_filter: bool, cc: &eframe::CreationContext<'_>, nes_mediator: Rc<RefCell<NesMediator>>) -> Result<RendererWidget, NesConsoleError> {
         let vec = HelpersUI::create_default_texture(width, height, Color32::DARK_GRAY);
 
         let texture_options = TextureOptions {
@@ -133,15 +190,21 @@ impl RendererWidget {
             width,
             texture,
             texture_options,
-            last_tick: Instant::now(),
             last_frame_counter: 0,
             frame_counter: 0,
             rendering_duration_ms: 0.0,
-            ui_fps: 0.0,
-            emulator_fps: 0.0,
+            ui_fps: FpsCounter::default(),
+            emulator_fps: FpsCounter::default(),
+            fps_overlay: false,
+            audio_buffer_fill: None,
             nes_frame: None,
             nes_mediator,
             menu_buttons,
+            speed: EmulationSpeed::default(),
+            ntsc_filter,
+            scaling: FrameScaling::default(),
+            stats: None,
+            toast: None,
         };
 
         Ok(widget)
@@ -158,9 +221,21 @@ impl RendererWidget {
                 match message {
                     NesMessage::Frame(nes_frame) => {
                         self.frame_counter = nes_frame.count();
+
+                        // the filtered frame is wider, it is scaled back to the NES aspect ratio when drawn
+                        let nes_frame = if self.ntsc_filter { NtscFilter::apply(&nes_frame) } else { nes_frame };
                         self.nes_frame = Some(ColorImage::from_rgba_unmultiplied([nes_frame.width(), nes_frame.height()], nes_frame.pixels()))
                     },
 
+                    // the work of the emulator over the last second
+                    NesMessage::Stats(stats) => self.stats = Some(stats),
+
+                    // how full the audio queue of the emulator is, for the FPS overlay
+                    NesMessage::AudioBufferFill(fill) => self.audio_buffer_fill = Some(fill),
+
+                    // the outcome of a save or a load of a state slot, shown over the frame for a while
+                    NesMessage::Toast(text) => self.toast = Some((text, Instant::now())),
+
                     _ => { warn!("unexpected message: {:?}", message); }
                 }
             }
@@ -169,38 +244,16 @@ impl RendererWidget {
         Ok(())
     }
 
+    /// One tick of the UI per repaint, the emulator ticks the frames run since the previous repaint.
     fn compute_fps(&mut self) {
-        const ALPHA: f32 = 0.1;
-
         let now = Instant::now();
-        let duration = (now - self.last_tick).as_secs_f32();
-
-        if duration > 0.0 {
-            // UI FPS: 1 / duration (EMA-smoothed)
-            let ui_fps = 1.0 / duration;
-            self.ui_fps = if self.ui_fps == 0.0 {
-                ui_fps
-            } else {
-                self.ui_fps + ALPHA * (ui_fps - self.ui_fps)
-            };
-
-            // Emulator FPS: delta frames / duration
-            let delta_frames = if self.frame_counter >= self.last_frame_counter {
-                (self.frame_counter - self.last_frame_counter) as f32
-            } else {
-                0.0
-            };
-
-            let emulator_fps = delta_frames / duration;
-            self.emulator_fps = if self.emulator_fps == 0.0 {
-                emulator_fps
-            } else {
-                self.emulator_fps + ALPHA * (emulator_fps - self.emulator_fps)
-            };
-
-            self.last_frame_counter = self.frame_counter;
-            self.last_tick = now;
-        }
+
+        // the frame counter starts over with a new ROM
+        let delta_frames = self.frame_counter.saturating_sub(self.last_frame_counter);
+
+        self.ui_fps.tick(now, 1);
+        self.emulator_fps.tick(now, delta_frames as u64);
+        self.last_frame_counter = self.frame_counter;
     }
 
     fn error_frame(&self, error: &NesConsoleError) -> ColorImage {
@@ -215,20 +268,67 @@ impl RendererWidget {
 
     fn renderer_window_inner(&mut self, ui: &mut Ui) -> Result<(), NesConsoleError> {
         let img_px = vec2(self.width as f32, self.height as f32);
-        let available_size = ui.available_size();
-        let scale = (available_size.x / img_px.x).min(available_size.y / img_px.y);
-        let size  = img_px * scale;
-        ui.vertical_centered_justified(|ui| {
-            let (_, duration) = measure_exec_time(|| {
-                ui.add(Image::new((self.texture.id(), size)));
-            });
-            self.compute_fps();
-            self.rendering_duration_ms = duration.as_secs_f64() * 1000.0;
+        let (viewport, _) = ui.allocate_exact_size(ui.available_size(), Sense::hover());
+        let destination = self.scaling.destination(img_px, viewport);
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+
+        // the viewport around the frame is left empty (letterboxing)
+        let (_, duration) = measure_exec_time(|| {
+            ui.painter().image(self.texture.id(), destination, uv, Color32::WHITE);
         });
+        self.compute_fps();
+        self.rendering_duration_ms = duration.as_secs_f64() * 1000.0;
+
+        if ui.input(|i| i.key_pressed(FPS_OVERLAY_KEY)) {
+            self.fps_overlay = !self.fps_overlay;
+        }
+
+        if self.fps_overlay {
+            self.draw_fps_overlay(ui, destination);
+        }
+
+        self.draw_toast(ui, destination);
 
         Ok(())
     }
 
+    /// The emulated and the host FPS, and the audio queue fill level, in the top left corner of the frame.
+    fn draw_fps_overlay(&self, ui: &mut Ui, destination: Rect) {
+        let host_fps = self.ui_fps.fps();
+        let frame_time_ms = if host_fps > 0.0 { 1000.0 / host_fps } else { 0.0 };
+
+        let mut text = format!("emulated: {:>5.1} fps\nhost: {:>5.1} fps ({:.1} ms)\nrendering: {:.3} ms",
+            self.emulator_fps.fps(), host_fps, frame_time_ms, self.rendering_duration_ms);
+
+        if let Some(fill) = self.audio_buffer_fill {
+            text.push_str(&format!("\naudio buffer: {:>3.0}%", fill * 100.0));
+        }
+
+        let painter = ui.painter();
+        let position = destination.left_top() + vec2(8.0, 8.0);
+        let galley = painter.layout_no_wrap(text, FontId::monospace(12.0), Color32::WHITE);
+        let background = Align2::LEFT_TOP.anchor_size(position, galley.size()).expand(4.0);
+
+        painter.rect_filled(background, 4.0, Color32::from_black_alpha(180));
+        painter.galley(background.shrink(4.0).min, galley, Color32::WHITE);
+    }
+
+    fn draw_toast(&mut self, ui: &mut Ui, destination: Rect) {
+        if let Some((_, shown_at)) = &self.toast && shown_at.elapsed() > TOAST_DURATION {
+            self.toast = None;
+        }
+
+        if let Some((text, _)) = &self.toast {
+            let painter = ui.painter();
+            let position = destination.center_bottom() - vec2(0.0, 8.0);
+            let galley = painter.layout_no_wrap(text.clone(), FontId::proportional(14.0), Color32::WHITE);
+            let background = Align2::CENTER_BOTTOM.anchor_size(position, galley.size()).expand(4.0);
+
+            painter.rect_filled(background, 4.0, Color32::from_black_alpha(180));
+            painter.galley(background.shrink(4.0).min, galley, Color32::WHITE);
+        }
+    }
+
     fn renderer_window(&mut self, ctx: &Context) -> Result<(), NesConsoleError> {
         let  _ = self.prepare_nes_frame();
 
@@ -0,0 +1,64 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use eframe::egui::Key;
+use mmnes_core::nes_console::NesConsoleError;
+
+pub const SAVE_STATE_SLOTS: u8 = 8;
+const SLOT_KEYS: [Key; SAVE_STATE_SLOTS as usize] = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8];
+
+/***
+ * The numbered save state slots of a ROM, each one a file next to the ROM: slot 3 of games/smb.nes
+ * is games/smb.ss3. The slots are bound to the keys 1 - 8 to save, Shift + 1 - 8 to load, not to
+ * F1 - F8: F1 - F3 ask the AI coach (ai_widget), F5, F7, F8, F10 and F11 drive the debugger
+ * (debugger_widget) and F9 toggles the FPS overlay (renderer_widget).
+ ***/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSlots {
+    rom_file: PathBuf,
+}
+
+impl SaveSlots {
+    pub fn new(rom_file: PathBuf) -> Self {
+        SaveSlots {
+            rom_file,
+        }
+    }
+
+    /// The slot of a key, from 1 to SAVE_STATE_SLOTS.
+    pub fn slot_for_key(key: Key) -> Option<u8> {
+        SLOT_KEYS.iter()
+            .position(|slot_key| *slot_key == key)
+            .map(|index| index as u8 + 1)
+    }
+
+    pub fn path(&self, slot: u8) -> PathBuf {
+        self.rom_file.with_extension(format!("ss{}", slot))
+    }
+
+    pub fn save(&self, slot: u8, state: &[u8]) -> Result<(), NesConsoleError> {
+        let path = self.slot_path(slot)?;
+
+        fs::write(&path, state)
+            .map_err(|e| NesConsoleError::IOError(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The state saved in the slot, an empty slot is an error of its own (nothing to load, not a failure).
+    pub fn load(&self, slot: u8) -> Result<Vec<u8>, NesConsoleError> {
+        let path = self.slot_path(slot)?;
+
+        match fs::read(&path) {
+            Ok(state) => Ok(state),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(NesConsoleError::SaveStateError(format!("slot {} is empty", slot))),
+            Err(e) => Err(NesConsoleError::IOError(format!("{}: {}", path.display(), e))),
+        }
+    }
+
+    fn slot_path(&self, slot: u8) -> Result<PathBuf, NesConsoleError> {
+        if slot == 0 || slot > SAVE_STATE_SLOTS {
+            return Err(NesConsoleError::SaveStateError(format!("no slot {}, the slots are 1 - {}", slot, SAVE_STATE_SLOTS)));
+        }
+
+        Ok(self.path(slot))
+    }
+}
@@ -1,11 +1,12 @@
 use std::fmt::{Display, Formatter};
-use log::info;
+use log::{debug, info};
 use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::Sdl;
 
-const SAMPLE_RATE: i32 = 44_100;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
 const CHUNK_SIZE: u16 = 1024;
 const BATCH_SAMPLES: usize = 1024;
+pub const DEFAULT_AUDIO_BUFFER_SIZE: usize = 4096;
 
 pub enum SoundPlayerError {
     SdlFailure(String),
@@ -23,7 +24,8 @@ pub struct SoundPlayer {
     #[allow(dead_code)]
     sdl: Sdl,
     audio_queue: AudioQueue<f32>,
-    batch_buffer: Vec<f32>
+    batch_buffer: Vec<f32>,
+    buffer_size: usize,
 }
 
 impl SoundPlayer {
@@ -33,11 +35,21 @@ impl SoundPlayer {
         self.batch_buffer.push(normalized_samples);
 
         if self.batch_buffer.len() >= BATCH_SAMPLES {
-            self.audio_queue.queue_audio(&self.batch_buffer).unwrap();
+            if self.queued_samples() + self.batch_buffer.len() <= self.buffer_size {
+                self.audio_queue.queue_audio(&self.batch_buffer).unwrap();
+            } else {
+                debug!("audio queue is full ({} samples queued), dropping {} samples ...", self.queued_samples(), self.batch_buffer.len());
+            }
+
             self.batch_buffer.clear();
         }
     }
 
+    /// Samples queued in the device and not yet played.
+    pub fn queued_samples(&self) -> usize {
+        self.audio_queue.size() as usize / size_of::<f32>()
+    }
+
     pub fn resume(&mut self) {
         self.audio_queue.resume();
     }
@@ -55,9 +67,9 @@ impl SoundPlayer {
         Ok(sdl)
     }
 
-    fn initialize() -> Result<Self, SoundPlayerError> {
+    fn initialize(buffer_size: usize, sample_rate: u32) -> Result<Self, SoundPlayerError> {
         let sdl = SoundPlayer::init_sdl()?;
-        info!("initializing audio system (queue): buffer size: chunk size: {} samples ...", CHUNK_SIZE);
+        info!("initializing audio system (queue): sample rate: {} Hz, buffer size: {} samples, chunk size: {} samples ...", sample_rate, buffer_size, CHUNK_SIZE);
 
         let audio_subsystem = if let Ok(audio) = sdl.audio() {
             audio
@@ -66,7 +78,7 @@ impl SoundPlayer {
         };
 
         let desired_spec = AudioSpecDesired {
-            freq: Some(SAMPLE_RATE),
+            freq: Some(sample_rate as i32),
             channels: Some(1),
             samples: Some(CHUNK_SIZE),
         };
@@ -80,14 +92,18 @@ impl SoundPlayer {
         let player = SoundPlayer {
             sdl,
             audio_queue,
-            batch_buffer: Vec::with_capacity(BATCH_SAMPLES)
+            batch_buffer: Vec::with_capacity(BATCH_SAMPLES),
+            buffer_size,
         };
 
         Ok(player)
     }
 
-    pub fn new() -> Result<Self, SoundPlayerError> {
-        let mut player = SoundPlayer::initialize()?;
+    /// ```buffer_size```: maximum number of samples queued to the device (latency),
+    /// the batches exceeding it are dropped.
+    /// ```sample_rate```: rate of the samples pushed by the emulator, SDL converts it if the device does not support it.
+    pub fn new(buffer_size: usize, sample_rate: u32) -> Result<Self, SoundPlayerError> {
+        let mut player = SoundPlayer::initialize(buffer_size, sample_rate)?;
         player.resume();
 
         Ok(player)
@@ -0,0 +1,71 @@
+use std::time::Duration;
+use crate::emulation_speed::{EmulationSpeed, MAX_SPEED};
+use crate::tests::init;
+
+const FRAME_DURATION: Duration = Duration::from_micros(16_639);
+
+#[test]
+fn normal_speed_runs_1_frame_per_real_frame() {
+    init();
+
+    let speed = EmulationSpeed::default();
+    assert_eq!(speed.frame_budget(FRAME_DURATION), (1, FRAME_DURATION));
+}
+
+#[test]
+fn fast_forward_2x_and_4x_run_2_and_4_frames_per_real_frame() {
+    init();
+
+    assert_eq!(EmulationSpeed::new(2.0).frame_budget(FRAME_DURATION), (2, FRAME_DURATION));
+    assert_eq!(EmulationSpeed::new(4.0).frame_budget(FRAME_DURATION), (4, FRAME_DURATION));
+}
+
+#[test]
+fn fractional_speed_shortens_the_real_frame() {
+    init();
+
+    let (frames, duration) = EmulationSpeed::new(1.5).frame_budget(FRAME_DURATION);
+
+    assert_eq!(frames, 1);
+    assert_eq!(duration, FRAME_DURATION.mul_f64(1.0 / 1.5));
+}
+
+#[test]
+fn only_the_first_frame_samples_are_played() {
+    init();
+
+    let speed = EmulationSpeed::new(4.0);
+
+    assert!(speed.plays_samples(0));
+    assert!((1..4).all(|frame| !speed.plays_samples(frame)));
+}
+
+#[test]
+fn speed_is_clamped_and_fast_forward_cycles() {
+    init();
+
+    assert_eq!(EmulationSpeed::new(100.0).multiplier(), MAX_SPEED);
+    assert_eq!(EmulationSpeed::default().next_fast_forward().multiplier(), 2.0);
+    assert_eq!(EmulationSpeed::new(2.0).next_fast_forward().multiplier(), 4.0);
+    assert_eq!(EmulationSpeed::new(4.0).next_fast_forward().multiplier(), 1.0);
+}
+
+#[test]
+fn slow_motion_runs_1_frame_over_2_and_4_real_frames() {
+    init();
+
+    assert_eq!(EmulationSpeed::new(0.5).frame_budget(FRAME_DURATION), (1, FRAME_DURATION * 2));
+    assert_eq!(EmulationSpeed::new(0.25).frame_budget(FRAME_DURATION), (1, FRAME_DURATION * 4));
+}
+
+#[test]
+fn slow_motion_is_muted_and_cycles() {
+    init();
+
+    assert!(!EmulationSpeed::new(0.5).plays_samples(0));
+    assert!(EmulationSpeed::default().plays_samples(0));
+    assert_eq!(EmulationSpeed::default().next_slow_motion().multiplier(), 0.5);
+    assert_eq!(EmulationSpeed::new(0.5).next_slow_motion().multiplier(), 0.25);
+    assert_eq!(EmulationSpeed::new(0.25).next_slow_motion().multiplier(), 1.0);
+    assert_eq!(EmulationSpeed::new(4.0).next_slow_motion().multiplier(), 1.0);
+}
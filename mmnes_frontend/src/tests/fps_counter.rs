@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+use crate::fps_counter::FpsCounter;
+use crate::tests::init;
+
+#[test]
+fn no_fps_before_two_ticks() {
+    init();
+
+    let mut counter = FpsCounter::new(4);
+    assert_eq!(counter.fps(), 0.0);
+
+    counter.tick(Instant::now(), 1);
+    assert_eq!(counter.fps(), 0.0);
+}
+
+#[test]
+fn fps_is_averaged_over_the_last_ticks_of_the_window() {
+    init();
+
+    let start = Instant::now();
+    let mut counter = FpsCounter::new(4);
+
+    // a stall of 100 ms, then 3 frames at 50 fps: the stall has left the window of 4 ticks
+    let timestamps_ms = [0, 100, 120, 140, 160];
+    for ms in timestamps_ms {
+        counter.tick(start + Duration::from_millis(ms), 1);
+    }
+
+    assert!((counter.fps() - 50.0).abs() < 0.01, "fps: {}", counter.fps());
+}
+
+#[test]
+fn a_jittery_sequence_is_smoothed_to_its_average_rate() {
+    init();
+
+    let start = Instant::now();
+    let mut counter = FpsCounter::new(5);
+
+    // 10 ms and 30 ms frames in turn: 4 frames over 80 ms
+    let timestamps_ms = [0, 10, 40, 50, 80];
+    for ms in timestamps_ms {
+        counter.tick(start + Duration::from_millis(ms), 1);
+    }
+
+    assert!((counter.fps() - 50.0).abs() < 0.01, "fps: {}", counter.fps());
+}
+
+#[test]
+fn emulated_frames_run_between_two_ticks_are_all_counted() {
+    init();
+
+    let start = Instant::now();
+    let mut counter = FpsCounter::new(3);
+
+    // fast forward: 2 frames per repaint of the UI at 60 Hz
+    counter.tick(start, 0);
+    counter.tick(start + Duration::from_micros(16_667), 2);
+    counter.tick(start + Duration::from_micros(33_333), 2);
+
+    assert!((counter.fps() - 120.0).abs() < 0.1, "fps: {}", counter.fps());
+}
@@ -0,0 +1,41 @@
+use std::time::Instant;
+use mmnes_core::region::Region;
+use crate::frame_pacing::FramePacing;
+use crate::tests::init;
+
+#[test]
+fn sleep_mode_frame_budget_is_the_ntsc_frame_duration() {
+    init();
+
+    let budget = FramePacing::frame_budget(Region::NTSC.frames_per_second());
+    let start = Instant::now();
+    let next = FramePacing::Sleep.sleep_until_next_frame(start + budget, budget);
+
+    // the deadline following the first frame is two NTSC frames (2 x 16 639.267 us) after the start
+    assert_eq!((next - start).as_micros(), 33_278);
+    assert_eq!(budget.as_micros(), 16_639);
+}
+
+#[test]
+fn sleep_mode_waits_for_the_deadline_and_returns_the_next_one() {
+    init();
+
+    let frame = FramePacing::frame_budget(Region::NTSC.frames_per_second());
+    let next = Instant::now() + frame;
+    let following = FramePacing::Sleep.sleep_until_next_frame(next, frame);
+
+    assert!(Instant::now() >= next);
+    assert_eq!(following, next + frame);
+}
+
+#[test]
+fn missed_deadlines_are_skipped() {
+    init();
+
+    let frame = FramePacing::frame_budget(Region::NTSC.frames_per_second());
+    let late = Instant::now() - frame * 3;
+    let next = FramePacing::Sleep.sleep_until_next_frame(late, frame);
+
+    assert!(next > Instant::now());
+    assert!(next <= Instant::now() + frame);
+}
@@ -0,0 +1,59 @@
+use eframe::egui::{pos2, vec2, Rect};
+use crate::frame_scaling::{FrameScaling, ScaleMode, PIXEL_ASPECT_RATIO};
+use crate::tests::init;
+
+const NES_FRAME: (f32, f32) = (256.0, 240.0);
+
+fn viewport(width: f32, height: f32) -> Rect {
+    Rect::from_min_size(pos2(0.0, 0.0), vec2(width, height))
+}
+
+/// The scaling reached from the default one through the buttons: ```mode``` cycled to, then the 8:7 toggle.
+fn scaling(mode: ScaleMode, aspect_correction: bool) -> FrameScaling {
+    let mut scaling = FrameScaling::default();
+
+    while scaling.mode() != mode {
+        scaling = scaling.next_mode();
+    }
+
+    if aspect_correction {
+        scaling = scaling.toggle_aspect_correction();
+    }
+
+    scaling
+}
+
+#[test]
+fn integer_scale_not_fitting_the_viewport_falls_back_to_the_largest_fitting_one_centered() {
+    init();
+
+    // 3x (768x720) does not fit in 700x600, 2x (512x480) does
+    let destination = scaling(ScaleMode::Integer(3), false).destination(vec2(NES_FRAME.0, NES_FRAME.1), viewport(700.0, 600.0));
+
+    assert_eq!(destination, Rect::from_min_size(pos2(94.0, 60.0), vec2(512.0, 480.0)));
+}
+
+#[test]
+fn fit_keeps_the_frame_ratio_and_aspect_correction_widens_the_pixels() {
+    init();
+
+    let frame = vec2(NES_FRAME.0, NES_FRAME.1);
+
+    let destination = scaling(ScaleMode::Fit, false).destination(frame, viewport(900.0, 480.0));
+    assert_eq!(destination, Rect::from_min_size(pos2(194.0, 0.0), vec2(512.0, 480.0)));
+
+    let destination = scaling(ScaleMode::Integer(1), true).destination(frame, viewport(900.0, 600.0));
+    assert_eq!(destination, Rect::from_center_size(pos2(450.0, 300.0), vec2(256.0 * PIXEL_ASPECT_RATIO, 240.0)));
+}
+
+#[test]
+fn scale_mode_cycles_and_is_at_least_1x() {
+    init();
+
+    let default = FrameScaling::default();
+    assert_eq!(default.next_mode().mode(), ScaleMode::Integer(1));
+    assert_eq!(default.next_mode().next_mode().next_mode().next_mode().mode(), ScaleMode::Fit);
+
+    let destination = scaling(ScaleMode::Integer(2), false).destination(vec2(NES_FRAME.0, NES_FRAME.1), viewport(100.0, 100.0));
+    assert_eq!(destination.size(), vec2(256.0, 240.0));
+}
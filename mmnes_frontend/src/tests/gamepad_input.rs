@@ -0,0 +1,30 @@
+use gilrs::{Axis, Button};
+use mmnes_core::key_event::{KeyEvent, NES_CONTROLLER_KEY_A, NES_CONTROLLER_KEY_LEFT, NES_CONTROLLER_KEY_UP};
+use crate::gamepad_input::GamepadMapping;
+use crate::tests::init;
+
+#[test]
+fn south_button_pressed_is_the_nes_a_button() {
+    init();
+
+    let mut mapping = GamepadMapping::new();
+
+    assert_eq!(mapping.map_button(Button::South, true), Some(KeyEvent { key: NES_CONTROLLER_KEY_A, pressed: true }));
+    assert_eq!(mapping.map_button(Button::South, true), None);
+    assert_eq!(mapping.map_button(Button::South, false), Some(KeyEvent { key: NES_CONTROLLER_KEY_A, pressed: false }));
+}
+
+#[test]
+fn stick_is_a_direction_beyond_the_deadzone_only() {
+    init();
+
+    let mut mapping = GamepadMapping::new();
+
+    assert!(mapping.map_axis(Axis::LeftStickX, -0.3).is_empty());
+    assert_eq!(mapping.map_axis(Axis::LeftStickX, -0.8), vec![KeyEvent { key: NES_CONTROLLER_KEY_LEFT, pressed: true }]);
+    assert!(mapping.map_axis(Axis::LeftStickX, -1.0).is_empty());
+    assert_eq!(mapping.map_axis(Axis::LeftStickX, 0.1), vec![KeyEvent { key: NES_CONTROLLER_KEY_LEFT, pressed: false }]);
+
+    mapping.map_axis(Axis::LeftStickY, 0.9);
+    assert_eq!(mapping.release_all(), vec![KeyEvent { key: NES_CONTROLLER_KEY_UP, pressed: false }]);
+}
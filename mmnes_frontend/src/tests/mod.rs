@@ -5,6 +5,14 @@ use mmnes_core::memory_bank::MemoryBank;
 
 mod llm_client;
 mod nes_rom_metadata_worker;
+mod emulation_speed;
+mod frame_pacing;
+mod frame_scaling;
+mod gamepad_input;
+mod save_slots;
+mod recent_roms;
+mod fps_counter;
+mod nes_front_end;
 
 static START: Once = Once::new();
 
@@ -14,4 +22,19 @@ fn init_logger_for_test() {
 
 pub fn init() {
     init_logger_for_test();
+}
+
+const INES_HEADER_SIZE: usize = 16;
+const NROM_PRG_ROM_BANK_SIZE: usize = 16 * 1024;
+const NROM_CHR_ROM_SIZE: usize = 8 * 1024;
+
+/// iNES image of a NROM cartridge of ```prg_rom``` (a multiple of 16 KB) and 8 KB of blank CHR ROM,
+/// the same as the one of the core tests which are not built with the front end.
+fn nrom_image(prg_rom: &[u8]) -> Vec<u8> {
+    let mut image = vec![0x4E, 0x45, 0x53, 0x1A, (prg_rom.len() / NROM_PRG_ROM_BANK_SIZE) as u8, 0x01, 0x00, 0x00];
+    image.resize(INES_HEADER_SIZE, 0x00);
+
+    image.extend_from_slice(prg_rom);
+    image.extend(vec![0x00; NROM_CHR_ROM_SIZE]);
+    image
 }
\ No newline at end of file
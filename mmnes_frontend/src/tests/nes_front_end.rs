@@ -0,0 +1,57 @@
+use std::fs;
+use std::ops::ControlFlow::Break;
+use std::path::PathBuf;
+use std::sync::mpsc::sync_channel;
+use mmnes_core::controller::ControllerType;
+use crate::frame_pacing::FramePacing;
+use crate::nes_front_end::{NesFrontEnd, NesFrontEndState};
+use crate::nes_message::NesMessage;
+use crate::sound_player::{DEFAULT_AUDIO_BUFFER_SIZE, DEFAULT_SAMPLE_RATE};
+use crate::tests::{init, nrom_image};
+
+const PRG_ROM_SIZE: usize = 16 * 1024;
+const CHANNEL_BOUND_SIZE: usize = 10;
+
+/// NROM-128 filled with ```fill```, the reset vector at $8000.
+fn create_nrom_file(name: &str, fill: u8) -> PathBuf {
+    let mut prg_rom = vec![fill; PRG_ROM_SIZE];
+    prg_rom[PRG_ROM_SIZE - 6..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    let path = std::env::temp_dir().join(format!("mmnes_{}_{}.nes", name, std::process::id()));
+    fs::write(&path, nrom_image(&prg_rom)).expect("failed to write rom file");
+
+    path
+}
+
+fn create_front_end() -> NesFrontEnd {
+    let (frame_tx, _) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+    let (_, command_rx) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+    let (debug_tx, _) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+    let (error_tx, _) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+    let (ppu_viewer_tx, _) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+    let (apu_viewer_tx, _) = sync_channel::<NesMessage>(CHANNEL_BOUND_SIZE);
+
+    NesFrontEnd::new(frame_tx, command_rx, debug_tx, error_tx, ppu_viewer_tx, apu_viewer_tx,
+                     DEFAULT_AUDIO_BUFFER_SIZE, DEFAULT_SAMPLE_RATE, ControllerType::StandardController, FramePacing::default(), None)
+        .expect("failed to create front end")
+}
+
+#[test]
+fn rom_loaded_at_runtime_replaces_the_prg_rom_of_the_previous_one() {
+    init();
+
+    let rom_a = create_nrom_file("rom_a", 0xEA);
+    let rom_b = create_nrom_file("rom_b", 0x4C);
+    let mut front = create_front_end();
+
+    let flow = front.process_message(NesMessage::LoadRom(rom_a.clone())).expect("failed to load rom a");
+    assert_eq!(flow, Break(NesFrontEndState::Running));
+    assert_eq!(front.nes().expect("no console").peek(0x8000).unwrap(), 0xEA);
+
+    let flow = front.process_message(NesMessage::LoadRom(rom_b.clone())).expect("failed to load rom b");
+    assert_eq!(flow, Break(NesFrontEndState::Running));
+    assert_eq!(front.nes().expect("no console").peek(0x8000).unwrap(), 0x4C);
+
+    let _ = fs::remove_file(rom_a);
+    let _ = fs::remove_file(rom_b);
+}
@@ -0,0 +1,51 @@
+use std::env::temp_dir;
+use std::fs;
+use std::path::PathBuf;
+use crate::recent_roms::RecentRoms;
+use crate::tests::init;
+
+#[test]
+fn a_rom_opened_twice_is_listed_once_at_the_top() {
+    init();
+
+    let mut recent_roms = RecentRoms::new(None, 3);
+    recent_roms.add(&PathBuf::from("smb.nes"));
+    recent_roms.add(&PathBuf::from("zelda.nes"));
+    recent_roms.add(&PathBuf::from("smb.nes"));
+
+    assert_eq!(recent_roms.paths(), &[PathBuf::from("smb.nes"), PathBuf::from("zelda.nes")]);
+}
+
+#[test]
+fn the_list_is_capped_at_its_maximum_dropping_the_oldest_rom() {
+    init();
+
+    let mut recent_roms = RecentRoms::new(None, 3);
+    for rom in ["1.nes", "2.nes", "3.nes", "4.nes"] {
+        recent_roms.add(&PathBuf::from(rom));
+    }
+
+    assert_eq!(recent_roms.paths(), &[PathBuf::from("4.nes"), PathBuf::from("3.nes"), PathBuf::from("2.nes")]);
+}
+
+#[test]
+fn saved_list_is_loaded_back_without_the_missing_roms() {
+    init();
+
+    let dir = temp_dir().join(format!("mmnes_recent_roms_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let rom = dir.join("smb.nes");
+    fs::write(&rom, [0x4E, 0x45, 0x53, 0x1A]).unwrap();
+
+    let file = dir.join("recent_roms");
+    let mut recent_roms = RecentRoms::new(Some(file.clone()), 3);
+    recent_roms.add(&dir.join("deleted.nes"));
+    recent_roms.add(&rom);
+    recent_roms.save().unwrap();
+
+    let loaded = RecentRoms::load(file, 3);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(loaded.unwrap().paths(), &[rom]);
+}
@@ -0,0 +1,38 @@
+use std::env::temp_dir;
+use std::fs;
+use eframe::egui::Key;
+use mmnes_core::nes_console::NesConsoleError;
+use crate::save_slots::SaveSlots;
+use crate::tests::init;
+
+#[test]
+fn slots_are_files_next_to_the_rom_bound_to_the_number_keys() {
+    init();
+
+    let slots = SaveSlots::new(temp_dir().join("smb.nes"));
+
+    assert_eq!(slots.path(3), temp_dir().join("smb.ss3"));
+    assert_eq!(SaveSlots::slot_for_key(Key::Num3), Some(3));
+    assert_eq!(SaveSlots::slot_for_key(Key::Num9), None);
+    assert_eq!(SaveSlots::slot_for_key(Key::F1), None);
+}
+
+#[test]
+fn saved_state_is_loaded_back_and_an_empty_slot_is_reported() {
+    init();
+
+    let slots = SaveSlots::new(temp_dir().join(format!("mmnes_save_slots_{}.nes", std::process::id())));
+    let _ = fs::remove_file(slots.path(2));
+
+    match slots.load(2) {
+        Err(NesConsoleError::SaveStateError(e)) => assert!(e.contains("empty")),
+        other => panic!("expected an empty slot, got {:?}", other),
+    }
+
+    slots.save(2, &[0x4D, 0x4D, 0x4E, 0x53]).unwrap();
+    let state = slots.load(2);
+    let _ = fs::remove_file(slots.path(2));
+
+    assert_eq!(state.unwrap(), vec![0x4D, 0x4D, 0x4E, 0x53]);
+    assert!(matches!(slots.save(9, &[]), Err(NesConsoleError::SaveStateError(_))));
+}